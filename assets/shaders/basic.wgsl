@@ -1,26 +1,99 @@
+// Set by the pipeline when the target stores colors as they are
+override ENCODE_SRGB: bool = false;
+
 struct CameraUniform {
     view_proj: mat4x4<f32>,
+    // Camera position, w is unused
+    eye: vec4<f32>,
 };
 @group(1) @binding(0) // 1.
 var<uniform> camera: CameraUniform;
 
+struct ClipUniform {
+    // Fragments where dot(plane.xyz, position) + plane.w < 0 are cut away
+    plane: vec4<f32>,
+};
+@group(1) @binding(1)
+var<uniform> clip: ClipUniform;
+
+struct EdgeUniform {
+    // Size of the render target in pixels
+    viewport: vec2<f32>,
+    // Width of edges in pixels, without the antialiased rim
+    width: f32,
+};
+@group(1) @binding(2)
+var<uniform> edge: EdgeUniform;
+
+struct ModelUniform {
+    transform: mat4x4<f32>,
+};
+@group(2) @binding(0)
+var<uniform> model_uniform: ModelUniform;
+
+struct LightUniform {
+    // Points from the surface towards the light
+    direction: vec3<f32>,
+    ambient: f32,
+};
+@group(3) @binding(0)
+var<uniform> light: LightUniform;
+
+struct MaterialUniform {
+    albedo: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+};
+@group(3) @binding(1)
+var<uniform> material: MaterialUniform;
+
+// Brightness of the surroundings reflected everywhere, all that lights metals away from highlights
+const ENVIRONMENT: f32 = 0.35;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) uv_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+}
+
+// Instance transform, applied before the model's
+struct InstanceInput {
+    @location(5) transform_0: vec4<f32>,
+    @location(6) transform_1: vec4<f32>,
+    @location(7) transform_2: vec4<f32>,
+    @location(8) transform_3: vec4<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) uv_coords: vec2<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
+}
+
+// Model space to world space
+fn model_transform(instance: InstanceInput) -> mat4x4<f32> {
+    return model_uniform.transform * mat4x4<f32>(
+        instance.transform_0,
+        instance.transform_1,
+        instance.transform_2,
+        instance.transform_3,
+    );
 }
 
 @vertex
 fn vs_main(
     model: VertexInput,
+    instance: InstanceInput,
 ) -> VertexOutput {
+    let transform = model_transform(instance);
     var out: VertexOutput;
     out.uv_coords = model.uv_coords;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0); // 2.
+    // Only correct for transforms without non-uniform scaling
+    out.world_normal = (transform * vec4<f32>(model.normal, 0.0)).xyz;
+    let world_position = transform * vec4<f32>(model.position, 1.0);
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position; // 2.
     return out;
 }
 
@@ -29,7 +102,199 @@ var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
+// Whether the section plane cuts away `position`
+fn is_clipped(position: vec3<f32>) -> bool {
+    return dot(clip.plane.xyz, position) + clip.plane.w < 0.0;
+}
+
+// The normal of the side being drawn, back faces are only drawn for double sided models
+fn facing_normal(world_normal: vec3<f32>, front_facing: bool) -> vec3<f32> {
+    return normalize(select(-world_normal, world_normal, front_facing));
+}
+
+// Linear to sRGB transfer function
+fn srgb_encode(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+// Every fragment shader's color on its way into the target
+fn output(color: vec4<f32>) -> vec4<f32> {
+    if ENCODE_SRGB {
+        return vec4<f32>(srgb_encode(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0))), color.a);
+    }
+    return color;
+}
+
+@fragment
+fn fs_main(
+    in: VertexOutput,
+    @builtin(front_facing) front_facing: bool,
+) -> @location(0) vec4<f32> {
+    if is_clipped(in.world_position) {
+        discard;
+    }
+    let color = textureSample(t_diffuse, s_diffuse, in.uv_coords);
+    // Lambert term, faces pointing away from the light only get the ambient part
+    let normal = facing_normal(in.world_normal, front_facing);
+    let diffuse = max(dot(normal, light.direction), 0.0);
+    let brightness = light.ambient + (1.0 - light.ambient) * diffuse;
+    return output(vec4<f32>(color.rgb * brightness, color.a));
+}
+
+// Diffuse and Blinn-Phong specular lighting, the highlight narrowing as the roughness drops and
+// taking on the base color as the surface gets metallic
+fn shade(base: vec3<f32>, metallic: f32, roughness: f32, normal: vec3<f32>, view: vec3<f32>) -> vec3<f32> {
+    let half_vector = normalize(light.direction + view);
+    let n_dot_l = max(dot(normal, light.direction), 0.0);
+    let n_dot_h = max(dot(normal, half_vector), 0.0);
+
+    let shininess = 2.0 / pow(clamp(roughness, 0.05, 1.0), 4.0) - 2.0;
+    let specular_color = mix(vec3<f32>(0.04), base, metallic);
+    let specular = specular_color * (shininess + 8.0) / 8.0 * pow(n_dot_h, shininess);
+    let diffuse = base * (1.0 - metallic);
+    let ambient = light.ambient * diffuse + ENVIRONMENT * specular_color;
+    return ambient + (diffuse + specular) * n_dot_l;
+}
+
+// Shades untextured models with the material instead of the texture
+@fragment
+fn fs_material(
+    in: VertexOutput,
+    @builtin(front_facing) front_facing: bool,
+) -> @location(0) vec4<f32> {
+    if is_clipped(in.world_position) {
+        discard;
+    }
+    let normal = facing_normal(in.world_normal, front_facing);
+    let view = normalize(camera.eye.xyz - in.world_position);
+    let color = shade(material.albedo, material.metallic, material.roughness, normal, view);
+    return output(vec4<f32>(color, 1.0));
+}
+
+struct VertexColorOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
+}
+
+// Like vs_main, but passes on a color per vertex instead of the texture coordinates
+@vertex
+fn vs_vertex_color(
+    model: VertexInput,
+    @location(3) color: vec3<f32>,
+    instance: InstanceInput,
+) -> VertexColorOutput {
+    let transform = model_transform(instance);
+    var out: VertexColorOutput;
+    out.color = color;
+    out.world_normal = (transform * vec4<f32>(model.normal, 0.0)).xyz;
+    let world_position = transform * vec4<f32>(model.position, 1.0);
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
+    return out;
+}
+
+// Lit like fs_main, with the vertex color in place of the texture
+@fragment
+fn fs_vertex_color(
+    in: VertexColorOutput,
+    @builtin(front_facing) front_facing: bool,
+) -> @location(0) vec4<f32> {
+    if is_clipped(in.world_position) {
+        discard;
+    }
+    let normal = facing_normal(in.world_normal, front_facing);
+    let diffuse = max(dot(normal, light.direction), 0.0);
+    let brightness = light.ambient + (1.0 - light.ambient) * diffuse;
+    return output(vec4<f32>(in.color * brightness, 1.0));
+}
+
+// Pulls edges towards the camera, in depth buffer units, so they win against the faces they border
+const EDGE_DEPTH_BIAS: f32 = 2e-4;
+
+// Pixels over which edges fade out on either side
+const EDGE_RIM: f32 = 1.0;
+
+struct EdgeInput {
+    @location(0) start: vec3<f32>,
+    @location(1) end: vec3<f32>,
+    // x is 0 at the start and 1 at the end, y the side of the segment, -1 or 1
+    @location(2) corner: vec2<f32>,
+}
+
+struct EdgeOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    // Distance from the middle of the edge in pixels, signed by the side
+    @location(1) offset: f32,
+}
+
+// Widens the segment into a quad of the edge width plus its rim on screen, the same number of
+// pixels however far away the edge is
+@vertex
+fn vs_edge(
+    in: EdgeInput,
+    instance: InstanceInput,
+) -> EdgeOutput {
+    let transform = model_transform(instance);
+    let world_start = transform * vec4<f32>(in.start, 1.0);
+    let world_end = transform * vec4<f32>(in.end, 1.0);
+    var clip_start = camera.view_proj * world_start;
+    var clip_end = camera.view_proj * world_end;
+    // Cut segments crossing the near plane at it, points behind the camera don't project
+    if clip_start.z < 0.0 && clip_end.z >= 0.0 {
+        clip_start = mix(clip_start, clip_end, clip_start.z / (clip_start.z - clip_end.z));
+    } else if clip_end.z < 0.0 && clip_start.z >= 0.0 {
+        clip_end = mix(clip_end, clip_start, clip_end.z / (clip_end.z - clip_start.z));
+    }
+
+    let pixels = 0.5 * edge.viewport;
+    let screen_start = clip_start.xy / clip_start.w * pixels;
+    let screen_end = clip_end.xy / clip_end.w * pixels;
+    let along = screen_end - screen_start;
+    // Segments seen end on collapse to a point, widened in any direction
+    var direction = vec2<f32>(1.0, 0.0);
+    if dot(along, along) > 1e-12 {
+        direction = normalize(along);
+    }
+    let half_width = 0.5 * edge.width + EDGE_RIM;
+    let offset = vec2<f32>(-direction.y, direction.x) * half_width * in.corner.y;
+
+    var out: EdgeOutput;
+    out.world_position = mix(world_start, world_end, in.corner.x).xyz;
+    out.clip_position = select(clip_start, clip_end, in.corner.x > 0.5);
+    out.clip_position.x += offset.x / pixels.x * out.clip_position.w;
+    out.clip_position.y += offset.y / pixels.y * out.clip_position.w;
+    out.clip_position.z -= EDGE_DEPTH_BIAS * out.clip_position.w;
+    out.offset = half_width * in.corner.y;
+    return out;
+}
+
+@fragment
+fn fs_edge(in: EdgeOutput) -> @location(0) vec4<f32> {
+    if is_clipped(in.world_position) {
+        discard;
+    }
+    // Covers the pixel fully within the edge width, fading out linearly over the rim
+    let coverage = clamp(0.5 * edge.width + 0.5 * EDGE_RIM - abs(in.offset), 0.0, 1.0);
+    return output(vec4<f32>(0.05, 0.05, 0.05, coverage));
+}
+
+// Lit like fs_main, but with a flat highlight color instead of the texture
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.uv_coords);
+fn fs_highlight(
+    in: VertexOutput,
+    @builtin(front_facing) front_facing: bool,
+) -> @location(0) vec4<f32> {
+    if is_clipped(in.world_position) {
+        discard;
+    }
+    let color = vec3<f32>(1.0, 0.55, 0.1);
+    let normal = facing_normal(in.world_normal, front_facing);
+    let diffuse = max(dot(normal, light.direction), 0.0);
+    let brightness = light.ambient + (1.0 - light.ambient) * diffuse;
+    return output(vec4<f32>(color * brightness, 1.0));
 }
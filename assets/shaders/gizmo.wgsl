@@ -0,0 +1,44 @@
+// Set by the pipeline when the target stores colors as they are
+override ENCODE_SRGB: bool = false;
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    line: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.color = line.color;
+    // Gizmo lines are already in world space
+    out.clip_position = camera.view_proj * vec4<f32>(line.position, 1.0);
+    return out;
+}
+
+// Linear to sRGB transfer function, as in basic.wgsl
+fn srgb_encode(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if ENCODE_SRGB {
+        return vec4<f32>(srgb_encode(in.color), 1.0);
+    }
+    return vec4<f32>(in.color, 1.0);
+}
@@ -0,0 +1,46 @@
+// Set by the pipeline when the surface stores colors as they are
+override ENCODE_SRGB: bool = false;
+// 0 for Reinhard, 1 for ACES, see `ToneMapping`
+override TONE_MAPPING: u32 = 1u;
+
+@group(0) @binding(0)
+var hdr: texture_2d<f32>;
+
+// A single triangle covering the screen, no vertex buffer needed
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Linear to sRGB transfer function
+fn srgb_encode(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + color);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    let mapped = (color * (a * color + b)) / (color * (c * color + d) + e);
+    return clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let hdr_color = max(textureLoad(hdr, vec2<i32>(position.xy), 0).rgb, vec3<f32>(0.0));
+    var color = select(aces(hdr_color), reinhard(hdr_color), TONE_MAPPING == 0u);
+    if ENCODE_SRGB {
+        color = srgb_encode(color);
+    }
+    return vec4<f32>(color, 1.0);
+}
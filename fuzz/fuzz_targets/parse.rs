@@ -0,0 +1,19 @@
+//! Feeds arbitrary bytes to the STEP parser, which must return an error rather than panic.
+//!
+//! Run with `cargo fuzz run parse`, seeding the corpus from `assets/step` helps it reach past
+//! the header quickly.
+#![no_main]
+
+// Dependencies
+use libfuzzer_sys::fuzz_target;
+
+// Local modules
+use brepimport::step::{parse, parse_lenient, parse_reader};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(src) = std::str::from_utf8(data) {
+        let _ = parse(src);
+        let _ = parse_lenient(src);
+    }
+    let _ = parse_reader(data);
+});
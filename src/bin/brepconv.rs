@@ -1,7 +1,30 @@
+// STD
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+// Dependencies
+use anyhow::{Context, Result, bail};
+use brepimport::import::{ImportError, ImportWarning, check_schema, read_stl};
+use brepimport::mesh::Vertex;
+use brepimport::mesh::manifold::check_manifold;
+use brepimport::mesh::weld::weld;
+use brepimport::step::tessellate::{
+    AUTO_DEFLECTION, TessellationOptions, tessellate_faces_with_progress,
+};
+use brepimport::step::{EntityGraph, LengthUnit, PartPlacements, normalize_units};
+use brepimport::{brep, convert, export, step};
+
 pub static HELP_TEXT: &str = r#"
-Format conversion between *.brep & *.step files.
+Format conversion between *.brep & *.step files, export to *.stl, *.obj, *.ply & *.glb meshes and
+to *.dxf wireframes.
 Synopsis:
-    brepconf [-f (step | brep)] <source> [-o <dest>]
+    brepconv [-f (step | brep)] <source> [-o <dest>] [--ascii-stl] [--ascii-ply]
+             [--epsilon <distance>] [--deflection (<distance> | auto)] [--units (mm | cm | m | in)]
+             [--lenient]
+    brepconv [-f (step | brep)] --validate <source> [-o <dest>]
 
 Options:
     -f (step | brep)
@@ -9,8 +32,549 @@ Options:
     -o <dest>
         Output the resulting file in path <dest>.
         If omitted, brepconv will append the proper file extension.
+        A '.stl', '.obj', '.ply' or '.glb' <dest> tessellates the model into a mesh, a '.dxf' <dest>
+        writes its edges as a wireframe.
+    --ascii-stl
+        Write STL meshes in the ASCII variant instead of the binary one.
+    --ascii-ply
+        Write PLY meshes in the ASCII variant instead of the binary little-endian one.
+    --epsilon <distance>
+        Merge STL, OBJ and PLY vertices closer than <distance>, so faces meet without cracks.
+        Defaults to 1e-5.
+    --deflection (<distance> | auto)
+        Subdivide curved geometry until meshes and wireframes stay within <distance> of it.
+        'auto' picks a tenth of a percent of the model's bounding box diagonal. Defaults to 0.01.
+    --units (mm | cm | m | in)
+        Unit to write meshes and wireframes in. Defaults to mm, <source> is converted from its
+        own units.
+    --lenient
+        Leave out STEP entities that fail to parse instead of giving up, and list them along
+        with faces that couldn't be tessellated.
+    --validate
+        Only parse <source> and print a summary of its contents. Exits with a failure status if
+        the file isn't structurally valid. No file is written.
+        With a '.stl' <dest>, also tessellate <source> as for writing it and check that the
+        mesh is watertight, counting non-manifold edges, boundary edges and isolated vertices.
+        A '.stl' <source> is welded with --epsilon and checked the same way.
+
+Exit status:
+    0   Success.
+    1   Any other failure, including invalid arguments.
+    2   A file couldn't be read or written.
+    3   <source> isn't a valid STEP file.
+    4   <source> names none of the STEP schemas brepconv reads (AP203, AP214 and AP242).
+        --lenient converts it anyway.
+    5   None of the faces of <source> lie on a surface that can be tessellated.
 "#;
 
-fn main() {
-    print!("{}", HELP_TEXT);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Step,
+    Brep,
+    /// Output only
+    Stl,
+    /// Output only
+    Obj,
+    /// Output only
+    Ply,
+    /// Output only
+    Glb,
+    /// Output only, edges as a wireframe
+    Dxf,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "step" | "stp" => Some(Format::Step),
+            "brep" => Some(Format::Brep),
+            "stl" => Some(Format::Stl),
+            "obj" => Some(Format::Obj),
+            "ply" => Some(Format::Ply),
+            "glb" => Some(Format::Glb),
+            "dxf" => Some(Format::Dxf),
+            _ => None,
+        }
+    }
+
+    fn from_path(path: &Path) -> Option<Self> {
+        Self::from_name(path.extension()?.to_str()?)
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Step => "step",
+            Format::Brep => "brep",
+            Format::Stl => "stl",
+            Format::Obj => "obj",
+            Format::Ply => "ply",
+            Format::Glb => "glb",
+            Format::Dxf => "dxf",
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            Format::Step => Format::Brep,
+            Format::Brep | Format::Stl | Format::Obj | Format::Ply | Format::Glb | Format::Dxf => {
+                Format::Step
+            },
+        }
+    }
+}
+
+struct Args {
+    format: Option<Format>,
+    source: PathBuf,
+    dest: Option<PathBuf>,
+    validate: bool,
+    ascii_stl: bool,
+    ascii_ply: bool,
+    /// Distance below which mesh vertices are merged
+    epsilon: f32,
+    tessellation: TessellationOptions,
+    /// Unit meshes are written in, `None` for millimetres
+    units: Option<LengthUnit>,
+    /// Skip malformed STEP entities rather than failing
+    lenient: bool,
+}
+
+/// Parses the command line, returns `None` if help was requested.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>> {
+    let mut format = None;
+    let mut source = None;
+    let mut dest = None;
+    let mut validate = false;
+    let mut ascii_stl = false;
+    let mut ascii_ply = false;
+    let mut epsilon = export::obj::DEFAULT_EPSILON;
+    let mut tessellation = TessellationOptions::default();
+    let mut units = None;
+    let mut lenient = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-f" => {
+                let name = args.next().context("-f expects a format")?;
+                format = Some(
+                    Format::from_name(&name)
+                        .with_context(|| format!("Unknown format '{}'", name))?,
+                );
+            },
+            "--validate" => validate = true,
+            "--ascii-stl" => ascii_stl = true,
+            "--ascii-ply" => ascii_ply = true,
+            "--lenient" => lenient = true,
+            "--epsilon" => {
+                let value = args.next().context("--epsilon expects a distance")?;
+                epsilon = value
+                    .parse()
+                    .with_context(|| format!("Invalid distance '{}'", value))?;
+            },
+            "--deflection" => {
+                let value = args
+                    .next()
+                    .context("--deflection expects a distance or auto")?;
+                if value == "auto" {
+                    tessellation.relative_deflection = Some(AUTO_DEFLECTION);
+                    continue;
+                }
+                let deflection: f64 = value
+                    .parse()
+                    .with_context(|| format!("Invalid distance '{}'", value))?;
+                if deflection.is_nan() || deflection <= 0.0 {
+                    bail!("--deflection must be positive");
+                }
+                tessellation.linear_deflection = deflection;
+                tessellation.relative_deflection = None;
+            },
+            "--units" => {
+                let name = args.next().context("--units expects a unit")?;
+                units = Some(
+                    LengthUnit::from_name(&name)
+                        .with_context(|| format!("Unknown unit '{}'", name))?,
+                );
+            },
+            "-o" => dest = Some(PathBuf::from(args.next().context("-o expects a path")?)),
+            _ if arg.starts_with('-') => bail!("Unknown option '{}'", arg),
+            _ if source.is_some() => bail!("Unexpected argument '{}'", arg),
+            _ => source = Some(PathBuf::from(arg)),
+        }
+    }
+    let Some(source) = source else {
+        bail!("Missing <source>");
+    };
+    Ok(Some(Args {
+        format,
+        source,
+        dest,
+        validate,
+        ascii_stl,
+        ascii_ply,
+        epsilon,
+        tessellation,
+        units,
+        lenient,
+    }))
+}
+
+/// Returns a callback printing the progress of `phase` as a percentage on stderr, if that's a
+/// terminal.
+fn progress_printer(phase: &'static str) -> impl FnMut(f32) {
+    let interactive = std::io::stderr().is_terminal();
+    let mut shown = None;
+    move |done| {
+        let percent = (done * 100.0) as u32;
+        if interactive && shown != Some(percent) {
+            shown = Some(percent);
+            eprint!("\r{} {}%", phase, percent);
+            if percent == 100 {
+                eprintln!();
+            }
+        }
+    }
+}
+
+/// Reads `path` as a STEP entity graph, translating BREP files on the way.
+///
+/// With `skipped`, STEP instances that fail to parse are left out and recorded there.
+fn read_graph(
+    format: Format,
+    path: &Path,
+    name: &str,
+    skipped: Option<&mut Vec<ImportWarning>>,
+) -> Result<EntityGraph> {
+    let mut progress = progress_printer("Parsing");
+    match format {
+        Format::Step => match skipped {
+            Some(skipped) => {
+                let (graph, errors) = step::read_step_lenient(path, &mut progress)?;
+                skipped.extend(errors.into_iter().map(ImportWarning::Entity));
+                if let Err(e) = check_schema(&graph) {
+                    log::warn!("Converting '{}' anyway: {}", path.display(), e);
+                }
+                Ok(graph)
+            },
+            None => {
+                let graph = step::read_step_with_progress(path, &mut progress)?;
+                check_schema(&graph)?;
+                Ok(graph)
+            },
+        },
+        Format::Brep => convert::brep_to_step(&brep::read_brep(path)?, name),
+        Format::Stl | Format::Obj | Format::Ply | Format::Glb | Format::Dxf => {
+            bail!("Mesh and wireframe files can't be read, only written")
+        },
+    }
+}
+
+/// Converts `graph` to millimetres and returns the factor from millimetres to the unit requested
+/// with `--units`.
+///
+/// Files whose units can't be detected stay unscaled, unless a unit was requested.
+fn normalize_for_export(graph: &mut EntityGraph, args: &Args) -> Result<f64> {
+    match normalize_units(graph) {
+        Ok(unit) => log::info!("'{}' is in {}", args.source.display(), unit),
+        Err(e) if args.units.is_some() => {
+            return Err(e.context("Couldn't detect the units to convert from"));
+        },
+        Err(e) => log::warn!("Keeping '{}' unscaled: {:#}", args.source.display(), e),
+    }
+    Ok(args
+        .units
+        .map_or(1.0, |unit| LengthUnit::Millimetre.scale_to(unit)))
+}
+
+/// Returns where the assembly of `graph`, read from `source`, places its parts. Assemblies that
+/// can't be resolved keep their parts where they are.
+fn part_placements(graph: &EntityGraph, source: &Path) -> PartPlacements {
+    PartPlacements::new(graph).unwrap_or_else(|e| {
+        log::warn!(
+            "Keeping the parts of '{}' unplaced: {:#}",
+            source.display(),
+            e
+        );
+        PartPlacements::default()
+    })
+}
+
+/// Prints what a STEP file contains and checks that every reference resolves.
+fn validate_step(path: &Path) -> Result<()> {
+    let graph = step::read_step(path)?;
+    println!("schema: {}", graph.schemas.join(", "));
+    println!("entities: {}", graph.len());
+    let mut counts = BTreeMap::new();
+    for entity in graph.entities.values() {
+        *counts.entry(entity.keyword.as_str()).or_insert(0) += 1;
+    }
+    for (keyword, count) in counts {
+        println!("    {}: {}", keyword, count);
+    }
+    println!(
+        "faces: {}, edges: {}, vertices: {}",
+        graph.instances_of("ADVANCED_FACE").count(),
+        graph.instances_of("EDGE_CURVE").count(),
+        graph.instances_of("VERTEX_POINT").count()
+    );
+    graph.check_references()
+}
+
+/// Prints what a BREP file contains and checks that every index resolves.
+fn validate_brep(path: &Path) -> Result<()> {
+    let model = brep::read_brep(path)?;
+    println!("format: CASCADE Topology V{}", model.version);
+    println!(
+        "locations: {}, curves: {}, 2d curves: {}, surfaces: {}, triangulations: {}",
+        model.locations.len(),
+        model.curves.len(),
+        model.curves_2d.len(),
+        model.surfaces.len(),
+        model.triangulations.len()
+    );
+    println!("shapes: {}", model.shapes.len());
+    println!(
+        "faces: {}, edges: {}, vertices: {}",
+        model.count(brep::ShapeKind::Face),
+        model.count(brep::ShapeKind::Edge),
+        model.count(brep::ShapeKind::Vertex)
+    );
+    model.validate()
+}
+
+/// Tessellates `source` like a mesh export to STL would and prints the defects slicers reject.
+fn validate_stl(input: Format, args: &Args, name: &str) -> Result<()> {
+    let (_, _, indices) = tessellate_mesh(input, args, name, None)?;
+    print_manifold_report(&indices, &args.source)
+}
+
+/// Welds the STL file `args.source` with `--epsilon` and prints the defects slicers reject.
+fn validate_stl_file(args: &Args) -> Result<()> {
+    let (vertices, indices) = read_stl(&args.source, Some(args.epsilon))?;
+    println!("vertices: {}", vertices.len());
+    print_manifold_report(&indices, &args.source)
+}
+
+/// Prints the [`check_manifold`] report of the welded triangle list `indices`, failing unless
+/// it's watertight.
+fn print_manifold_report(indices: &[u32], source: &Path) -> Result<()> {
+    let report = check_manifold(indices);
+    println!("triangles: {}", indices.len() / 3);
+    println!(
+        "non-manifold edges: {}, boundary edges: {}, isolated vertices: {}",
+        report.non_manifold_edges, report.boundary_edges, report.isolated_vertices
+    );
+    if !report.is_watertight() {
+        bail!("The STL mesh of '{}' isn't watertight", source.display());
+    }
+    Ok(())
+}
+
+/// Reads `args.source` and tessellates it into a mesh in the units requested with `--units`.
+///
+/// Returns the triangles as tessellated, then welded into vertices and indices, see [`weld`].
+fn tessellate_mesh(
+    input: Format,
+    args: &Args,
+    name: &str,
+    mut skipped: Option<&mut Vec<ImportWarning>>,
+) -> Result<(Vec<Vertex>, Vec<Vertex>, Vec<u32>)> {
+    let mut graph = read_graph(input, &args.source, name, skipped.as_deref_mut())
+        .with_context(|| format!("Couldn't convert '{}'", args.source.display()))?;
+    let scale = normalize_for_export(&mut graph, args)?;
+    let mut progress = progress_printer("Tessellating");
+    let mut tessellation =
+        tessellate_faces_with_progress(&graph, &args.tessellation, &mut progress)?;
+    part_placements(&graph, &args.source).place_tessellation(&mut tessellation);
+    if let Some(skipped) = skipped {
+        let faces = tessellation.skipped.into_iter();
+        skipped.extend(faces.map(|(id, reason)| ImportWarning::Face(id, reason)));
+    }
+    let mut vertices = tessellation.vertices;
+    if vertices.is_empty() {
+        if let Some(unsupported) = step::unsupported_surface(&graph) {
+            return Err(ImportError::from(unsupported).into());
+        }
+        bail!("'{}' has no faces to tessellate", args.source.display());
+    }
+    if scale != 1.0 {
+        for vertex in &mut vertices {
+            vertex.position = vertex.position.map(|c| (c as f64 * scale) as f32);
+        }
+    }
+    // Faces are tessellated one by one, welding closes the cracks along their edges
+    let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+    let (welded, indices) = weld(&vertices, &indices, args.epsilon);
+    Ok((vertices, welded, indices))
+}
+
+/// Prints what a `--lenient` conversion of `source` left out.
+fn print_skipped(source: &Path, skipped: &[ImportWarning]) {
+    if skipped.is_empty() {
+        return;
+    }
+    eprintln!(
+        "brepconv: skipped {} entities of '{}':",
+        skipped.len(),
+        source.display()
+    );
+    for warning in skipped {
+        eprintln!("    {}", warning);
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    let input = match args.format {
+        Some(format) => format,
+        None => Format::from_path(&args.source).with_context(|| {
+            format!(
+                "Can't infer the format of '{}', pass it with -f",
+                args.source.display()
+            )
+        })?,
+    };
+    let name = args
+        .source
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    if args.validate {
+        match input {
+            Format::Step => validate_step(&args.source)?,
+            Format::Brep => validate_brep(&args.source)?,
+            Format::Stl => validate_stl_file(&args)?,
+            Format::Obj | Format::Ply | Format::Glb | Format::Dxf => {
+                bail!("Mesh and wireframe files can't be validated")
+            },
+        }
+        if input != Format::Stl
+            && args.dest.as_deref().and_then(Format::from_path) == Some(Format::Stl)
+        {
+            validate_stl(input, &args, &name)?;
+        }
+        println!("'{}' is valid", args.source.display());
+        return Ok(());
+    }
+    // Unknown destination extensions keep the default of converting to the other CAD format
+    let output = args
+        .dest
+        .as_deref()
+        .and_then(Format::from_path)
+        .unwrap_or(input.other());
+    let dest = args
+        .dest
+        .clone()
+        .unwrap_or_else(|| args.source.with_extension(output.extension()));
+
+    let convert_error = || format!("Couldn't convert '{}'", args.source.display());
+    let mut skipped = Vec::new();
+    let mut lenient = args.lenient.then_some(&mut skipped);
+    let create = || {
+        File::create(&dest)
+            .map(BufWriter::new)
+            .with_context(|| format!("Couldn't create '{}'", dest.display()))
+    };
+    match (input, output) {
+        (Format::Stl | Format::Obj | Format::Ply | Format::Glb | Format::Dxf, _) => {
+            bail!("Mesh and wireframe files can't be read, only written")
+        },
+        (Format::Step, Format::Brep) => {
+            let graph = read_graph(input, &args.source, &name, lenient.as_deref_mut())?;
+            let model = convert::step_to_brep(&graph).with_context(convert_error)?;
+            brep::write_brep(&model, &mut create()?)?;
+        },
+        (Format::Brep, Format::Brep) => {
+            let model = brep::read_brep(&args.source)?;
+            brep::write_brep(&model, &mut create()?)?;
+        },
+        (_, Format::Step) => {
+            let graph = read_graph(input, &args.source, &name, lenient.as_deref_mut())
+                .with_context(convert_error)?;
+            let file_name = dest
+                .file_name()
+                .map(|s| s.to_string_lossy())
+                .unwrap_or_default();
+            step::write_step(&graph, &file_name, &mut create()?)?;
+        },
+        (_, Format::Dxf) => {
+            let mut graph = read_graph(input, &args.source, &name, lenient.as_deref_mut())
+                .with_context(convert_error)?;
+            let scale = normalize_for_export(&mut graph, &args)?;
+            let mut edges = step::edges(&graph, &args.tessellation);
+            part_placements(&graph, &args.source).place_edges(&mut edges);
+            if edges.is_empty() {
+                bail!("'{}' has no edges", args.source.display());
+            }
+            if scale != 1.0 {
+                for point in edges.iter_mut().flat_map(|edge| &mut edge.points) {
+                    *point = point.map(|c| c * scale);
+                }
+            }
+            export::write_dxf(&edges, &mut create()?)?;
+        },
+        (_, Format::Stl | Format::Obj | Format::Ply | Format::Glb) => {
+            let (vertices, welded, indices) = tessellate_mesh(input, &args, &name, lenient)?;
+            let mut w = create()?;
+            match output {
+                Format::Obj => export::write_obj_welded(&welded, &indices, args.epsilon, &mut w)?,
+                Format::Ply => {
+                    let format = if args.ascii_ply {
+                        export::PlyFormat::Ascii
+                    } else {
+                        export::PlyFormat::BinaryLittleEndian
+                    };
+                    export::write_ply(&welded, &indices, None, format, &mut w)?;
+                },
+                Format::Glb => {
+                    let (vertices, normals, indices) = export::flat_shaded(&vertices);
+                    export::write_glb(&vertices, &normals, &indices, &mut w)?;
+                },
+                _ => {
+                    // STL has no index buffer, but the facets now share bit-identical corners
+                    let vertices: Vec<_> = indices.iter().map(|&i| welded[i as usize]).collect();
+                    if args.ascii_stl {
+                        export::write_stl_ascii(&vertices, &name, &mut w)?;
+                    } else {
+                        export::write_stl_binary(&vertices, &mut w)?;
+                    }
+                },
+            }
+        },
+    }
+    print_skipped(&args.source, &skipped);
+    log::info!("Wrote '{}'", dest.display());
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{}", HELP_TEXT);
+            return ExitCode::SUCCESS;
+        },
+        Err(e) => {
+            eprintln!("brepconv: error: {:#}", e);
+            eprint!("{}", HELP_TEXT);
+            return ExitCode::FAILURE;
+        },
+    };
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("brepconv: error: {:#}", e);
+            exit_code(&ImportError::from(e))
+        },
+    }
+}
+
+/// Exit status of a failed conversion, see the help text.
+fn exit_code(e: &ImportError) -> ExitCode {
+    match e {
+        ImportError::Io(_) => ExitCode::from(2),
+        ImportError::ParseError(_) => ExitCode::from(3),
+        ImportError::UnknownSchema(_) => ExitCode::from(4),
+        ImportError::UnsupportedSurface { .. } => ExitCode::from(5),
+        ImportError::Cancelled | ImportError::Other(_) => ExitCode::FAILURE,
+    }
 }
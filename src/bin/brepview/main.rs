@@ -1,165 +1,222 @@
-mod mesh;
-mod prelude;
-mod state;
+//! Native viewer: the library's [`State`] in a window, with imports on a worker thread and the
+//! render options picked on the command line.
 // STD
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 // Dependencies
-use bytemuck::{Pod, Zeroable};
-use wgpu::VertexAttribute;
+use anyhow::{Context, Result, anyhow, bail};
+use log::{error, info, warn};
 use winit::{
     application::ApplicationHandler,
     event::{KeyEvent, WindowEvent},
-    event_loop::{ActiveEventLoop, EventLoop},
+    event_loop::{ActiveEventLoop, EventLoop, EventLoopProxy},
     keyboard::{KeyCode, PhysicalKey},
     window::Window,
 };
 // Local
-use crate::{
-    prelude::*,
-    state::{PipelineInfo, ShaderInfo, State},
-    mesh::Vertex,
-};
+use brepimport::import::{self, ImportError};
+use brepimport::material::MaterialPreset;
+use brepimport::state::{RenderOptions, State};
+use brepimport::step::tessellate::{Tessellation, TessellationOptions};
+
+/// Title of the window, import errors are shown after it
+const WINDOW_TITLE: &str = "A fantastic window!";
+
+/// Samples per pixel the window is drawn with, unless the adapter can't
+const SAMPLE_COUNT: u32 = 4;
+
+/// Imports finished on a worker thread, see [`spawn_import`]
+enum ImportEvent {
+    /// Replaces the drawn models
+    Loaded(Tessellation),
+    /// The import failed, with the message to show
+    Failed(String),
+}
 
 /// Handle for a graphical application.
 #[derive(Default)]
-struct App<'a> {
+struct App {
     /// The graphical state of [`App`]
-    state: Option<State<'a>>,
+    state: Option<State>,
+    /// Sends finished imports back to the event loop
+    proxy: Option<EventLoopProxy<ImportEvent>>,
+    /// File to import once the window exists, from the command line
+    startup_file: Option<PathBuf>,
+    /// Cancellation flag of the latest import, see [`cancel_import`]
+    import: Option<Arc<AtomicBool>>,
+    /// Set on the command line, see [`parse_args`]
+    options: RenderOptions,
 }
 
-#[repr(C, packed)]
-#[derive(Clone, Copy, Pod, Zeroable)]
-struct MyVertex {
-    position: [f32; 3],
-    color: [f32; 3],
+/// Imports the STEP, BREP, STL or OBJ file at `path`, going by its extension. STEP imports stop
+/// once `cancel` is set.
+///
+/// The triangles of STL and OBJ meshes are unrolled into a tessellation without faces, so they're
+/// shaded with the material.
+fn import_file(path: &Path, cancel: &AtomicBool) -> Result<Tessellation, ImportError> {
+    let options = TessellationOptions::auto();
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    let (vertices, indices) = match extension.to_ascii_lowercase().as_str() {
+        "step" | "stp" => {
+            return import::import_step_cancellable(path, &options, &mut |_| {}, cancel);
+        },
+        "brep" => return Ok(import::import_brep(path, &options)?),
+        "stl" => import::read_stl(path, None)?,
+        "obj" => import::read_obj(path)?,
+        _ => {
+            return Err(anyhow!(
+                "Can't open '{}', expected a STEP, BREP, STL or OBJ file",
+                path.display()
+            )
+            .into());
+        },
+    };
+    let vertices: Vec<_> = indices.iter().map(|&i| vertices[i as usize]).collect();
+    Ok(Tessellation {
+        face_ids: vec![0; vertices.len() / 3],
+        vertices,
+        ..Default::default()
+    })
 }
 
-impl MyVertex {
-    const ATTRIBUTES: [VertexAttribute; 2] = wgpu::vertex_attr_array![
-        0 => Float32x3,// position
-        1 => Float32x3,// color
-    ];
+/// Imports the file at `path` on a worker thread, the window keeps redrawing meanwhile, see
+/// [`import_file`]. The tessellation reaches [`State::load_tessellation`] as an
+/// [`ImportEvent::Loaded`] sent through `proxy`, replacing the drawn models, or the error as an
+/// [`ImportEvent::Failed`].
+///
+/// Returns the flag cancelling the import, once set the tessellation is dropped instead of being
+/// sent.
+fn spawn_import(proxy: EventLoopProxy<ImportEvent>, path: &Path) -> Arc<AtomicBool> {
+    info!("Importing '{}'", path.display());
+    let path = path.to_path_buf();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let flag = cancel.clone();
+    std::thread::spawn(move || {
+        let tessellation = match import_file(&path, &flag) {
+            Ok(tessellation) => tessellation,
+            Err(ImportError::Cancelled) => {
+                info!("Cancelled importing '{}'", path.display());
+                return;
+            },
+            Err(e) => {
+                error!("Couldn't import '{}': {:#}", path.display(), e);
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let message = format!("Couldn't import '{}': {}", name, e);
+                let _ = proxy.send_event(ImportEvent::Failed(message));
+                return;
+            },
+        };
+        // Cancelled too late to stop the import, but a newer one may be underway
+        if flag.load(Ordering::Relaxed) {
+            info!("Cancelled importing '{}'", path.display());
+            return;
+        }
+        info!(
+            "Imported '{}', {} triangles",
+            path.display(),
+            tessellation.face_ids.len()
+        );
+        // Only fails once the event loop is gone, and the window with it
+        let _ = proxy.send_event(ImportEvent::Loaded(tessellation));
+    });
+    cancel
 }
 
-impl Vertex for MyVertex {
-    fn layout<'a>() -> wgpu::VertexBufferLayout<'a> {
-        wgpu::VertexBufferLayout {
-            array_stride: size_of::<Self>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &Self::ATTRIBUTES,
-        }
-    }
-    fn data<'a>(&self) -> &'a [u8] {
-        &[1u8, 2u8, 3u8]
+/// Cancels the import behind `import`, if any. Finished imports ignore it.
+fn cancel_import(import: &mut Option<Arc<AtomicBool>>) {
+    if let Some(cancel) = import.take() {
+        cancel.store(true, Ordering::Relaxed);
     }
 }
 
-// Winding: CCW
-static VERTEX_DATA: &[MyVertex] = &[
-    // Top Center
-    MyVertex {
-        position: [0.0, 0.5, 0.1],
-        color: [1.0, 0.0, 0.0],
-    },
-    // Bottom Left
-    MyVertex {
-        position: [-0.5, -0.5, 0.1],
-        color: [0.0, 1.0, 0.0],
-    },
-    // Bottom Right
-    MyVertex {
-        position: [0.5, -0.5, 0.1],
-        color: [0.0, 0.0, 1.0],
-    },
-];
-
-impl ApplicationHandler<state::ResourceEvent<'static>> for App<'_> {
-    /// Creates the window and event loop
+impl ApplicationHandler<ImportEvent> for App {
+    /// Creates the window and the [`State`] drawing into it
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         info!("Creating new Window");
-        let window_attributes = Window::default_attributes().with_title("A fantastic window!");
-        let window = event_loop.create_window(window_attributes).unwrap();
-        let window = Arc::new(window);
-
-        let info = PipelineInfo {
-            vertex_layout: MyVertex::layout(),
-            vertex_buffer_init: wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(VERTEX_DATA),
-                usage: wgpu::BufferUsages::VERTEX,
-            },
-            index_buffer_init: (0, None),
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: None,
-            shader_info: ShaderInfo {
-                desc: wgpu::ShaderModuleDescriptor {
-                    label: Some("Shader Model"),
-                    source: wgpu::ShaderSource::Wgsl(include_str!("./shader.wgsl").into()),
-                },
-                vertex_entry: Some("vs_main"),
-                fragment_entry: Some("fs_main"),
+        let window_attributes = Window::default_attributes().with_title(WINDOW_TITLE);
+        let window = match event_loop.create_window(window_attributes) {
+            Ok(window) => Arc::new(window),
+            Err(e) => {
+                eprintln!("brepview: error: Couldn't create a window: {}", e);
+                event_loop.exit();
+                return;
             },
         };
-        self.state = Some(pollster::block_on(State::new(window, info)).unwrap());
+        match pollster::block_on(State::with_options(window, self.options.clone())) {
+            Ok(state) => self.state = Some(state),
+            Err(e) => {
+                eprintln!("brepview: error: {:#}", e);
+                event_loop.exit();
+                return;
+            },
+        }
         info!("Window was created.");
+        if let (Some(proxy), Some(path)) = (&self.proxy, self.startup_file.take()) {
+            self.import = Some(spawn_import(proxy.clone(), &path));
+        }
     }
 
-    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: state::ResourceEvent<'static>) {
-        let state = match &mut self.state {
-            Some(state) => state,
-            None => return,
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: ImportEvent) {
+        let Some(state) = &mut self.state else {
+            return;
         };
-        state.handle_event(event).expect("Couldn't handle user event.");
+        match event {
+            ImportEvent::Loaded(tessellation) => {
+                if let Err(e) = state.load_tessellation(tessellation) {
+                    error!("{:#}", e);
+                    state.show_toast(&format!("{:#}", e));
+                }
+            },
+            ImportEvent::Failed(message) => state.show_toast(&message),
+        }
     }
 
     fn window_event(
         &mut self,
-        event_loop: &winit::event_loop::ActiveEventLoop,
-        id: winit::window::WindowId,
-        event: winit::event::WindowEvent,
+        event_loop: &ActiveEventLoop,
+        _window_id: winit::window::WindowId,
+        event: WindowEvent,
     ) {
-        let state = match &mut self.state {
-            Some(state) => state,
-            None => return,
+        let Some(state) = &mut self.state else {
+            return;
         };
-        trace!("Received window event: {:?}", event);
         match event {
-            WindowEvent::Focused(v) => {
-                if v == true {
-                    info!("Window {:?} was focused.", id);
-                }
-            },
-            WindowEvent::RedrawRequested => match state.render() {
-                Err(e) => {
-                    error!("state.render() returned error: {:?}", e);
-                    panic!();
-                },
-                _ => (),
-            },
-            WindowEvent::Resized(size) => {
-                state.resize(size);
-            },
             WindowEvent::CloseRequested => {
                 info!("Window is now closing.");
                 event_loop.exit();
             },
-            WindowEvent::MouseInput {
-                button,
-                state: button_state,
-                ..
-            } => {
-                if log_mouse_event() {
-                    debug!(
-                        "Mouse event: button = {:?}, is_pressed = {:?}",
-                        button, button_state
-                    );
+            WindowEvent::Resized(size) => state.resize(size.width, size.height),
+            WindowEvent::RedrawRequested => {
+                state.update();
+                match state.render() {
+                    Ok(()) => (),
+                    Err(wgpu::SurfaceError::OutOfMemory) => {
+                        error!("Out of memory, exiting.");
+                        event_loop.exit();
+                    },
+                    // Timeouts and the like only cost a frame
+                    Err(e) => warn!("Couldn't render a frame: {}", e),
                 }
             },
-            WindowEvent::CursorMoved { position, .. } => {
-                if log_mouse_event() {
-                    debug!("Mouse event: position = {:?}", position);
+            WindowEvent::DroppedFile(path) => {
+                if let Some(proxy) = &self.proxy {
+                    // Only the latest file is shown, the one still importing is wasted work
+                    cancel_import(&mut self.import);
+                    self.import = Some(spawn_import(proxy.clone(), &path));
                 }
             },
+            WindowEvent::MouseInput {
+                state: button_state,
+                button,
+                ..
+            } => state.handle_mouse_button(button, button_state.is_pressed()),
+            WindowEvent::CursorMoved { position, .. } => state.handle_cursor_moved(position),
+            WindowEvent::CursorLeft { .. } => state.cursor_position = None,
+            WindowEvent::MouseWheel { delta, .. } => state.handle_mouse_wheel(delta),
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
@@ -168,37 +225,75 @@ impl ApplicationHandler<state::ResourceEvent<'static>> for App<'_> {
                         ..
                     },
                 ..
-            } => {
-                if log_key_event() {
-                    debug!(
-                        "Key event: code = {:?}, is_pressed = {:?}",
-                        code,
-                        key_state.is_pressed()
-                    );
-                }
-                match (code, key_state.is_pressed()) {
-                    (KeyCode::KeyQ, true) => event_loop.exit(),
-                    _ => (),
-                }
+            } => match (code, key_state.is_pressed()) {
+                (KeyCode::Escape, true) => cancel_import(&mut self.import),
+                (code, is_pressed) => state.handle_key(event_loop, code, is_pressed),
             },
             _ => (),
         }
     }
 }
 
-const fn log_mouse_event() -> bool {
-    false
-}
-
-const fn log_key_event() -> bool {
-    true
+/// Reads the file to open and the render options from the command line.
+fn parse_args(app: &mut App) -> Result<()> {
+    app.options.sample_count = SAMPLE_COUNT;
+    app.options.present_mode = wgpu::PresentMode::Fifo;
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--no-vsync" {
+            app.options.present_mode = wgpu::PresentMode::Mailbox;
+        } else if arg == "--color-space" {
+            let Some(value) = args.next() else {
+                bail!("--color-space needs a value: srgb, linear or hdr");
+            };
+            app.options.color_space = value.to_string_lossy().parse()?;
+        } else if arg == "--material" {
+            let Some(value) = args.next() else {
+                bail!("--material needs a value: steel, plastic or brass");
+            };
+            let preset: MaterialPreset = value.to_string_lossy().parse()?;
+            app.options.material = preset.material();
+        } else if arg == "--post-processing" {
+            app.options.post_processing = true;
+        } else if arg == "--tone-mapping" {
+            let Some(value) = args.next() else {
+                bail!("--tone-mapping needs a value: reinhard or aces");
+            };
+            app.options.tone_mapping = value.to_string_lossy().parse()?;
+        } else if arg == "--depth-bias" {
+            let Some(value) = args.next() else {
+                bail!("--depth-bias needs a value: a whole number of depth steps");
+            };
+            let value = value.to_string_lossy();
+            app.options.depth_bias = value
+                .parse()
+                .with_context(|| format!("Invalid depth bias '{}'", value))?;
+        } else if arg == "--depth-bias-slope-scale" {
+            let Some(value) = args.next() else {
+                bail!("--depth-bias-slope-scale needs a value: a factor like 1.0");
+            };
+            let value = value.to_string_lossy();
+            app.options.depth_bias_slope_scale = value
+                .parse()
+                .with_context(|| format!("Invalid depth bias slope scale '{}'", value))?;
+        } else if app.startup_file.is_none() {
+            app.startup_file = Some(PathBuf::from(arg));
+        } else {
+            bail!("Unexpected argument '{}'", arg.to_string_lossy());
+        }
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
     env_logger::init();
     info!("App was started.");
     let event_loop = EventLoop::with_user_event().build()?;
-    let mut app = App::default();
+    let mut app = App {
+        proxy: Some(event_loop.create_proxy()),
+        ..App::default()
+    };
+    parse_args(&mut app)?;
     event_loop.run_app(&mut app)?;
     Ok(())
 }
@@ -0,0 +1,284 @@
+// STD
+use std::hash::{Hash, Hasher};
+
+// Local modules
+use super::model::*;
+use crate::hash::StableHasher;
+
+/// Coordinates are compared in steps of this size, so they survive a round-trip through text
+const QUANTUM: f64 = 1e-9;
+
+/// Feeds `value` rounded to [`QUANTUM`] into `state`, so `-0.0` and `0.0` hash alike.
+fn hash_f64(value: f64, state: &mut impl Hasher) {
+    ((value / QUANTUM).round() as i64).hash(state);
+}
+
+fn hash_point(point: &[f64], state: &mut impl Hasher) {
+    point.iter().for_each(|&c| hash_f64(c, state));
+}
+
+/// Shape hashes, computed at most once per shape since edges and vertices are shared
+struct Fingerprinter<'a> {
+    model: &'a BrepModel,
+    shapes: Vec<Option<u64>>,
+}
+
+impl Fingerprinter<'_> {
+    /// Hashes the location with the 1-based `index` by its transform, `0` is the identity.
+    fn hash_location(&self, index: usize, state: &mut impl Hasher) {
+        let Some(location) = index
+            .checked_sub(1)
+            .and_then(|i| self.model.locations.get(i))
+        else {
+            0u8.hash(state);
+            return;
+        };
+        match location {
+            Location::Elementary(rows) => {
+                1u8.hash(state);
+                rows.iter().for_each(|row| hash_point(row, state));
+            },
+            Location::Composed(factors) => {
+                2u8.hash(state);
+                for &(location, power) in factors {
+                    // Composed locations only reference earlier ones, so this terminates
+                    if location < index {
+                        self.hash_location(location, state);
+                    }
+                    power.hash(state);
+                }
+            },
+        }
+    }
+
+    fn hash_curve(&self, index: usize, state: &mut impl Hasher) {
+        match self.model.curve(index) {
+            Some(Curve::Line { origin, direction }) => {
+                1u8.hash(state);
+                hash_point(origin, state);
+                hash_point(direction, state);
+            },
+            Some(Curve::Raw(tokens)) => {
+                2u8.hash(state);
+                tokens.hash(state);
+            },
+            Some(Curve::Circle {
+                center,
+                normal,
+                x_dir,
+                y_dir,
+                radius,
+            }) => {
+                3u8.hash(state);
+                [center, normal, x_dir, y_dir]
+                    .iter()
+                    .for_each(|p| hash_point(*p, state));
+                hash_f64(*radius, state);
+            },
+            None => 0u8.hash(state),
+        }
+    }
+
+    fn hash_surface(&self, index: usize, state: &mut impl Hasher) {
+        match self.model.surface(index) {
+            Some(Surface::Plane {
+                origin,
+                normal,
+                x_dir,
+                y_dir,
+            }) => {
+                1u8.hash(state);
+                [origin, normal, x_dir, y_dir]
+                    .iter()
+                    .for_each(|p| hash_point(*p, state));
+            },
+            Some(Surface::Raw(tokens)) => {
+                2u8.hash(state);
+                tokens.hash(state);
+            },
+            Some(
+                surface
+                @ (Surface::Cylinder { .. } | Surface::Cone { .. } | Surface::Sphere { .. }),
+            ) => {
+                std::mem::discriminant(surface).hash(state);
+                surface
+                    .frame()
+                    .iter()
+                    .flatten()
+                    .for_each(|p| hash_point(p, state));
+                match *surface {
+                    Surface::Cone {
+                        radius, semi_angle, ..
+                    } => {
+                        hash_f64(radius, state);
+                        hash_f64(semi_angle, state);
+                    },
+                    Surface::Cylinder { radius, .. } | Surface::Sphere { radius, .. } => {
+                        hash_f64(radius, state);
+                    },
+                    _ => {},
+                }
+            },
+            None => 0u8.hash(state),
+        }
+    }
+
+    fn hash_geometry(&self, geometry: &ShapeGeometry, state: &mut impl Hasher) {
+        std::mem::discriminant(geometry).hash(state);
+        match geometry {
+            ShapeGeometry::Vertex { point, .. } => hash_point(point, state),
+            ShapeGeometry::Edge {
+                degenerated, reps, ..
+            } => {
+                degenerated.hash(state);
+                for rep in reps {
+                    match rep {
+                        EdgeRep::Curve3d {
+                            curve,
+                            location,
+                            first,
+                            last,
+                        } => {
+                            self.hash_curve(*curve, state);
+                            self.hash_location(*location, state);
+                            hash_f64(*first, state);
+                            hash_f64(*last, state);
+                        },
+                        // Parametric representations depend on the faces, which are hashed anyway
+                        EdgeRep::Raw(_) => {},
+                    }
+                }
+            },
+            ShapeGeometry::Face {
+                surface, location, ..
+            } => {
+                self.hash_surface(*surface, state);
+                self.hash_location(*location, state);
+            },
+            ShapeGeometry::None => {},
+        }
+    }
+
+    /// Returns the hash of the shape with the 1-based `index` and everything below it.
+    fn shape(&mut self, index: usize) -> u64 {
+        if let Some(hash) = self.shapes.get(index.wrapping_sub(1)).copied().flatten() {
+            return hash;
+        }
+        let Some(shape) = self.model.shape(index) else {
+            return 0;
+        };
+        // Placeholder while hashing the children, so a malformed cycle can't recurse forever
+        self.shapes[index - 1] = Some(0);
+        let mut state = StableHasher::new();
+        shape.kind.hash(&mut state);
+        self.hash_geometry(&shape.geometry, &mut state);
+        // Sorted, so the order sub-shapes are listed in doesn't matter
+        let mut children: Vec<u64> = shape
+            .children
+            .iter()
+            .map(|child| self.shape_ref(child))
+            .collect();
+        children.sort_unstable();
+        children.hash(&mut state);
+        let hash = state.finish();
+        self.shapes[index - 1] = Some(hash);
+        hash
+    }
+
+    fn shape_ref(&mut self, shape_ref: &ShapeRef) -> u64 {
+        let mut state = StableHasher::new();
+        self.shape(shape_ref.shape).hash(&mut state);
+        shape_ref.orientation.hash(&mut state);
+        self.hash_location(shape_ref.location, &mut state);
+        state.finish()
+    }
+}
+
+impl BrepModel {
+    /// Returns a hash of the model's geometry and topology, equal for models describing the same
+    /// shape.
+    ///
+    /// Shapes, curves, surfaces and locations are hashed by what they contain rather than by
+    /// their indices, so the numbering and order of the tables don't matter, and coordinates are
+    /// rounded to 1e-9 so writing and reading the model back keeps the fingerprint. Meshes,
+    /// tolerances, flags and the file version are left out. Without a root, the shapes no other
+    /// shape references are hashed as a whole.
+    pub fn fingerprint(&self) -> u64 {
+        let mut fingerprinter = Fingerprinter {
+            model: self,
+            shapes: vec![None; self.shapes.len()],
+        };
+        if let Some(root) = &self.root {
+            return fingerprinter.shape_ref(root);
+        }
+        let mut referenced = vec![false; self.shapes.len()];
+        for child in self.shapes.iter().flat_map(|shape| &shape.children) {
+            if let Some(flag) = referenced.get_mut(child.shape.wrapping_sub(1)) {
+                *flag = true;
+            }
+        }
+        let mut top_level: Vec<u64> = (1..=self.shapes.len())
+            .filter(|&index| !referenced[index - 1])
+            .map(|index| fingerprinter.shape(index))
+            .collect();
+        top_level.sort_unstable();
+        let mut state = StableHasher::new();
+        top_level.hash(&mut state);
+        state.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brep::{parse_brep, read_brep, write_brep};
+
+    fn test_model() -> BrepModel {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/brep/test.brep");
+        read_brep(&path).unwrap()
+    }
+
+    #[test]
+    fn survives_write_and_read() {
+        let model = test_model();
+        let mut bytes = Vec::new();
+        write_brep(&model, &mut bytes).unwrap();
+        let reread = parse_brep(std::str::from_utf8(&bytes).unwrap()).unwrap();
+        assert_eq!(reread.fingerprint(), model.fingerprint());
+    }
+
+    #[test]
+    fn ignores_numbering_and_order() {
+        let model = test_model();
+        let count = model.shapes.len();
+        let renumber = |shape_ref: &mut ShapeRef| shape_ref.shape = count + 1 - shape_ref.shape;
+
+        // Shapes listed back to front, with their sub-shapes reversed as well
+        let mut reordered = model.clone();
+        reordered.shapes.reverse();
+        for shape in &mut reordered.shapes {
+            shape.children.reverse();
+            shape.children.iter_mut().for_each(renumber);
+        }
+        reordered.root.iter_mut().for_each(renumber);
+        reordered.validate().unwrap();
+        assert_ne!(reordered, model);
+        assert_eq!(reordered.fingerprint(), model.fingerprint());
+    }
+
+    #[test]
+    fn changes_when_a_vertex_moves() {
+        let model = test_model();
+        let mut moved = model.clone();
+        let point = moved
+            .shapes
+            .iter_mut()
+            .find_map(|shape| match &mut shape.geometry {
+                ShapeGeometry::Vertex { point, .. } => Some(point),
+                _ => None,
+            })
+            .unwrap();
+        point[0] += 1e-3;
+        assert_ne!(moved.fingerprint(), model.fingerprint());
+    }
+}
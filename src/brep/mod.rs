@@ -0,0 +1,9 @@
+//! Reader and writer for OpenCASCADE ASCII `.brep` files.
+mod fingerprint;
+pub mod model;
+pub mod reader;
+pub mod writer;
+
+pub use model::*;
+pub use reader::{parse_brep, read_brep};
+pub use writer::write_brep;
@@ -0,0 +1,429 @@
+// Dependencies
+use anyhow::{Result, anyhow, bail};
+use cgmath::{Matrix4, SquareMatrix};
+
+/// An in-memory OpenCASCADE BREP shape set.
+///
+/// Geometry and shape indices follow the file format: they're 1-based and `0` means "none".
+/// Geometry the crate doesn't understand yet is kept as raw tokens so it survives a round-trip.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BrepModel {
+    /// Format revision from the `CASCADE Topology V<n>` header
+    pub version: u32,
+    pub locations: Vec<Location>,
+    pub curves_2d: Vec<Curve2d>,
+    pub curves: Vec<Curve>,
+    pub polygons_3d: Vec<Polygon3d>,
+    pub polygons_on_triangulation: Vec<PolygonOnTriangulation>,
+    pub surfaces: Vec<Surface>,
+    pub triangulations: Vec<Triangulation>,
+    /// Topological shapes, `shapes[i - 1]` is the shape with index `i`
+    pub shapes: Vec<TShape>,
+    /// The shape the file describes
+    pub root: Option<ShapeRef>,
+}
+
+impl Surface {
+    /// Returns the origin, main axis, x and y directions of the frame the surface is placed in.
+    ///
+    /// OpenCASCADE frames may be left-handed, in which case the surface normal points the other
+    /// way. Raw surfaces return `None`.
+    pub fn frame(&self) -> Option<[[f64; 3]; 4]> {
+        match *self {
+            Surface::Plane {
+                origin,
+                normal: axis,
+                x_dir,
+                y_dir,
+            }
+            | Surface::Cylinder {
+                origin,
+                axis,
+                x_dir,
+                y_dir,
+                ..
+            }
+            | Surface::Cone {
+                origin,
+                axis,
+                x_dir,
+                y_dir,
+                ..
+            }
+            | Surface::Sphere {
+                center: origin,
+                axis,
+                x_dir,
+                y_dir,
+                ..
+            } => Some([origin, axis, x_dir, y_dir]),
+            Surface::Raw(_) => None,
+        }
+    }
+}
+
+impl BrepModel {
+    /// Returns the shape with the 1-based `index`.
+    pub fn shape(&self, index: usize) -> Option<&TShape> {
+        index.checked_sub(1).and_then(|i| self.shapes.get(i))
+    }
+
+    /// Returns the curve with the 1-based `index`.
+    pub fn curve(&self, index: usize) -> Option<&Curve> {
+        index.checked_sub(1).and_then(|i| self.curves.get(i))
+    }
+
+    /// Returns the surface with the 1-based `index`.
+    pub fn surface(&self, index: usize) -> Option<&Surface> {
+        index.checked_sub(1).and_then(|i| self.surfaces.get(i))
+    }
+
+    /// Returns the transform of every location, `transforms[i - 1]` belongs to the location with
+    /// index `i`.
+    ///
+    /// Composed locations multiply their factors right to left, like OpenCASCADE reads them, and
+    /// may only reference earlier locations.
+    pub fn location_transforms(&self) -> Result<Vec<Matrix4<f64>>> {
+        let mut transforms: Vec<Matrix4<f64>> = Vec::with_capacity(self.locations.len());
+        for (i, location) in self.locations.iter().enumerate() {
+            let transform = match location {
+                Location::Elementary(rows) => Matrix4::new(
+                    rows[0][0], rows[1][0], rows[2][0], 0.0, //
+                    rows[0][1], rows[1][1], rows[2][1], 0.0, //
+                    rows[0][2], rows[1][2], rows[2][2], 0.0, //
+                    rows[0][3], rows[1][3], rows[2][3], 1.0,
+                ),
+                Location::Composed(factors) => {
+                    let mut transform = Matrix4::identity();
+                    for &(factor, power) in factors {
+                        let Some(&base) = factor.checked_sub(1).and_then(|f| transforms.get(f))
+                        else {
+                            bail!("Location {} references location {}", i + 1, factor);
+                        };
+                        let mut base = if power < 0 {
+                            base.invert()
+                                .ok_or_else(|| anyhow!("Location {} can't be inverted", factor))?
+                        } else {
+                            base
+                        };
+                        // Exponentiation by squaring, powers may be large
+                        let mut powered = Matrix4::identity();
+                        let mut exponent = power.unsigned_abs();
+                        while exponent > 0 {
+                            if exponent & 1 == 1 {
+                                powered = powered * base;
+                            }
+                            base = base * base;
+                            exponent >>= 1;
+                        }
+                        transform = powered * transform;
+                    }
+                    transform
+                },
+            };
+            transforms.push(transform);
+        }
+        Ok(transforms)
+    }
+
+    /// Counts the shapes of the given kind.
+    pub fn count(&self, kind: ShapeKind) -> usize {
+        self.shapes.iter().filter(|s| s.kind == kind).count()
+    }
+
+    /// Checks that every shape, location, curve, surface and triangulation index points into its
+    /// table.
+    pub fn validate(&self) -> Result<()> {
+        let check = |what: &str, index: usize, len: usize, owner: usize| -> Result<()> {
+            if index > len {
+                bail!(
+                    "Shape {}: {} {} is out of range (there are {})",
+                    owner,
+                    what,
+                    index,
+                    len
+                );
+            }
+            Ok(())
+        };
+        for (i, shape) in self.shapes.iter().enumerate() {
+            let owner = i + 1;
+            for child in &shape.children {
+                if self.shape(child.shape).is_none() {
+                    bail!(
+                        "Shape {}: child shape {} is out of range",
+                        owner,
+                        child.shape
+                    );
+                }
+                check("location", child.location, self.locations.len(), owner)?;
+            }
+            match &shape.geometry {
+                ShapeGeometry::Edge { reps, .. } => {
+                    for rep in reps {
+                        if let EdgeRep::Curve3d {
+                            curve, location, ..
+                        } = rep
+                        {
+                            check("curve", *curve, self.curves.len(), owner)?;
+                            check("location", *location, self.locations.len(), owner)?;
+                        }
+                    }
+                },
+                ShapeGeometry::Face {
+                    surface,
+                    location,
+                    triangulation,
+                    ..
+                } => {
+                    check("surface", *surface, self.surfaces.len(), owner)?;
+                    check("location", *location, self.locations.len(), owner)?;
+                    if let Some(triangulation) = triangulation {
+                        check(
+                            "triangulation",
+                            *triangulation,
+                            self.triangulations.len(),
+                            owner,
+                        )?;
+                    }
+                },
+                ShapeGeometry::Vertex { .. } | ShapeGeometry::None => {},
+            }
+        }
+        if let Some(root) = self.root
+            && self.shape(root.shape).is_none()
+        {
+            bail!("Root shape {} is out of range", root.shape);
+        }
+        Ok(())
+    }
+}
+
+/// An entry of the `Locations` table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Location {
+    /// A 3x4 affine transform, stored row by row
+    Elementary([[f64; 4]; 3]),
+    /// Product of earlier locations raised to a power, as `(location index, power)` pairs
+    Composed(Vec<(usize, i32)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Curve2d {
+    Line {
+        origin: [f64; 2],
+        direction: [f64; 2],
+    },
+    /// Any other curve, as its raw tokens including the type code
+    Raw(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Curve {
+    Line {
+        origin: [f64; 3],
+        direction: [f64; 3],
+    },
+    /// Parametrized by the angle from `x_dir`, counterclockwise around `normal`
+    Circle {
+        center: [f64; 3],
+        normal: [f64; 3],
+        x_dir: [f64; 3],
+        y_dir: [f64; 3],
+        radius: f64,
+    },
+    /// Any other curve, as its raw tokens including the type code
+    Raw(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Surface {
+    Plane {
+        origin: [f64; 3],
+        normal: [f64; 3],
+        x_dir: [f64; 3],
+        y_dir: [f64; 3],
+    },
+    Cylinder {
+        origin: [f64; 3],
+        axis: [f64; 3],
+        x_dir: [f64; 3],
+        y_dir: [f64; 3],
+        radius: f64,
+    },
+    /// `radius` is measured in the plane through `origin`, `semi_angle` is in radians
+    Cone {
+        origin: [f64; 3],
+        axis: [f64; 3],
+        x_dir: [f64; 3],
+        y_dir: [f64; 3],
+        radius: f64,
+        semi_angle: f64,
+    },
+    Sphere {
+        center: [f64; 3],
+        axis: [f64; 3],
+        x_dir: [f64; 3],
+        y_dir: [f64; 3],
+        radius: f64,
+    },
+    /// Any other surface, as its raw tokens including the type code
+    Raw(Vec<String>),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Polygon3d {
+    pub deflection: f64,
+    pub nodes: Vec<[f64; 3]>,
+    pub parameters: Option<Vec<f64>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PolygonOnTriangulation {
+    /// 1-based node indices into the triangulation
+    pub nodes: Vec<usize>,
+    pub deflection: f64,
+    pub parameters: Option<Vec<f64>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Triangulation {
+    pub deflection: f64,
+    pub nodes: Vec<[f64; 3]>,
+    pub uv_nodes: Option<Vec<[f64; 2]>>,
+    /// 1-based node indices
+    pub triangles: Vec<[usize; 3]>,
+    /// Per-node normals, only stored from format V3 on
+    pub normals: Option<Vec<[f32; 3]>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShapeKind {
+    Vertex,
+    Edge,
+    Wire,
+    Face,
+    Shell,
+    Solid,
+    CompSolid,
+    Compound,
+}
+
+impl ShapeKind {
+    /// The two letter tag used in the `TShapes` section.
+    pub fn tag(self) -> &'static str {
+        match self {
+            ShapeKind::Vertex => "Ve",
+            ShapeKind::Edge => "Ed",
+            ShapeKind::Wire => "Wi",
+            ShapeKind::Face => "Fa",
+            ShapeKind::Shell => "Sh",
+            ShapeKind::Solid => "So",
+            ShapeKind::CompSolid => "CS",
+            ShapeKind::Compound => "Co",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        Some(match tag {
+            "Ve" => ShapeKind::Vertex,
+            "Ed" => ShapeKind::Edge,
+            "Wi" => ShapeKind::Wire,
+            "Fa" => ShapeKind::Face,
+            "Sh" => ShapeKind::Shell,
+            "So" => ShapeKind::Solid,
+            "CS" => ShapeKind::CompSolid,
+            "Co" => ShapeKind::Compound,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Orientation {
+    Forward,
+    Reversed,
+    Internal,
+    External,
+}
+
+impl Orientation {
+    pub fn to_char(self) -> char {
+        match self {
+            Orientation::Forward => '+',
+            Orientation::Reversed => '-',
+            Orientation::Internal => 'i',
+            Orientation::External => 'e',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        Some(match c {
+            '+' => Orientation::Forward,
+            '-' => Orientation::Reversed,
+            'i' => Orientation::Internal,
+            'e' => Orientation::External,
+            _ => return None,
+        })
+    }
+}
+
+/// A located, oriented reference to a [`TShape`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShapeRef {
+    pub orientation: Orientation,
+    /// 1-based shape index
+    pub shape: usize,
+    /// 1-based location index, `0` for identity
+    pub location: usize,
+}
+
+/// Geometric representation of an edge.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdgeRep {
+    /// `1`: a 3D curve restricted to `[first, last]`
+    Curve3d {
+        curve: usize,
+        location: usize,
+        first: f64,
+        last: f64,
+    },
+    /// Any other representation, as its raw tokens including the type code
+    Raw(Vec<String>),
+}
+
+/// Geometry attached to a [`TShape`]. Wires, shells, solids and compounds carry none.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShapeGeometry {
+    Vertex {
+        tolerance: f64,
+        point: [f64; 3],
+        /// Point-on-curve/surface representations as raw tokens
+        reps: Vec<Vec<String>>,
+    },
+    Edge {
+        tolerance: f64,
+        same_parameter: bool,
+        same_range: bool,
+        degenerated: bool,
+        reps: Vec<EdgeRep>,
+    },
+    Face {
+        natural_restriction: bool,
+        tolerance: f64,
+        surface: usize,
+        location: usize,
+        triangulation: Option<usize>,
+    },
+    None,
+}
+
+/// A topological shape of the `TShapes` section.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TShape {
+    pub kind: ShapeKind,
+    pub geometry: ShapeGeometry,
+    /// Free, modified, checked, orientable, closed, infinite and convex flags, e.g. `0101101`
+    pub flags: String,
+    pub children: Vec<ShapeRef>,
+}
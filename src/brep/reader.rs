@@ -0,0 +1,647 @@
+// STD
+use std::path::Path;
+use std::str::{FromStr, SplitWhitespace};
+
+// Dependencies
+use anyhow::{Context, Result, anyhow, bail};
+
+// Local modules
+use super::model::*;
+
+/// Format revisions the reader understands.
+pub const SUPPORTED_VERSIONS: &[u32] = &[1, 2, 3];
+
+/// Whitespace separated token stream over the body of a BREP file.
+struct Tokens<'a> {
+    inner: std::iter::Peekable<SplitWhitespace<'a>>,
+}
+
+impl<'a> Tokens<'a> {
+    fn next(&mut self) -> Result<&'a str> {
+        self.inner.next().context("Unexpected end of BREP file")
+    }
+
+    fn peek(&mut self) -> Option<&'a str> {
+        self.inner.peek().copied()
+    }
+
+    fn parse<T: FromStr>(&mut self) -> Result<T> {
+        let token = self.next()?;
+        token
+            .parse()
+            .map_err(|_| anyhow!("Expected a number but found '{}'", token))
+    }
+
+    fn f64(&mut self) -> Result<f64> {
+        self.parse()
+    }
+
+    fn usize(&mut self) -> Result<usize> {
+        self.parse()
+    }
+
+    fn bool(&mut self) -> Result<bool> {
+        Ok(self.parse::<u8>()? != 0)
+    }
+
+    fn point<const N: usize>(&mut self) -> Result<[f64; N]> {
+        let mut out = [0.0; N];
+        for o in &mut out {
+            *o = self.f64()?;
+        }
+        Ok(out)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        let token = self.next()?;
+        if token != expected {
+            bail!("Expected '{}' but found '{}'", expected, token);
+        }
+        Ok(())
+    }
+
+    /// Moves `n` tokens into `raw`.
+    fn take(&mut self, n: usize, raw: &mut Vec<String>) -> Result<()> {
+        for _ in 0..n {
+            raw.push(self.next()?.to_owned());
+        }
+        Ok(())
+    }
+
+    /// Moves a number into `raw` and returns it.
+    fn take_usize(&mut self, raw: &mut Vec<String>) -> Result<usize> {
+        let token = self.next()?;
+        raw.push(token.to_owned());
+        token
+            .parse()
+            .map_err(|_| anyhow!("Expected a count but found '{}'", token))
+    }
+}
+
+/// Copies the tokens of a 2D (`dim = 2`) or 3D (`dim = 3`) curve record into `raw`.
+fn raw_curve(t: &mut Tokens, dim: usize, raw: &mut Vec<String>) -> Result<()> {
+    let kind = t.take_usize(raw)?;
+    match kind {
+        // Line: location, direction
+        1 => t.take(2 * dim, raw),
+        // Circle: location, (normal,) x, y, radius
+        2 => t.take(if dim == 3 { 13 } else { 7 }, raw),
+        // Ellipse and hyperbola: location, (normal,) x, y, two radii
+        3 | 5 => t.take(if dim == 3 { 14 } else { 8 }, raw),
+        // Parabola: location, (normal,) x, y, focal length
+        4 => t.take(if dim == 3 { 13 } else { 7 }, raw),
+        // Bezier: rational, degree, poles
+        6 => {
+            let rational = t.take_usize(raw)? != 0;
+            let degree = t.take_usize(raw)?;
+            t.take((degree + 1) * (dim + rational as usize), raw)
+        },
+        // B-spline: rational, periodic, degree, poles, knots, poles, knots
+        7 => {
+            let rational = t.take_usize(raw)? != 0;
+            t.take(2, raw)?;
+            let poles = t.take_usize(raw)?;
+            let knots = t.take_usize(raw)?;
+            t.take(poles * (dim + rational as usize) + knots * 2, raw)
+        },
+        // Trimmed: first, last, basis curve
+        8 => {
+            t.take(2, raw)?;
+            raw_curve(t, dim, raw)
+        },
+        // Offset: distance, (direction,) basis curve
+        9 => {
+            t.take(if dim == 3 { 4 } else { 1 }, raw)?;
+            raw_curve(t, dim, raw)
+        },
+        _ => bail!("Unknown curve type {}", kind),
+    }
+}
+
+/// Copies the tokens of a surface record into `raw`.
+fn raw_surface(t: &mut Tokens, raw: &mut Vec<String>) -> Result<()> {
+    let kind = t.take_usize(raw)?;
+    match kind {
+        // Plane: location, normal, x, y
+        1 => t.take(12, raw),
+        // Cylinder and sphere: frame, radius
+        2 | 4 => t.take(13, raw),
+        // Cone and torus: frame, two values
+        3 | 5 => t.take(14, raw),
+        // Linear extrusion: direction, basis curve
+        6 => {
+            t.take(3, raw)?;
+            raw_curve(t, 3, raw)
+        },
+        // Revolution: location, axis, basis curve
+        7 => {
+            t.take(6, raw)?;
+            raw_curve(t, 3, raw)
+        },
+        // Bezier: rationals, degrees, poles
+        8 => {
+            let u_rational = t.take_usize(raw)? != 0;
+            let v_rational = t.take_usize(raw)? != 0;
+            let u_degree = t.take_usize(raw)?;
+            let v_degree = t.take_usize(raw)?;
+            let weighted = (u_rational || v_rational) as usize;
+            t.take((u_degree + 1) * (v_degree + 1) * (3 + weighted), raw)
+        },
+        // B-spline: rationals, periodicity, degrees, counts, poles, u knots, v knots
+        9 => {
+            let u_rational = t.take_usize(raw)? != 0;
+            let v_rational = t.take_usize(raw)? != 0;
+            t.take(4, raw)?;
+            let u_poles = t.take_usize(raw)?;
+            let v_poles = t.take_usize(raw)?;
+            let u_knots = t.take_usize(raw)?;
+            let v_knots = t.take_usize(raw)?;
+            let weighted = (u_rational || v_rational) as usize;
+            t.take(
+                u_poles * v_poles * (3 + weighted) + (u_knots + v_knots) * 2,
+                raw,
+            )
+        },
+        // Rectangular trimmed: bounds, basis surface
+        10 => {
+            t.take(4, raw)?;
+            raw_surface(t, raw)
+        },
+        // Offset: distance, basis surface
+        11 => {
+            t.take(1, raw)?;
+            raw_surface(t, raw)
+        },
+        _ => bail!("Unknown surface type {}", kind),
+    }
+}
+
+fn read_locations(t: &mut Tokens) -> Result<Vec<Location>> {
+    let count = t.usize()?;
+    let mut locations = Vec::with_capacity(count);
+    for _ in 0..count {
+        let location = match t.usize()? {
+            1 => {
+                let mut rows = [[0.0; 4]; 3];
+                for row in &mut rows {
+                    *row = t.point()?;
+                }
+                Location::Elementary(rows)
+            },
+            2 => {
+                let mut factors = Vec::new();
+                loop {
+                    let index = t.usize()?;
+                    if index == 0 {
+                        break;
+                    }
+                    factors.push((index, t.parse()?));
+                }
+                Location::Composed(factors)
+            },
+            kind => bail!("Unknown location type {}", kind),
+        };
+        locations.push(location);
+    }
+    Ok(locations)
+}
+
+fn read_curves_2d(t: &mut Tokens) -> Result<Vec<Curve2d>> {
+    let count = t.usize()?;
+    let mut curves = Vec::with_capacity(count);
+    for _ in 0..count {
+        if t.peek() == Some("1") {
+            t.next()?;
+            curves.push(Curve2d::Line {
+                origin: t.point()?,
+                direction: t.point()?,
+            });
+        } else {
+            let mut raw = Vec::new();
+            raw_curve(t, 2, &mut raw)?;
+            curves.push(Curve2d::Raw(raw));
+        }
+    }
+    Ok(curves)
+}
+
+fn read_curves(t: &mut Tokens) -> Result<Vec<Curve>> {
+    let count = t.usize()?;
+    let mut curves = Vec::with_capacity(count);
+    for _ in 0..count {
+        let curve = match t.peek() {
+            Some("1") => {
+                t.next()?;
+                Curve::Line {
+                    origin: t.point()?,
+                    direction: t.point()?,
+                }
+            },
+            Some("2") => {
+                t.next()?;
+                Curve::Circle {
+                    center: t.point()?,
+                    normal: t.point()?,
+                    x_dir: t.point()?,
+                    y_dir: t.point()?,
+                    radius: t.f64()?,
+                }
+            },
+            _ => {
+                let mut raw = Vec::new();
+                raw_curve(t, 3, &mut raw)?;
+                Curve::Raw(raw)
+            },
+        };
+        curves.push(curve);
+    }
+    Ok(curves)
+}
+
+fn read_surfaces(t: &mut Tokens) -> Result<Vec<Surface>> {
+    let count = t.usize()?;
+    let mut surfaces = Vec::with_capacity(count);
+    for _ in 0..count {
+        let surface = match t.peek() {
+            Some("1") => {
+                t.next()?;
+                Surface::Plane {
+                    origin: t.point()?,
+                    normal: t.point()?,
+                    x_dir: t.point()?,
+                    y_dir: t.point()?,
+                }
+            },
+            Some("2") => {
+                t.next()?;
+                Surface::Cylinder {
+                    origin: t.point()?,
+                    axis: t.point()?,
+                    x_dir: t.point()?,
+                    y_dir: t.point()?,
+                    radius: t.f64()?,
+                }
+            },
+            Some("3") => {
+                t.next()?;
+                Surface::Cone {
+                    origin: t.point()?,
+                    axis: t.point()?,
+                    x_dir: t.point()?,
+                    y_dir: t.point()?,
+                    radius: t.f64()?,
+                    semi_angle: t.f64()?,
+                }
+            },
+            Some("4") => {
+                t.next()?;
+                Surface::Sphere {
+                    center: t.point()?,
+                    axis: t.point()?,
+                    x_dir: t.point()?,
+                    y_dir: t.point()?,
+                    radius: t.f64()?,
+                }
+            },
+            _ => {
+                let mut raw = Vec::new();
+                raw_surface(t, &mut raw)?;
+                Surface::Raw(raw)
+            },
+        };
+        surfaces.push(surface);
+    }
+    Ok(surfaces)
+}
+
+fn read_polygons_3d(t: &mut Tokens) -> Result<Vec<Polygon3d>> {
+    let count = t.usize()?;
+    let mut polygons = Vec::with_capacity(count);
+    for _ in 0..count {
+        let nodes = t.usize()?;
+        let has_parameters = t.bool()?;
+        let deflection = t.f64()?;
+        let nodes: Vec<[f64; 3]> = (0..nodes).map(|_| t.point()).collect::<Result<_>>()?;
+        let parameters = if has_parameters {
+            Some((0..nodes.len()).map(|_| t.f64()).collect::<Result<_>>()?)
+        } else {
+            None
+        };
+        polygons.push(Polygon3d {
+            deflection,
+            nodes,
+            parameters,
+        });
+    }
+    Ok(polygons)
+}
+
+fn read_polygons_on_triangulation(t: &mut Tokens) -> Result<Vec<PolygonOnTriangulation>> {
+    let count = t.usize()?;
+    let mut polygons = Vec::with_capacity(count);
+    for _ in 0..count {
+        let len = t.usize()?;
+        let nodes: Vec<usize> = (0..len).map(|_| t.usize()).collect::<Result<_>>()?;
+        t.expect("p")?;
+        let deflection = t.f64()?;
+        let parameters = if t.bool()? {
+            Some((0..len).map(|_| t.f64()).collect::<Result<_>>()?)
+        } else {
+            None
+        };
+        polygons.push(PolygonOnTriangulation {
+            nodes,
+            deflection,
+            parameters,
+        });
+    }
+    Ok(polygons)
+}
+
+fn read_triangulations(t: &mut Tokens, version: u32) -> Result<Vec<Triangulation>> {
+    let count = t.usize()?;
+    let mut triangulations = Vec::with_capacity(count);
+    for _ in 0..count {
+        let node_count = t.usize()?;
+        let triangle_count = t.usize()?;
+        let has_uv = t.bool()?;
+        let has_normals = version >= 3 && t.bool()?;
+        let deflection = t.f64()?;
+        let nodes = (0..node_count).map(|_| t.point()).collect::<Result<_>>()?;
+        let uv_nodes = if has_uv {
+            Some((0..node_count).map(|_| t.point()).collect::<Result<_>>()?)
+        } else {
+            None
+        };
+        let mut triangles = Vec::with_capacity(triangle_count);
+        for _ in 0..triangle_count {
+            triangles.push([t.usize()?, t.usize()?, t.usize()?]);
+        }
+        let normals = if has_normals {
+            let mut normals = Vec::with_capacity(node_count);
+            for _ in 0..node_count {
+                normals.push([t.parse()?, t.parse()?, t.parse()?]);
+            }
+            Some(normals)
+        } else {
+            None
+        };
+        triangulations.push(Triangulation {
+            deflection,
+            nodes,
+            uv_nodes,
+            triangles,
+            normals,
+        });
+    }
+    Ok(triangulations)
+}
+
+fn read_vertex(t: &mut Tokens) -> Result<ShapeGeometry> {
+    let tolerance = t.f64()?;
+    let point = t.point()?;
+    let mut reps = Vec::new();
+    loop {
+        let mut raw = vec![t.next()?.to_owned()];
+        let kind = t.take_usize(&mut raw)?;
+        match kind {
+            0 => break,
+            // Parameter on curve: curve
+            1 => t.take(1, &mut raw)?,
+            // Parameter on curve on surface, or on surface: curve/parameter, surface
+            2 | 3 => t.take(2, &mut raw)?,
+            _ => bail!("Unknown vertex representation {}", kind),
+        }
+        // Location
+        t.take(1, &mut raw)?;
+        reps.push(raw);
+    }
+    Ok(ShapeGeometry::Vertex {
+        tolerance,
+        point,
+        reps,
+    })
+}
+
+fn read_edge(t: &mut Tokens, version: u32) -> Result<ShapeGeometry> {
+    let tolerance = t.f64()?;
+    let same_parameter = t.bool()?;
+    let same_range = t.bool()?;
+    let degenerated = t.bool()?;
+    let mut reps = Vec::new();
+    loop {
+        let kind = t.usize()?;
+        let rep = match kind {
+            0 => break,
+            1 => EdgeRep::Curve3d {
+                curve: t.usize()?,
+                location: t.usize()?,
+                first: t.f64()?,
+                last: t.f64()?,
+            },
+            // Curve on surface: pcurve, surface, location, first, last, then the pcurve's UV end
+            // points from V2 on. Closed surfaces carry a second pcurve and a continuity.
+            2 => {
+                let mut raw = vec![kind.to_string()];
+                t.take(if version >= 2 { 9 } else { 5 }, &mut raw)?;
+                EdgeRep::Raw(raw)
+            },
+            3 => {
+                let mut raw = vec![kind.to_string()];
+                t.take(if version >= 2 { 15 } else { 7 }, &mut raw)?;
+                EdgeRep::Raw(raw)
+            },
+            // Regularity: continuity, surface, location, surface, location
+            4 => {
+                let mut raw = vec![kind.to_string()];
+                t.take(5, &mut raw)?;
+                EdgeRep::Raw(raw)
+            },
+            // Polygon 3D: polygon, location
+            5 => {
+                let mut raw = vec![kind.to_string()];
+                t.take(2, &mut raw)?;
+                EdgeRep::Raw(raw)
+            },
+            // Polygon(s) on triangulation, triangulation, location
+            6 | 7 => {
+                let mut raw = vec![kind.to_string()];
+                t.take(if kind == 7 { 4 } else { 3 }, &mut raw)?;
+                EdgeRep::Raw(raw)
+            },
+            _ => bail!("Unknown edge representation {}", kind),
+        };
+        reps.push(rep);
+    }
+    Ok(ShapeGeometry::Edge {
+        tolerance,
+        same_parameter,
+        same_range,
+        degenerated,
+        reps,
+    })
+}
+
+fn read_face(t: &mut Tokens) -> Result<ShapeGeometry> {
+    let natural_restriction = t.bool()?;
+    let tolerance = t.f64()?;
+    let surface = t.usize()?;
+    let location = t.usize()?;
+    // The triangulation is optional, the flags that follow are always 7 digits long
+    let triangulation = if t.peek() == Some("2") {
+        t.next()?;
+        Some(t.usize()?)
+    } else {
+        None
+    };
+    Ok(ShapeGeometry::Face {
+        natural_restriction,
+        tolerance,
+        surface,
+        location,
+        triangulation,
+    })
+}
+
+fn read_shape_ref(token: &str, location: usize) -> Result<ShapeRef> {
+    let mut chars = token.chars();
+    let orientation = chars
+        .next()
+        .and_then(Orientation::from_char)
+        .with_context(|| format!("Malformed shape reference '{}'", token))?;
+    let shape = chars
+        .as_str()
+        .parse()
+        .map_err(|_| anyhow!("Malformed shape reference '{}'", token))?;
+    Ok(ShapeRef {
+        orientation,
+        shape,
+        location,
+    })
+}
+
+fn read_shapes(t: &mut Tokens, version: u32) -> Result<Vec<TShape>> {
+    let count = t.usize()?;
+    let mut shapes = Vec::with_capacity(count);
+    for _ in 0..count {
+        let tag = t.next()?;
+        let kind = ShapeKind::from_tag(tag).with_context(|| format!("Unknown shape '{}'", tag))?;
+        let geometry = match kind {
+            ShapeKind::Vertex => read_vertex(t)?,
+            ShapeKind::Edge => read_edge(t, version)?,
+            ShapeKind::Face => read_face(t)?,
+            _ => ShapeGeometry::None,
+        };
+        let flags = t.next()?.to_owned();
+        let mut children = Vec::new();
+        loop {
+            let token = t.next()?;
+            if token == "*" {
+                break;
+            }
+            children.push(read_shape_ref(token, t.usize()?)?);
+        }
+        shapes.push(TShape {
+            kind,
+            geometry,
+            flags,
+            children,
+        });
+    }
+    // Shapes are written from the highest index down
+    shapes.reverse();
+    Ok(shapes)
+}
+
+/// Parses the contents of an OpenCASCADE ASCII `.brep` file.
+pub fn parse_brep(src: &str) -> Result<BrepModel> {
+    const HEADER: &str = "CASCADE Topology V";
+    let Some(start) = src.find(HEADER) else {
+        bail!("Not a BREP file: missing '{}' header", HEADER);
+    };
+    let header_line = src[start..].lines().next().unwrap_or_default();
+    let version_str = &header_line[HEADER.len()..];
+    let version_str = version_str
+        .split(|c: char| !c.is_ascii_digit())
+        .next()
+        .unwrap_or_default();
+    let version: u32 = version_str
+        .parse()
+        .map_err(|_| anyhow!("Malformed BREP header '{}'", header_line.trim()))?;
+    if !SUPPORTED_VERSIONS.contains(&version) {
+        bail!("Unsupported BREP revision '{}'", header_line.trim());
+    }
+
+    let body = &src[start + header_line.len()..];
+    let mut t = Tokens {
+        inner: body.split_whitespace().peekable(),
+    };
+    let mut model = BrepModel {
+        version,
+        ..Default::default()
+    };
+
+    loop {
+        let section = t.next()?;
+        match section {
+            "Locations" => model.locations = read_locations(&mut t)?,
+            "Curve2ds" => model.curves_2d = read_curves_2d(&mut t)?,
+            "Curves" => model.curves = read_curves(&mut t)?,
+            "Polygon3D" => model.polygons_3d = read_polygons_3d(&mut t)?,
+            "PolygonOnTriangulations" => {
+                model.polygons_on_triangulation = read_polygons_on_triangulation(&mut t)?
+            },
+            "Surfaces" => model.surfaces = read_surfaces(&mut t)?,
+            "Triangulations" => model.triangulations = read_triangulations(&mut t, version)?,
+            "TShapes" => {
+                model.shapes = read_shapes(&mut t, version)?;
+                break;
+            },
+            _ => bail!("Unknown BREP section '{}'", section),
+        }
+    }
+
+    // The root shape follows the shape table, an empty shape is written as `*`
+    model.root = match t.next() {
+        Ok("*") | Err(_) => None,
+        Ok(token) => Some(read_shape_ref(token, t.usize()?)?),
+    };
+    if let Some(root) = model.root
+        && model.shape(root.shape).is_none()
+    {
+        bail!("Root shape {} is out of range", root.shape);
+    }
+    Ok(model)
+}
+
+/// Reads an OpenCASCADE ASCII `.brep` file.
+pub fn read_brep(path: &Path) -> Result<BrepModel> {
+    let src = std::fs::read_to_string(path)
+        .with_context(|| format!("Couldn't read '{}'", path.display()))?;
+    parse_brep(&src).with_context(|| format!("Couldn't parse '{}'", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_bad_headers() {
+        let error = parse_brep("DBRep_DrawableShape\n").unwrap_err();
+        assert!(error.to_string().contains("Not a BREP file"), "{}", error);
+        let error = parse_brep("CASCADE Topology V9, (c) Matra-Datavision\n").unwrap_err();
+        assert!(error.to_string().contains("Unsupported"), "{}", error);
+    }
+
+    #[test]
+    fn reads_an_empty_shape_set() {
+        let model = parse_brep(
+            "CASCADE Topology V1, (c) Matra-Datavision\n\
+             Locations 0\nCurve2ds 0\nCurves 0\nPolygon3D 0\nPolygonOnTriangulations 0\n\
+             Surfaces 0\nTriangulations 0\n\nTShapes 0\n\n*\n",
+        )
+        .unwrap();
+        assert_eq!(model.version, 1);
+        assert!(model.shapes.is_empty() && model.root.is_none());
+    }
+}
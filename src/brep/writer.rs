@@ -0,0 +1,381 @@
+// STD
+use std::io::Write;
+
+// Dependencies
+use anyhow::Result;
+
+// Local modules
+use super::model::*;
+
+/// Writes `values` separated by spaces.
+fn write_values<T: std::fmt::Display>(w: &mut impl Write, values: &[T]) -> Result<()> {
+    for (i, v) in values.iter().enumerate() {
+        if i > 0 {
+            write!(w, " ")?;
+        }
+        write!(w, "{}", v)?;
+    }
+    Ok(())
+}
+
+fn write_locations(model: &BrepModel, w: &mut impl Write) -> Result<()> {
+    writeln!(w, "Locations {}", model.locations.len())?;
+    for location in &model.locations {
+        match location {
+            Location::Elementary(rows) => {
+                writeln!(w, "1")?;
+                for row in rows {
+                    write_values(w, row)?;
+                    writeln!(w)?;
+                }
+            },
+            Location::Composed(factors) => {
+                write!(w, "2 ")?;
+                for (index, power) in factors {
+                    write!(w, " {} {}", index, power)?;
+                }
+                writeln!(w, " 0")?;
+            },
+        }
+    }
+    Ok(())
+}
+
+fn write_curves_2d(model: &BrepModel, w: &mut impl Write) -> Result<()> {
+    writeln!(w, "Curve2ds {}", model.curves_2d.len())?;
+    for curve in &model.curves_2d {
+        match curve {
+            Curve2d::Line { origin, direction } => {
+                write!(w, "1 ")?;
+                write_values(w, &[origin[0], origin[1], direction[0], direction[1]])?;
+            },
+            Curve2d::Raw(raw) => write_values(w, raw)?,
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+fn write_curves(model: &BrepModel, w: &mut impl Write) -> Result<()> {
+    writeln!(w, "Curves {}", model.curves.len())?;
+    for curve in &model.curves {
+        match curve {
+            Curve::Line { origin, direction } => {
+                write!(w, "1 ")?;
+                write_values(w, origin)?;
+                write!(w, " ")?;
+                write_values(w, direction)?;
+            },
+            Curve::Circle {
+                center,
+                normal,
+                x_dir,
+                y_dir,
+                radius,
+            } => {
+                write!(w, "2 ")?;
+                write_values(w, [*center, *normal, *x_dir, *y_dir].as_flattened())?;
+                write!(w, " {}", radius)?;
+            },
+            Curve::Raw(raw) => write_values(w, raw)?,
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+fn write_polygons_3d(model: &BrepModel, w: &mut impl Write) -> Result<()> {
+    writeln!(w, "Polygon3D {}", model.polygons_3d.len())?;
+    for polygon in &model.polygons_3d {
+        writeln!(
+            w,
+            "{} {}",
+            polygon.nodes.len(),
+            polygon.parameters.is_some() as u8
+        )?;
+        writeln!(w, "{}", polygon.deflection)?;
+        write_values(w, polygon.nodes.as_flattened())?;
+        writeln!(w)?;
+        if let Some(parameters) = &polygon.parameters {
+            write_values(w, parameters)?;
+            writeln!(w)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_polygons_on_triangulation(model: &BrepModel, w: &mut impl Write) -> Result<()> {
+    writeln!(
+        w,
+        "PolygonOnTriangulations {}",
+        model.polygons_on_triangulation.len()
+    )?;
+    for polygon in &model.polygons_on_triangulation {
+        write!(w, "{} ", polygon.nodes.len())?;
+        write_values(w, &polygon.nodes)?;
+        writeln!(w)?;
+        write!(
+            w,
+            "p {} {}",
+            polygon.deflection,
+            polygon.parameters.is_some() as u8
+        )?;
+        if let Some(parameters) = &polygon.parameters {
+            write!(w, " ")?;
+            write_values(w, parameters)?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+fn write_surfaces(model: &BrepModel, w: &mut impl Write) -> Result<()> {
+    writeln!(w, "Surfaces {}", model.surfaces.len())?;
+    for surface in &model.surfaces {
+        match surface {
+            Surface::Plane {
+                origin,
+                normal,
+                x_dir,
+                y_dir,
+            } => {
+                write!(w, "1 ")?;
+                write_values(w, [*origin, *normal, *x_dir, *y_dir].as_flattened())?;
+            },
+            Surface::Cylinder {
+                origin,
+                axis,
+                x_dir,
+                y_dir,
+                radius,
+            } => {
+                write!(w, "2 ")?;
+                write_values(w, [*origin, *axis, *x_dir, *y_dir].as_flattened())?;
+                write!(w, " {}", radius)?;
+            },
+            Surface::Cone {
+                origin,
+                axis,
+                x_dir,
+                y_dir,
+                radius,
+                semi_angle,
+            } => {
+                write!(w, "3 ")?;
+                write_values(w, [*origin, *axis, *x_dir, *y_dir].as_flattened())?;
+                write!(w, " {} {}", radius, semi_angle)?;
+            },
+            Surface::Sphere {
+                center,
+                axis,
+                x_dir,
+                y_dir,
+                radius,
+            } => {
+                write!(w, "4 ")?;
+                write_values(w, [*center, *axis, *x_dir, *y_dir].as_flattened())?;
+                write!(w, " {}", radius)?;
+            },
+            Surface::Raw(raw) => write_values(w, raw)?,
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+fn write_triangulations(model: &BrepModel, w: &mut impl Write) -> Result<()> {
+    writeln!(w, "Triangulations {}", model.triangulations.len())?;
+    for triangulation in &model.triangulations {
+        write!(
+            w,
+            "{} {} {}",
+            triangulation.nodes.len(),
+            triangulation.triangles.len(),
+            triangulation.uv_nodes.is_some() as u8
+        )?;
+        // Normals are only part of the format from V3 on
+        let normals = triangulation
+            .normals
+            .as_ref()
+            .filter(|_| model.version >= 3);
+        if model.version >= 3 {
+            write!(w, " {}", normals.is_some() as u8)?;
+        }
+        writeln!(w, " {}", triangulation.deflection)?;
+        write_values(w, triangulation.nodes.as_flattened())?;
+        if let Some(uv_nodes) = &triangulation.uv_nodes {
+            write!(w, " ")?;
+            write_values(w, uv_nodes.as_flattened())?;
+        }
+        write!(w, " ")?;
+        write_values(w, triangulation.triangles.as_flattened())?;
+        if let Some(normals) = normals {
+            write!(w, " ")?;
+            write_values(w, normals.as_flattened())?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+fn write_geometry(geometry: &ShapeGeometry, w: &mut impl Write) -> Result<()> {
+    match geometry {
+        ShapeGeometry::Vertex {
+            tolerance,
+            point,
+            reps,
+        } => {
+            writeln!(w, "{}", tolerance)?;
+            write_values(w, point)?;
+            writeln!(w)?;
+            for rep in reps {
+                write_values(w, rep)?;
+                writeln!(w)?;
+            }
+            writeln!(w, "0 0")?;
+        },
+        ShapeGeometry::Edge {
+            tolerance,
+            same_parameter,
+            same_range,
+            degenerated,
+            reps,
+        } => {
+            writeln!(
+                w,
+                " {} {} {} {}",
+                tolerance, *same_parameter as u8, *same_range as u8, *degenerated as u8
+            )?;
+            for rep in reps {
+                match rep {
+                    EdgeRep::Curve3d {
+                        curve,
+                        location,
+                        first,
+                        last,
+                    } => write!(w, "1  {} {} {} {}", curve, location, first, last)?,
+                    EdgeRep::Raw(raw) => write_values(w, raw)?,
+                }
+                writeln!(w)?;
+            }
+            writeln!(w, "0")?;
+        },
+        ShapeGeometry::Face {
+            natural_restriction,
+            tolerance,
+            surface,
+            location,
+            triangulation,
+        } => {
+            writeln!(
+                w,
+                "{}  {} {} {}",
+                *natural_restriction as u8, tolerance, surface, location
+            )?;
+            if let Some(triangulation) = triangulation {
+                writeln!(w, "2  {}", triangulation)?;
+            }
+        },
+        ShapeGeometry::None => {},
+    }
+    Ok(())
+}
+
+fn write_shape_ref(shape: &ShapeRef, w: &mut impl Write) -> Result<()> {
+    write!(
+        w,
+        "{}{} {}",
+        shape.orientation.to_char(),
+        shape.shape,
+        shape.location
+    )?;
+    Ok(())
+}
+
+fn write_shapes(model: &BrepModel, w: &mut impl Write) -> Result<()> {
+    writeln!(w, "TShapes {}", model.shapes.len())?;
+    // Shapes are written from the highest index down, so children precede their parents
+    for shape in model.shapes.iter().rev() {
+        writeln!(w, "{}", shape.kind.tag())?;
+        write_geometry(&shape.geometry, w)?;
+        writeln!(w)?;
+        writeln!(w, "{}", shape.flags)?;
+        for child in &shape.children {
+            write_shape_ref(child, w)?;
+            write!(w, " ")?;
+        }
+        writeln!(w, "*")?;
+    }
+    Ok(())
+}
+
+/// Writes `model` as an OpenCASCADE ASCII `.brep` file.
+///
+/// The header uses the model's format revision, or V1 for models built in memory
+/// (`version == 0`), since some geometry records differ between revisions.
+pub fn write_brep(model: &BrepModel, w: &mut impl Write) -> Result<()> {
+    let version = model.version.max(1);
+    writeln!(w, "CASCADE Topology V{}, (c) Matra-Datavision", version)?;
+    write_locations(model, w)?;
+    write_curves_2d(model, w)?;
+    write_curves(model, w)?;
+    write_polygons_3d(model, w)?;
+    write_polygons_on_triangulation(model, w)?;
+    write_surfaces(model, w)?;
+    write_triangulations(model, w)?;
+    writeln!(w)?;
+    write_shapes(model, w)?;
+    writeln!(w)?;
+    match &model.root {
+        Some(root) => write_shape_ref(root, w)?,
+        None => write!(w, "*")?,
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brep::{ShapeKind, parse_brep, read_brep};
+
+    #[test]
+    fn round_trips_topology() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/brep/test.brep");
+        let model = read_brep(&path).unwrap();
+        let mut bytes = Vec::new();
+        write_brep(&model, &mut bytes).unwrap();
+        let reread = parse_brep(std::str::from_utf8(&bytes).unwrap()).unwrap();
+        reread.validate().unwrap();
+
+        for kind in [
+            ShapeKind::Vertex,
+            ShapeKind::Edge,
+            ShapeKind::Wire,
+            ShapeKind::Face,
+            ShapeKind::Shell,
+            ShapeKind::Solid,
+            ShapeKind::CompSolid,
+            ShapeKind::Compound,
+        ] {
+            assert_eq!(reread.count(kind), model.count(kind), "{:?}", kind);
+        }
+        assert!(model.count(ShapeKind::Face) > 0);
+        assert_eq!(reread.shapes.len(), model.shapes.len());
+        assert_eq!(reread.locations.len(), model.locations.len());
+        assert_eq!(reread.curves.len(), model.curves.len());
+        assert_eq!(reread.curves_2d.len(), model.curves_2d.len());
+        assert_eq!(reread.surfaces.len(), model.surfaces.len());
+        assert_eq!(reread.triangulations.len(), model.triangulations.len());
+        assert_eq!(reread.root, model.root);
+        assert_eq!(reread, model);
+
+        // Writing the re-read model gives the same file again
+        let mut again = Vec::new();
+        write_brep(&reread, &mut again).unwrap();
+        assert_eq!(
+            String::from_utf8(again).unwrap(),
+            String::from_utf8(bytes).unwrap()
+        );
+    }
+}
@@ -1,13 +1,98 @@
-use winit::keyboard::KeyCode;
+// STD
+use std::time::Duration;
+
+// Dependencies
+use winit::{event::MouseScrollDelta, keyboard::KeyCode};
+
+// Local modules
+use crate::frustum::Frustum;
+
+/// How the camera maps the view volume onto the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Vertical field of view in degrees
+    Perspective { fovy: f32 },
+    /// Height of the view volume in world units. Parallel lines stay parallel.
+    Orthographic { height: f32 },
+}
+
+/// Standard CAD orientations, named after the side of the model the camera looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StandardView {
+    /// Looking along -z
+    Front,
+    /// Looking along -x
+    Right,
+    /// Looking down along -y, with -z at the top of the screen
+    Top,
+    /// Looking at the front, right and top sides at once
+    Isometric,
+}
+
+impl StandardView {
+    /// Returns the unit vector pointing from the target to the eye.
+    pub fn direction(self) -> cgmath::Vector3<f32> {
+        use cgmath::InnerSpace;
+        match self {
+            Self::Front => cgmath::Vector3::unit_z(),
+            Self::Right => cgmath::Vector3::unit_x(),
+            // Looking straight along `up` leaves the view matrix without a screen up, so tilt
+            // towards the front by a hair
+            Self::Top => cgmath::Vector3::new(0.0, 1.0, 1e-4).normalize(),
+            Self::Isometric => cgmath::Vector3::new(1.0, 1.0, 1.0).normalize(),
+        }
+    }
+}
+
+/// Where the camera is and what it looks at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub eye: cgmath::Point3<f32>,
+    pub target: cgmath::Point3<f32>,
+}
+
+impl CameraPose {
+    /// Returns the pose a fraction `t` of the way from `self` to `other`.
+    ///
+    /// The offset from target to eye turns and scales separately, so the eye swings around the
+    /// target instead of cutting through it.
+    pub fn interpolate(&self, other: &Self, t: f32) -> Self {
+        use cgmath::{EuclideanSpace, InnerSpace, VectorSpace};
+        let target = cgmath::Point3::from_vec(self.target.to_vec().lerp(other.target.to_vec(), t));
+        let (from, to) = (self.eye - self.target, other.eye - other.target);
+        let direction = from.normalize().lerp(to.normalize(), t);
+        let offset = if direction.magnitude2() > 1e-6 {
+            direction.normalize() * (from.magnitude() + (to.magnitude() - from.magnitude()) * t)
+        } else {
+            // Opposite directions, there's no side to swing around
+            from.lerp(to, t)
+        };
+        Self {
+            eye: target + offset,
+            target,
+        }
+    }
+}
+
+/// Transition started by [`Camera::animate_to()`]
+#[derive(Debug, Clone, Copy)]
+struct CameraAnimation {
+    from: CameraPose,
+    to: CameraPose,
+    duration: Duration,
+    elapsed: Duration,
+}
 
 pub struct Camera {
     pub eye: cgmath::Point3<f32>,
     pub target: cgmath::Point3<f32>,
     pub up: cgmath::Vector3<f32>,
     pub aspect: f32,
-    pub fovy: f32,
+    pub projection: Projection,
     pub znear: f32,
     pub zfar: f32,
+    /// Transition in progress, advanced by [`Camera::advance_animation()`]
+    animation: Option<CameraAnimation>,
 }
 
 impl Camera {
@@ -29,17 +114,149 @@ impl Camera {
             // which way is "up"
             up: cgmath::Vector3::unit_y(),
             aspect,
-            fovy: 45.0,
+            projection: Projection::Perspective { fovy: 45.0 },
             znear: 0.1,
             zfar: 100.0,
+            animation: None,
+        }
+    }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose {
+            eye: self.eye,
+            target: self.target,
         }
     }
 
+    /// Jumps to `pose`, cancelling any transition.
+    pub fn set_pose(&mut self, pose: CameraPose) {
+        self.animation = None;
+        self.eye = pose.eye;
+        self.target = pose.target;
+    }
+
+    /// Starts an eased transition from the current pose to `pose`, jumping right away if
+    /// `duration` is zero. Overrides user input until it's done.
+    pub fn animate_to(&mut self, pose: CameraPose, duration: Duration) {
+        if duration.is_zero() {
+            self.set_pose(pose);
+            return;
+        }
+        self.animation = Some(CameraAnimation {
+            from: self.pose(),
+            to: pose,
+            duration,
+            elapsed: Duration::ZERO,
+        });
+    }
+
+    /// Returns whether a transition started by [`Camera::animate_to()`] is in progress.
+    pub fn is_animating(&self) -> bool {
+        self.animation.is_some()
+    }
+
+    /// Moves the transition in progress forward by `dt`.
+    pub fn advance_animation(&mut self, dt: Duration) {
+        let Some(animation) = &mut self.animation else {
+            return;
+        };
+        animation.elapsed += dt;
+        let t = (animation.elapsed.as_secs_f32() / animation.duration.as_secs_f32()).min(1.0);
+        // Smoothstep, slow at both ends
+        let eased = t * t * (3.0 - 2.0 * t);
+        let pose = animation.from.interpolate(&animation.to, eased);
+        self.eye = pose.eye;
+        self.target = pose.target;
+        if t >= 1.0 {
+            self.animation = None;
+        }
+    }
+
+    /// Moves the camera so it orbits around the center of the box `min`..`max`, keeping its
+    /// view direction and distance.
+    pub fn center_on(&mut self, min: cgmath::Point3<f32>, max: cgmath::Point3<f32>) {
+        use cgmath::EuclideanSpace;
+        let center = min.midpoint(max);
+        self.eye += center - self.target;
+        self.target = center;
+    }
+
+    /// Moves the camera so the box `min`..`max` is centered and fully visible, keeping the view
+    /// direction. The box's bounding sphere is fitted into the narrower of the two fields of view.
+    pub fn fit_to_bounds(&mut self, min: [f32; 3], max: [f32; 3]) {
+        use cgmath::{EuclideanSpace, InnerSpace};
+        let (min, max) = (cgmath::Point3::from(min), cgmath::Point3::from(max));
+        self.fit_to_sphere(min.midpoint(max).into(), (max - min).magnitude() / 2.0);
+    }
+
+    /// Moves the camera so the sphere around `center` is centered and fully visible, keeping the
+    /// view direction. The sphere is fitted into the narrower of the two fields of view, at
+    /// `radius / sin(fov / 2)` from its center.
+    pub fn fit_to_sphere(&mut self, center: [f32; 3], radius: f32) {
+        use cgmath::InnerSpace;
+        let center = cgmath::Point3::from(center);
+        // Keep a flat or empty model from collapsing the distance to zero
+        let radius = radius.max(self.znear);
+        let distance = match &mut self.projection {
+            Projection::Perspective { fovy } => {
+                let half_fovy = fovy.to_radians() / 2.0;
+                let half_fovx = (half_fovy.tan() * self.aspect).atan();
+                radius / half_fovy.min(half_fovx).sin()
+            },
+            Projection::Orthographic { height } => {
+                *height = 2.0 * radius * (1.0 / self.aspect).max(1.0);
+                2.0 * radius
+            },
+        };
+        let direction = (self.target - self.eye).normalize();
+        self.target = center;
+        self.eye = center - direction * distance;
+        self.zfar = self.zfar.max(distance + radius);
+    }
+
+    /// Moves the eye so it looks at the target from `view`, keeping the distance.
+    pub fn set_view(&mut self, view: StandardView) {
+        use cgmath::InnerSpace;
+        let distance = (self.eye - self.target).magnitude();
+        self.eye = self.target + view.direction() * distance;
+    }
+
+    /// Switches between perspective and orthographic projection. The plane through the target
+    /// keeps its size on screen.
+    pub fn toggle_projection(&mut self) {
+        use cgmath::InnerSpace;
+        let distance = (self.target - self.eye).magnitude();
+        self.projection = match self.projection {
+            Projection::Perspective { fovy } => Projection::Orthographic {
+                height: 2.0 * distance * (fovy.to_radians() / 2.0).tan(),
+            },
+            Projection::Orthographic { height } => Projection::Perspective {
+                fovy: (2.0 * (height / (2.0 * distance)).atan()).to_degrees(),
+            },
+        };
+    }
+
     /// Returns the combinded `Projection * View` matrix
     pub fn build_vp_matrix(&self) -> cgmath::Matrix4<f32> {
         let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
-        let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
-        return Self::OPENGL_TO_WGPU_MATRIX * proj * view;
+        let proj = match self.projection {
+            Projection::Perspective { fovy } => {
+                cgmath::perspective(cgmath::Deg(fovy), self.aspect, self.znear, self.zfar)
+            },
+            Projection::Orthographic { height } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect;
+                cgmath::ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.znear,
+                    self.zfar,
+                )
+            },
+        };
+        Self::OPENGL_TO_WGPU_MATRIX * proj * view
     }
 }
 
@@ -52,6 +269,8 @@ pub struct CameraUniform {
     // We can't use cgmath with bytemuck directly, so we'll have
     // to convert the Matrix4 into a 4x4 f32 array
     vp_mat: [[f32; 4]; 4],
+    /// Camera position for specular highlights, `w` is unused
+    eye: [f32; 4],
 }
 
 impl CameraUniform {
@@ -59,16 +278,47 @@ impl CameraUniform {
         use cgmath::SquareMatrix;
         Self {
             vp_mat: cgmath::Matrix4::identity().into(),
+            eye: [0.0; 4],
         }
     }
 
     pub fn update_vp(&mut self, camera: &Camera) {
         self.vp_mat = camera.build_vp_matrix().into();
+        self.eye = camera.eye.to_homogeneous().into();
+    }
+
+    /// Returns the frustum of the view-projection last set by [`CameraUniform::update_vp`].
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_proj(self.vp_mat.into())
+    }
+}
+
+impl Default for CameraUniform {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+/// Mouse buttons held down while the cursor moves
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MouseButtons {
+    pub left: bool,
+    pub middle: bool,
+    pub right: bool,
+}
+
 pub struct CameraController {
     speed: f32,
+    /// Fraction of the distance to the target covered by one scroll line
+    zoom_sensitivity: f32,
+    /// Scroll lines that haven't been applied to the camera yet
+    zoom_delta: f32,
+    /// Cursor movement, in pixels, that hasn't been applied to the camera yet
+    orbit_delta: (f32, f32),
+    /// Like `orbit_delta`, but for panning
+    pan_delta: (f32, f32),
+    /// Point orbited around instead of the camera's target
+    pivot: Option<cgmath::Point3<f32>>,
     is_forward_pressed: bool,
     is_backward_pressed: bool,
     is_left_pressed: bool,
@@ -76,9 +326,14 @@ pub struct CameraController {
 }
 
 impl CameraController {
-    pub fn new(speed: f32) -> CameraController {
+    pub fn new(speed: f32, zoom_sensitivity: f32) -> CameraController {
         Self {
             speed,
+            zoom_sensitivity,
+            zoom_delta: 0.0,
+            orbit_delta: (0.0, 0.0),
+            pan_delta: (0.0, 0.0),
+            pivot: None,
             is_forward_pressed: false,
             is_backward_pressed: false,
             is_left_pressed: false,
@@ -86,6 +341,15 @@ impl CameraController {
         }
     }
 
+    /// Orbits around `pivot` from now on, or around the camera's target for `None`.
+    pub fn set_pivot(&mut self, pivot: Option<cgmath::Point3<f32>>) {
+        self.pivot = pivot;
+    }
+
+    pub fn pivot(&self) -> Option<cgmath::Point3<f32>> {
+        self.pivot
+    }
+
     pub fn handle_key(&mut self, key: KeyCode, is_pressed: bool) -> bool {
         match key {
             KeyCode::KeyW | KeyCode::ArrowUp => {
@@ -108,7 +372,30 @@ impl CameraController {
         }
     }
 
-    pub fn update_camera(&self, camera: &mut Camera) {
+    /// Accumulates a cursor movement of `dx`, `dy` pixels. Dragging with the left button orbits
+    /// the camera around its target, dragging with the middle button pans it.
+    pub fn handle_mouse(&mut self, dx: f64, dy: f64, buttons: MouseButtons) {
+        if buttons.left {
+            self.orbit_delta.0 += dx as f32;
+            self.orbit_delta.1 += dy as f32;
+        }
+        if buttons.middle {
+            self.pan_delta.0 += dx as f32;
+            self.pan_delta.1 += dy as f32;
+        }
+    }
+
+    /// Accumulates a mouse wheel movement. Scrolling up zooms in.
+    pub fn handle_scroll(&mut self, delta: MouseScrollDelta) {
+        // Touchpads report pixels, count this many as one wheel line
+        const PIXELS_PER_LINE: f64 = 20.0;
+        self.zoom_delta += match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(position) => (position.y / PIXELS_PER_LINE) as f32,
+        };
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera) {
         use cgmath::InnerSpace;
         let forward = camera.target - camera.eye;
         let forward_norm = forward.normalize();
@@ -136,5 +423,87 @@ impl CameraController {
         if self.is_left_pressed {
             camera.eye = camera.target - (forward - right * self.speed).normalize() * forward_mag;
         }
+
+        self.apply_orbit(camera);
+        self.apply_zoom(camera);
+        self.apply_pan(camera);
+    }
+
+    /// Moves both eye and target in the view plane by the accumulated cursor movement, so the
+    /// model follows the cursor. The step grows with the distance to the target.
+    fn apply_pan(&mut self, camera: &mut Camera) {
+        use cgmath::InnerSpace;
+        const DISTANCE_PER_PIXEL: f32 = 0.001;
+
+        let (dx, dy) = std::mem::take(&mut self.pan_delta);
+        if dx == 0.0 && dy == 0.0 {
+            return;
+        }
+        let forward = camera.target - camera.eye;
+        let right = forward.cross(camera.up);
+        if right.magnitude2() == 0.0 {
+            return;
+        }
+        let right = right.normalize();
+        let up = right.cross(forward).normalize();
+        let scale = forward.magnitude() * DISTANCE_PER_PIXEL;
+        // Screen y grows downwards
+        let translation = right * (-dx * scale) + up * (dy * scale);
+        camera.eye += translation;
+        camera.target += translation;
+    }
+
+    /// Dollies the eye along the view direction by the accumulated scroll lines. Each line covers
+    /// the same fraction of the remaining distance, and the eye stops at the near plane so the
+    /// target never gets clipped.
+    fn apply_zoom(&mut self, camera: &mut Camera) {
+        use cgmath::InnerSpace;
+        let lines = std::mem::take(&mut self.zoom_delta);
+        if lines == 0.0 {
+            return;
+        }
+        let offset = camera.eye - camera.target;
+        let distance = offset.magnitude();
+        let new_distance = (distance * (1.0 - self.zoom_sensitivity).powf(lines)).max(camera.znear);
+        camera.eye = camera.target + offset * (new_distance / distance);
+        // Moving the eye doesn't change an orthographic image, shrink the view volume instead
+        if let Projection::Orthographic { height } = &mut camera.projection {
+            *height *= new_distance / distance;
+        }
+    }
+
+    /// Rotates the eye around the target, or the pivot if there is one, by the accumulated cursor
+    /// movement. Horizontal movement turns around `camera.up`, vertical movement tilts towards
+    /// it, stopping just short of the poles so the view never flips. Around a pivot the target
+    /// turns along, so the pivot stays put on screen.
+    fn apply_orbit(&mut self, camera: &mut Camera) {
+        use cgmath::InnerSpace;
+        const RADIANS_PER_PIXEL: f32 = 0.005;
+        const POLE_MARGIN: f32 = 0.01;
+
+        let (dx, dy) = std::mem::take(&mut self.orbit_delta);
+        if dx == 0.0 && dy == 0.0 {
+            return;
+        }
+        let up = camera.up.normalize();
+        let yaw = cgmath::Matrix3::from_axis_angle(up, cgmath::Rad(-dx * RADIANS_PER_PIXEL));
+        let offset = yaw * (camera.eye - camera.target);
+
+        let right = offset.cross(up);
+        let rotation = if right.magnitude2() == 0.0 {
+            // Looking straight along `up`, there's no axis to tilt around
+            yaw
+        } else {
+            let polar = offset.angle(up).0;
+            let new_polar = (polar - dy * RADIANS_PER_PIXEL)
+                .clamp(POLE_MARGIN, std::f32::consts::PI - POLE_MARGIN);
+            // A positive rotation around `offset x up` moves the eye towards `up`
+            let pitch =
+                cgmath::Matrix3::from_axis_angle(right.normalize(), cgmath::Rad(polar - new_polar));
+            pitch * yaw
+        };
+        let pivot = self.pivot.unwrap_or(camera.target);
+        camera.eye = pivot + rotation * (camera.eye - pivot);
+        camera.target = pivot + rotation * (camera.target - pivot);
     }
 }
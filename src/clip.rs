@@ -0,0 +1,32 @@
+/// Shader uniform for the section plane
+///
+/// Must format the *uniform* for storage in the *clip* buffer [`crate::State::clip_buffer`]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ClipUniform {
+    /// `(a, b, c, d)` of the plane `a*x + b*y + c*z + d = 0` in world space. Fragments where the
+    /// left hand side is negative are discarded.
+    plane: [f32; 4],
+}
+
+impl ClipUniform {
+    /// A plane that keeps everything
+    const DISABLED: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    pub fn new() -> Self {
+        Self {
+            plane: Self::DISABLED,
+        }
+    }
+
+    /// Clips at `plane`, or nowhere for `None`.
+    pub fn update_plane(&mut self, plane: Option<[f32; 4]>) {
+        self.plane = plane.unwrap_or(Self::DISABLED);
+    }
+}
+
+impl Default for ClipUniform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
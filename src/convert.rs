@@ -0,0 +1,1360 @@
+//! Conversion between STEP entity graphs and BREP models.
+// STD
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::f64::consts::TAU;
+
+// Dependencies
+use anyhow::{Result, anyhow, bail};
+use cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+
+// Local modules
+use crate::brep::{
+    BrepModel, Curve, EdgeRep, Orientation, ShapeGeometry, ShapeKind, ShapeRef, Surface, TShape,
+};
+use crate::step::geometry::{cartesian_point, direction, placement};
+use crate::step::{Entity, EntityGraph, Parameter};
+
+/// Tolerance assigned to translated vertices, edges and faces.
+const TOLERANCE: f64 = 1e-7;
+
+fn orientation(forward: bool) -> Orientation {
+    if forward {
+        Orientation::Forward
+    } else {
+        Orientation::Reversed
+    }
+}
+
+/// Builds a [`BrepModel`] from the topology of a STEP [`EntityGraph`].
+struct BrepBuilder<'a> {
+    graph: &'a EntityGraph,
+    model: BrepModel,
+    /// Shapes already translated, keyed by STEP instance id
+    translated: HashMap<u64, usize>,
+    /// Descriptions of the instances that couldn't be translated
+    unsupported: BTreeSet<String>,
+}
+
+impl<'a> BrepBuilder<'a> {
+    fn new(graph: &'a EntityGraph) -> Self {
+        Self {
+            graph,
+            model: BrepModel {
+                version: 1,
+                ..Default::default()
+            },
+            translated: HashMap::new(),
+            unsupported: BTreeSet::new(),
+        }
+    }
+
+    /// Adds a shape and returns its (temporary) index.
+    ///
+    /// Shapes are added children first, [`BrepBuilder::finish`] renumbers them so children end up
+    /// with the higher indices as OpenCASCADE expects.
+    fn push(&mut self, id: Option<u64>, shape: TShape) -> usize {
+        self.model.shapes.push(shape);
+        let index = self.model.shapes.len();
+        if let Some(id) = id {
+            self.translated.insert(id, index);
+        }
+        index
+    }
+
+    fn vertex(&mut self, id: u64) -> Result<usize> {
+        if let Some(&index) = self.translated.get(&id) {
+            return Ok(index);
+        }
+        self.graph.get_as(id, "VERTEX_POINT")?;
+        let point = cartesian_point(self.graph, id)?;
+        Ok(self.push(
+            Some(id),
+            TShape {
+                kind: ShapeKind::Vertex,
+                geometry: ShapeGeometry::Vertex {
+                    tolerance: TOLERANCE,
+                    point,
+                    reps: Vec::new(),
+                },
+                flags: "0101101".into(),
+                children: Vec::new(),
+            },
+        ))
+    }
+
+    fn edge(&mut self, id: u64) -> Result<usize> {
+        if let Some(&index) = self.translated.get(&id) {
+            return Ok(index);
+        }
+        let graph = self.graph;
+        let edge = graph.get_as(id, "EDGE_CURVE")?;
+        let curve = graph.get(edge.ref_arg(3)?)?;
+        let start = Point3::from(cartesian_point(graph, edge.ref_arg(1)?)?);
+        let end = Point3::from(cartesian_point(graph, edge.ref_arg(2)?)?);
+        let same_sense = edge.arg(4)?.as_bool() != Some(false);
+
+        // Orient the curve along the edge so the parameter range increases from start to end
+        let (curve, first, last) = match curve.keyword.as_str() {
+            "LINE" => {
+                let origin = Point3::from(cartesian_point(graph, curve.ref_arg(1)?)?);
+                let mut dir = Vector3::from(direction(graph, curve.ref_arg(2)?)?);
+                if !same_sense {
+                    dir = -dir;
+                }
+                let first = (start - origin).dot(dir);
+                let last = (end - origin).dot(dir);
+                let line = Curve::Line {
+                    origin: origin.into(),
+                    direction: dir.into(),
+                };
+                (line, first, last)
+            },
+            "CIRCLE" => {
+                let frame = placement(graph, curve.ref_arg(1)?)?;
+                let Some(radius) = curve.arg(2)?.as_f64() else {
+                    bail!("#{}: CIRCLE radius is not a number", curve.id);
+                };
+                let (mut normal, mut y) = (frame.z, frame.y);
+                if !same_sense {
+                    normal = -normal;
+                    y = -y;
+                }
+                let angle = |p: Point3<f64>| {
+                    let d = p - frame.origin;
+                    d.dot(y).atan2(d.dot(frame.x)).rem_euclid(TAU)
+                };
+                let first = angle(start);
+                let mut last = angle(end);
+                // A closed edge runs all the way around
+                if edge.ref_arg(1)? == edge.ref_arg(2)? || last <= first {
+                    last += TAU;
+                }
+                let circle = Curve::Circle {
+                    center: frame.origin.into(),
+                    normal: normal.into(),
+                    x_dir: frame.x.into(),
+                    y_dir: y.into(),
+                    radius,
+                };
+                (circle, first, last)
+            },
+            other => bail!("#{}: {} edges can't be translated yet", curve.id, other),
+        };
+
+        self.model.curves.push(curve);
+        let curve = self.model.curves.len();
+        let children = vec![
+            ShapeRef {
+                orientation: Orientation::Forward,
+                shape: self.vertex(edge.ref_arg(1)?)?,
+                location: 0,
+            },
+            ShapeRef {
+                orientation: Orientation::Reversed,
+                shape: self.vertex(edge.ref_arg(2)?)?,
+                location: 0,
+            },
+        ];
+        Ok(self.push(
+            Some(id),
+            TShape {
+                kind: ShapeKind::Edge,
+                geometry: ShapeGeometry::Edge {
+                    tolerance: TOLERANCE,
+                    same_parameter: true,
+                    same_range: true,
+                    degenerated: false,
+                    reps: vec![EdgeRep::Curve3d {
+                        curve,
+                        location: 0,
+                        first,
+                        last,
+                    }],
+                },
+                flags: "0101000".into(),
+                children,
+            },
+        ))
+    }
+
+    fn wire(&mut self, id: u64) -> Result<usize> {
+        if let Some(&index) = self.translated.get(&id) {
+            return Ok(index);
+        }
+        let graph = self.graph;
+        let edge_loop = graph.get_as(id, "EDGE_LOOP")?;
+        let mut children = Vec::new();
+        for oriented in edge_loop.list_arg(1)? {
+            let oriented = oriented
+                .as_ref()
+                .ok_or_else(|| anyhow!("#{}: EDGE_LOOP edge is not a reference", id))?;
+            let oriented = graph.get_as(oriented, "ORIENTED_EDGE")?;
+            children.push(ShapeRef {
+                orientation: orientation(oriented.arg(4)?.as_bool().unwrap_or(true)),
+                shape: self.edge(oriented.ref_arg(3)?)?,
+                location: 0,
+            });
+        }
+        Ok(self.push(
+            Some(id),
+            TShape {
+                kind: ShapeKind::Wire,
+                geometry: ShapeGeometry::None,
+                flags: "0101000".into(),
+                children,
+            },
+        ))
+    }
+
+    fn face(&mut self, id: u64) -> Result<usize> {
+        if let Some(&index) = self.translated.get(&id) {
+            return Ok(index);
+        }
+        let graph = self.graph;
+        let face = graph.get_as(id, "ADVANCED_FACE")?;
+        let surface = graph.get(face.ref_arg(2)?)?;
+        let frame = || -> Result<_> {
+            let frame = placement(graph, surface.ref_arg(1)?)?;
+            Ok((
+                frame.origin.into(),
+                frame.z.into(),
+                frame.x.into(),
+                frame.y.into(),
+            ))
+        };
+        let number = |i: usize| -> Result<f64> {
+            surface.arg(i)?.as_f64().ok_or_else(|| {
+                anyhow!(
+                    "#{}: {} argument {} is not a number",
+                    surface.id,
+                    surface.keyword,
+                    i
+                )
+            })
+        };
+        let translated = match surface.keyword.as_str() {
+            "PLANE" => {
+                let (origin, normal, x_dir, y_dir) = frame()?;
+                Surface::Plane {
+                    origin,
+                    normal,
+                    x_dir,
+                    y_dir,
+                }
+            },
+            "CYLINDRICAL_SURFACE" => {
+                let (origin, axis, x_dir, y_dir) = frame()?;
+                Surface::Cylinder {
+                    origin,
+                    axis,
+                    x_dir,
+                    y_dir,
+                    radius: number(2)?,
+                }
+            },
+            "CONICAL_SURFACE" => {
+                let (origin, axis, x_dir, y_dir) = frame()?;
+                Surface::Cone {
+                    origin,
+                    axis,
+                    x_dir,
+                    y_dir,
+                    radius: number(2)?,
+                    semi_angle: number(3)?,
+                }
+            },
+            "SPHERICAL_SURFACE" => {
+                let (center, axis, x_dir, y_dir) = frame()?;
+                Surface::Sphere {
+                    center,
+                    axis,
+                    x_dir,
+                    y_dir,
+                    radius: number(2)?,
+                }
+            },
+            other => bail!(
+                "#{}: {} surfaces can't be translated yet",
+                surface.id,
+                other
+            ),
+        };
+        self.model.surfaces.push(translated);
+        let surface = self.model.surfaces.len();
+
+        // STEP orients the bounds along the face normal, BREP along the surface normal. The face
+        // itself is reversed in its shell instead.
+        let same_sense = face.arg(3)?.as_bool().unwrap_or(true);
+        let mut children = Vec::new();
+        for bound in face.list_arg(1)? {
+            let bound = bound
+                .as_ref()
+                .ok_or_else(|| anyhow!("#{}: face bound is not a reference", id))?;
+            let bound = graph.get(bound)?;
+            if !bound.is("FACE_BOUND") && !bound.is("FACE_OUTER_BOUND") {
+                bail!(
+                    "#{}: {} face bounds can't be translated",
+                    bound.id,
+                    bound.keyword
+                );
+            }
+            let forward = bound.arg(2)?.as_bool().unwrap_or(true);
+            children.push(ShapeRef {
+                orientation: orientation(forward == same_sense),
+                shape: self.wire(bound.ref_arg(1)?)?,
+                location: 0,
+            });
+        }
+        Ok(self.push(
+            Some(id),
+            TShape {
+                kind: ShapeKind::Face,
+                geometry: ShapeGeometry::Face {
+                    natural_restriction: false,
+                    tolerance: TOLERANCE,
+                    surface,
+                    location: 0,
+                    triangulation: None,
+                },
+                flags: "0101000".into(),
+                children,
+            },
+        ))
+    }
+
+    /// Translates the faces of a `CLOSED_SHELL` or `OPEN_SHELL`, recording the untranslatable
+    /// ones instead of failing.
+    fn shell(&mut self, id: u64) -> Result<usize> {
+        if let Some(&index) = self.translated.get(&id) {
+            return Ok(index);
+        }
+        let graph = self.graph;
+        let shell = graph.get(id)?;
+        let closed = match shell.keyword.as_str() {
+            "CLOSED_SHELL" => true,
+            "OPEN_SHELL" => false,
+            other => bail!("#{}: {} shells can't be translated", id, other),
+        };
+        let mut children = Vec::new();
+        for face in shell.list_arg(1)?.iter().filter_map(|f| f.as_ref()) {
+            let same_sense = graph.get(face)?.arg(3)?.as_bool().unwrap_or(true);
+            match self.face(face) {
+                Ok(shape) => children.push(ShapeRef {
+                    orientation: orientation(same_sense),
+                    shape,
+                    location: 0,
+                }),
+                Err(e) => {
+                    self.unsupported.insert(format!("{:#}", e));
+                },
+            }
+        }
+        Ok(self.push(
+            Some(id),
+            TShape {
+                kind: ShapeKind::Shell,
+                geometry: ShapeGeometry::None,
+                flags: if closed { "0101100" } else { "0101000" }.into(),
+                children,
+            },
+        ))
+    }
+
+    fn solid(&mut self, id: u64) -> Result<usize> {
+        let solid = self.graph.get_as(id, "MANIFOLD_SOLID_BREP")?;
+        let shell = self.shell(solid.ref_arg(1)?)?;
+        Ok(self.push(
+            Some(id),
+            TShape {
+                kind: ShapeKind::Solid,
+                geometry: ShapeGeometry::None,
+                flags: "0100000".into(),
+                children: vec![ShapeRef {
+                    orientation: Orientation::Forward,
+                    shape: shell,
+                    location: 0,
+                }],
+            },
+        ))
+    }
+
+    /// Renumbers the shapes so children have higher indices than their parents and sets the root.
+    fn finish(mut self, roots: Vec<usize>) -> Result<BrepModel> {
+        if !self.unsupported.is_empty() {
+            let list: Vec<String> = self.unsupported.into_iter().collect();
+            bail!(
+                "{} entities can't be translated:\n  {}",
+                list.len(),
+                list.join("\n  ")
+            );
+        }
+        let root = match roots.as_slice() {
+            [] => bail!("No solids, shells or faces to translate"),
+            [root] => *root,
+            _ => self.push(
+                None,
+                TShape {
+                    kind: ShapeKind::Compound,
+                    geometry: ShapeGeometry::None,
+                    flags: "1100000".into(),
+                    children: roots
+                        .iter()
+                        .map(|&shape| ShapeRef {
+                            orientation: Orientation::Forward,
+                            shape,
+                            location: 0,
+                        })
+                        .collect(),
+                },
+            ),
+        };
+
+        let count = self.model.shapes.len();
+        let renumber = |index: usize| count + 1 - index;
+        self.model.shapes.reverse();
+        for shape in &mut self.model.shapes {
+            for child in &mut shape.children {
+                child.shape = renumber(child.shape);
+            }
+        }
+        self.model.root = Some(ShapeRef {
+            orientation: Orientation::Forward,
+            shape: renumber(root),
+            location: 0,
+        });
+        Ok(self.model)
+    }
+}
+
+/// Translates the solids (or, failing that, the shells or faces) of a STEP file into a
+/// [`BrepModel`].
+///
+/// Supports `VERTEX_POINT`s, `EDGE_CURVE`s on `LINE`s and `CIRCLE`s, and `ADVANCED_FACE`s on
+/// `PLANE`s, `CYLINDRICAL_SURFACE`s, `CONICAL_SURFACE`s and `SPHERICAL_SURFACE`s. Fails with a list
+/// of every instance that can't be translated yet.
+///
+/// Edges only get their 3D curve. OpenCASCADE derives the missing pcurves on planes by itself,
+/// faces on curved surfaces need `ShapeFix_Shape` to add them before meshing.
+pub fn step_to_brep(graph: &EntityGraph) -> Result<BrepModel> {
+    let mut builder = BrepBuilder::new(graph);
+    let mut roots = Vec::new();
+
+    let solids: Vec<u64> = graph
+        .instances_of("MANIFOLD_SOLID_BREP")
+        .map(|e| e.id)
+        .collect();
+    if !solids.is_empty() {
+        for id in solids {
+            roots.push(builder.solid(id)?);
+        }
+    } else {
+        let shells: Vec<u64> = graph
+            .instances_of("CLOSED_SHELL")
+            .chain(graph.instances_of("OPEN_SHELL"))
+            .map(|e| e.id)
+            .collect();
+        if !shells.is_empty() {
+            for id in shells {
+                roots.push(builder.shell(id)?);
+            }
+        } else {
+            let faces: Vec<u64> = graph.instances_of("ADVANCED_FACE").map(|e| e.id).collect();
+            for id in faces {
+                match builder.face(id) {
+                    Ok(face) => roots.push(face),
+                    Err(e) => {
+                        builder.unsupported.insert(format!("{:#}", e));
+                    },
+                }
+            }
+        }
+    }
+    builder.finish(roots)
+}
+
+/// A transform rounded to [`TOLERANCE`], so a shape reached through different location chains
+/// that place it alike is only translated once.
+type TransformKey = [i64; 12];
+
+fn transform_key(transform: &Matrix4<f64>) -> TransformKey {
+    let mut key = [0; 12];
+    let columns = [transform.x, transform.y, transform.z, transform.w];
+    for (k, v) in key
+        .iter_mut()
+        .zip(columns.iter().flat_map(|c| [c.x, c.y, c.z]))
+    {
+        *k = (v / TOLERANCE).round() as i64;
+    }
+    key
+}
+
+/// Builds a STEP [`EntityGraph`] from a [`BrepModel`], assigning increasing instance ids.
+///
+/// Shapes are translated where their location chain places them, relative to the root.
+struct StepBuilder<'a> {
+    model: &'a BrepModel,
+    /// Transforms of the model's locations, see [`BrepModel::location_transforms`]
+    locations: Vec<Matrix4<f64>>,
+    graph: EntityGraph,
+    next_id: u64,
+    /// Instances already emitted, keyed by shape index, whether it's used reversed and where
+    /// it's placed
+    translated: HashMap<(usize, bool, TransformKey), u64>,
+    /// Descriptions of the shapes that couldn't be translated
+    unsupported: BTreeSet<String>,
+}
+
+fn real(v: f64) -> Parameter {
+    Parameter::Real(v)
+}
+
+fn string(s: &str) -> Parameter {
+    Parameter::String(s.into())
+}
+
+fn boolean(b: bool) -> Parameter {
+    Parameter::Enum(if b { "T" } else { "F" }.into())
+}
+
+fn refs(ids: impl IntoIterator<Item = u64>) -> Parameter {
+    Parameter::List(ids.into_iter().map(Parameter::Ref).collect())
+}
+
+fn reals(values: &[f64]) -> Parameter {
+    Parameter::List(values.iter().copied().map(real).collect())
+}
+
+impl<'a> StepBuilder<'a> {
+    fn new(model: &'a BrepModel) -> Result<Self> {
+        Ok(Self {
+            model,
+            locations: model.location_transforms()?,
+            graph: EntityGraph::default(),
+            next_id: 1,
+            translated: HashMap::new(),
+            unsupported: BTreeSet::new(),
+        })
+    }
+
+    /// Adds an instance with the next free id and returns the id.
+    fn add(&mut self, keyword: &str, args: Vec<Parameter>) -> u64 {
+        self.add_complex(keyword, args, Vec::new())
+    }
+
+    fn add_complex(
+        &mut self,
+        keyword: &str,
+        args: Vec<Parameter>,
+        partials: Vec<(&str, Vec<Parameter>)>,
+    ) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.graph.entities.insert(
+            id,
+            Entity {
+                id,
+                keyword: keyword.into(),
+                args,
+                partials: partials
+                    .into_iter()
+                    .map(|(k, args)| (k.into(), args))
+                    .collect(),
+            },
+        );
+        id
+    }
+
+    fn point(&mut self, p: [f64; 3]) -> u64 {
+        self.add("CARTESIAN_POINT", vec![string(""), reals(&p)])
+    }
+
+    fn direction(&mut self, d: [f64; 3]) -> u64 {
+        self.add("DIRECTION", vec![string(""), reals(&d)])
+    }
+
+    fn placement(&mut self, origin: [f64; 3], z: [f64; 3], x: [f64; 3]) -> u64 {
+        let origin = self.point(origin);
+        let z = self.direction(z);
+        let x = self.direction(x);
+        self.add(
+            "AXIS2_PLACEMENT_3D",
+            vec![
+                string(""),
+                Parameter::Ref(origin),
+                Parameter::Ref(z),
+                Parameter::Ref(x),
+            ],
+        )
+    }
+
+    /// Adds the `LINE` or `CIRCLE` for the curve with index `index`, placed by `transform`.
+    fn curve(&mut self, index: usize, transform: &Matrix4<f64>) -> Result<u64> {
+        Ok(match self.model.curve(index) {
+            Some(&Curve::Line { origin, direction }) => {
+                let origin = self.point(transform.transform_point(origin.into()).into());
+                let direction = transform.transform_vector(direction.into()).normalize();
+                let direction = self.direction(direction.into());
+                let vector = self.add(
+                    "VECTOR",
+                    vec![string(""), Parameter::Ref(direction), real(1.0)],
+                );
+                self.add(
+                    "LINE",
+                    vec![string(""), Parameter::Ref(origin), Parameter::Ref(vector)],
+                )
+            },
+            Some(&Curve::Circle {
+                center,
+                x_dir,
+                y_dir,
+                radius,
+                ..
+            }) => {
+                let x = transform.transform_vector(x_dir.into());
+                let y = transform.transform_vector(y_dir.into());
+                // The circle runs from x towards y, even if `transform` mirrors it
+                let normal = x.cross(y).normalize();
+                let center = transform.transform_point(center.into());
+                let placement = self.placement(center.into(), normal.into(), x.normalize().into());
+                self.add(
+                    "CIRCLE",
+                    vec![
+                        string(""),
+                        Parameter::Ref(placement),
+                        real(radius * x.magnitude()),
+                    ],
+                )
+            },
+            _ => bail!(
+                "Curve {}: only lines and circles can be translated yet",
+                index
+            ),
+        })
+    }
+
+    /// Adds the elementary surface with index `index` placed by `transform`, returning its id and
+    /// whether its frame is left-handed.
+    fn surface(&mut self, index: usize, transform: &Matrix4<f64>) -> Result<(u64, bool)> {
+        let model = self.model;
+        let surface = model.surface(index);
+        let Some([origin, axis, x_dir, y_dir]) = surface.and_then(Surface::frame) else {
+            bail!(
+                "Surface {}: only planes, cylinders, cones and spheres can be translated yet",
+                index
+            );
+        };
+        let origin = transform.transform_point(origin.into());
+        let [axis, x, y] = [axis, x_dir, y_dir].map(|d| transform.transform_vector(d.into()));
+        let scale = x.magnitude();
+        let placement = Parameter::Ref(self.placement(
+            origin.into(),
+            axis.normalize().into(),
+            x.normalize().into(),
+        ));
+        let (keyword, mut args) = match surface {
+            Some(&Surface::Cylinder { radius, .. }) => {
+                ("CYLINDRICAL_SURFACE", vec![real(radius * scale)])
+            },
+            Some(&Surface::Cone {
+                radius, semi_angle, ..
+            }) => (
+                "CONICAL_SURFACE",
+                vec![real(radius * scale), real(semi_angle)],
+            ),
+            Some(&Surface::Sphere { radius, .. }) => {
+                ("SPHERICAL_SURFACE", vec![real(radius * scale)])
+            },
+            _ => ("PLANE", Vec::new()),
+        };
+        args.splice(0..0, [string(""), placement]);
+        let left_handed = x.cross(y).dot(axis) < 0.0;
+        Ok((self.add(keyword, args), left_handed))
+    }
+
+    /// Returns the transform of the location with the 1-based `index`, `0` being the identity.
+    fn location(&self, index: usize) -> Result<Matrix4<f64>> {
+        match index.checked_sub(1) {
+            None => Ok(Matrix4::identity()),
+            Some(i) => self
+                .locations
+                .get(i)
+                .copied()
+                .ok_or_else(|| anyhow!("Location {} is out of range", index)),
+        }
+    }
+
+    /// Returns the shape a reference points at and the transform placing it, given `parent`,
+    /// the transform of the shape holding the reference.
+    fn shape(&self, shape: &ShapeRef, parent: &Matrix4<f64>) -> Result<(&'a TShape, Matrix4<f64>)> {
+        let model = self.model;
+        let tshape = model
+            .shape(shape.shape)
+            .ok_or_else(|| anyhow!("Shape {} is out of range", shape.shape))?;
+        Ok((tshape, parent * self.location(shape.location)?))
+    }
+
+    fn vertex(&mut self, shape: &ShapeRef, parent: &Matrix4<f64>) -> Result<u64> {
+        let (tshape, transform) = self.shape(shape, parent)?;
+        let key = (shape.shape, false, transform_key(&transform));
+        if let Some(&id) = self.translated.get(&key) {
+            return Ok(id);
+        }
+        let ShapeGeometry::Vertex { point, .. } = tshape.geometry else {
+            bail!("Shape {} is not a vertex", shape.shape);
+        };
+        let point = self.point(transform.transform_point(point.into()).into());
+        let id = self.add("VERTEX_POINT", vec![string(""), Parameter::Ref(point)]);
+        self.translated.insert(key, id);
+        Ok(id)
+    }
+
+    fn edge(&mut self, shape: &ShapeRef, parent: &Matrix4<f64>) -> Result<u64> {
+        let (tshape, transform) = self.shape(shape, parent)?;
+        let key = (shape.shape, false, transform_key(&transform));
+        if let Some(&id) = self.translated.get(&key) {
+            return Ok(id);
+        }
+        let ShapeGeometry::Edge { reps, .. } = &tshape.geometry else {
+            bail!("Shape {} is not an edge", shape.shape);
+        };
+        let Some((curve, location)) = reps.iter().find_map(|rep| match rep {
+            EdgeRep::Curve3d {
+                curve, location, ..
+            } => Some((*curve, *location)),
+            EdgeRep::Raw(_) => None,
+        }) else {
+            bail!(
+                "Shape {}: edges without a 3D curve can't be translated",
+                shape.shape
+            );
+        };
+        let curve = self.curve(curve, &(transform * self.location(location)?))?;
+
+        // The forward vertex starts the edge, the reversed one ends it
+        let mut start = None;
+        let mut end = None;
+        for child in &tshape.children {
+            match child.orientation {
+                Orientation::Forward => start = Some(self.vertex(child, &transform)?),
+                Orientation::Reversed => end = Some(self.vertex(child, &transform)?),
+                _ => {},
+            }
+        }
+        let (Some(start), Some(end)) = (start, end) else {
+            bail!(
+                "Shape {}: edges need a start and an end vertex",
+                shape.shape
+            );
+        };
+
+        let id = self.add(
+            "EDGE_CURVE",
+            vec![
+                string(""),
+                Parameter::Ref(start),
+                Parameter::Ref(end),
+                Parameter::Ref(curve),
+                // BREP curves run from the start vertex to the end vertex, like the edge itself
+                boolean(true),
+            ],
+        );
+        self.translated.insert(key, id);
+        Ok(id)
+    }
+
+    fn wire(&mut self, shape: &ShapeRef, parent: &Matrix4<f64>) -> Result<u64> {
+        let (tshape, transform) = self.shape(shape, parent)?;
+        let key = (shape.shape, false, transform_key(&transform));
+        if let Some(&id) = self.translated.get(&key) {
+            return Ok(id);
+        }
+        let mut edges = Vec::new();
+        for child in &tshape.children {
+            let edge = self.edge(child, &transform)?;
+            edges.push(self.add(
+                "ORIENTED_EDGE",
+                vec![
+                    string(""),
+                    Parameter::Derived,
+                    Parameter::Derived,
+                    Parameter::Ref(edge),
+                    boolean(child.orientation != Orientation::Reversed),
+                ],
+            ));
+        }
+        let id = self.add("EDGE_LOOP", vec![string(""), refs(edges)]);
+        self.translated.insert(key, id);
+        Ok(id)
+    }
+
+    /// Translates a face as it's used by `shape`: a reversed face becomes an `ADVANCED_FACE`
+    /// that runs against its surface.
+    fn face(&mut self, shape: &ShapeRef, parent: &Matrix4<f64>) -> Result<u64> {
+        let same_sense = shape.orientation != Orientation::Reversed;
+        let (tshape, transform) = self.shape(shape, parent)?;
+        let key = (shape.shape, !same_sense, transform_key(&transform));
+        if let Some(&id) = self.translated.get(&key) {
+            return Ok(id);
+        }
+        let ShapeGeometry::Face {
+            surface, location, ..
+        } = tshape.geometry
+        else {
+            bail!("Shape {} is not a face", shape.shape);
+        };
+        let (surface, left_handed) =
+            self.surface(surface, &(transform * self.location(location)?))?;
+
+        let mut bounds = Vec::new();
+        for (i, child) in tshape.children.iter().enumerate() {
+            let edge_loop = self.wire(child, &transform)?;
+            let forward = (child.orientation != Orientation::Reversed) == same_sense;
+            bounds.push(self.add(
+                if i == 0 {
+                    "FACE_OUTER_BOUND"
+                } else {
+                    "FACE_BOUND"
+                },
+                vec![string(""), Parameter::Ref(edge_loop), boolean(forward)],
+            ));
+        }
+        let id = self.add(
+            "ADVANCED_FACE",
+            vec![
+                string(""),
+                refs(bounds),
+                Parameter::Ref(surface),
+                // STEP placements are right-handed, a left-handed frame flips the surface normal
+                boolean(same_sense != left_handed),
+            ],
+        );
+        self.translated.insert(key, id);
+        Ok(id)
+    }
+
+    /// Translates the faces of a shell, recording the untranslatable ones instead of failing.
+    fn shell(&mut self, shape: &ShapeRef, parent: &Matrix4<f64>) -> Result<u64> {
+        let (tshape, transform) = self.shape(shape, parent)?;
+        let mut faces = Vec::new();
+        for child in &tshape.children {
+            // A reversed shell reverses its faces
+            let mut child = *child;
+            if shape.orientation == Orientation::Reversed {
+                child.orientation = match child.orientation {
+                    Orientation::Forward => Orientation::Reversed,
+                    Orientation::Reversed => Orientation::Forward,
+                    other => other,
+                };
+            }
+            match self.face(&child, &transform) {
+                Ok(face) => faces.push(face),
+                Err(e) => {
+                    self.unsupported.insert(format!("{:#}", e));
+                },
+            }
+        }
+        // The fifth flag marks closed shapes
+        let closed = tshape.flags.as_bytes().get(4) == Some(&b'1');
+        Ok(self.add(
+            if closed { "CLOSED_SHELL" } else { "OPEN_SHELL" },
+            vec![string(""), refs(faces)],
+        ))
+    }
+
+    /// Collects the `EDGE_CURVE`s of a lone edge or wire into `edges`.
+    fn free_edges(
+        &mut self,
+        shape: &ShapeRef,
+        parent: &Matrix4<f64>,
+        edges: &mut Vec<u64>,
+    ) -> Result<()> {
+        let (tshape, transform) = self.shape(shape, parent)?;
+        match tshape.kind {
+            ShapeKind::Edge => edges.push(self.edge(shape, parent)?),
+            _ => {
+                for child in &tshape.children {
+                    edges.push(self.edge(child, &transform)?);
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Translates `shape`, placed in its parent by `parent`, into representation items,
+    /// descending through compounds.
+    ///
+    /// Edges and wires outside of faces are gathered into `edges` for a wireframe.
+    fn items(
+        &mut self,
+        shape: &ShapeRef,
+        parent: &Matrix4<f64>,
+        items: &mut Vec<u64>,
+        edges: &mut Vec<u64>,
+    ) -> Result<()> {
+        let (tshape, transform) = self.shape(shape, parent)?;
+        match tshape.kind {
+            ShapeKind::Compound | ShapeKind::CompSolid => {
+                for child in &tshape.children {
+                    self.items(child, &transform, items, edges)?;
+                }
+            },
+            ShapeKind::Solid => {
+                let [shell] = tshape.children.as_slice() else {
+                    bail!(
+                        "Shape {}: solids with voids can't be translated yet",
+                        shape.shape
+                    );
+                };
+                let shell = self.shell(shell, &transform)?;
+                items.push(self.add(
+                    "MANIFOLD_SOLID_BREP",
+                    vec![string(""), Parameter::Ref(shell)],
+                ));
+            },
+            ShapeKind::Shell => {
+                let shell = self.shell(shape, parent)?;
+                items.push(self.add("SHELL_BASED_SURFACE_MODEL", vec![string(""), refs([shell])]));
+            },
+            ShapeKind::Face => {
+                let face = self.face(shape, parent)?;
+                let shell = self.add("OPEN_SHELL", vec![string(""), refs([face])]);
+                items.push(self.add("SHELL_BASED_SURFACE_MODEL", vec![string(""), refs([shell])]));
+            },
+            ShapeKind::Wire | ShapeKind::Edge => self.free_edges(shape, parent, edges)?,
+            ShapeKind::Vertex => bail!(
+                "Shape {}: vertices can't be translated on their own",
+                shape.shape
+            ),
+        }
+        Ok(())
+    }
+
+    /// Adds the millimetre unit and representation context AP214 requires, returns the context.
+    fn context(&mut self) -> u64 {
+        let length = self.add_complex(
+            "LENGTH_UNIT",
+            vec![],
+            vec![
+                ("NAMED_UNIT", vec![Parameter::Derived]),
+                (
+                    "SI_UNIT",
+                    vec![
+                        Parameter::Enum("MILLI".into()),
+                        Parameter::Enum("METRE".into()),
+                    ],
+                ),
+            ],
+        );
+        let angle = self.add_complex(
+            "NAMED_UNIT",
+            vec![Parameter::Derived],
+            vec![
+                ("PLANE_ANGLE_UNIT", vec![]),
+                (
+                    "SI_UNIT",
+                    vec![Parameter::Unset, Parameter::Enum("RADIAN".into())],
+                ),
+            ],
+        );
+        let solid_angle = self.add_complex(
+            "NAMED_UNIT",
+            vec![Parameter::Derived],
+            vec![
+                (
+                    "SI_UNIT",
+                    vec![Parameter::Unset, Parameter::Enum("STERADIAN".into())],
+                ),
+                ("SOLID_ANGLE_UNIT", vec![]),
+            ],
+        );
+        let uncertainty = self.add(
+            "UNCERTAINTY_MEASURE_WITH_UNIT",
+            vec![
+                Parameter::Typed("LENGTH_MEASURE".into(), Box::new(real(TOLERANCE))),
+                Parameter::Ref(length),
+                string("distance_accuracy_value"),
+                string("confusion accuracy"),
+            ],
+        );
+        self.add_complex(
+            "GEOMETRIC_REPRESENTATION_CONTEXT",
+            vec![Parameter::Integer(3)],
+            vec![
+                (
+                    "GLOBAL_UNCERTAINTY_ASSIGNED_CONTEXT",
+                    vec![refs([uncertainty])],
+                ),
+                (
+                    "GLOBAL_UNIT_ASSIGNED_CONTEXT",
+                    vec![refs([length, angle, solid_angle])],
+                ),
+                (
+                    "REPRESENTATION_CONTEXT",
+                    vec![
+                        string("Context #1"),
+                        string("3D Context with UNIT and UNCERTAINTY"),
+                    ],
+                ),
+            ],
+        )
+    }
+
+    /// Adds the product structure AP214 hangs the shape representation off.
+    fn product(&mut self, name: &str, representation: u64) {
+        let application = self.add("APPLICATION_CONTEXT", vec![string("automotive design")]);
+        self.add(
+            "APPLICATION_PROTOCOL_DEFINITION",
+            vec![
+                string("draft international standard"),
+                string("automotive_design"),
+                Parameter::Integer(1998),
+                Parameter::Ref(application),
+            ],
+        );
+        let product_context = self.add(
+            "PRODUCT_CONTEXT",
+            vec![
+                string(""),
+                Parameter::Ref(application),
+                string("mechanical"),
+            ],
+        );
+        let product = self.add(
+            "PRODUCT",
+            vec![
+                string(name),
+                string(name),
+                string(""),
+                refs([product_context]),
+            ],
+        );
+        let formation = self.add(
+            "PRODUCT_DEFINITION_FORMATION",
+            vec![string(""), string(""), Parameter::Ref(product)],
+        );
+        let definition_context = self.add(
+            "PRODUCT_DEFINITION_CONTEXT",
+            vec![
+                string("part definition"),
+                Parameter::Ref(application),
+                string("design"),
+            ],
+        );
+        let definition = self.add(
+            "PRODUCT_DEFINITION",
+            vec![
+                string("design"),
+                string(""),
+                Parameter::Ref(formation),
+                Parameter::Ref(definition_context),
+            ],
+        );
+        let shape = self.add(
+            "PRODUCT_DEFINITION_SHAPE",
+            vec![string(""), string(""), Parameter::Ref(definition)],
+        );
+        self.add(
+            "SHAPE_DEFINITION_REPRESENTATION",
+            vec![Parameter::Ref(shape), Parameter::Ref(representation)],
+        );
+    }
+}
+
+/// Translates the root shape of a [`BrepModel`] into a minimal AP214 [`EntityGraph`] for a
+/// product called `name`.
+///
+/// Supports vertices, edges on lines and circles, and faces on planes, cylinders, cones and
+/// spheres. Located shapes, curves and surfaces are moved where their locations place them, edges
+/// and wires outside of faces become a wireframe. Fails with a list of every shape that can't be
+/// translated yet.
+pub fn brep_to_step(model: &BrepModel, name: &str) -> Result<EntityGraph> {
+    let Some(root) = model.root else {
+        bail!("The model has no root shape");
+    };
+    let mut builder = StepBuilder::new(model)?;
+    let context = builder.context();
+    let mut items = Vec::new();
+    let mut edges = Vec::new();
+    builder.items(&root, &Matrix4::identity(), &mut items, &mut edges)?;
+    if !builder.unsupported.is_empty() {
+        let list: Vec<String> = builder.unsupported.into_iter().collect();
+        bail!(
+            "{} shapes can't be translated:\n  {}",
+            list.len(),
+            list.join("\n  ")
+        );
+    }
+
+    let solids_only = items
+        .iter()
+        .all(|id| builder.graph.entities[id].keyword == "MANIFOLD_SOLID_BREP");
+    let keyword = match (items.is_empty(), edges.is_empty()) {
+        (false, true) if solids_only => "ADVANCED_BREP_SHAPE_REPRESENTATION",
+        (false, true) => "MANIFOLD_SURFACE_SHAPE_REPRESENTATION",
+        (true, false) => "EDGE_BASED_WIREFRAME_SHAPE_REPRESENTATION",
+        _ => "SHAPE_REPRESENTATION",
+    };
+    if !edges.is_empty() {
+        let mut seen = HashSet::new();
+        edges.retain(|&edge| seen.insert(edge));
+        let set = builder.add("CONNECTED_EDGE_SET", vec![string(""), refs(edges)]);
+        items.push(builder.add("EDGE_BASED_WIREFRAME_MODEL", vec![string(""), refs([set])]));
+    }
+    let origin = builder.placement([0.0; 3], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]);
+    items.insert(0, origin);
+    let representation = builder.add(
+        keyword,
+        vec![string(name), refs(items), Parameter::Ref(context)],
+    );
+    builder.product(name, representation);
+    Ok(builder.graph)
+}
+
+#[cfg(test)]
+mod tests {
+    // STD
+    use std::path::Path;
+
+    // Dependencies
+    use cgmath::Vector3;
+
+    use super::*;
+    use crate::brep::{Location, parse_brep, write_brep};
+    use crate::step::{TessellationOptions, read_step, tessellate_faces};
+
+    fn asset(name: &str) -> EntityGraph {
+        read_step(
+            &Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("assets/step")
+                .join(name),
+        )
+        .unwrap()
+    }
+
+    /// Counts the instances of `keyword` in `graph`.
+    fn count(graph: &EntityGraph, keyword: &str) -> usize {
+        graph.instances_of(keyword).count()
+    }
+
+    /// Returns the total area and signed volume of the tessellated faces of `graph`.
+    fn measure(graph: &EntityGraph) -> (f64, f64) {
+        let tessellation = tessellate_faces(graph, &TessellationOptions::default()).unwrap();
+        assert!(
+            tessellation.skipped.is_empty(),
+            "{:?}",
+            tessellation.skipped
+        );
+        let (mut area, mut volume) = (0.0, 0.0);
+        for triangle in tessellation.vertices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| {
+                let [x, y, z] = triangle[i].position;
+                Vector3::new(x as f64, y as f64, z as f64)
+            });
+            area += (b - a).cross(c - a).magnitude() / 2.0;
+            volume += a.dot(b.cross(c)) / 6.0;
+        }
+        (area, volume)
+    }
+
+    fn assert_round_trip(name: &str) {
+        let graph = asset(name);
+        let model = step_to_brep(&graph).unwrap();
+        model.validate().unwrap();
+
+        // Through the text format too, which has to keep the typed curves and surfaces
+        let mut text = Vec::new();
+        write_brep(&model, &mut text).unwrap();
+        let reread = parse_brep(std::str::from_utf8(&text).unwrap()).unwrap();
+        assert_eq!(reread, model, "{}", name);
+
+        let back = brep_to_step(&reread, name).unwrap();
+        back.check_references().unwrap();
+        for keyword in [
+            "ADVANCED_FACE",
+            "EDGE_CURVE",
+            "VERTEX_POINT",
+            "PLANE",
+            "CYLINDRICAL_SURFACE",
+            "CONICAL_SURFACE",
+            "SPHERICAL_SURFACE",
+            "LINE",
+            "CIRCLE",
+        ] {
+            assert_eq!(
+                count(&back, keyword),
+                count(&graph, keyword),
+                "{}: {}",
+                name,
+                keyword
+            );
+        }
+        let (area, volume) = measure(&graph);
+        let (back_area, back_volume) = measure(&back);
+        assert!(
+            (back_area - area).abs() < 1e-3 * area,
+            "{}: area {} became {}",
+            name,
+            area,
+            back_area
+        );
+        assert!(
+            (back_volume - volume).abs() < 1e-3 * volume.abs().max(1.0),
+            "{}: volume {} became {}",
+            name,
+            volume,
+            back_volume
+        );
+    }
+
+    #[test]
+    fn round_trips_planar_solids() {
+        assert_round_trip("cube.step");
+    }
+
+    #[test]
+    fn round_trips_circles_and_cylinders() {
+        assert_round_trip("plate_with_hole.step");
+        assert_round_trip("plate_with_hole_inch.step");
+        assert_round_trip("red_cube_blue_cylinder.step");
+    }
+
+    #[test]
+    fn round_trips_cones_and_spheres() {
+        assert_round_trip("cone.step");
+        assert_round_trip("sphere.step");
+    }
+
+    #[test]
+    fn orients_circles_along_their_edges() {
+        let model = step_to_brep(&asset("plate_with_hole.step")).unwrap();
+        for shape in &model.shapes {
+            let ShapeGeometry::Edge { reps, .. } = &shape.geometry else {
+                continue;
+            };
+            let [
+                EdgeRep::Curve3d {
+                    curve, first, last, ..
+                },
+            ] = reps.as_slice()
+            else {
+                panic!("Expected a single 3D curve");
+            };
+            let Some(&Curve::Circle {
+                center,
+                x_dir,
+                y_dir,
+                radius,
+                ..
+            }) = model.curve(*curve)
+            else {
+                continue;
+            };
+            assert!(last > first);
+            // The curve starts at the forward vertex
+            let start = shape
+                .children
+                .iter()
+                .find(|c| c.orientation == Orientation::Forward)
+                .unwrap();
+            let ShapeGeometry::Vertex { point, .. } = model.shape(start.shape).unwrap().geometry
+            else {
+                panic!("Expected a vertex");
+            };
+            let at = Vector3::from(center)
+                + (Vector3::from(x_dir) * first.cos() + Vector3::from(y_dir) * first.sin())
+                    * radius;
+            assert!((at - Vector3::from(point)).magnitude() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn places_located_shapes() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/brep/test.brep");
+        let model = crate::brep::read_brep(&path).unwrap();
+        let graph = brep_to_step(&model, "test").unwrap();
+
+        // Written out and parsed back, as brepconv does
+        let mut text = Vec::new();
+        crate::step::write_step(&graph, "test.step", &mut text).unwrap();
+        let graph = crate::step::parse(std::str::from_utf8(&text).unwrap()).unwrap();
+        graph.check_references().unwrap();
+        assert_eq!(count(&graph, "ADVANCED_FACE"), 6);
+        assert_eq!(count(&graph, "EDGE_BASED_WIREFRAME_MODEL"), 1);
+
+        // The 1x2x3 box is turned and moved to (4, 5, 6) by a composed location
+        let (min, max) = crate::step::geometry::cartesian_points(&graph)
+            .unwrap()
+            .into_iter()
+            .filter(|(id, _)| {
+                graph
+                    .instances_of("VERTEX_POINT")
+                    .any(|v| v.references() == [*id])
+            })
+            .fold(([f64::MAX; 3], [f64::MIN; 3]), |(min, max), (_, p)| {
+                (
+                    [0, 1, 2].map(|i| min[i].min(p[i])),
+                    [0, 1, 2].map(|i| max[i].max(p[i])),
+                )
+            });
+        // The free edge runs from (1, 0, 0) to (2, 0, 0) unplaced
+        assert_eq!(min, [1.0, 0.0, 0.0]);
+        assert_eq!(max, [7.0, 6.0, 8.0]);
+        let (area, volume) = measure(&graph);
+        assert!((area - 22.0).abs() < 1e-9, "area {}", area);
+        assert!((volume - 6.0).abs() < 1e-9, "volume {}", volume);
+    }
+
+    #[test]
+    fn composes_locations() {
+        let translation = |x: f64| {
+            Location::Elementary([
+                [1.0, 0.0, 0.0, x],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+            ])
+        };
+        let quarter_turn = Location::Elementary([
+            [0.0, -1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ]);
+        let model = BrepModel {
+            locations: vec![
+                translation(2.0),
+                quarter_turn,
+                Location::Composed(vec![(1, 3)]),
+                Location::Composed(vec![(1, -1)]),
+                // Turned first, then moved
+                Location::Composed(vec![(2, 1), (1, 1)]),
+            ],
+            ..Default::default()
+        };
+        let transforms = model.location_transforms().unwrap();
+        let at = |i: usize| transforms[i].transform_point(Point3::new(1.0, 0.0, 0.0));
+        assert_eq!(at(2), Point3::new(7.0, 0.0, 0.0));
+        assert_eq!(at(3), Point3::new(-1.0, 0.0, 0.0));
+        assert_eq!(at(4), Point3::new(2.0, 1.0, 0.0));
+
+        let forward = BrepModel {
+            locations: vec![Location::Composed(vec![(1, 1)])],
+            ..Default::default()
+        };
+        assert!(forward.location_transforms().is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_surfaces() {
+        let error = step_to_brep(
+            &crate::step::parse(
+                "ISO-10303-21;\nHEADER;\nENDSEC;\nDATA;\n\
+             #1 = TOROIDAL_SURFACE('',#2,5.,1.);\n\
+             #2 = AXIS2_PLACEMENT_3D('',#3,$,$);\n\
+             #3 = CARTESIAN_POINT('',(0.,0.,0.));\n\
+             #4 = ADVANCED_FACE('',(),#1,.T.);\nENDSEC;\nEND-ISO-10303-21;\n",
+            )
+            .unwrap(),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("TOROIDAL_SURFACE"), "{}", error);
+    }
+}
@@ -0,0 +1,40 @@
+/// Width of BREP edges until [`crate::State::set_edge_width`] picks another, in pixels
+pub const DEFAULT_EDGE_WIDTH: f32 = 1.5;
+
+/// Shader uniform for drawing BREP edges as quads of a constant width on screen
+///
+/// Must format the *uniform* for storage in the *edge* buffer [`crate::State::edge_buffer`]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct EdgeUniform {
+    /// Size of the render target in pixels
+    viewport: [f32; 2],
+    /// Width of the edges in pixels, the antialiased fringe comes on top
+    width: f32,
+    _padding: f32,
+}
+
+impl EdgeUniform {
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut uniform = Self {
+            viewport: [0.0; 2],
+            width: DEFAULT_EDGE_WIDTH,
+            _padding: 0.0,
+        };
+        uniform.update_viewport(width, height);
+        uniform
+    }
+
+    /// Matches the size of the render target, edges are offset in its pixels.
+    pub fn update_viewport(&mut self, width: u32, height: u32) {
+        self.viewport = [width.max(1) as f32, height.max(1) as f32];
+    }
+
+    pub fn update_width(&mut self, width: f32) {
+        self.width = width.max(0.0);
+    }
+
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+}
@@ -0,0 +1,150 @@
+// STD
+use std::io::Write;
+
+// Dependencies
+use anyhow::Result;
+
+// Local modules
+use crate::step::tessellate::Polyline;
+
+/// Writes a DXF group: the group code and its value, each on a line of its own.
+fn group(w: &mut impl Write, code: u16, value: impl std::fmt::Display) -> Result<()> {
+    writeln!(w, "{:>3}\n{}", code, value)?;
+    Ok(())
+}
+
+/// Writes `point` with the group codes `base`, `base + 10` and `base + 20` for x, y and z.
+fn point(w: &mut impl Write, base: u16, point: [f64; 3]) -> Result<()> {
+    for (i, value) in point.into_iter().enumerate() {
+        group(w, base + 10 * i as u16, value)?;
+    }
+    Ok(())
+}
+
+/// Writes `edges` as an ASCII DXF R12 wireframe on layer `0`.
+///
+/// Straight edges become `LINE`s, subdivided ones 3D `POLYLINE`s with a `VERTEX` per point.
+/// Edges with less than two points are left out.
+pub fn write_dxf(edges: &[Polyline], w: &mut impl Write) -> Result<()> {
+    group(w, 0, "SECTION")?;
+    group(w, 2, "HEADER")?;
+    group(w, 9, "$ACADVER")?;
+    group(w, 1, "AC1009")?;
+    group(w, 0, "ENDSEC")?;
+
+    group(w, 0, "SECTION")?;
+    group(w, 2, "ENTITIES")?;
+    for edge in edges {
+        match edge.points.as_slice() {
+            [] | [_] => {},
+            &[start, end] => {
+                group(w, 0, "LINE")?;
+                group(w, 8, "0")?;
+                point(w, 10, start)?;
+                point(w, 11, end)?;
+            },
+            points => {
+                group(w, 0, "POLYLINE")?;
+                group(w, 8, "0")?;
+                // Vertices follow
+                group(w, 66, 1)?;
+                point(w, 10, [0.0; 3])?;
+                // 3D polyline
+                group(w, 70, 8)?;
+                for &vertex in points {
+                    group(w, 0, "VERTEX")?;
+                    group(w, 8, "0")?;
+                    point(w, 10, vertex)?;
+                    // 3D polyline vertex
+                    group(w, 70, 32)?;
+                }
+                group(w, 0, "SEQEND")?;
+                group(w, 8, "0")?;
+            },
+        }
+    }
+    group(w, 0, "ENDSEC")?;
+    group(w, 0, "EOF")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polyline(edge_id: u64, points: &[[f64; 3]]) -> Polyline {
+        Polyline {
+            edge_id,
+            points: points.to_vec(),
+        }
+    }
+
+    /// Splits DXF text into its `(group code, value)` pairs.
+    fn groups(text: &str) -> Vec<(u16, &str)> {
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len() % 2, 0, "Group code without a value");
+        lines
+            .chunks_exact(2)
+            .map(|pair| (pair[0].trim().parse().unwrap(), pair[1]))
+            .collect()
+    }
+
+    #[test]
+    fn writes_lines_and_polylines() {
+        let edges = [
+            polyline(1, &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]]),
+            polyline(2, &[[0.0, 0.0, 0.0], [0.5, 0.5, 0.0], [1.0, 1.0, 1.0]]),
+            polyline(3, &[[2.0, 2.0, 2.0]]),
+        ];
+        let mut bytes = Vec::new();
+        write_dxf(&edges, &mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        let groups = groups(&text);
+
+        assert_eq!(
+            groups[..4],
+            [
+                (0, "SECTION"),
+                (2, "HEADER"),
+                (9, "$ACADVER"),
+                (1, "AC1009")
+            ]
+        );
+        assert_eq!(groups[groups.len() - 2..], [(0, "ENDSEC"), (0, "EOF")]);
+        let entities: Vec<&str> = groups
+            .iter()
+            .filter(|&&(code, _)| code == 0)
+            .map(|&(_, value)| value)
+            .collect();
+        assert_eq!(
+            entities,
+            [
+                "SECTION", "ENDSEC", "SECTION", "LINE", "POLYLINE", "VERTEX", "VERTEX", "VERTEX",
+                "SEQEND", "ENDSEC", "EOF"
+            ]
+        );
+
+        // The line's start and end, x, y and z of each
+        let line = groups.iter().position(|&g| g == (0, "LINE")).unwrap();
+        assert_eq!(
+            groups[line + 1..line + 8],
+            [
+                (8, "0"),
+                (10, "0"),
+                (20, "0"),
+                (30, "0"),
+                (11, "1"),
+                (21, "0"),
+                (31, "0")
+            ]
+        );
+        // The last vertex of the 3D polyline
+        let vertex = groups.iter().rposition(|&g| g == (0, "VERTEX")).unwrap();
+        assert_eq!(
+            groups[vertex + 1..vertex + 6],
+            [(8, "0"), (10, "1"), (20, "1"), (30, "1"), (70, "32")]
+        );
+        // The single point edge is left out
+        assert!(!groups.iter().any(|&(_, value)| value == "2"));
+    }
+}
@@ -0,0 +1,220 @@
+// STD
+use std::collections::HashMap;
+use std::io::Write;
+
+// Dependencies
+use anyhow::{Result, bail};
+use cgmath::{InnerSpace, Vector3};
+
+// Local modules
+use crate::mesh::Vertex;
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: &[u8; 4] = b"JSON";
+const CHUNK_BIN: &[u8; 4] = b"BIN\0";
+
+// Constants from the glTF 2.0 specification
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const TRIANGLES: u32 = 4;
+
+/// Turns a non-indexed triangle list into an indexed mesh with facet normals.
+///
+/// Corners are only shared between triangles with the same position and normal, so flat faces
+/// stay flat instead of being smoothed over their edges.
+pub fn flat_shaded(vertices: &[Vertex]) -> (Vec<Vertex>, Vec<[f32; 3]>, Vec<u32>) {
+    let mut corners: HashMap<([u32; 3], [u32; 3]), u32> = HashMap::new();
+    let mut unique = Vec::new();
+    let mut normals = Vec::new();
+    let mut indices = Vec::with_capacity(vertices.len());
+    for triangle in vertices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(triangle[i].position));
+        let normal = (b - a).cross(c - a);
+        let normal: [f32; 3] = if normal.magnitude2() > 0.0 {
+            normal.normalize().into()
+        } else {
+            [0.0; 3]
+        };
+        for vertex in triangle {
+            let key = (vertex.position.map(f32::to_bits), normal.map(f32::to_bits));
+            let index = *corners.entry(key).or_insert_with(|| {
+                unique.push(*vertex);
+                normals.push(normal);
+                unique.len() as u32 - 1
+            });
+            indices.push(index);
+        }
+    }
+    (unique, normals, indices)
+}
+
+/// Appends `values` to `bin` as little-endian bytes and returns the `(offset, length)` written.
+fn push_f32s(bin: &mut Vec<u8>, values: impl IntoIterator<Item = f32>) -> (usize, usize) {
+    let offset = bin.len();
+    values
+        .into_iter()
+        .for_each(|v| bin.extend_from_slice(&v.to_le_bytes()));
+    (offset, bin.len() - offset)
+}
+
+/// Writes a chunk, padding its data to 4 bytes with `padding`.
+fn write_chunk(w: &mut impl Write, kind: &[u8; 4], data: &[u8], padding: u8) -> Result<()> {
+    let padded = data.len().next_multiple_of(4);
+    w.write_all(&(padded as u32).to_le_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    w.write_all(&vec![padding; padded - data.len()])?;
+    Ok(())
+}
+
+/// Writes an indexed triangle mesh as a binary glTF 2.0 (`.glb`) file with a single mesh.
+///
+/// Positions, `normals` (one per vertex) and the 0-based `indices` share one binary buffer. The
+/// `POSITION` accessor carries the bounds most loaders require.
+pub fn write_glb(
+    verts: &[Vertex],
+    normals: &[[f32; 3]],
+    indices: &[u32],
+    w: &mut impl Write,
+) -> Result<()> {
+    if verts.is_empty() || indices.is_empty() {
+        bail!("Can't write an empty mesh");
+    }
+    if normals.len() != verts.len() {
+        bail!(
+            "Expected one normal per vertex but got {} for {} vertices",
+            normals.len(),
+            verts.len()
+        );
+    }
+    if !indices.len().is_multiple_of(3) {
+        bail!("Expected a triangle list but got {} indices", indices.len());
+    }
+    if let Some(index) = indices.iter().find(|&&i| i as usize >= verts.len()) {
+        bail!("Index {} is out of range ({} vertices)", index, verts.len());
+    }
+    if verts
+        .iter()
+        .flat_map(|v| v.position)
+        .any(|c| !c.is_finite())
+    {
+        bail!("Can't write non-finite positions");
+    }
+
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for v in verts {
+        for ((lo, hi), c) in min.iter_mut().zip(&mut max).zip(v.position) {
+            *lo = lo.min(c);
+            *hi = hi.max(c);
+        }
+    }
+
+    let mut bin = Vec::new();
+    let positions = push_f32s(&mut bin, verts.iter().flat_map(|v| v.position));
+    let normal_data = push_f32s(&mut bin, normals.iter().flatten().copied());
+    let index_offset = bin.len();
+    indices
+        .iter()
+        .for_each(|i| bin.extend_from_slice(&i.to_le_bytes()));
+    let index_data = (index_offset, bin.len() - index_offset);
+
+    let json = format!(
+        concat!(
+            r#"{{"asset":{{"version":"2.0","generator":"brepimport"}},"#,
+            r#""scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"#,
+            r#""meshes":[{{"primitives":[{{"attributes":{{"POSITION":0,"NORMAL":1}},"indices":2,"mode":{mode}}}]}}],"#,
+            r#""buffers":[{{"byteLength":{buffer_len}}}],"#,
+            r#""bufferViews":["#,
+            r#"{{"buffer":0,"byteOffset":{p_off},"byteLength":{p_len},"target":{array}}},"#,
+            r#"{{"buffer":0,"byteOffset":{n_off},"byteLength":{n_len},"target":{array}}},"#,
+            r#"{{"buffer":0,"byteOffset":{i_off},"byteLength":{i_len},"target":{element}}}],"#,
+            r#""accessors":["#,
+            r#"{{"bufferView":0,"componentType":{float},"count":{vertex_count},"type":"VEC3","min":[{min}],"max":[{max}]}},"#,
+            r#"{{"bufferView":1,"componentType":{float},"count":{vertex_count},"type":"VEC3"}},"#,
+            r#"{{"bufferView":2,"componentType":{uint},"count":{index_count},"type":"SCALAR"}}]}}"#,
+        ),
+        mode = TRIANGLES,
+        buffer_len = bin.len(),
+        p_off = positions.0,
+        p_len = positions.1,
+        n_off = normal_data.0,
+        n_len = normal_data.1,
+        i_off = index_data.0,
+        i_len = index_data.1,
+        array = ARRAY_BUFFER,
+        element = ELEMENT_ARRAY_BUFFER,
+        float = FLOAT,
+        uint = UNSIGNED_INT,
+        vertex_count = verts.len(),
+        index_count = indices.len(),
+        min = min.map(|c| c.to_string()).join(","),
+        max = max.map(|c| c.to_string()).join(","),
+    );
+
+    // Header and chunk headers take 12 and 2 * 8 bytes
+    let length = 12 + 8 + json.len().next_multiple_of(4) + 8 + bin.len().next_multiple_of(4);
+    let Ok(length) = u32::try_from(length) else {
+        bail!("Mesh is too large for a GLB file");
+    };
+    w.write_all(GLB_MAGIC)?;
+    w.write_all(&GLB_VERSION.to_le_bytes())?;
+    w.write_all(&length.to_le_bytes())?;
+    write_chunk(w, CHUNK_JSON, json.as_bytes(), b' ')?;
+    write_chunk(w, CHUNK_BIN, &bin, 0)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> Vec<Vertex> {
+        [[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 1.0, -1.0]]
+            .map(|position| Vertex {
+                position,
+                uv_coords: [0.0; 2],
+                normal: [0.0; 3],
+            })
+            .to_vec()
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn writes_glb_header_and_chunks() {
+        let (verts, normals, indices) = flat_shaded(&triangle());
+        let mut bytes = Vec::new();
+        write_glb(&verts, &normals, &indices, &mut bytes).unwrap();
+
+        assert_eq!(&bytes[..4], GLB_MAGIC);
+        assert_eq!(u32_at(&bytes, 4), GLB_VERSION);
+        assert_eq!(u32_at(&bytes, 8) as usize, bytes.len());
+
+        let json_len = u32_at(&bytes, 12) as usize;
+        assert_eq!(&bytes[16..20], CHUNK_JSON);
+        assert_eq!(json_len % 4, 0);
+        let json = std::str::from_utf8(&bytes[20..20 + json_len]).unwrap();
+        assert!(json.trim_end().ends_with('}'));
+        assert!(json.contains(r#""min":[0,0,-1],"max":[2,1,0]"#));
+
+        // Three positions and normals of 12 bytes, and three 4-byte indices
+        let bin = 20 + json_len;
+        assert_eq!(u32_at(&bytes, bin), 3 * 12 * 2 + 3 * 4);
+        assert_eq!(&bytes[bin + 4..bin + 8], CHUNK_BIN);
+        assert_eq!(bytes.len(), bin + 8 + 84);
+        assert_eq!(u32_at(&bytes, bytes.len() - 4), 2);
+    }
+
+    #[test]
+    fn rejects_partial_triangles() {
+        let (verts, normals, _) = flat_shaded(&triangle());
+        assert!(write_glb(&verts, &normals, &[0, 1], &mut Vec::new()).is_err());
+        assert!(write_glb(&verts, &normals, &[0, 1, 3], &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,12 @@
+//! Mesh exporters for tessellated models, and a wireframe exporter for their edges.
+pub mod dxf;
+pub mod gltf;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+
+pub use dxf::write_dxf;
+pub use gltf::{flat_shaded, write_glb};
+pub use obj::{write_obj, write_obj_welded};
+pub use ply::{PlyFormat, write_ply, write_ply_header};
+pub use stl::{write_stl_ascii, write_stl_binary};
@@ -0,0 +1,146 @@
+// STD
+use std::collections::HashMap;
+use std::io::Write;
+
+// Dependencies
+use anyhow::{Result, bail};
+use cgmath::{InnerSpace, Vector3};
+
+// Local modules
+use crate::mesh::Vertex;
+use crate::mesh::weld::weld;
+
+/// Distance below which [`write_obj`] merges positions by default.
+pub const DEFAULT_EPSILON: f32 = 1e-5;
+
+/// Writes an indexed triangle mesh as a Wavefront OBJ with `v`, `vn` and `f` records, merging
+/// vertices within [`DEFAULT_EPSILON`] of each other.
+pub fn write_obj(verts: &[Vertex], indices: &[u32], w: &mut impl Write) -> Result<()> {
+    write_obj_welded(verts, indices, DEFAULT_EPSILON, w)
+}
+
+/// Writes an indexed triangle mesh as a Wavefront OBJ, after [`weld`]ing vertices within
+/// `epsilon` of each other.
+///
+/// `indices` are 0-based like a wgpu index buffer, and the welded buffers are the ones a `Mesh`
+/// would be built from. Every face gets its facet normal, identical normals are shared.
+pub fn write_obj_welded(
+    verts: &[Vertex],
+    indices: &[u32],
+    epsilon: f32,
+    w: &mut impl Write,
+) -> Result<()> {
+    if !indices.len().is_multiple_of(3) {
+        bail!("Expected a triangle list but got {} indices", indices.len());
+    }
+    if let Some(index) = indices.iter().find(|&&i| i as usize >= verts.len()) {
+        bail!("Index {} is out of range ({} vertices)", index, verts.len());
+    }
+    let (verts, indices) = weld(verts, indices, epsilon);
+
+    writeln!(w, "# Wavefront OBJ written by brepimport")?;
+    for [x, y, z] in verts.iter().map(|v| v.position) {
+        writeln!(w, "v {} {} {}", x, y, z)?;
+    }
+
+    // Normals are keyed by their bit patterns so identical facets share one record
+    let mut normal_indices: HashMap<[u32; 3], usize> = HashMap::new();
+    let mut normals = Vec::new();
+    let mut faces = Vec::with_capacity(indices.len() / 3);
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(verts[triangle[i] as usize].position));
+        let normal = (b - a).cross(c - a);
+        let normal: [f32; 3] = if normal.magnitude2() > 0.0 {
+            normal.normalize().into()
+        } else {
+            [0.0; 3]
+        };
+        let n = *normal_indices
+            .entry(normal.map(f32::to_bits))
+            .or_insert_with(|| {
+                normals.push(normal);
+                normals.len()
+            });
+        faces.push((triangle, n));
+    }
+    for [x, y, z] in normals {
+        writeln!(w, "vn {} {} {}", x, y, z)?;
+    }
+    for (triangle, n) in faces {
+        writeln!(
+            w,
+            "f {}//{} {}//{} {}//{}",
+            triangle[0] + 1,
+            n,
+            triangle[1] + 1,
+            n,
+            triangle[2] + 1,
+            n
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the 12 triangles of a unit cube, each with its own three vertices.
+    fn cube() -> Vec<Vertex> {
+        let corner = |i: usize| [i & 1, (i >> 1) & 1, (i >> 2) & 1].map(|c| c as f32);
+        let quads = [
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ];
+        quads
+            .iter()
+            .flat_map(|q| [q[0], q[1], q[2], q[0], q[2], q[3]])
+            .map(|i| Vertex {
+                position: corner(i),
+                uv_coords: [0.0; 2],
+                normal: [0.0; 3],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shares_welded_vertices() {
+        let vertices = cube();
+        let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+        let mut bytes = Vec::new();
+        write_obj(&vertices, &indices, &mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        let records = |kind: &str| text.lines().filter(|l| l.starts_with(kind)).count();
+        assert_eq!(records("v "), 8);
+        assert_eq!(records("vn "), 6);
+        assert_eq!(records("f "), 12);
+
+        // Indices are 1-based and every face uses the normal of its side
+        for face in text.lines().filter(|l| l.starts_with("f ")) {
+            let refs: Vec<(usize, usize)> = face
+                .split_whitespace()
+                .skip(1)
+                .map(|r| {
+                    let (v, n) = r.split_once("//").unwrap();
+                    (v.parse().unwrap(), n.parse().unwrap())
+                })
+                .collect();
+            assert!(
+                refs.iter()
+                    .all(|&(v, n)| (1..=8).contains(&v) && (1..=6).contains(&n))
+            );
+            assert!(refs.iter().all(|&(_, n)| n == refs[0].1));
+        }
+    }
+
+    #[test]
+    fn rejects_bad_indices() {
+        let vertices = cube();
+        assert!(write_obj(&vertices, &[0, 1], &mut Vec::new()).is_err());
+        assert!(write_obj(&vertices, &[0, 1, 36], &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+// STD
+use std::io::Write;
+
+// Dependencies
+use anyhow::{Result, bail};
+
+// Local modules
+use crate::mesh::Vertex;
+
+/// Encoding of the PLY body, the header is ASCII either way
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    #[default]
+    BinaryLittleEndian,
+}
+
+/// Writes the header of a PLY file with `vertices` vertex and `faces` face elements.
+///
+/// Vertices have `x`/`y`/`z` and `nx`/`ny`/`nz` float properties, followed by `red`/`green`/`blue`
+/// bytes if `colors` is set. Faces list their corners as `uint` indices.
+pub fn write_ply_header(
+    vertices: usize,
+    faces: usize,
+    colors: bool,
+    format: PlyFormat,
+    w: &mut impl Write,
+) -> Result<()> {
+    writeln!(w, "ply")?;
+    match format {
+        PlyFormat::Ascii => writeln!(w, "format ascii 1.0")?,
+        PlyFormat::BinaryLittleEndian => writeln!(w, "format binary_little_endian 1.0")?,
+    }
+    writeln!(w, "comment written by brepimport")?;
+    writeln!(w, "element vertex {}", vertices)?;
+    for name in ["x", "y", "z", "nx", "ny", "nz"] {
+        writeln!(w, "property float {}", name)?;
+    }
+    if colors {
+        for name in ["red", "green", "blue"] {
+            writeln!(w, "property uchar {}", name)?;
+        }
+    }
+    writeln!(w, "element face {}", faces)?;
+    writeln!(w, "property list uchar uint vertex_indices")?;
+    writeln!(w, "end_header")?;
+    Ok(())
+}
+
+/// Writes an indexed triangle mesh as PLY, with the vertex normals and optionally a color per
+/// vertex.
+///
+/// `indices` are 0-based like a wgpu index buffer, so pass a welded mesh to share vertices
+/// between faces.
+pub fn write_ply(
+    verts: &[Vertex],
+    indices: &[u32],
+    colors: Option<&[[u8; 3]]>,
+    format: PlyFormat,
+    w: &mut impl Write,
+) -> Result<()> {
+    if !indices.len().is_multiple_of(3) {
+        bail!("Expected a triangle list but got {} indices", indices.len());
+    }
+    if let Some(index) = indices.iter().find(|&&i| i as usize >= verts.len()) {
+        bail!("Index {} is out of range ({} vertices)", index, verts.len());
+    }
+    if let Some(colors) = colors
+        && colors.len() != verts.len()
+    {
+        bail!(
+            "Expected a color per vertex but got {} colors for {} vertices",
+            colors.len(),
+            verts.len()
+        );
+    }
+
+    write_ply_header(verts.len(), indices.len() / 3, colors.is_some(), format, w)?;
+    for (i, vertex) in verts.iter().enumerate() {
+        let floats = vertex.position.into_iter().chain(vertex.normal);
+        let color = colors.map(|colors| colors[i]);
+        match format {
+            PlyFormat::Ascii => {
+                let values: Vec<String> = floats.map(|v| v.to_string()).collect();
+                write!(w, "{}", values.join(" "))?;
+                if let Some([r, g, b]) = color {
+                    write!(w, " {} {} {}", r, g, b)?;
+                }
+                writeln!(w)?;
+            },
+            PlyFormat::BinaryLittleEndian => {
+                for value in floats {
+                    w.write_all(&value.to_le_bytes())?;
+                }
+                if let Some(color) = color {
+                    w.write_all(&color)?;
+                }
+            },
+        }
+    }
+    for triangle in indices.chunks_exact(3) {
+        match format {
+            PlyFormat::Ascii => {
+                writeln!(w, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?;
+            },
+            PlyFormat::BinaryLittleEndian => {
+                w.write_all(&[3])?;
+                for index in triangle {
+                    w.write_all(&index.to_le_bytes())?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(position: [f32; 3]) -> Vertex {
+        Vertex {
+            position,
+            uv_coords: [0.0; 2],
+            normal: [0.0, 0.0, 1.0],
+        }
+    }
+
+    /// A unit square of two triangles sharing their diagonal.
+    fn square() -> (Vec<Vertex>, Vec<u32>) {
+        let vertices = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ]
+        .map(vertex)
+        .to_vec();
+        (vertices, vec![0, 1, 2, 0, 2, 3])
+    }
+
+    const COLORS: [[u8; 3]; 4] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [10, 20, 30]];
+
+    /// Splits `bytes` after the `end_header` line, into the header text and the body.
+    fn split_header(bytes: &[u8]) -> (&str, &[u8]) {
+        let end = b"end_header\n";
+        let at = bytes
+            .windows(end.len())
+            .position(|w| w == end)
+            .expect("No end_header")
+            + end.len();
+        (std::str::from_utf8(&bytes[..at]).unwrap(), &bytes[at..])
+    }
+
+    #[test]
+    fn writes_ascii_vertices_and_faces() {
+        let (vertices, indices) = square();
+        let mut bytes = Vec::new();
+        write_ply(
+            &vertices,
+            &indices,
+            Some(&COLORS),
+            PlyFormat::Ascii,
+            &mut bytes,
+        )
+        .unwrap();
+        let (header, body) = split_header(&bytes);
+        assert!(header.starts_with("ply\nformat ascii 1.0\n"));
+        assert!(header.contains("element vertex 4\n"));
+        assert!(header.contains("property uchar blue\n"));
+        assert!(header.contains("element face 2\n"));
+
+        let lines: Vec<&str> = std::str::from_utf8(body).unwrap().lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "0 0 0 0 0 1 255 0 0",
+                "1 0 0 0 0 1 0 255 0",
+                "1 1 0 0 0 1 0 0 255",
+                "0 1 0 0 0 1 10 20 30",
+                "3 0 1 2",
+                "3 0 2 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_binary_little_endian_records() {
+        let (vertices, indices) = square();
+        let mut bytes = Vec::new();
+        write_ply(
+            &vertices,
+            &indices,
+            None,
+            PlyFormat::BinaryLittleEndian,
+            &mut bytes,
+        )
+        .unwrap();
+        let (header, body) = split_header(&bytes);
+        assert!(header.starts_with("ply\nformat binary_little_endian 1.0\n"));
+        assert!(!header.contains("property uchar red"));
+
+        // Six floats per vertex, then a count byte and three indices per face
+        assert_eq!(body.len(), 4 * 6 * 4 + 2 * (1 + 3 * 4));
+        let f32_at = |i: usize| f32::from_le_bytes(body[4 * i..4 * i + 4].try_into().unwrap());
+        assert_eq!(
+            (12..18).map(f32_at).collect::<Vec<_>>(),
+            [1.0, 1.0, 0.0, 0.0, 0.0, 1.0]
+        );
+        let faces = &body[4 * 6 * 4..];
+        for (triangle, face) in indices.chunks_exact(3).zip(faces.chunks_exact(13)) {
+            assert_eq!(face[0], 3);
+            let corners: Vec<u32> = face[1..]
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            assert_eq!(corners, triangle);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_indices_and_colors() {
+        let (vertices, indices) = square();
+        let write = |indices: &[u32], colors: Option<&[[u8; 3]]>| {
+            write_ply(
+                &vertices,
+                indices,
+                colors,
+                PlyFormat::Ascii,
+                &mut Vec::new(),
+            )
+        };
+        assert!(write(&indices[..5], None).is_err());
+        assert!(write(&[0, 1, 4], None).is_err());
+        assert!(write(&indices, Some(&COLORS[..3])).is_err());
+        assert!(write(&indices, Some(&COLORS)).is_ok());
+    }
+}
@@ -0,0 +1,138 @@
+// STD
+use std::io::Write;
+
+// Dependencies
+use anyhow::{Result, bail};
+use cgmath::{InnerSpace, Vector3};
+
+// Local modules
+use crate::mesh::Vertex;
+
+/// Returns the unit normal of a counter-clockwise triangle, or zero if it's degenerate.
+fn facet_normal(triangle: &[Vertex]) -> [f32; 3] {
+    let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(triangle[i].position));
+    let normal = (b - a).cross(c - a);
+    if normal.magnitude2() == 0.0 {
+        return [0.0; 3];
+    }
+    normal.normalize().into()
+}
+
+fn check_triangles(vertices: &[Vertex]) -> Result<()> {
+    if !vertices.len().is_multiple_of(3) {
+        bail!(
+            "Expected a triangle list but got {} vertices",
+            vertices.len()
+        );
+    }
+    Ok(())
+}
+
+/// Writes a non-indexed triangle list as binary STL: an 80-byte header, the little-endian
+/// triangle count and one 50-byte record per facet.
+pub fn write_stl_binary(vertices: &[Vertex], w: &mut impl Write) -> Result<()> {
+    check_triangles(vertices)?;
+    let Ok(count) = u32::try_from(vertices.len() / 3) else {
+        bail!("Too many triangles for binary STL");
+    };
+    let mut header = [0u8; 80];
+    let text = b"binary STL written by brepimport";
+    header[..text.len()].copy_from_slice(text);
+    w.write_all(&header)?;
+    w.write_all(&count.to_le_bytes())?;
+    for triangle in vertices.chunks_exact(3) {
+        let normal = facet_normal(triangle);
+        let positions = triangle.iter().map(|v| v.position);
+        for value in std::iter::once(normal).chain(positions).flatten() {
+            w.write_all(&value.to_le_bytes())?;
+        }
+        // Attribute byte count, unused
+        w.write_all(&[0, 0])?;
+    }
+    Ok(())
+}
+
+/// Writes a non-indexed triangle list as ASCII STL with the solid called `name`.
+pub fn write_stl_ascii(vertices: &[Vertex], name: &str, w: &mut impl Write) -> Result<()> {
+    check_triangles(vertices)?;
+    writeln!(w, "solid {}", name)?;
+    for triangle in vertices.chunks_exact(3) {
+        let [nx, ny, nz] = facet_normal(triangle);
+        writeln!(w, "  facet normal {:e} {:e} {:e}", nx, ny, nz)?;
+        writeln!(w, "    outer loop")?;
+        for [x, y, z] in triangle.iter().map(|v| v.position) {
+            writeln!(w, "      vertex {:e} {:e} {:e}", x, y, z)?;
+        }
+        writeln!(w, "    endloop")?;
+        writeln!(w, "  endfacet")?;
+    }
+    writeln!(w, "endsolid {}", name)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(position: [f32; 3]) -> Vertex {
+        Vertex {
+            position,
+            uv_coords: [0.0; 2],
+            normal: [0.0; 3],
+        }
+    }
+
+    fn triangles() -> Vec<Vertex> {
+        [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0],
+        ]
+        .map(vertex)
+        .to_vec()
+    }
+
+    fn f32_at(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn writes_binary_layout() {
+        let mut bytes = Vec::new();
+        write_stl_binary(&triangles(), &mut bytes).unwrap();
+        assert_eq!(bytes.len(), 80 + 4 + 2 * 50);
+        assert!(bytes[..80].starts_with(b"binary STL"));
+        assert_eq!(u32::from_le_bytes(bytes[80..84].try_into().unwrap()), 2);
+
+        // Normal, three vertices and the attribute byte count
+        let second = 84 + 50;
+        let normal = [0, 1, 2].map(|i| f32_at(&bytes, second + 4 * i));
+        assert_eq!(normal, [0.0, 1.0, 0.0]);
+        let last = [9, 10, 11].map(|i| f32_at(&bytes, second + 4 * i));
+        assert_eq!(last, [1.0, 0.0, 0.0]);
+        assert_eq!(bytes[second + 48..second + 50], [0, 0]);
+    }
+
+    #[test]
+    fn writes_ascii_facets() {
+        let mut bytes = Vec::new();
+        write_stl_ascii(&triangles(), "part", &mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("solid part\n"));
+        assert!(text.ends_with("endsolid part\n"));
+        assert_eq!(text.matches("facet normal").count(), 2);
+        assert_eq!(text.matches("vertex").count(), 6);
+        assert!(text.contains("facet normal 0e0 0e0 1e0"));
+    }
+
+    #[test]
+    fn rejects_partial_triangles() {
+        let mut vertices = triangles();
+        vertices.pop();
+        assert!(write_stl_binary(&vertices, &mut Vec::new()).is_err());
+        assert!(write_stl_ascii(&vertices, "part", &mut Vec::new()).is_err());
+    }
+}
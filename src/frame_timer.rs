@@ -0,0 +1,84 @@
+// STD
+use std::collections::VecDeque;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+// Dependencies
+// The standard clock panics in the browser
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// Number of frames [`FrameTimer`] averages over
+const ROLLING_FRAMES: usize = 60;
+
+/// Tracks the time between rendered frames.
+pub struct FrameTimer {
+    last_frame: Option<Instant>,
+    /// Durations of the last [`ROLLING_FRAMES`] frames, newest last
+    frame_times: VecDeque<Duration>,
+    last_report: Instant,
+}
+
+impl FrameTimer {
+    pub fn new() -> Self {
+        Self {
+            last_frame: None,
+            frame_times: VecDeque::with_capacity(ROLLING_FRAMES),
+            last_report: Instant::now(),
+        }
+    }
+
+    /// Records that a frame is being rendered now. Returns `true` once per second, when it's time
+    /// to report the frame rate.
+    pub fn tick(&mut self) -> bool {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame.replace(now) {
+            if self.frame_times.len() == ROLLING_FRAMES {
+                self.frame_times.pop_front();
+            }
+            self.frame_times.push_back(now - last_frame);
+        }
+        if now - self.last_report >= Duration::from_secs(1) {
+            self.last_report = now;
+            return true;
+        }
+        false
+    }
+
+    /// Duration of the last frame, zero before the second frame.
+    pub fn frame_time(&self) -> Duration {
+        self.frame_times.back().copied().unwrap_or_default()
+    }
+
+    /// Frame rate derived from the last frame alone.
+    pub fn fps(&self) -> f32 {
+        Self::rate(self.frame_time())
+    }
+
+    /// Mean duration of the last 60 frames.
+    pub fn average_frame_time(&self) -> Duration {
+        if self.frame_times.is_empty() {
+            return Duration::ZERO;
+        }
+        self.frame_times.iter().sum::<Duration>() / self.frame_times.len() as u32
+    }
+
+    /// Frame rate derived from [`FrameTimer::average_frame_time`].
+    pub fn average_fps(&self) -> f32 {
+        Self::rate(self.average_frame_time())
+    }
+
+    fn rate(frame_time: Duration) -> f32 {
+        if frame_time.is_zero() {
+            return 0.0;
+        }
+        1.0 / frame_time.as_secs_f32()
+    }
+}
+
+impl Default for FrameTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
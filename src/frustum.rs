@@ -0,0 +1,66 @@
+// Dependencies
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix4, Point3, Transform, Vector4};
+
+/// The six planes bounding what a view-projection matrix maps into clip space, for culling
+/// whatever lies fully outside of it.
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far, each as `(normal, offset)` with a unit normal
+    /// pointing into the frustum
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes of `view_proj` with the Gribb-Hartmann method, each plane is a sum or
+    /// difference of the matrix's rows.
+    pub fn from_view_proj(view_proj: Matrix4<f32>) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| view_proj.row(i));
+        // wgpu clip space has its depth in 0..1, so the near plane is the depth row alone
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            let length = plane.truncate().magnitude();
+            if length > 0.0 { plane / length } else { plane }
+        });
+        Self { planes }
+    }
+
+    /// Returns whether the sphere around `center` reaches into the frustum. Spheres near its
+    /// corners can pass without doing so, which only costs a draw that didn't need to happen.
+    pub fn intersects_sphere(&self, center: Point3<f32>, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(center.to_vec()) + plane.w >= -radius)
+    }
+}
+
+/// Maps the sphere around `center` through `m`, an affine transform, into one that encloses the
+/// result, scaled by the transform's largest scale factor.
+pub fn transform_sphere(m: Matrix4<f32>, center: Point3<f32>, radius: f32) -> (Point3<f32>, f32) {
+    let scale = [m.x, m.y, m.z]
+        .map(|axis| axis.truncate().magnitude())
+        .into_iter()
+        .fold(0.0, f32::max);
+    (m.transform_point(center), radius * scale)
+}
+
+/// Returns the smallest sphere enclosing the spheres `a` and `b`, each as `(center, radius)`.
+pub fn merge_spheres(a: (Point3<f32>, f32), b: (Point3<f32>, f32)) -> (Point3<f32>, f32) {
+    let ((a_center, a_radius), (b_center, b_radius)) = (a, b);
+    let offset = b_center - a_center;
+    let distance = offset.magnitude();
+    if distance + b_radius <= a_radius {
+        return a;
+    }
+    if distance + a_radius <= b_radius {
+        return b;
+    }
+    // Spans from the far side of `a` to the far side of `b`
+    let radius = (distance + a_radius + b_radius) / 2.0;
+    (a_center + offset * ((radius - a_radius) / distance), radius)
+}
+
+/// How many models the last frame drew and how many it skipped as being out of view
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CullStats {
+    pub drawn: usize,
+    pub culled: usize,
+}
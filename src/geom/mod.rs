@@ -0,0 +1,7 @@
+//! Evaluation of the curves and surfaces CAD models are built from, and triangulation of the
+//! regions they bound.
+pub mod nurbs;
+pub mod triangulate;
+
+pub use nurbs::{NurbsCurve, NurbsSurface};
+pub use triangulate::triangulate;
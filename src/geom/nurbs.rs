@@ -0,0 +1,353 @@
+// Dependencies
+use anyhow::{Result, bail};
+use cgmath::{InnerSpace, Vector3, Vector4};
+
+/// Distance in parameter space [`NurbsSurface::normal`] steps inside when the surface is
+/// degenerate at the requested point, relative to the domain size
+const DEGENERATE_NUDGE: f64 = 1e-6;
+/// Newton steps [`NurbsSurface::closest_parameters`] takes after the coarse search
+const INVERSION_ITERATIONS: usize = 12;
+/// Samples per direction of the coarse search of [`NurbsSurface::closest_parameters`]
+const INVERSION_SAMPLES: usize = 16;
+
+/// Returns the index `k` of the knot span `knots[k]..knots[k + 1]` containing `t`.
+///
+/// `count` is the number of control points, `t` is clamped to the curve's domain.
+fn find_span(degree: usize, knots: &[f64], count: usize, t: f64) -> usize {
+    if t >= knots[count] {
+        return count - 1;
+    }
+    if t <= knots[degree] {
+        return degree;
+    }
+    // The last knot not above `t`, skipping the repeated ones
+    knots[degree..=count].partition_point(|&k| k <= t) + degree - 1
+}
+
+/// Evaluates the B-spline with homogeneous control points `points` at `t` using de Boor's
+/// algorithm.
+fn de_boor(degree: usize, knots: &[f64], points: &[Vector4<f64>], t: f64) -> Vector4<f64> {
+    let k = find_span(degree, knots, points.len(), t);
+    let mut d: Vec<Vector4<f64>> = points[k - degree..=k].to_vec();
+    for r in 1..=degree {
+        for j in (r..=degree).rev() {
+            let i = j + k - degree;
+            let span = knots[i + degree + 1 - r] - knots[i];
+            let alpha = if span == 0.0 {
+                0.0
+            } else {
+                (t - knots[i]) / span
+            };
+            d[j] = d[j - 1] * (1.0 - alpha) + d[j] * alpha;
+        }
+    }
+    d[degree]
+}
+
+/// Returns the control points of the derivative of a B-spline, which has one degree less and
+/// uses `knots[1..knots.len() - 1]`.
+fn hodograph(degree: usize, knots: &[f64], points: &[Vector4<f64>]) -> Vec<Vector4<f64>> {
+    points
+        .windows(2)
+        .enumerate()
+        .map(|(i, pair)| {
+            let span = knots[i + degree + 1] - knots[i + 1];
+            if span == 0.0 {
+                Vector4::new(0.0, 0.0, 0.0, 0.0)
+            } else {
+                (pair[1] - pair[0]) * (degree as f64 / span)
+            }
+        })
+        .collect()
+}
+
+/// Evaluates the B-spline and its first derivative at `t`.
+fn de_boor_derivative(
+    degree: usize,
+    knots: &[f64],
+    points: &[Vector4<f64>],
+    t: f64,
+) -> (Vector4<f64>, Vector4<f64>) {
+    let value = de_boor(degree, knots, points, t);
+    if degree == 0 {
+        return (value, Vector4::new(0.0, 0.0, 0.0, 0.0));
+    }
+    let derivative = hodograph(degree, knots, points);
+    let inner = &knots[1..knots.len() - 1];
+    (value, de_boor(degree - 1, inner, &derivative, t))
+}
+
+/// Projects a homogeneous point and its derivative back to 3D.
+fn project(value: Vector4<f64>, derivative: Vector4<f64>) -> (Vector3<f64>, Vector3<f64>) {
+    let point = value.truncate() / value.w;
+    (
+        point,
+        (derivative.truncate() - point * derivative.w) / value.w,
+    )
+}
+
+/// Turns `points` and optional `weights` into homogeneous control points.
+fn homogeneous(points: &[[f64; 3]], weights: Option<&[f64]>) -> Vec<Vector4<f64>> {
+    points
+        .iter()
+        .enumerate()
+        .map(|(i, &[x, y, z])| {
+            let w = weights.map_or(1.0, |w| w[i]);
+            Vector4::new(x * w, y * w, z * w, w)
+        })
+        .collect()
+}
+
+/// Checks that `knots` fit `count` control points of `degree` and never decrease.
+fn check_knots(degree: usize, count: usize, knots: &[f64]) -> Result<()> {
+    if count <= degree {
+        bail!("Degree {} needs more than {} control points", degree, count);
+    }
+    if knots.len() != count + degree + 1 {
+        bail!(
+            "Expected {} knots for {} control points of degree {} but got {}",
+            count + degree + 1,
+            count,
+            degree,
+            knots.len()
+        );
+    }
+    if knots.windows(2).any(|pair| pair[1] < pair[0]) {
+        bail!("Knots must not decrease");
+    }
+    if knots[count] <= knots[degree] {
+        bail!("Knots span an empty domain");
+    }
+    Ok(())
+}
+
+/// A non-uniform rational B-spline curve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NurbsCurve {
+    degree: usize,
+    /// Weighted control points, `(w * x, w * y, w * z, w)`
+    points: Vec<Vector4<f64>>,
+    /// Knot vector with every knot repeated by its multiplicity
+    knots: Vec<f64>,
+}
+
+impl NurbsCurve {
+    /// `weights` default to 1, making the curve non-rational.
+    pub fn new(
+        degree: usize,
+        points: &[[f64; 3]],
+        weights: Option<&[f64]>,
+        knots: Vec<f64>,
+    ) -> Result<Self> {
+        check_knots(degree, points.len(), &knots)?;
+        if weights.is_some_and(|w| w.len() != points.len()) {
+            bail!(
+                "Expected a weight for each of the {} control points",
+                points.len()
+            );
+        }
+        Ok(Self {
+            degree,
+            points: homogeneous(points, weights),
+            knots,
+        })
+    }
+
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    /// Returns the parameter range the curve is defined on.
+    pub fn domain(&self) -> [f64; 2] {
+        [self.knots[self.degree], self.knots[self.points.len()]]
+    }
+
+    /// Returns the number of non-empty knot spans, a lower bound for how many segments the curve
+    /// needs.
+    pub fn spans(&self) -> usize {
+        let [start, end] = self.domain();
+        let mut knots = self.knots.clone();
+        knots.dedup();
+        knots.iter().filter(|&&k| k > start && k <= end).count()
+    }
+
+    /// Returns the point at parameter `t`.
+    pub fn evaluate(&self, t: f64) -> [f64; 3] {
+        let value = de_boor(self.degree, &self.knots, &self.points, t);
+        (value.truncate() / value.w).into()
+    }
+
+    /// Returns the unnormalized tangent at parameter `t`.
+    pub fn tangent(&self, t: f64) -> [f64; 3] {
+        let (value, derivative) = de_boor_derivative(self.degree, &self.knots, &self.points, t);
+        project(value, derivative).1.into()
+    }
+}
+
+/// A non-uniform rational B-spline surface.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NurbsSurface {
+    u_degree: usize,
+    v_degree: usize,
+    /// Weighted control points, `points[i][j]` is the `i`th along `u` and the `j`th along `v`
+    points: Vec<Vec<Vector4<f64>>>,
+    u_knots: Vec<f64>,
+    v_knots: Vec<f64>,
+}
+
+impl NurbsSurface {
+    /// `points[i][j]` is the `i`th control point along `u` and the `j`th along `v`, `weights` are
+    /// laid out the same way and default to 1.
+    pub fn new(
+        u_degree: usize,
+        v_degree: usize,
+        points: &[Vec<[f64; 3]>],
+        weights: Option<&[Vec<f64>]>,
+        u_knots: Vec<f64>,
+        v_knots: Vec<f64>,
+    ) -> Result<Self> {
+        let v_count = points.first().map_or(0, Vec::len);
+        if points.iter().any(|row| row.len() != v_count) {
+            bail!("Control point rows differ in length");
+        }
+        check_knots(u_degree, points.len(), &u_knots)?;
+        check_knots(v_degree, v_count, &v_knots)?;
+        let weight_rows =
+            weights.map(|w| w.len() == points.len() && w.iter().all(|row| row.len() == v_count));
+        if weight_rows == Some(false) {
+            bail!("Expected a weight for every control point");
+        }
+        let points = points
+            .iter()
+            .enumerate()
+            .map(|(i, row)| homogeneous(row, weights.map(|w| w[i].as_slice())))
+            .collect();
+        Ok(Self {
+            u_degree,
+            v_degree,
+            points,
+            u_knots,
+            v_knots,
+        })
+    }
+
+    /// Returns the degrees along `u` and `v`.
+    pub fn degrees(&self) -> (usize, usize) {
+        (self.u_degree, self.v_degree)
+    }
+
+    /// Returns the `u` and `v` ranges the surface is defined on.
+    pub fn domain(&self) -> ([f64; 2], [f64; 2]) {
+        let v_count = self.points[0].len();
+        (
+            [self.u_knots[self.u_degree], self.u_knots[self.points.len()]],
+            [self.v_knots[self.v_degree], self.v_knots[v_count]],
+        )
+    }
+
+    /// Returns the number of non-empty knot spans along `u` and `v`.
+    pub fn spans(&self) -> (usize, usize) {
+        let (u, v) = self.domain();
+        let count = |knots: &[f64], [start, end]: [f64; 2]| {
+            let mut knots = knots.to_vec();
+            knots.dedup();
+            knots.iter().filter(|&&k| k > start && k <= end).count()
+        };
+        (count(&self.u_knots, u), count(&self.v_knots, v))
+    }
+
+    /// Evaluates every row along `v` at `v`, giving the control points of the curve along `u`.
+    fn column(&self, v: f64) -> Vec<Vector4<f64>> {
+        self.points
+            .iter()
+            .map(|row| de_boor(self.v_degree, &self.v_knots, row, v))
+            .collect()
+    }
+
+    /// Returns the point at `(u, v)`.
+    pub fn evaluate(&self, u: f64, v: f64) -> [f64; 3] {
+        let value = de_boor(self.u_degree, &self.u_knots, &self.column(v), u);
+        (value.truncate() / value.w).into()
+    }
+
+    /// Returns the point at `(u, v)` and the partial derivatives along `u` and `v`.
+    fn derivatives(&self, u: f64, v: f64) -> (Vector3<f64>, Vector3<f64>, Vector3<f64>) {
+        let column = self.column(v);
+        let (value, du) = de_boor_derivative(self.u_degree, &self.u_knots, &column, u);
+        // The derivative along `v` of every row, combined along `u`
+        let dv_column: Vec<Vector4<f64>> = self
+            .points
+            .iter()
+            .map(|row| de_boor_derivative(self.v_degree, &self.v_knots, row, v).1)
+            .collect();
+        let dv = de_boor(self.u_degree, &self.u_knots, &dv_column, u);
+        let (point, du) = project(value, du);
+        let (_, dv) = project(value, dv);
+        (point, du, dv)
+    }
+
+    /// Returns the unit normal at `(u, v)`, `du × dv`.
+    ///
+    /// Where the surface is degenerate, like at a pole, the normal is taken slightly inside the
+    /// domain instead. Returns zero if that doesn't help either.
+    pub fn normal(&self, u: f64, v: f64) -> [f64; 3] {
+        let ([u0, u1], [v0, v1]) = self.domain();
+        let (_, du, dv) = self.derivatives(u, v);
+        let mut normal = du.cross(dv);
+        if normal.magnitude2() < 1e-24 {
+            let nudge = |t: f64, start: f64, end: f64| {
+                t + (0.5 * (start + end) - t).signum() * DEGENERATE_NUDGE * (end - start)
+            };
+            let (_, du, dv) = self.derivatives(nudge(u, u0, u1), nudge(v, v0, v1));
+            normal = du.cross(dv);
+        }
+        if normal.magnitude2() == 0.0 {
+            return [0.0; 3];
+        }
+        normal.normalize().into()
+    }
+
+    /// Returns the parameters of the surface point closest to `p`.
+    ///
+    /// Meant for points on or close to the surface, like the corners of a face's bounds. Searches a
+    /// coarse grid first, then refines with Gauss-Newton steps, which converge slowly for points
+    /// far off strongly curved surfaces.
+    pub fn closest_parameters(&self, p: [f64; 3]) -> [f64; 2] {
+        let p = Vector3::from(p);
+        let ([u0, u1], [v0, v1]) = self.domain();
+        let lerp = |start: f64, end: f64, i: usize| {
+            start + (end - start) * i as f64 / (INVERSION_SAMPLES - 1) as f64
+        };
+        let mut best = (f64::INFINITY, [u0, v0]);
+        for i in 0..INVERSION_SAMPLES {
+            for j in 0..INVERSION_SAMPLES {
+                let (u, v) = (lerp(u0, u1, i), lerp(v0, v1, j));
+                let distance = (Vector3::from(self.evaluate(u, v)) - p).magnitude2();
+                if distance < best.0 {
+                    best = (distance, [u, v]);
+                }
+            }
+        }
+
+        let [mut u, mut v] = best.1;
+        for _ in 0..INVERSION_ITERATIONS {
+            let (point, du, dv) = self.derivatives(u, v);
+            let d = p - point;
+            // Gauss-Newton step, solving the 2x2 normal equations
+            let (a, b, c) = (du.dot(du), du.dot(dv), dv.dot(dv));
+            let det = a * c - b * b;
+            if det.abs() < 1e-24 {
+                break;
+            }
+            let (ru, rv) = (du.dot(d), dv.dot(d));
+            let step_u = (c * ru - b * rv) / det;
+            let step_v = (a * rv - b * ru) / det;
+            u = (u + step_u).clamp(u0, u1);
+            v = (v + step_v).clamp(v0, v1);
+            if step_u.abs() < 1e-12 * (u1 - u0) && step_v.abs() < 1e-12 * (v1 - v0) {
+                break;
+            }
+        }
+        [u, v]
+    }
+}
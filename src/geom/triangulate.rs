@@ -0,0 +1,223 @@
+/// Returns twice the signed area of the triangle `[o, a, b]`, positive if it's counter-clockwise.
+fn cross(o: [f64; 2], a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+/// Returns twice the signed area of the polygon `ring` indexes into `points`.
+fn ring_area(points: &[[f64; 2]], ring: &[usize]) -> f64 {
+    (0..ring.len())
+        .map(|i| {
+            let a = points[ring[i]];
+            let b = points[ring[(i + 1) % ring.len()]];
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum()
+}
+
+/// Whether the segments `a`-`b` and `c`-`d` cross at a point inside both of them.
+fn segments_cross(a: [f64; 2], b: [f64; 2], c: [f64; 2], d: [f64; 2]) -> bool {
+    cross(a, b, c) * cross(a, b, d) < 0.0 && cross(c, d, a) * cross(c, d, b) < 0.0
+}
+
+/// Whether `p` lies inside the counter-clockwise triangle `[a, b, c]` or on its border.
+fn in_triangle(p: [f64; 2], [a, b, c]: [[f64; 2]; 3]) -> bool {
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+/// Whether `p` lies within the interior angle of the counter-clockwise `ring` at position `i`.
+fn in_corner(points: &[[f64; 2]], ring: &[usize], i: usize, p: [f64; 2]) -> bool {
+    let n = ring.len();
+    let prev = points[ring[(i + n - 1) % n]];
+    let corner = points[ring[i]];
+    let next = points[ring[(i + 1) % n]];
+    let left_of_prev = cross(prev, corner, p);
+    let left_of_next = cross(corner, next, p);
+    if cross(prev, corner, next) >= 0.0 {
+        left_of_prev > 0.0 && left_of_next > 0.0
+    } else {
+        left_of_prev > 0.0 || left_of_next > 0.0
+    }
+}
+
+/// Whether the segment `a`-`b` crosses an edge of any of `rings`.
+fn crosses_rings(points: &[[f64; 2]], rings: &[&[usize]], a: [f64; 2], b: [f64; 2]) -> bool {
+    rings.iter().any(|ring| {
+        (0..ring.len()).any(|i| {
+            let c = points[ring[i]];
+            let d = points[ring[(i + 1) % ring.len()]];
+            segments_cross(a, b, c, d)
+        })
+    })
+}
+
+/// Splices `hole` into the counter-clockwise `ring` along a bridge from the hole's rightmost point
+/// to the nearest point of `ring` it can see, which must cross neither `ring` nor `others`.
+fn bridge_hole(points: &[[f64; 2]], ring: &mut Vec<usize>, hole: &[usize], others: &[&[usize]]) {
+    let Some(start) = (0..hole.len()).max_by(|&a, &b| {
+        points[hole[a]][0]
+            .total_cmp(&points[hole[b]][0])
+            .then(points[hole[b]][1].total_cmp(&points[hole[a]][1]))
+    }) else {
+        return;
+    };
+    let m = points[hole[start]];
+    let distance = |i: usize| {
+        let p = points[ring[i]];
+        (p[0] - m[0]).powi(2) + (p[1] - m[1]).powi(2)
+    };
+    let mut candidates: Vec<usize> = (0..ring.len()).collect();
+    candidates.sort_by(|&a, &b| distance(a).total_cmp(&distance(b)));
+    let visible = candidates.iter().copied().find(|&i| {
+        let p = points[ring[i]];
+        in_corner(points, ring, i, m)
+            && !crosses_rings(points, &[ring.as_slice(), hole], m, p)
+            && !crosses_rings(points, others, m, p)
+    });
+    // Holes touching the outline may not see it cleanly, bridge to the nearest point regardless
+    let Some(i) = visible.or(candidates.first().copied()) else {
+        return;
+    };
+
+    // Walk around the hole from `m` back to `m`, then return to the ring along the same bridge
+    let mut bridged = Vec::with_capacity(hole.len() + 2);
+    bridged.extend_from_slice(&hole[start..]);
+    bridged.extend_from_slice(&hole[..=start]);
+    bridged.push(ring[i]);
+    ring.splice(i + 1..i + 1, bridged);
+}
+
+/// Triangulates the polygon bounded by `outer`, minus the polygons bounded by `holes`, by ear
+/// clipping.
+///
+/// Returns counter-clockwise triangles of indices into `outer` followed by each of `holes` in
+/// turn. Loops may wind either way, holes are bridged into the outline so the result stays a
+/// single polygon. Collinear points along an edge are kept, so edges shared with neighbouring
+/// faces line up.
+pub fn triangulate(outer: &[[f64; 2]], holes: &[Vec<[f64; 2]>]) -> Vec<[usize; 3]> {
+    let points: Vec<[f64; 2]> = outer
+        .iter()
+        .chain(holes.iter().flatten())
+        .copied()
+        .collect();
+    let mut ring: Vec<usize> = (0..outer.len()).collect();
+    if ring_area(&points, &ring) < 0.0 {
+        ring.reverse();
+    }
+
+    // Holes wind clockwise so the bridged outline keeps the polygon on its left
+    let mut offset = outer.len();
+    let mut hole_rings: Vec<Vec<usize>> = holes
+        .iter()
+        .map(|hole| {
+            let mut hole_ring: Vec<usize> = (offset..offset + hole.len()).collect();
+            offset += hole.len();
+            if ring_area(&points, &hole_ring) > 0.0 {
+                hole_ring.reverse();
+            }
+            hole_ring
+        })
+        .filter(|hole_ring| hole_ring.len() >= 3)
+        .collect();
+    // Bridging the rightmost holes first keeps later bridges from having to pass them
+    let max_x = |hole: &Vec<usize>| hole.iter().map(|&i| points[i][0]).fold(f64::MIN, f64::max);
+    hole_rings.sort_by(|a, b| max_x(b).total_cmp(&max_x(a)));
+    for h in 0..hole_rings.len() {
+        let others: Vec<&[usize]> = hole_rings[h + 1..].iter().map(Vec::as_slice).collect();
+        bridge_hole(&points, &mut ring, &hole_rings[h], &others);
+    }
+
+    let mut triangles = Vec::with_capacity(ring.len().saturating_sub(2));
+    let mut i = 0;
+    let mut misses = 0;
+    while ring.len() > 3 {
+        let n = ring.len();
+        i %= n;
+        let corners = [ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]];
+        let [a, b, c] = corners.map(|k| points[k]);
+        let area = cross(a, b, c);
+
+        let spike =
+            area == 0.0 && (a[0] - b[0]) * (c[0] - b[0]) + (a[1] - b[1]) * (c[1] - b[1]) > 0.0;
+        let ear = area > 0.0
+            && !ring.iter().any(|&k| {
+                let p = points[k];
+                !corners.contains(&k) && ![a, b, c].contains(&p) && in_triangle(p, [a, b, c])
+            });
+        if spike || ear || misses >= n {
+            // Without an ear left the polygon self-intersects, clip anyway to make progress
+            if area > 0.0 {
+                triangles.push(corners);
+            }
+            ring.remove(i);
+            misses = 0;
+        } else {
+            i += 1;
+            misses += 1;
+        }
+    }
+    if ring.len() == 3 && cross(points[ring[0]], points[ring[1]], points[ring[2]]) > 0.0 {
+        triangles.push([ring[0], ring[1], ring[2]]);
+    }
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the total area of `triangles` indexing `outer` followed by `holes`.
+    fn area(outer: &[[f64; 2]], holes: &[Vec<[f64; 2]>], triangles: &[[usize; 3]]) -> f64 {
+        let points: Vec<[f64; 2]> = outer
+            .iter()
+            .chain(holes.iter().flatten())
+            .copied()
+            .collect();
+        triangles
+            .iter()
+            .map(|t| {
+                let area = cross(points[t[0]], points[t[1]], points[t[2]]);
+                assert!(area > 0.0, "{:?} isn't counter-clockwise", t);
+                0.5 * area
+            })
+            .sum()
+    }
+
+    #[test]
+    fn cuts_holes() {
+        let outer = [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]];
+        // Holes may wind either way
+        let holes = vec![vec![[1.0, 1.0], [3.0, 1.0], [3.0, 3.0], [1.0, 3.0]]];
+        let triangles = triangulate(&outer, &holes);
+        // Bridging adds two corners to the eight, and a ring of n corners makes n - 2 triangles
+        assert_eq!(triangles.len(), 8);
+        assert_eq!(area(&outer, &holes, &triangles), 12.0);
+    }
+
+    #[test]
+    fn keeps_collinear_corners() {
+        let outer = [
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [2.0, 2.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+            [0.0, 1.0],
+        ];
+        let triangles = triangulate(&outer, &[]);
+        assert_eq!(triangles.len(), 6);
+        assert_eq!(area(&outer, &[], &triangles), 4.0);
+        // Every corner ends up in a triangle, so neighbouring faces share them
+        for i in 0..outer.len() {
+            assert!(triangles.iter().any(|t| t.contains(&i)), "{} left out", i);
+        }
+    }
+
+    #[test]
+    fn skips_degenerate_polygons() {
+        assert!(triangulate(&[[0.0, 0.0], [1.0, 0.0]], &[]).is_empty());
+        let line = [[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [3.0, 0.0]];
+        assert!(triangulate(&line, &[]).is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+// STD
+use std::ops::Range;
+
+// Dependencies
+use wgpu::util::DeviceExt;
+
+// Local modules
+use crate::state::ColorSpace;
+use crate::texture;
+
+/// Length of each axis line in world units
+const AXIS_LENGTH: f32 = 1.0;
+/// Distance between neighbouring grid lines in world units
+const GRID_SPACING: f32 = 0.1;
+/// Number of grid lines on each side of an axis
+const GRID_LINES: i32 = 10;
+const GRID_COLOR: [f32; 3] = [0.35, 0.35, 0.35];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GizmoVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+impl GizmoVertex {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+            wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GizmoVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Line segments of the ground grid, lying in the XZ plane around the origin
+fn grid_lines() -> Vec<GizmoVertex> {
+    let extent = GRID_LINES as f32 * GRID_SPACING;
+    (-GRID_LINES..=GRID_LINES)
+        .flat_map(|i| {
+            let offset = i as f32 * GRID_SPACING;
+            [
+                [offset, 0.0, -extent],
+                [offset, 0.0, extent],
+                [-extent, 0.0, offset],
+                [extent, 0.0, offset],
+            ]
+        })
+        .map(|position| GizmoVertex {
+            position,
+            color: GRID_COLOR,
+        })
+        .collect()
+}
+
+/// Line segments of the X, Y and Z axes, colored red, green and blue
+fn axis_lines() -> Vec<GizmoVertex> {
+    (0..3)
+        .flat_map(|axis| {
+            let mut end = [0.0; 3];
+            end[axis] = AXIS_LENGTH;
+            let mut color = [0.0; 3];
+            color[axis] = 1.0;
+            [
+                GizmoVertex {
+                    position: [0.0; 3],
+                    color,
+                },
+                GizmoVertex {
+                    position: end,
+                    color,
+                },
+            ]
+        })
+        .collect()
+}
+
+/// World-space axes and ground grid, drawn as lines on top of the models as a reference frame
+pub struct Gizmo {
+    pub visible: bool,
+    /// Draw the ground grid along with the axes
+    pub show_grid: bool,
+    vertex_buffer: wgpu::Buffer,
+    grid: Range<u32>,
+    axes: Range<u32>,
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+impl Gizmo {
+    /// `camera_bind_group_layout` is bound to `@group(0)` of the gizmo shader. `format` and
+    /// `sample_count` must match the color target the models are drawn into.
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let grid = grid_lines();
+        let axes = axis_lines();
+        let grid_end = grid.len() as u32;
+        let axes_end = grid_end + axes.len() as u32;
+        // Axes come last so they win over the grid lines running along them
+        let vertices = [grid, axes].concat();
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Gizmo: Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Gizmo Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../assets/shaders/gizmo.wgsl").into()),
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Gizmo Pipeline Layout"),
+                bind_group_layouts: &[camera_bind_group_layout],
+                immediate_size: 0,
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Gizmo Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[GizmoVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &[(
+                        "ENCODE_SRGB",
+                        ColorSpace::needs_srgb_encode(format) as u8 as f64,
+                    )],
+                    ..Default::default()
+                },
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            // Depth tested against the models, so solids hide the lines behind them
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Self {
+            visible: true,
+            show_grid: true,
+            vertex_buffer,
+            grid: 0..grid_end,
+            axes: grid_end..axes_end,
+            render_pipeline,
+        }
+    }
+
+    /// Draws the gizmo into `render_pass` unless it's hidden, `camera_bind_group` must match the
+    /// layout the gizmo was created with.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        if !self.visible {
+            return;
+        }
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        if self.show_grid {
+            render_pass.draw(self.grid.clone(), 0..1);
+        }
+        render_pass.draw(self.axes.clone(), 0..1);
+    }
+}
@@ -0,0 +1,23 @@
+// STD
+use std::path::Path;
+
+// Dependencies
+use anyhow::{Context, Result};
+
+// Local modules
+use crate::brep::read_brep;
+use crate::convert::brep_to_step;
+use crate::step::tessellate::{Tessellation, TessellationOptions, tessellate_faces};
+
+/// Reads an OpenCASCADE `.brep` file and tessellates its faces with `options`, by way of the STEP
+/// instances it converts to, see [`brep_to_step`].
+///
+/// BREP files carry no units, coordinates are kept as they are. Face ids refer to the converted
+/// instances rather than anything in the file.
+pub fn import_brep(path: &Path, options: &TessellationOptions) -> Result<Tessellation> {
+    let model = read_brep(path)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let graph = brep_to_step(&model, &name)
+        .with_context(|| format!("Couldn't convert '{}'", path.display()))?;
+    tessellate_faces(&graph, options)
+}
@@ -0,0 +1,120 @@
+// STD
+use std::io;
+
+// Local modules
+use crate::step::tessellate::{FaceId, UnsupportedSurface};
+use crate::step::{Cancelled, ParseError};
+
+/// Why an import failed, for callers that handle some failures differently than others.
+///
+/// Errors raised along the way are sorted into their kind by [`ImportError::from`], the ones
+/// without a kind of their own end up in [`ImportError::Other`] with their whole context.
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    /// The file's `FILE_SCHEMA` names none of the application protocols the importer reads
+    #[error("Unknown schema '{}'", .0.join("', '"))]
+    UnknownSchema(Vec<String>),
+    /// Nothing could be tessellated and `face` lies on a surface that isn't supported, by the
+    /// keyword of that surface
+    #[error("face #{face}: {keyword} surfaces aren't supported")]
+    UnsupportedSurface { face: FaceId, keyword: String },
+    /// The file isn't valid ISO-10303-21
+    #[error(transparent)]
+    ParseError(#[from] ParseError),
+    /// The file couldn't be read
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    /// Stopped through the import's cancellation flag
+    #[error("{}", Cancelled)]
+    Cancelled,
+    /// Anything else, such as a malformed entity the tessellator tripped over
+    // Transparent, so `{:#}` keeps printing the whole context
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<UnsupportedSurface> for ImportError {
+    fn from(e: UnsupportedSurface) -> Self {
+        Self::UnsupportedSurface {
+            face: e.face,
+            keyword: e.keyword,
+        }
+    }
+}
+
+impl From<Cancelled> for ImportError {
+    fn from(_: Cancelled) -> Self {
+        Self::Cancelled
+    }
+}
+
+impl From<anyhow::Error> for ImportError {
+    /// Sorts `e` into its kind by the error it was raised with, looking through the context
+    /// added on the way up.
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<ImportError>() {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<Cancelled>() {
+            Ok(cancelled) => return cancelled.into(),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<ParseError>() {
+            Ok(parse_error) => return parse_error.into(),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<UnsupportedSurface>() {
+            Ok(unsupported) => return unsupported.into(),
+            Err(e) => e,
+        };
+        match e.downcast::<io::Error>() {
+            Ok(io_error) => io_error.into(),
+            Err(e) => Self::Other(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_errors_by_kind_through_context() {
+        let parse: anyhow::Error = ParseError::instance(7, "bad").into();
+        let e = ImportError::from(parse.context("while reading"));
+        assert!(matches!(&e, ImportError::ParseError(p) if p.instance == Some(7)));
+
+        let io = anyhow::Error::from(io::Error::from(io::ErrorKind::NotFound)).context("opening");
+        assert!(matches!(ImportError::from(io), ImportError::Io(_)));
+
+        let cancelled = anyhow::Error::from(Cancelled).context("tessellating");
+        assert!(matches!(
+            ImportError::from(cancelled),
+            ImportError::Cancelled
+        ));
+
+        let unsupported = anyhow::Error::from(UnsupportedSurface {
+            face: 12,
+            keyword: "OFFSET_SURFACE".into(),
+        });
+        assert!(matches!(
+            ImportError::from(unsupported),
+            ImportError::UnsupportedSurface { face: 12, ref keyword } if keyword == "OFFSET_SURFACE"
+        ));
+
+        let schema: anyhow::Error = ImportError::UnknownSchema(vec!["X".into()]).into();
+        assert!(matches!(
+            ImportError::from(schema),
+            ImportError::UnknownSchema(_)
+        ));
+    }
+
+    #[test]
+    fn keeps_the_context_of_other_errors() {
+        let e = ImportError::from(anyhow::anyhow!("inner").context("outer"));
+        assert!(matches!(e, ImportError::Other(_)));
+        assert_eq!(format!("{}", e), "outer");
+        assert_eq!(format!("{:#}", e), "outer: inner");
+    }
+}
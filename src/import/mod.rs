@@ -0,0 +1,14 @@
+//! Importers turning mesh and CAD files into tessellated models for viewing.
+pub mod brep;
+pub mod error;
+pub mod obj;
+pub mod step;
+pub mod stl;
+
+pub use brep::import_brep;
+pub use error::ImportError;
+pub use obj::{parse_obj, read_obj};
+pub use step::{
+    ImportWarning, check_schema, import_step, import_step_cancellable, import_step_lenient,
+};
+pub use stl::{parse_stl, read_stl};
@@ -0,0 +1,152 @@
+// STD
+use std::collections::HashMap;
+use std::path::Path;
+
+// Dependencies
+use anyhow::{Context, Result, bail};
+use cgmath::{InnerSpace, Vector3};
+
+// Local modules
+use crate::mesh::Vertex;
+
+/// Parses the `index`th component of a face corner, resolving negative indices relative to the
+/// `count` elements read so far. Returns a 0-based index.
+fn resolve_index(index: &str, count: usize) -> Result<usize> {
+    let i: i64 = index
+        .parse()
+        .with_context(|| format!("Invalid index '{}'", index))?;
+    let resolved = match i {
+        1.. => i - 1,
+        ..0 => count as i64 + i,
+        0 => bail!("Indices start at 1"),
+    };
+    if resolved < 0 || resolved as usize >= count {
+        bail!("Index {} is out of range ({} elements)", i, count);
+    }
+    Ok(resolved as usize)
+}
+
+/// Parses `N` floats from `fields`, optional trailing components are ignored.
+fn parse_floats<const N: usize>(fields: &mut std::str::SplitWhitespace) -> Result<[f32; N]> {
+    let mut out = [0.0; N];
+    for value in &mut out {
+        let field = fields.next().context("Missing coordinate")?;
+        *value = field
+            .parse()
+            .with_context(|| format!("Invalid coordinate '{}'", field))?;
+    }
+    Ok(out)
+}
+
+/// Key of a deduplicated vertex: `v`, `vt` and either `vn` or the bits of the facet normal
+type CornerKey = (usize, Option<usize>, Result<usize, [u32; 3]>);
+
+/// Accumulates the records of an OBJ file line by line.
+#[derive(Default)]
+struct ObjParser {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    seen: HashMap<CornerKey, u32>,
+}
+
+impl ObjParser {
+    fn line(&mut self, line: &str) -> Result<()> {
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("v") => self.positions.push(parse_floats(&mut fields)?),
+            Some("vt") => {
+                let [u, v] = parse_floats(&mut fields)?;
+                // OBJ's v axis points up, wgpu's down
+                self.uvs.push([u, 1.0 - v]);
+            },
+            Some("vn") => self.normals.push(parse_floats(&mut fields)?),
+            Some("f") => self.face(fields)?,
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn face(&mut self, fields: std::str::SplitWhitespace) -> Result<()> {
+        let mut corners = Vec::new();
+        for corner in fields {
+            let mut parts = corner.split('/');
+            let v = resolve_index(parts.next().unwrap_or_default(), self.positions.len())?;
+            let vt = match parts.next() {
+                None | Some("") => None,
+                Some(vt) => Some(resolve_index(vt, self.uvs.len())?),
+            };
+            let vn = match parts.next() {
+                None | Some("") => None,
+                Some(vn) => Some(resolve_index(vn, self.normals.len())?),
+            };
+            corners.push((v, vt, vn));
+        }
+        if corners.len() < 3 {
+            bail!("Face has fewer than 3 corners");
+        }
+
+        // Newell's method, robust for slightly non-planar polygons
+        let mut facet = Vector3::new(0.0, 0.0, 0.0);
+        for (i, &(a, ..)) in corners.iter().enumerate() {
+            let a = self.positions[a];
+            let b = self.positions[corners[(i + 1) % corners.len()].0];
+            facet.x += (a[1] - b[1]) * (a[2] + b[2]);
+            facet.y += (a[2] - b[2]) * (a[0] + b[0]);
+            facet.z += (a[0] - b[0]) * (a[1] + b[1]);
+        }
+        let facet: [f32; 3] = if facet.magnitude2() > 0.0 {
+            facet.normalize().into()
+        } else {
+            [0.0; 3]
+        };
+
+        let mut corner_indices = Vec::with_capacity(corners.len());
+        for (v, vt, vn) in corners {
+            let key = (v, vt, vn.ok_or(facet.map(f32::to_bits)));
+            let index = match self.seen.get(&key) {
+                Some(&index) => index,
+                None => {
+                    self.vertices.push(Vertex {
+                        position: self.positions[v],
+                        uv_coords: vt.map_or([0.0; 2], |vt| self.uvs[vt]),
+                        normal: vn.map_or(facet, |vn| self.normals[vn]),
+                    });
+                    let index = self.vertices.len() as u32 - 1;
+                    self.seen.insert(key, index);
+                    index
+                },
+            };
+            corner_indices.push(index);
+        }
+        for i in 1..corner_indices.len() - 1 {
+            self.indices
+                .extend([corner_indices[0], corner_indices[i], corner_indices[i + 1]]);
+        }
+        Ok(())
+    }
+}
+
+/// Parses a Wavefront OBJ into a vertex and index buffer.
+///
+/// Only `v`, `vt`, `vn` and `f` records are read. Polygons are fan-triangulated, so only convex
+/// faces come out correct. Corners sharing the same `v/vt/vn` triple collapse into one vertex, and
+/// faces without `vn` get their facet normal. The indices are 0-based like a wgpu index buffer.
+pub fn parse_obj(src: &str) -> Result<(Vec<Vertex>, Vec<u32>)> {
+    let mut parser = ObjParser::default();
+    for (line_number, line) in src.lines().enumerate() {
+        parser
+            .line(line)
+            .with_context(|| format!("Line {}: '{}'", line_number + 1, line.trim()))?;
+    }
+    Ok((parser.vertices, parser.indices))
+}
+
+/// Reads a Wavefront OBJ file, see [`parse_obj`].
+pub fn read_obj(path: &Path) -> Result<(Vec<Vertex>, Vec<u32>)> {
+    let src = std::fs::read_to_string(path)
+        .with_context(|| format!("Couldn't read '{}'", path.display()))?;
+    parse_obj(&src).with_context(|| format!("Couldn't parse '{}'", path.display()))
+}
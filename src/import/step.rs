@@ -0,0 +1,175 @@
+// STD
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+// Local modules
+use super::ImportError;
+use crate::step::cache::tessellate_cached_with;
+use crate::step::tessellate::{FaceId, Tessellation, TessellationOptions};
+use crate::step::{
+    EntityGraph, ParseError, PartPlacements, normalize_units, read_step_cancellable,
+    read_step_lenient, read_step_with_progress, unsupported_surface,
+};
+
+/// Result of the STEP importers, failing with an [`ImportError`]
+pub type Result<T, E = ImportError> = std::result::Result<T, E>;
+
+/// Share of an import's progress that parsing takes up, tessellating takes the rest
+const PARSE_SHARE: f32 = 0.4;
+
+/// Application protocols the importer reads, matched against the start of the `FILE_SCHEMA`
+/// names: AP203 in both editions, AP214 and AP242
+const KNOWN_SCHEMAS: &[&str] = &[
+    "CONFIG_CONTROL_DESIGN",
+    "AP203_CONFIGURATION_CONTROLLED_3D_DESIGN",
+    "AUTOMOTIVE_DESIGN",
+    "AP214",
+    "AP242_MANAGED_MODEL_BASED_3D_ENGINEERING",
+];
+
+/// Fails with [`ImportError::UnknownSchema`] if `graph` names schemas but none of the
+/// [`KNOWN_SCHEMAS`]. Files naming no schema at all are given the benefit of the doubt.
+pub fn check_schema(graph: &EntityGraph) -> Result<()> {
+    let schemas: Vec<&String> = graph
+        .schemas
+        .iter()
+        .filter(|schema| !schema.trim().is_empty())
+        .collect();
+    let known = |schema: &str| {
+        let schema = schema.trim().to_ascii_uppercase();
+        KNOWN_SCHEMAS.iter().any(|known| schema.starts_with(known))
+    };
+    if schemas.is_empty() || schemas.iter().any(|schema| known(schema)) {
+        return Ok(());
+    }
+    Err(ImportError::UnknownSchema(
+        schemas.into_iter().cloned().collect(),
+    ))
+}
+
+/// Something [`import_step_lenient`] left out of the model instead of failing
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportWarning {
+    /// An instance that failed to parse
+    Entity(ParseError),
+    /// A face that couldn't be tessellated, with the reason why
+    Face(FaceId, String),
+}
+
+impl fmt::Display for ImportWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportWarning::Entity(e) => write!(f, "{}", e),
+            ImportWarning::Face(id, reason) => write!(f, "face #{}: {}", id, reason),
+        }
+    }
+}
+
+/// Reads a STEP file and tessellates its faces, converting it to millimetres on the way, see
+/// [`normalize_units`]. Files whose units can't be detected are kept unscaled.
+///
+/// Fails with an [`ImportError`] telling why, files of an unknown schema are rejected before
+/// they're tessellated, see [`check_schema`]. Faces that can't be tessellated are skipped unless
+/// that leaves nothing, see [`ImportError::UnsupportedSurface`].
+///
+/// The parts of assemblies are moved to where they're placed, see [`PartPlacements`].
+///
+/// Tessellations are cached, reimporting an unchanged file with the same `options` only parses
+/// it again, see [`tessellate_cached`](crate::step::tessellate_cached).
+///
+/// `progress` is called with the fraction of the import done so far, from 0 to 1. Parsing covers
+/// the first `0.4` of it and tessellating the rest.
+pub fn import_step(
+    path: &Path,
+    options: &TessellationOptions,
+    progress: &mut dyn FnMut(f32),
+) -> Result<Tessellation> {
+    let mut graph = read_step_with_progress(path, &mut |done| progress(done * PARSE_SHARE))?;
+    check_schema(&graph)?;
+    let tessellation = tessellate(path, &mut graph, options, progress, None)?;
+    check_tessellated(&graph, tessellation)
+}
+
+/// Like [`import_step`], but stops with [`ImportError::Cancelled`] once `cancel` is set, both
+/// while parsing and while tessellating. Set it from another thread to abandon an import nobody
+/// waits for anymore.
+pub fn import_step_cancellable(
+    path: &Path,
+    options: &TessellationOptions,
+    progress: &mut dyn FnMut(f32),
+    cancel: &AtomicBool,
+) -> Result<Tessellation> {
+    let mut graph = read_step_cancellable(path, &mut |done| progress(done * PARSE_SHARE), cancel)?;
+    check_schema(&graph)?;
+    let tessellation = tessellate(path, &mut graph, options, progress, Some(cancel))?;
+    check_tessellated(&graph, tessellation)
+}
+
+/// Like [`import_step`], but leaves out instances that fail to parse instead of failing, see
+/// [`read_step_lenient`]. Returns what could be imported along with everything that was left
+/// out, including faces that couldn't be tessellated.
+///
+/// Files of an unknown schema are imported all the same, with a warning.
+pub fn import_step_lenient(
+    path: &Path,
+    options: &TessellationOptions,
+    progress: &mut dyn FnMut(f32),
+) -> Result<(Tessellation, Vec<ImportWarning>)> {
+    let (mut graph, skipped) = read_step_lenient(path, &mut |done| progress(done * PARSE_SHARE))?;
+    if let Err(e) = check_schema(&graph) {
+        log::warn!("Importing '{}' anyway: {}", path.display(), e);
+    }
+    let tessellation = tessellate(path, &mut graph, options, progress, None)?;
+    let faces = tessellation
+        .skipped
+        .iter()
+        .map(|(id, reason)| ImportWarning::Face(*id, reason.clone()));
+    let warnings = skipped
+        .into_iter()
+        .map(ImportWarning::Entity)
+        .chain(faces)
+        .collect();
+    Ok((tessellation, warnings))
+}
+
+/// Converts `graph`, read from `path`, to millimetres and tessellates it, reporting progress
+/// past the share parsing took, and places the parts of assemblies. Stops once `cancel` is set, if
+/// given.
+fn tessellate(
+    path: &Path,
+    graph: &mut EntityGraph,
+    options: &TessellationOptions,
+    progress: &mut dyn FnMut(f32),
+    cancel: Option<&AtomicBool>,
+) -> Result<Tessellation> {
+    if let Err(e) = normalize_units(graph) {
+        log::warn!("Keeping '{}' unscaled: {:#}", path.display(), e);
+    }
+    let mut tessellation = tessellate_cached_with(
+        graph,
+        options,
+        &mut |done| progress(PARSE_SHARE + done * (1.0 - PARSE_SHARE)),
+        cancel,
+    )?;
+    match PartPlacements::new(graph) {
+        Ok(placements) => placements.place_tessellation(&mut tessellation),
+        Err(e) => log::warn!(
+            "Keeping the parts of '{}' unplaced: {:#}",
+            path.display(),
+            e
+        ),
+    }
+    Ok(tessellation)
+}
+
+/// Returns `tessellation` of `graph`, unless it's empty because of faces on surfaces that aren't
+/// supported.
+fn check_tessellated(graph: &EntityGraph, tessellation: Tessellation) -> Result<Tessellation> {
+    if tessellation.vertices.is_empty()
+        && let Some(unsupported) = unsupported_surface(graph)
+    {
+        return Err(unsupported.into());
+    }
+    Ok(tessellation)
+}
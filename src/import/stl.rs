@@ -0,0 +1,132 @@
+// STD
+use std::path::Path;
+
+// Dependencies
+use anyhow::{Context, Result, bail};
+use cgmath::{InnerSpace, Vector3};
+
+// Local modules
+use crate::mesh::{Vertex, normals::compute_smooth_normals};
+
+/// Returns the unit normal of the counter-clockwise triangle `corners`, or zero if it's
+/// degenerate.
+fn facet_normal(corners: &[[f32; 3]; 3]) -> [f32; 3] {
+    let [a, b, c] = corners.map(Vector3::from);
+    let normal = (b - a).cross(c - a);
+    if normal.magnitude2() == 0.0 {
+        return [0.0; 3];
+    }
+    normal.normalize().into()
+}
+
+/// Pushes a facet's three vertices, using the stored normal unless it's zero.
+fn push_facet(vertices: &mut Vec<Vertex>, normal: [f32; 3], corners: [[f32; 3]; 3]) {
+    let normal = if normal == [0.0; 3] {
+        facet_normal(&corners)
+    } else {
+        normal
+    };
+    vertices.extend(corners.map(|position| Vertex {
+        position,
+        uv_coords: [0.0; 2],
+        normal,
+    }));
+}
+
+/// Returns `true` if `bytes` is exactly as long as the binary STL its header announces.
+///
+/// ASCII files start with `solid`, but so do plenty of binary headers, so the size is what
+/// decides.
+fn is_binary(bytes: &[u8]) -> bool {
+    let Some(count) = bytes.get(80..84) else {
+        return false;
+    };
+    let count = u32::from_le_bytes(count.try_into().unwrap()) as usize;
+    bytes.len() == 84 + count * 50
+}
+
+fn parse_binary(bytes: &[u8]) -> Vec<Vertex> {
+    let mut vertices = Vec::new();
+    for record in bytes[84..].chunks_exact(50) {
+        let float = |i: usize| f32::from_le_bytes(record[i * 4..i * 4 + 4].try_into().unwrap());
+        let vector = |i: usize| [float(i), float(i + 1), float(i + 2)];
+        push_facet(&mut vertices, vector(0), [vector(3), vector(6), vector(9)]);
+    }
+    vertices
+}
+
+fn parse_ascii(src: &str) -> Result<Vec<Vertex>> {
+    let mut vertices = Vec::new();
+    let mut normal = [0.0; 3];
+    let mut corners = Vec::with_capacity(3);
+    for (line_number, line) in src.lines().enumerate() {
+        let parse_line = |fields: std::str::SplitWhitespace| -> Result<[f32; 3]> {
+            let values = fields
+                .map(|field| field.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .with_context(|| format!("Line {}: '{}'", line_number + 1, line.trim()))?;
+            let Ok(vector) = <[f32; 3]>::try_from(values) else {
+                bail!("Line {}: expected 3 coordinates", line_number + 1);
+            };
+            Ok(vector)
+        };
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("facet") => {
+                fields.next(); // "normal"
+                normal = parse_line(fields)?;
+                corners.clear();
+            },
+            Some("vertex") => corners.push(parse_line(fields)?),
+            Some("endfacet") => {
+                let Ok(facet) = <[[f32; 3]; 3]>::try_from(corners.as_slice()) else {
+                    bail!(
+                        "Line {}: facet has {} vertices instead of 3",
+                        line_number + 1,
+                        corners.len()
+                    );
+                };
+                push_facet(&mut vertices, normal, facet);
+            },
+            _ => {},
+        }
+    }
+    Ok(vertices)
+}
+
+/// Parses an ASCII or binary STL into a non-indexed triangle list with facet normals.
+pub fn parse_stl(bytes: &[u8]) -> Result<Vec<Vertex>> {
+    if is_binary(bytes) {
+        return Ok(parse_binary(bytes));
+    }
+    let Ok(src) = std::str::from_utf8(bytes) else {
+        bail!("Neither a binary STL of the announced size nor ASCII text");
+    };
+    if !src.trim_start().starts_with("solid") {
+        bail!("ASCII STL doesn't start with 'solid'");
+    }
+    parse_ascii(src)
+}
+
+/// Reads an STL file into a vertex and index buffer.
+///
+/// Without `weld`, every facet keeps its own vertices and flat normal. With it, vertices closer
+/// than the given distance are merged and get smooth, area-weighted normals.
+pub fn read_stl(path: &Path, weld: Option<f32>) -> Result<(Vec<Vertex>, Vec<u32>)> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Couldn't read '{}'", path.display()))?;
+    let vertices =
+        parse_stl(&bytes).with_context(|| format!("Couldn't parse '{}'", path.display()))?;
+    let Some(epsilon) = weld else {
+        let indices = (0..vertices.len() as u32).collect();
+        return Ok((vertices, indices));
+    };
+    let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+    let (mut vertices, indices) = crate::mesh::weld::weld(&vertices, &indices, epsilon);
+    let positions: Vec<[f32; 3]> = vertices.iter().map(|v| v.position).collect();
+    let normals = compute_smooth_normals(&positions, &indices, 180.0);
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        vertex.normal = normal;
+    }
+    Ok((vertices, indices))
+}
@@ -1,10 +1,33 @@
+//! Import, conversion and viewing of BREP models from STEP and OpenCASCADE `.brep` files.
+//!
+//! The parsers, tessellator and mesh exporters don't depend on the viewer, so the crate can be
+//! used from other tools:
+//!
+//! ```no_run
+//! use brepimport::step::{TessellationOptions, parse, tessellate_faces};
+//!
+//! let graph = parse(&std::fs::read_to_string("part.step")?)?;
+//! let mesh = tessellate_faces(&graph, &TessellationOptions::default())?;
+//! println!("{} triangles", mesh.vertices.len() / 3);
+//! # anyhow::Ok(())
+//! ```
+//!
+//! - [`step`] reads and writes STEP files and tessellates their faces
+//! - [`brep`] reads and writes OpenCASCADE `.brep` files, [`convert`] translates to and from STEP
+//! - [`geom`] evaluates NURBS and triangulates planar regions
+//! - [`mesh`], [`import`] and [`export`] deal with triangle meshes and their file formats
+//! - [`state`], [`model`] and the remaining modules make up the wgpu viewer
 // STD
 use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
 use std::time::SystemTime;
 
 // Dependencies
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
+// The standard clock panics in the browser
+#[cfg(target_arch = "wasm32")]
+use web_time::SystemTime;
 use winit::{
     application::ApplicationHandler,
     event::*,
@@ -13,9 +36,26 @@ use winit::{
     window::Window,
 };
 // Local modules
+pub mod brep;
 pub mod camera;
+pub mod clip;
+pub mod convert;
+pub mod edge;
+pub mod export;
+pub mod frame_timer;
+pub mod frustum;
+pub mod geom;
+pub mod gizmo;
+mod hash;
+pub mod import;
+pub mod light;
+pub mod material;
 pub mod mesh;
+pub mod model;
+pub mod pick;
+pub mod postprocess;
 pub mod state;
+pub mod step;
 pub mod texture;
 use mesh::Vertex;
 use state::State;
@@ -24,14 +64,14 @@ use state::State;
 #[rustfmt::skip]
 const VERTICES: &[Vertex] = &[
     // Changed
-    Vertex { position: [-0.0868241, 0.49240386, 0.0], uv_coords: [0.4131759, 0.00759614], }, // A
-    Vertex { position: [-0.49513406, 0.06958647, 0.0], uv_coords: [0.0048659444, 0.43041354], }, // B
-    Vertex { position: [-0.21918549, -0.44939706, 0.0], uv_coords: [0.28081453, 0.949397], }, // C
-    Vertex { position: [0.35966998, -0.3473291, 0.0], uv_coords: [0.85967, 0.84732914], }, // D
-    Vertex { position: [0.44147372, 0.2347359, 0.0], uv_coords: [0.9414737, 0.2652641], }, // E
+    Vertex { position: [-0.0868241, 0.49240386, 0.0], uv_coords: [0.4131759, 0.00759614], normal: [0.0, 0.0, 1.0], }, // A
+    Vertex { position: [-0.49513406, 0.06958647, 0.0], uv_coords: [0.0048659444, 0.43041354], normal: [0.0, 0.0, 1.0], }, // B
+    Vertex { position: [-0.21918549, -0.44939706, 0.0], uv_coords: [0.28081453, 0.949397], normal: [0.0, 0.0, 1.0], }, // C
+    Vertex { position: [0.35966998, -0.3473291, 0.0], uv_coords: [0.85967, 0.84732914], normal: [0.0, 0.0, 1.0], }, // D
+    Vertex { position: [0.44147372, 0.2347359, 0.0], uv_coords: [0.9414737, 0.2652641], normal: [0.0, 0.0, 1.0], }, // E
 ];
 #[rustfmt::skip]
-pub const INDICES: &[u16] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
+pub(crate) const INDICES: &[u32] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
 
 pub struct App {
     #[cfg(target_arch = "wasm32")]
@@ -59,6 +99,14 @@ impl App {
     }
 }
 
+// On the web `App::new` needs the event loop to send the state back through
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ApplicationHandler<State> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         #[allow(unused_mut)]
@@ -93,22 +141,24 @@ impl ApplicationHandler<State> for App {
         {
             // If we are not on web we can use pollster to
             // await the
-            self.state = Some(pollster::block_on(State::new(window)).unwrap());
+            match pollster::block_on(State::new(window)) {
+                Ok(state) => self.state = Some(state),
+                Err(e) => {
+                    eprintln!("error: {:#}", e);
+                    event_loop.exit();
+                }
+            }
         }
 
         #[cfg(target_arch = "wasm32")]
         {
             if let Some(proxy) = self.proxy.take() {
                 wasm_bindgen_futures::spawn_local(async move {
-                    assert!(
-                        proxy
-                            .send_event(
-                                State::new(window)
-                                    .await
-                                    .expect("Unable to create canvas!!!")
-                            )
-                            .is_ok()
-                    )
+                    // Browsers without WebGPU end up here, there's nothing to draw with
+                    match State::new(window).await {
+                        Ok(state) => assert!(proxy.send_event(state).is_ok()),
+                        Err(e) => log::error!("{:#}", e),
+                    }
                 });
             }
         }
@@ -117,12 +167,9 @@ impl ApplicationHandler<State> for App {
     #[allow(unused_mut)]
     fn user_event(&mut self, _event_loop: &ActiveEventLoop, mut event: State) {
         #[cfg(target_arch = "wasm32")]
-        {
-            event.window.request_redraw();
-            event.resize(
-                event.window.inner_size().width,
-                event.window.inner_size().height,
-            );
+        if let Some(window) = event.window.clone() {
+            window.request_redraw();
+            event.resize(window.inner_size().width, window.inner_size().height);
         }
         self.state = Some(event);
     }
@@ -145,20 +192,29 @@ impl ApplicationHandler<State> for App {
                 state.update();
                 match state.render() {
                     Ok(_) => {}
-                    // Reconfigure the surface if it's lost or outdated
-                    Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-                        let size = state.window.inner_size();
-                        state.resize(size.width, size.height);
+                    Err(wgpu::SurfaceError::OutOfMemory) => {
+                        log::error!("Unable to render, out of memory");
+                        event_loop.exit();
                     }
                     Err(e) => {
                         log::error!("Unable to render {}", e);
                     }
                 }
             }
-            WindowEvent::MouseInput { state, button, .. } => match (button, state.is_pressed()) {
-                (MouseButton::Left, true) => {}
-                (MouseButton::Left, false) => {}
-                _ => {}
+            WindowEvent::MouseInput {
+                state: button_state,
+                button,
+                ..
+            } => state.handle_mouse_button(button, button_state.is_pressed()),
+            WindowEvent::CursorMoved { position, .. } => state.handle_cursor_moved(position),
+            WindowEvent::CursorLeft { .. } => state.cursor_position = None,
+            WindowEvent::MouseWheel { delta, .. } => state.handle_mouse_wheel(delta),
+            WindowEvent::DroppedFile(path) => match state.open_file(&path) {
+                Ok(()) => log::info!("Loaded '{}'", path.display()),
+                Err(e) => {
+                    log::error!("{:#}", e);
+                    state.show_toast(&format!("{:#}", e));
+                }
             },
             WindowEvent::KeyboardInput {
                 event:
@@ -185,32 +241,33 @@ pub fn run() -> anyhow::Result<()> {
     }
 
     let event_loop = EventLoop::with_user_event().build()?;
+    #[allow(unused_mut)]
     let mut app = App::new(
         #[cfg(target_arch = "wasm32")]
         &event_loop,
     );
     log::info!("App initialized!");
     log::info!("init_time: {:?}", app.init_time());
+    #[cfg(not(target_arch = "wasm32"))]
     event_loop.run_app(&mut app)?;
+    // The browser drives the event loop, so this returns right away
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::EventLoopExtWebSys;
+        event_loop.spawn_app(app);
+    }
 
     Ok(())
 }
 
+/// Starts the viewer as soon as the wasm module is initialized, drawing into the page's
+/// `<canvas id="canvas">`.
+///
+/// Module scripts run once the document is parsed, so the canvas is already there. Errors setting
+/// up the event loop are thrown to JavaScript.
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen(start)]
 pub fn run_web() -> Result<(), wasm_bindgen::JsValue> {
     console_error_panic_hook::set_once();
-
-    let window = web_sys::window().unwrap_throw();
-    let document = window.document().unwrap_throw();
-
-    let body = document.body().unwrap_throw();
-
-    let closure = Closure::once_into_js(move || {
-        crate::run().unwrap_throw();
-    });
-
-    body.set_onload(Some(closure.unchecked_ref()));
-
-    Ok(())
+    run().map_err(|e| wasm_bindgen::JsValue::from_str(&format!("{:#}", e)))
 }
@@ -0,0 +1,34 @@
+/// Shader uniform for a single directional light
+///
+/// Must format the *uniform* for storage in the *light* buffer [`crate::State::light_buffer`]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightUniform {
+    /// Normalized direction pointing from the surface towards the light
+    direction: [f32; 3],
+    /// Fraction of the base color that's lit regardless of the light direction
+    ambient: f32,
+}
+
+impl LightUniform {
+    pub fn new() -> Self {
+        // Over the right shoulder of the default camera
+        let mut uniform = Self {
+            direction: [0.0; 3],
+            ambient: 0.15,
+        };
+        uniform.update_direction(cgmath::Vector3::new(0.5, 1.0, 0.75));
+        uniform
+    }
+
+    pub fn update_direction(&mut self, direction: cgmath::Vector3<f32>) {
+        use cgmath::InnerSpace;
+        self.direction = direction.normalize().into();
+    }
+}
+
+impl Default for LightUniform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,109 @@
+// Dependencies
+use anyhow::{Result, bail};
+use bytemuck::{Pod, Zeroable};
+
+/// Surface the lighting shader shades models with where they have neither a texture nor colors of
+/// their own
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    /// Linear base color, the diffuse color of dielectrics and the reflectance of metals
+    pub albedo: [f32; 3],
+    /// `0.0` for dielectrics like plastic, `1.0` for bare metal
+    pub metallic: f32,
+    /// `0.0` is a mirror, `1.0` spreads highlights out until they're gone
+    pub roughness: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        MaterialPreset::default().material()
+    }
+}
+
+impl Material {
+    /// Returns the uniform [`crate::state::State`] binds to `@group(3) @binding(1)`.
+    pub fn uniform(&self) -> MaterialUniform {
+        MaterialUniform {
+            albedo: self.albedo,
+            metallic: self.metallic,
+            roughness: self.roughness,
+            _padding: [0.0; 3],
+        }
+    }
+
+    /// Returns the preset the material is, `None` for one of its own.
+    pub fn preset(&self) -> Option<MaterialPreset> {
+        let presets = [
+            MaterialPreset::Steel,
+            MaterialPreset::Plastic,
+            MaterialPreset::Brass,
+        ];
+        presets
+            .into_iter()
+            .find(|preset| preset.material() == *self)
+    }
+}
+
+/// Shader uniform for a [`Material`], laid out like the `MaterialUniform` struct of `basic.wgsl`
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct MaterialUniform {
+    albedo: [f32; 3],
+    metallic: f32,
+    roughness: f32,
+    /// WGSL rounds the struct up to the 16 byte alignment of its `vec3`
+    _padding: [f32; 3],
+}
+
+/// Materials picked with `--material <steel|plastic|brass>` and cycled with M
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MaterialPreset {
+    Steel,
+    #[default]
+    Plastic,
+    Brass,
+}
+
+impl MaterialPreset {
+    pub fn material(self) -> Material {
+        match self {
+            Self::Steel => Material {
+                albedo: [0.56, 0.57, 0.58],
+                metallic: 1.0,
+                roughness: 0.35,
+            },
+            Self::Plastic => Material {
+                albedo: [0.6, 0.6, 0.6],
+                metallic: 0.0,
+                roughness: 0.5,
+            },
+            Self::Brass => Material {
+                albedo: [0.91, 0.78, 0.42],
+                metallic: 1.0,
+                roughness: 0.3,
+            },
+        }
+    }
+
+    /// Returns the preset after this one, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Steel => Self::Plastic,
+            Self::Plastic => Self::Brass,
+            Self::Brass => Self::Steel,
+        }
+    }
+}
+
+impl std::str::FromStr for MaterialPreset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "steel" => Ok(Self::Steel),
+            "plastic" => Ok(Self::Plastic),
+            "brass" => Ok(Self::Brass),
+            _ => bail!("Unknown material '{}', expected steel, plastic or brass", s),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+// Dependencies
+use cgmath::{InnerSpace, Vector3};
+
+// Local modules
+use super::Vertex;
+
+/// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    /// Returns the smallest box containing every point, or `None` if there are no points.
+    pub fn from_points(points: impl IntoIterator<Item = [f32; 3]>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |aabb, p| Self {
+                min: std::array::from_fn(|i| aabb.min[i].min(p[i])),
+                max: std::array::from_fn(|i| aabb.max[i].max(p[i])),
+            },
+        ))
+    }
+
+    pub fn center(&self) -> [f32; 3] {
+        std::array::from_fn(|i| (self.min[i] + self.max[i]) / 2.0)
+    }
+}
+
+/// Returns a sphere enclosing the positions of `vertices` as `(center, radius)`, with Ritter's
+/// algorithm.
+///
+/// The sphere starts out spanning two points far apart and grows to take in every point outside
+/// of it. It's not the smallest enclosing sphere, but within a few percent of it in two passes
+/// over `vertices`, and usually tighter than the sphere around their bounding box. An empty mesh
+/// has a zero sized sphere at the origin.
+pub fn bounding_sphere(vertices: &[Vertex]) -> ([f32; 3], f32) {
+    let Some(first) = vertices.first() else {
+        return ([0.0; 3], 0.0);
+    };
+    let positions = || vertices.iter().map(|v| Vector3::from(v.position));
+    let farthest_from = |from: Vector3<f32>| {
+        positions()
+            .max_by(|a, b| {
+                (*a - from)
+                    .magnitude2()
+                    .total_cmp(&(*b - from).magnitude2())
+            })
+            .unwrap_or(from)
+    };
+    let a = farthest_from(first.position.into());
+    let b = farthest_from(a);
+    let mut center = (a + b) / 2.0;
+    let mut radius = (b - a).magnitude() / 2.0;
+    for p in positions() {
+        let distance = (p - center).magnitude();
+        if distance > radius {
+            // Move the center towards `p` just enough for the opposite side to stay put
+            let grown = (radius + distance) / 2.0;
+            center += (p - center) * ((grown - radius) / distance);
+            radius = grown;
+        }
+    }
+    (center.into(), radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boxes_points() {
+        assert_eq!(Aabb::from_points([]), None);
+        let aabb =
+            Aabb::from_points([[1.0, -2.0, 3.0], [-1.0, 4.0, 0.5], [0.0, 0.0, 2.0]]).unwrap();
+        assert_eq!(aabb.min, [-1.0, -2.0, 0.5]);
+        assert_eq!(aabb.max, [1.0, 4.0, 3.0]);
+        assert_eq!(aabb.center(), [0.0, 1.0, 1.75]);
+
+        let point = Aabb::from_points([[5.0; 3]]).unwrap();
+        assert_eq!((point.min, point.max), ([5.0; 3], [5.0; 3]));
+    }
+
+    fn vertex(position: [f32; 3]) -> Vertex {
+        Vertex {
+            position,
+            uv_coords: [0.0; 2],
+            normal: [0.0; 3],
+        }
+    }
+
+    fn assert_encloses(vertices: &[Vertex], (center, radius): ([f32; 3], f32)) {
+        for v in vertices {
+            let distance = (Vector3::from(v.position) - Vector3::from(center)).magnitude();
+            assert!(
+                distance <= radius * (1.0 + 1e-6),
+                "{:?} is {} from {:?}, past the radius {}",
+                v.position,
+                distance,
+                center,
+                radius
+            );
+        }
+    }
+
+    #[test]
+    fn sphere_encloses_every_vertex() {
+        // The corners of a cube, off the origin and in an order the first guess doesn't span
+        let cube: Vec<Vertex> = [1, 6, 0, 3, 7, 2, 5, 4]
+            .map(|i: usize| {
+                vertex([i & 1, (i >> 1) & 1, (i >> 2) & 1].map(|c| 2.0 * c as f32 + 1.0))
+            })
+            .to_vec();
+        let sphere = bounding_sphere(&cube);
+        assert_encloses(&cube, sphere);
+        // Within a few percent of the sphere through the corners
+        let (center, radius) = sphere;
+        assert!(radius <= 3f32.sqrt() * 1.05, "{}", radius);
+        assert!((Vector3::from(center) - Vector3::new(2.0, 2.0, 2.0)).magnitude() < 0.1 * radius);
+
+        let point = [vertex([1.0, -2.0, 3.0])];
+        assert_eq!(bounding_sphere(&point), ([1.0, -2.0, 3.0], 0.0));
+        assert_encloses(&point, bounding_sphere(&point));
+        assert_eq!(bounding_sphere(&[]), ([0.0; 3], 0.0));
+    }
+}
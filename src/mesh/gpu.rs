@@ -0,0 +1,639 @@
+// STD
+use std::collections::HashMap;
+use std::ops::Range;
+
+// Dependencies
+use anyhow::{Result, bail};
+use wgpu::util::DeviceExt;
+
+// Local modules
+use super::{Aabb, VertexLayout as Vertex};
+
+/// Size of the vertex buffer of [`MeshBuffers`], in bytes
+pub const MESH_VERTEX_BYTES: wgpu::BufferAddress = 64 << 20;
+/// Size of the index buffer of [`MeshBuffers`], in bytes
+pub const MESH_INDEX_BYTES: wgpu::BufferAddress = 16 << 20;
+
+/// A mesh resource handle for wgpu that guarantees vertex layout uniformity.
+///
+/// Created using [`MeshBuffers::alloc()`] which sub-allocates the mesh's vertices and indices
+/// within buffers shared with other meshes, or with buffers of its own by [`Mesh::from()`] and
+/// [`Mesh::upload()`].
+pub struct Mesh<V: Vertex> {
+    /// Device [`Mesh::upload()`] creates buffers on
+    device: wgpu::Device,
+    /// Buffer the vertices are sub-allocated from, possibly shared with other meshes. `None`
+    /// until vertices are uploaded.
+    vertex_buffer: Option<wgpu::Buffer>,
+    /// Byte offset of the first vertex in [`Mesh::vertex_buffer`]
+    vertex_offset: wgpu::BufferAddress,
+    /// Number of vertices reserved for the mesh
+    vertex_count: u32,
+    /// Buffer the indices are sub-allocated from, `None` for unindexed meshes
+    index_buffer: Option<wgpu::Buffer>,
+    /// Byte offset of the first index in [`Mesh::index_buffer`]
+    index_offset: wgpu::BufferAddress,
+    /// Number of indices reserved for the mesh
+    index_count: u32,
+    /// Width of the indices, see [`index_format()`]
+    index_format: wgpu::IndexFormat,
+    /// Bounds of the uploaded vertex positions, `None` without vertices
+    bounds: Option<Aabb>,
+    _marker: std::marker::PhantomData<V>,
+}
+
+/// Functions and methods for loading and manipulating raw mesh data on a wgpu device.
+impl<V: Vertex> Mesh<V> {
+    /// Creates an empty mesh on `device`. No buffers are allocated until [`Mesh::upload()`].
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            device: device.clone(),
+            vertex_buffer: None,
+            vertex_offset: 0,
+            vertex_count: 0,
+            index_buffer: None,
+            index_offset: 0,
+            index_count: 0,
+            index_format: wgpu::IndexFormat::Uint16,
+            bounds: None,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Wraps regions of shared buffers `vertices` were written to, `vertex_offset` and
+    /// `index_offset` are in bytes.
+    fn from_regions(
+        device: &wgpu::Device,
+        vertex_buffer: wgpu::Buffer,
+        vertex_offset: wgpu::BufferAddress,
+        vertices: &[V],
+        index: Option<(wgpu::Buffer, wgpu::BufferAddress, u32, wgpu::IndexFormat)>,
+    ) -> Self {
+        let mut mesh = Self::new(device);
+        mesh.vertex_buffer = Some(vertex_buffer);
+        mesh.vertex_offset = vertex_offset;
+        mesh.vertex_count = vertices.len() as u32;
+        mesh.bounds = Aabb::from_points(vertices.iter().map(V::position));
+        if let Some((buffer, offset, count, format)) = index {
+            mesh.index_buffer = Some(buffer);
+            mesh.index_offset = offset;
+            mesh.index_count = count;
+            mesh.index_format = format;
+        }
+        mesh
+    }
+
+    /// Uploads `vertices` and `indices` into buffers of their own, see [`Mesh::upload()`].
+    ///
+    /// Use [`MeshBuffers::alloc()`] to share buffers with other meshes instead.
+    pub fn from(device: &wgpu::Device, vertices: &[V], indices: &[u32]) -> Result<Self> {
+        let mut mesh = Self::new(device);
+        mesh.upload(vertices, indices)?;
+        Ok(mesh)
+    }
+
+    /// Uploads a non-indexed triangle list, sharing byte-identical vertices through a generated
+    /// index buffer, see [`dedup_vertices()`].
+    pub fn from_vertices(device: &wgpu::Device, vertices: &[V]) -> Self {
+        let (unique, indices) = dedup_vertices(vertices);
+        let packed = pack_indices_unchecked(&indices, unique.len());
+        let mut mesh = Self::new(device);
+        mesh.replace_buffers(&unique, Some((packed, indices.len() as u32)));
+        mesh
+    }
+
+    /// Replaces the mesh's data with `vertices` and `indices`, reallocating its buffers to fit
+    /// them exactly. Indices are stored as `u16` unless there are more than `u16::MAX` vertices,
+    /// empty `indices` leave the mesh unindexed.
+    ///
+    /// A sub-allocated mesh gets buffers of its own, its old region stays reserved. Fails if an
+    /// index is out of range, keeping the current data.
+    pub fn upload(&mut self, vertices: &[V], indices: &[u32]) -> Result<()> {
+        let index = if indices.is_empty() {
+            None
+        } else {
+            Some((pack_indices(indices, vertices.len())?, indices.len() as u32))
+        };
+        self.replace_buffers(vertices, index);
+        Ok(())
+    }
+
+    /// Creates the buffers of [`Mesh::upload()`], `index` holds the packed indices and their
+    /// count. Empty `vertices` leave the mesh without buffers.
+    fn replace_buffers(
+        &mut self,
+        vertices: &[V],
+        index: Option<((wgpu::IndexFormat, Vec<u8>), u32)>,
+    ) {
+        let device = &self.device;
+        self.vertex_buffer = (!vertices.is_empty()).then(|| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Mesh Vertex Buffer"),
+                contents: bytemuck::cast_slice(vertices),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            })
+        });
+        self.vertex_offset = 0;
+        self.vertex_count = vertices.len() as u32;
+        self.bounds = Aabb::from_points(vertices.iter().map(V::position));
+        let index = index.filter(|_| !vertices.is_empty());
+        self.index_buffer = index.as_ref().map(|((_, bytes), _)| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Mesh Index Buffer"),
+                contents: bytes,
+                usage: wgpu::BufferUsages::INDEX,
+            })
+        });
+        self.index_offset = 0;
+        (self.index_format, self.index_count) = match index {
+            Some(((format, _), count)) => (format, count),
+            None => (wgpu::IndexFormat::Uint16, 0),
+        };
+    }
+
+    /// Returns the bounding box of the mesh's vertex positions, `None` if it has no vertices.
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.bounds
+    }
+
+    /// Byte range of the mesh's vertices within their shared buffer.
+    pub fn vertex_range(&self) -> Range<wgpu::BufferAddress> {
+        let stride = std::mem::size_of::<V>() as wgpu::BufferAddress;
+        self.vertex_offset..self.vertex_offset + self.vertex_count as wgpu::BufferAddress * stride
+    }
+
+    /// Byte range of the mesh's indices within their shared buffer, empty without indices.
+    pub fn index_range(&self) -> Range<wgpu::BufferAddress> {
+        let stride = index_stride(self.index_format);
+        self.index_offset..self.index_offset + self.index_count as wgpu::BufferAddress * stride
+    }
+
+    /// Returns the mesh's vertices to bind as a vertex buffer, `None` without vertices.
+    pub fn vertex_slice(&self) -> Option<wgpu::BufferSlice<'_>> {
+        let buffer = self.vertex_buffer.as_ref()?;
+        (self.vertex_count > 0).then(|| buffer.slice(self.vertex_range()))
+    }
+
+    /// Returns the mesh's indices to bind as an index buffer, `None` for unindexed meshes.
+    pub fn index_slice(&self) -> Option<wgpu::BufferSlice<'_>> {
+        let buffer = self.index_buffer.as_ref()?;
+        (self.index_count > 0).then(|| buffer.slice(self.index_range()))
+    }
+
+    /// Binds the mesh's vertices to vertex buffer `slot`, along with its indices if it has any,
+    /// and draws `instances` of it. Draws nothing without vertices.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, slot: u32, instances: Range<u32>) {
+        let Some(vertices) = self.vertex_slice() else {
+            return;
+        };
+        render_pass.set_vertex_buffer(slot, vertices);
+        match self.index_slice() {
+            Some(indices) => {
+                render_pass.set_index_buffer(indices, self.index_format);
+                render_pass.draw_indexed(0..self.index_count, 0, instances);
+            }
+            None => render_pass.draw(0..self.vertex_count, instances),
+        }
+    }
+
+    /// Format to pass to [`wgpu::RenderPass::set_index_buffer()`] along with
+    /// [`Mesh::index_range()`].
+    pub fn index_format(&self) -> wgpu::IndexFormat {
+        self.index_format
+    }
+
+    /// Number of vertices reserved for the mesh.
+    pub fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    /// Number of indices reserved for the mesh, `0` for unindexed meshes.
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    /// Overwrites the mesh's vertices starting at vertex `offset` with `vertices`, in place.
+    ///
+    /// The write is queued like any [`wgpu::Queue::write_buffer()`], so it lands before the next
+    /// submitted frame. Fails instead of touching the neighbouring meshes if `vertices` don't fit
+    /// into the mesh's region. [`Mesh::bounds()`] grows to take in the new positions, but doesn't
+    /// shrink, the overwritten ones are gone.
+    pub fn update_vertices(
+        &mut self,
+        queue: &wgpu::Queue,
+        offset: u32,
+        vertices: &[V],
+    ) -> Result<()> {
+        let end = offset as u64 + vertices.len() as u64;
+        if end > self.vertex_count as u64 {
+            bail!(
+                "Can't write vertices {}..{} of a mesh with {} vertices",
+                offset,
+                end,
+                self.vertex_count
+            );
+        }
+        let stride = std::mem::size_of::<V>() as wgpu::BufferAddress;
+        if !stride.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT) {
+            bail!(
+                "Vertices of {} bytes aren't a multiple of {} bytes, can't write them in place",
+                stride,
+                wgpu::COPY_BUFFER_ALIGNMENT
+            );
+        }
+        if vertices.is_empty() {
+            return Ok(());
+        }
+        let Some(buffer) = &self.vertex_buffer else {
+            return Ok(());
+        };
+        let start = self.vertex_offset + offset as wgpu::BufferAddress * stride;
+        queue.write_buffer(buffer, start, bytemuck::cast_slice(vertices));
+        let corners = self.bounds.into_iter().flat_map(|b| [b.min, b.max]);
+        self.bounds = Aabb::from_points(corners.chain(vertices.iter().map(V::position)));
+        Ok(())
+    }
+}
+
+/// Collapses byte-identical vertices, returning the unique ones in order of first appearance
+/// and an index into them for every vertex of `vertices`.
+pub fn dedup_vertices<V: Vertex>(vertices: &[V]) -> (Vec<V>, Vec<u32>) {
+    let mut seen: HashMap<&[u8], u32> = HashMap::new();
+    let mut unique = Vec::new();
+    let indices = vertices
+        .iter()
+        .map(|vertex| {
+            *seen.entry(bytemuck::bytes_of(vertex)).or_insert_with(|| {
+                unique.push(*vertex);
+                unique.len() as u32 - 1
+            })
+        })
+        .collect();
+    (unique, indices)
+}
+
+/// Bump allocator handing out disjoint byte ranges of a buffer shared between meshes.
+#[derive(Debug, Clone)]
+pub struct Suballocator {
+    capacity: wgpu::BufferAddress,
+    used: wgpu::BufferAddress,
+}
+
+impl Suballocator {
+    pub fn new(capacity: wgpu::BufferAddress) -> Self {
+        Self { capacity, used: 0 }
+    }
+
+    /// Reserves `size` bytes starting at a multiple of `align`, `None` once they don't fit.
+    pub fn alloc(
+        &mut self,
+        size: wgpu::BufferAddress,
+        align: wgpu::BufferAddress,
+    ) -> Option<Range<wgpu::BufferAddress>> {
+        let start = self.used.checked_next_multiple_of(align.max(1))?;
+        let end = start.checked_add(size)?;
+        if end > self.capacity {
+            return None;
+        }
+        self.used = end;
+        Some(start..end)
+    }
+}
+
+/// Vertex and index buffers shared by every [`Mesh`] allocated from them.
+pub struct MeshBuffers {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    vertices: Suballocator,
+    indices: Suballocator,
+}
+
+impl MeshBuffers {
+    /// Creates buffers of [`MESH_VERTEX_BYTES`] and [`MESH_INDEX_BYTES`] on `device`.
+    pub fn new(device: &wgpu::Device) -> Self {
+        let create = |label, size, usage| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: usage | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        Self {
+            vertex_buffer: create(
+                "Shared Mesh Vertex Buffer",
+                MESH_VERTEX_BYTES,
+                wgpu::BufferUsages::VERTEX,
+            ),
+            index_buffer: create(
+                "Shared Mesh Index Buffer",
+                MESH_INDEX_BYTES,
+                wgpu::BufferUsages::INDEX,
+            ),
+            vertices: Suballocator::new(MESH_VERTEX_BYTES),
+            indices: Suballocator::new(MESH_INDEX_BYTES),
+        }
+    }
+
+    /// Sub-allocates a mesh and uploads `vertices` and `indices` into it through `queue`, as
+    /// `u16` unless there are more than `u16::MAX` vertices.
+    ///
+    /// Meshes get disjoint regions of the same two buffers rather than a buffer each. Fails if an
+    /// index is out of range, or once the buffers are full, without reserving anything.
+    pub fn alloc<V: Vertex>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        vertices: &[V],
+        indices: Option<&[u32]>,
+    ) -> Result<Mesh<V>> {
+        let stride = std::mem::size_of::<V>() as wgpu::BufferAddress;
+        if !stride.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT) {
+            bail!(
+                "Vertices of {} bytes aren't a multiple of {} bytes, they can't be uploaded",
+                stride,
+                wgpu::COPY_BUFFER_ALIGNMENT
+            );
+        }
+
+        // Reserve both regions before committing either, so a failure leaks nothing
+        let mut vertex_alloc = self.vertices.clone();
+        let mut index_alloc = self.indices.clone();
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(vertices);
+        let Some(vertex_range) =
+            vertex_alloc.alloc(vertex_bytes.len() as u64, wgpu::COPY_BUFFER_ALIGNMENT)
+        else {
+            bail!("{} vertices don't fit into the mesh buffer", vertices.len());
+        };
+        let index_range = match indices {
+            Some(indices) => {
+                let (format, bytes) = pack_indices(indices, vertices.len())?;
+                let size = bytes.len() as u64;
+                let Some(range) = index_alloc.alloc(size, wgpu::COPY_BUFFER_ALIGNMENT) else {
+                    bail!("{} indices don't fit into the mesh buffer", indices.len());
+                };
+                Some((range, format, bytes, indices.len()))
+            }
+            None => None,
+        };
+        self.vertices = vertex_alloc;
+        self.indices = index_alloc;
+
+        if !vertex_bytes.is_empty() {
+            queue.write_buffer(&self.vertex_buffer, vertex_range.start, vertex_bytes);
+        }
+        let index = index_range.map(|(range, format, bytes, count)| {
+            if !bytes.is_empty() {
+                queue.write_buffer(&self.index_buffer, range.start, &bytes);
+            }
+            (self.index_buffer.clone(), range.start, count as u32, format)
+        });
+        Ok(Mesh::from_regions(
+            device,
+            self.vertex_buffer.clone(),
+            vertex_range.start,
+            vertices,
+            index,
+        ))
+    }
+}
+
+/// Returns the narrowest format indexing `vertex_count` vertices, [`wgpu::IndexFormat::Uint32`]
+/// once there are more than `u16::MAX`.
+pub fn index_format(vertex_count: usize) -> wgpu::IndexFormat {
+    if vertex_count <= u16::MAX as usize {
+        wgpu::IndexFormat::Uint16
+    } else {
+        wgpu::IndexFormat::Uint32
+    }
+}
+
+/// Size of a single index of `format` in bytes.
+pub fn index_stride(format: wgpu::IndexFormat) -> wgpu::BufferAddress {
+    match format {
+        wgpu::IndexFormat::Uint16 => 2,
+        wgpu::IndexFormat::Uint32 => 4,
+    }
+}
+
+/// Packs `indices` into `vertex_count` vertices in the [`index_format()`] of their count, padded
+/// to [`wgpu::COPY_BUFFER_ALIGNMENT`] so they can be written to a buffer.
+///
+/// Fails if an index points past the vertices, it would draw garbage instead.
+pub fn pack_indices(indices: &[u32], vertex_count: usize) -> Result<(wgpu::IndexFormat, Vec<u8>)> {
+    if let Some(index) = indices.iter().find(|&&i| i as usize >= vertex_count) {
+        bail!(
+            "Index {} is out of range of {} vertices",
+            index,
+            vertex_count
+        );
+    }
+    Ok(pack_indices_unchecked(indices, vertex_count))
+}
+
+/// [`pack_indices()`] for indices known to be in range.
+fn pack_indices_unchecked(indices: &[u32], vertex_count: usize) -> (wgpu::IndexFormat, Vec<u8>) {
+    let format = index_format(vertex_count);
+    let mut bytes: Vec<u8> = match format {
+        wgpu::IndexFormat::Uint16 => indices
+            .iter()
+            .flat_map(|&i| (i as u16).to_ne_bytes())
+            .collect(),
+        wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(indices).to_vec(),
+    };
+    let padded = bytes
+        .len()
+        .next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize);
+    bytes.resize(padded, 0);
+    (format, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::Vertex as MeshVertex;
+
+    /// Returns the 12 triangles of a unit cube, each with its own three vertices.
+    fn cube() -> Vec<MeshVertex> {
+        let corner = |i: usize| [i & 1, (i >> 1) & 1, (i >> 2) & 1].map(|c| c as f32);
+        let quads = [
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ];
+        quads
+            .iter()
+            .flat_map(|q| [q[0], q[1], q[2], q[0], q[2], q[3]])
+            .map(|i| MeshVertex {
+                position: corner(i),
+                uv_coords: [0.0; 2],
+                normal: [0.0; 3],
+            })
+            .collect()
+    }
+
+    /// Opens a device on any adapter, `None` where there's none to skip GPU tests.
+    fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+                .ok()?;
+        pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).ok()
+    }
+
+    #[test]
+    fn uploads_into_buffers_of_their_own() {
+        let Some((device, _)) = device() else {
+            eprintln!("No adapter, skipping");
+            return;
+        };
+        let mut mesh = Mesh::<MeshVertex>::new(&device);
+        assert!(mesh.vertex_buffer.is_none() && mesh.index_buffer.is_none());
+        assert_eq!((mesh.vertex_count(), mesh.index_count()), (0, 0));
+
+        let vertices = cube();
+        mesh.upload(&vertices[..6], &[0, 1, 2, 3, 4, 5]).unwrap();
+        let stride = std::mem::size_of::<MeshVertex>() as u64;
+        assert_eq!((mesh.vertex_count(), mesh.index_count()), (6, 6));
+        assert_eq!(mesh.vertex_buffer.as_ref().unwrap().size(), 6 * stride);
+        assert_eq!(mesh.vertex_range(), 0..6 * stride);
+        assert_eq!(mesh.index_range(), 0..12);
+        let bounds = mesh.bounds().unwrap();
+        assert_eq!((bounds.min, bounds.max), ([0.0; 3], [1.0, 1.0, 0.0]));
+
+        // A failed upload keeps the mesh as it was
+        assert!(mesh.upload(&vertices[..3], &[0, 1, 3]).is_err());
+        assert_eq!((mesh.vertex_count(), mesh.index_count()), (6, 6));
+
+        mesh.upload(&vertices[..3], &[]).unwrap();
+        assert_eq!((mesh.vertex_count(), mesh.index_count()), (3, 0));
+        assert_eq!(mesh.vertex_buffer.as_ref().unwrap().size(), 3 * stride);
+        assert!(mesh.index_buffer.is_none());
+
+        mesh.upload(&[], &[]).unwrap();
+        assert_eq!(mesh.bounds(), None);
+    }
+
+    #[test]
+    fn suballocates_disjoint_regions() {
+        let Some((device, queue)) = device() else {
+            eprintln!("No adapter, skipping");
+            return;
+        };
+        let mut buffers = MeshBuffers::new(&device);
+        let (vertices, indices) = dedup_vertices(&cube());
+        let a = buffers
+            .alloc(&device, &queue, &vertices, Some(&indices))
+            .unwrap();
+        // Failures reserve nothing, the next mesh follows right after the first
+        assert!(
+            buffers
+                .alloc(&device, &queue, &vertices, Some(&[8]))
+                .is_err()
+        );
+        let b = buffers
+            .alloc(&device, &queue, &vertices[..3], Some(&[0, 1, 2]))
+            .unwrap();
+        let c = buffers.alloc(&device, &queue, &vertices, None).unwrap();
+
+        for mesh in [&a, &b, &c] {
+            assert!(mesh.vertex_buffer.as_ref() == Some(&buffers.vertex_buffer));
+        }
+        assert!(a.index_buffer == b.index_buffer && c.index_buffer.is_none());
+        assert_eq!(a.vertex_range().end, b.vertex_range().start);
+        assert!(b.vertex_range().end <= c.vertex_range().start);
+        assert!(a.index_range().end <= b.index_range().start);
+        assert_eq!((a.vertex_count(), a.index_count()), (8, 36));
+        assert_eq!((b.vertex_count(), b.index_count()), (3, 3));
+        assert_eq!((c.vertex_count(), c.index_count()), (8, 0));
+    }
+
+    #[test]
+    fn packs_indices_by_vertex_count() {
+        let small = u16::MAX as usize;
+        assert_eq!(index_format(small), wgpu::IndexFormat::Uint16);
+        assert_eq!(index_format(small + 1), wgpu::IndexFormat::Uint32);
+        assert_eq!(index_stride(wgpu::IndexFormat::Uint16), 2);
+        assert_eq!(index_stride(wgpu::IndexFormat::Uint32), 4);
+
+        // Three 16-bit indices are padded to eight bytes
+        let (format, bytes) = pack_indices(&[0, 1, small as u32 - 1], small).unwrap();
+        assert_eq!(format, wgpu::IndexFormat::Uint16);
+        let packed: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(packed, [0, 1, u16::MAX - 1, 0]);
+
+        let (format, bytes) = pack_indices(&[0, 1, small as u32], small + 1).unwrap();
+        assert_eq!(format, wgpu::IndexFormat::Uint32);
+        let packed: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(packed, [0, 1, small as u32]);
+
+        // An index past the vertices would be truncated or draw garbage
+        assert!(pack_indices(&[0, 1, small as u32], small).is_err());
+        assert!(pack_indices(&[3], 3).is_err());
+        assert_eq!(pack_indices(&[], 0).unwrap().1, []);
+    }
+
+    #[test]
+    fn indexes_high_poly_sphere_with_u32() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/step/sphere.step");
+        let graph = crate::step::read_step(&path).unwrap();
+        // Fine enough for the radius 5 sphere to need more than 65535 vertices
+        let options = crate::step::TessellationOptions {
+            linear_deflection: 1e-4,
+            angular_deflection: 1f64.to_radians(),
+            ..Default::default()
+        };
+        let tessellation = crate::step::tessellate_faces(&graph, &options).unwrap();
+        let (vertices, indices) = dedup_vertices(&tessellation.vertices);
+        assert!(
+            vertices.len() > u16::MAX as usize,
+            "{} vertices",
+            vertices.len()
+        );
+        assert_eq!(indices.len(), tessellation.vertices.len());
+
+        let (format, bytes) = pack_indices(&indices, vertices.len()).unwrap();
+        assert_eq!(format, wgpu::IndexFormat::Uint32);
+        assert_eq!(bytes.len(), 4 * indices.len());
+        let packed: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(packed, indices);
+        assert!(packed.iter().all(|&i| (i as usize) < vertices.len()));
+
+        let Some((device, _)) = device() else {
+            eprintln!("No adapter, skipping");
+            return;
+        };
+        let mut mesh = Mesh::<MeshVertex>::new(&device);
+        mesh.upload(&vertices, &indices).unwrap();
+        assert_eq!(mesh.index_format(), wgpu::IndexFormat::Uint32);
+        assert_eq!(mesh.index_count() as usize, indices.len());
+        assert_eq!(mesh.vertex_count() as usize, vertices.len());
+    }
+
+    #[test]
+    fn dedups_cube_corners() {
+        let vertices = cube();
+        assert_eq!(vertices.len(), 36);
+        let (unique, indices) = dedup_vertices(&vertices);
+        assert_eq!(unique.len(), 8);
+        assert_eq!(indices.len(), 36);
+        for (vertex, &index) in vertices.iter().zip(&indices) {
+            assert_eq!(unique[index as usize].position, vertex.position);
+        }
+    }
+}
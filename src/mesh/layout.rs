@@ -0,0 +1,100 @@
+/// Vertex types that can be uploaded to a vertex buffer as they are.
+pub trait VertexLayout: bytemuck::Pod + bytemuck::Zeroable {
+    /// Returns the layout of the vertex attributes.
+    fn layout() -> wgpu::VertexBufferLayout<'static>;
+
+    /// Returns the position attribute of the vertex.
+    fn position(&self) -> [f32; 3];
+}
+
+/// Builder for a [`wgpu::VertexBufferLayout`] of tightly packed attributes.
+///
+/// Each attribute's offset is the sum of the sizes of the ones pushed before it, so the field
+/// order of the vertex struct must match the push order and the struct must be `#[repr(C)]`.
+#[derive(Debug, Default, Clone)]
+pub struct VertexAttrs {
+    attributes: Vec<wgpu::VertexAttribute>,
+    stride: wgpu::BufferAddress,
+}
+
+impl VertexAttrs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an attribute read by `@location(shader_location)` in the vertex shader.
+    pub fn push(mut self, format: wgpu::VertexFormat, shader_location: u32) -> Self {
+        self.attributes.push(wgpu::VertexAttribute {
+            format,
+            offset: self.stride,
+            shader_location,
+        });
+        self.stride += format.size();
+        self
+    }
+
+    /// Size of a single vertex in bytes.
+    pub fn stride(&self) -> wgpu::BufferAddress {
+        self.stride
+    }
+
+    /// Returns the per-vertex layout.
+    ///
+    /// The attributes are leaked to satisfy the `'static` lifetime, so build each layout once.
+    pub fn build(self) -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: self.stride,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: Vec::leak(self.attributes),
+        }
+    }
+}
+
+/// Returns the layout of tightly packed attributes at shader locations `0..attrs.len()`.
+///
+/// See: [`VertexAttrs`] for the requirements on the vertex struct.
+pub fn layout_from(attrs: &[wgpu::VertexFormat]) -> wgpu::VertexBufferLayout<'static> {
+    attrs
+        .iter()
+        .zip(0..)
+        .fold(VertexAttrs::new(), |layout, (&format, location)| {
+            layout.push(format, location)
+        })
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::Vertex;
+
+    #[test]
+    fn packs_vertex_attributes() {
+        let layout = Vertex::desc();
+        assert_eq!(layout.array_stride, std::mem::size_of::<Vertex>() as u64);
+        assert_eq!(layout.step_mode, wgpu::VertexStepMode::Vertex);
+        let attributes: Vec<_> = layout
+            .attributes
+            .iter()
+            .map(|a| (a.offset, a.shader_location, a.format))
+            .collect();
+        assert_eq!(
+            attributes,
+            [
+                (0, 0, wgpu::VertexFormat::Float32x3),
+                (12, 1, wgpu::VertexFormat::Float32x2),
+                (20, 2, wgpu::VertexFormat::Float32x3),
+            ]
+        );
+        // Cached, so the attributes are only leaked once
+        assert!(std::ptr::eq(layout.attributes, Vertex::desc().attributes));
+
+        let attrs = VertexAttrs::new()
+            .push(wgpu::VertexFormat::Float32x4, 3)
+            .push(wgpu::VertexFormat::Uint32, 5);
+        assert_eq!(attrs.stride(), 20);
+        let layout = attrs.build();
+        assert_eq!(layout.attributes[1].offset, 16);
+        assert_eq!(layout.attributes[1].shader_location, 5);
+    }
+}
@@ -0,0 +1,94 @@
+// STD
+use std::collections::HashMap;
+
+/// Defects [`check_manifold`] found in an indexed triangle mesh.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ManifoldReport {
+    /// Edges shared by more than two triangles
+    pub non_manifold_edges: usize,
+    /// Edges used by a single triangle, bordering a hole or the rim of an open surface
+    pub boundary_edges: usize,
+    /// Vertices no triangle uses
+    pub isolated_vertices: usize,
+}
+
+impl ManifoldReport {
+    /// Whether the mesh closes up into a volume, every edge joining exactly two triangles.
+    pub fn is_watertight(&self) -> bool {
+        self.non_manifold_edges == 0 && self.boundary_edges == 0
+    }
+}
+
+/// Counts the edges of the triangle list `indices` that aren't shared by exactly two triangles,
+/// along with the vertices left out of every triangle.
+///
+/// Edges are matched by their vertex indices regardless of direction, so the mesh should be
+/// welded first, see [`weld`](super::weld::weld). Vertices are numbered up to the highest index
+/// used, unused ones past it can't be told apart and aren't counted.
+pub fn check_manifold(indices: &[u32]) -> ManifoldReport {
+    let mut edges: HashMap<(u32, u32), usize> = HashMap::new();
+    let vertex_count = indices.iter().max().map_or(0, |&max| max as usize + 1);
+    let mut used = vec![false; vertex_count];
+    for triangle in indices.chunks_exact(3) {
+        for i in 0..3 {
+            let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+            *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+            used[a as usize] = true;
+        }
+    }
+    ManifoldReport {
+        non_manifold_edges: edges.values().filter(|&&n| n > 2).count(),
+        boundary_edges: edges.values().filter(|&&n| n == 1).count(),
+        isolated_vertices: used.iter().filter(|&&used| !used).count(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The four triangles of a tetrahedron over the vertices `0..4`.
+    const TETRAHEDRON: [u32; 12] = [0, 2, 1, 0, 1, 3, 1, 2, 3, 0, 3, 2];
+
+    #[test]
+    fn closed_mesh_is_watertight() {
+        let report = check_manifold(&TETRAHEDRON);
+        assert_eq!(report, ManifoldReport::default());
+        assert!(report.is_watertight());
+        assert_eq!(check_manifold(&[]), ManifoldReport::default());
+    }
+
+    #[test]
+    fn counts_boundary_edges() {
+        // Without its last triangle the tetrahedron has a triangular hole
+        let report = check_manifold(&TETRAHEDRON[..9]);
+        assert_eq!(report.boundary_edges, 3);
+        assert_eq!(
+            (report.non_manifold_edges, report.isolated_vertices),
+            (0, 0)
+        );
+        assert!(!report.is_watertight());
+    }
+
+    #[test]
+    fn counts_non_manifold_edges() {
+        // A fin sticking out of the edge 0-1 gives it a third triangle, and two boundary edges
+        let mut indices = TETRAHEDRON.to_vec();
+        indices.extend([0, 1, 4]);
+        let report = check_manifold(&indices);
+        assert_eq!(report.non_manifold_edges, 1);
+        assert_eq!(report.boundary_edges, 2);
+        assert!(!report.is_watertight());
+    }
+
+    #[test]
+    fn counts_isolated_vertices() {
+        // A second closed mesh over 6..9 skips the vertices 4 and 5
+        let mut indices = TETRAHEDRON.to_vec();
+        indices.extend([6, 7, 8, 6, 8, 7]);
+        let report = check_manifold(&indices);
+        assert_eq!(report.isolated_vertices, 2);
+        // Its two triangles back to back close up, unused vertices don't open the mesh
+        assert!(report.is_watertight());
+    }
+}
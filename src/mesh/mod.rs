@@ -0,0 +1,53 @@
+//! Vertex types and their buffer layouts, meshes uploaded to the GPU, along with normal
+//! generation, vertex welding, bounding spheres and manifold checks.
+// STD
+use std::sync::OnceLock;
+
+// Local modules
+pub mod bounds;
+pub mod gpu;
+pub mod layout;
+pub mod manifold;
+pub mod normals;
+pub mod weld;
+
+pub use bounds::{Aabb, bounding_sphere};
+pub use gpu::{Mesh, MeshBuffers};
+pub use layout::{VertexLayout, layout_from};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub uv_coords: [f32; 2],
+    pub normal: [f32; 3],
+}
+unsafe impl bytemuck::Pod for Vertex {}
+unsafe impl bytemuck::Zeroable for Vertex {}
+
+impl Vertex {
+    /// Formats of the position, texture coordinates and normal, at shader locations 0, 1 and 2
+    const ATTRIBUTES: [wgpu::VertexFormat; 3] = [
+        wgpu::VertexFormat::Float32x3,
+        wgpu::VertexFormat::Float32x2,
+        wgpu::VertexFormat::Float32x3,
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        // Built once, the attributes of a layout are leaked
+        static LAYOUT: OnceLock<wgpu::VertexBufferLayout<'static>> = OnceLock::new();
+        LAYOUT
+            .get_or_init(|| layout_from(&Self::ATTRIBUTES))
+            .clone()
+    }
+}
+
+impl VertexLayout for Vertex {
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        Self::desc()
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+}
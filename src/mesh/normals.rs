@@ -0,0 +1,70 @@
+// STD
+use std::collections::HashMap;
+
+// Dependencies
+use cgmath::{InnerSpace, Vector3, Zero};
+
+/// Returns the normal of every triangle, scaled by twice its area.
+fn face_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<Vector3<f32>> {
+    indices
+        .chunks_exact(3)
+        .map(|t| {
+            let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(positions[t[i] as usize]));
+            (b - a).cross(c - a)
+        })
+        .collect()
+}
+
+/// Computes per-vertex normals by accumulating the area-weighted normals of the surrounding
+/// triangles.
+///
+/// Vertices at the same position are smoothed across, unless the angle between the triangle's
+/// normal and the vertex's own triangles exceeds `crease_angle` degrees. Meshes whose vertices
+/// are split along sharp edges keep those edges sharp, shared vertices are always smoothed.
+/// Vertices without any non-degenerate triangle get a zero normal.
+pub fn compute_smooth_normals(
+    positions: &[[f32; 3]],
+    indices: &[u32],
+    crease_angle: f32,
+) -> Vec<[f32; 3]> {
+    let faces = face_normals(positions, indices);
+    let cos_crease = crease_angle.to_radians().cos();
+
+    // The vertex's own direction, and the triangles touching each position
+    let mut own = vec![Vector3::zero(); positions.len()];
+    let mut around: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (face, t) in indices.chunks_exact(3).enumerate() {
+        for &i in t {
+            own[i as usize] += faces[face];
+            let touching = around
+                .entry(positions[i as usize].map(f32::to_bits))
+                .or_default();
+            if touching.last() != Some(&face) {
+                touching.push(face);
+            }
+        }
+    }
+
+    positions
+        .iter()
+        .zip(own)
+        .map(|(position, own)| {
+            if own.magnitude2() == 0.0 {
+                return [0.0; 3];
+            }
+            let own = own.normalize();
+            let mut sum = Vector3::zero();
+            for &face in &around[&position.map(f32::to_bits)] {
+                let normal = faces[face];
+                if normal.magnitude2() > 0.0 && normal.normalize().dot(own) >= cos_crease {
+                    sum += normal;
+                }
+            }
+            if sum.magnitude2() == 0.0 {
+                own.into()
+            } else {
+                sum.normalize().into()
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,54 @@
+// STD
+use std::collections::HashMap;
+
+// Local modules
+use super::Vertex;
+
+/// Returns the grid cell of `epsilon` sized cells `position` falls into.
+fn cell(position: [f32; 3], epsilon: f32) -> [i64; 3] {
+    position.map(|c| (c / epsilon).floor() as i64)
+}
+
+/// Merges vertices whose positions lie within `epsilon` of each other, across the whole mesh, so
+/// faces tessellated independently end up sharing the vertices along their common edges.
+///
+/// Every vertex is compared with those in the neighbouring grid cells too, so nearby points never
+/// straddle a cell border unmerged.
+/// Each merged vertex keeps the attributes of the first vertex of its group, and triangles that
+/// collapse to a line or point are dropped. The winding of the remaining triangles is kept.
+pub fn weld(vertices: &[Vertex], indices: &[u32], epsilon: f32) -> (Vec<Vertex>, Vec<u32>) {
+    let epsilon = epsilon.max(f32::MIN_POSITIVE);
+    let mut cells: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+    let mut welded: Vec<Vertex> = Vec::new();
+    let mut remap = Vec::with_capacity(vertices.len());
+    for vertex in vertices {
+        let [x, y, z] = cell(vertex.position, epsilon);
+        let neighbours = (-1..=1)
+            .flat_map(|i| (-1..=1).flat_map(move |j| (-1..=1).map(move |k| [x + i, y + j, z + k])));
+        let close = neighbours
+            .filter_map(|c| cells.get(&c))
+            .flatten()
+            .copied()
+            .find(|&w| {
+                let p = welded[w as usize].position;
+                let d2: f32 = (0..3).map(|i| (p[i] - vertex.position[i]).powi(2)).sum();
+                d2 <= epsilon * epsilon
+            });
+        let index = close.unwrap_or_else(|| {
+            welded.push(*vertex);
+            let index = welded.len() as u32 - 1;
+            cells.entry([x, y, z]).or_default().push(index);
+            index
+        });
+        remap.push(index);
+    }
+
+    let mut welded_indices = Vec::with_capacity(indices.len());
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| remap[triangle[i] as usize]);
+        if a != b && b != c && a != c {
+            welded_indices.extend([a, b, c]);
+        }
+    }
+    (welded, welded_indices)
+}
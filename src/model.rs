@@ -0,0 +1,538 @@
+// Dependencies
+use wgpu::util::DeviceExt;
+
+// Local modules
+use crate::frustum::{Frustum, merge_spheres, transform_sphere};
+use crate::mesh::{Aabb, Mesh, Vertex, bounding_sphere};
+use crate::pick::Ray;
+use crate::step::tessellate::{FaceId, Polyline, SurfaceType, Tessellation};
+
+/// Shader uniform for a model's transform matrix
+///
+/// Must format the *uniform* for storage in the *transform* buffer of a [`Model`]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ModelUniform {
+    transform: [[f32; 4]; 4],
+}
+
+impl ModelUniform {
+    pub fn new(transform: cgmath::Matrix4<f32>) -> Self {
+        Self {
+            transform: transform.into(),
+        }
+    }
+}
+
+/// Per-instance transform of a [`Model`], read as four `vec4` columns at `@location(5)` to
+/// `@location(8)` of the vertex shader
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    transform: [[f32; 4]; 4],
+}
+
+impl InstanceRaw {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Float32x4,
+        8 => Float32x4,
+    ];
+
+    /// Layout of [`Model::instance_buffer`], stepped once per instance.
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Corner of the quad an edge segment is drawn as, read at `@location(0)` to `@location(2)` of
+/// `vs_edge`
+///
+/// Every corner carries both ends of the segment, the vertex shader widens the quad on screen.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct EdgeVertex {
+    start: [f32; 3],
+    end: [f32; 3],
+    /// `x` is `0.0` at the start and `1.0` at the end, `y` the side of the segment, `-1.0` or
+    /// `1.0`
+    corner: [f32; 2],
+}
+
+impl EdgeVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x3,
+        2 => Float32x2,
+    ];
+
+    /// Corners of the two triangles covering a segment
+    const CORNERS: [[f32; 2]; 6] = [
+        [0.0, -1.0],
+        [1.0, -1.0],
+        [1.0, 1.0],
+        [0.0, -1.0],
+        [1.0, 1.0],
+        [0.0, 1.0],
+    ];
+
+    /// Layout of [`Model::edges`], stepped once per vertex.
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<EdgeVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+
+    /// Returns the six corners of the quad covering the segment from `start` to `end`.
+    fn quad([start, end]: [[f64; 3]; 2]) -> impl Iterator<Item = Self> {
+        let (start, end) = (start.map(|x| x as f32), end.map(|x| x as f32));
+        Self::CORNERS
+            .into_iter()
+            .map(move |corner| Self { start, end, corner })
+    }
+}
+
+/// A [`Mesh`] on the GPU together with the transform it's drawn with
+///
+/// The mesh is drawn once per instance, each with its own transform applied before the model's.
+/// A new model has a single instance at the identity.
+pub struct Model {
+    /// Vertices and indices, possibly sub-allocated from buffers shared with other models
+    mesh: Mesh<Vertex>,
+    /// Bound to `@group(2)` in the vertex shader
+    pub transform_bind_group: wgpu::BindGroup,
+    transform_buffer: wgpu::Buffer,
+    trans: cgmath::Matrix4<f32>,
+    /// Transform of every instance, relative to the model's
+    instances: Vec<cgmath::Matrix4<f32>>,
+    /// [`InstanceRaw`] of every instance, bound to vertex buffer slot 1
+    instance_buffer: wgpu::Buffer,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    /// CPU copies of the vertex positions and indices, for picking
+    positions: Vec<cgmath::Point3<f32>>,
+    indices: Vec<u32>,
+    /// Center and radius of a sphere around the mesh, before any transform, for culling
+    bounding_sphere: (cgmath::Point3<f32>, f32),
+    /// BREP face of every triangle, if the model was tessellated from one
+    face_ids: Option<Vec<FaceId>>,
+    /// Indices of the highlighted face's triangles and their count
+    highlight: Option<(wgpu::Buffer, u32)>,
+    /// Quads of the BREP edge segments and their vertex count, if the model was tessellated from
+    /// one
+    edges: Option<(wgpu::Buffer, u32)>,
+    /// Color of every vertex in debug color modes, see [`Model::debug_colors`]
+    debug_colors: Option<wgpu::Buffer>,
+    /// Authored color of every vertex, see [`Model::face_colors`]
+    face_colors: Option<wgpu::Buffer>,
+    /// Draw back faces too, see [`Model::set_double_sided`]
+    double_sided: bool,
+    /// Sample the texture for the base color rather than shading with the material, see
+    /// [`Model::set_textured`]
+    textured: bool,
+}
+
+impl Model {
+    const COLOR_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![3 => Float32x3];
+
+    /// Layout of [`Model::debug_colors`] and [`Model::face_colors`], read as a `vec3` at
+    /// `@location(3)` of the vertex shader.
+    pub fn color_desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::COLOR_ATTRIBUTES,
+        }
+    }
+
+    /// Layout of [`Model::transform_bind_group`], pipelines drawing models must include it.
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("model_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        })
+    }
+
+    /// Draws `mesh`, which holds `vertices` drawn through `indices`, with an identity transform.
+    /// The model keeps copies of the positions and indices for picking, unindexed meshes pass a
+    /// running index.
+    ///
+    /// Models are textured until [`Model::set_textured`] says otherwise.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        mesh: Mesh<Vertex>,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Self {
+        use cgmath::SquareMatrix;
+        let trans = cgmath::Matrix4::identity();
+
+        let transform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Model: Transform Buffer"),
+            contents: bytemuck::cast_slice(&[ModelUniform::new(trans)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let instances = vec![cgmath::Matrix4::identity()];
+        let instance_buffer = Self::create_instance_buffer(device, &instances);
+        let (center, radius) = bounding_sphere(vertices);
+        let transform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: transform_buffer.as_entire_binding(),
+            }],
+            label: Some("model_bind_group"),
+        });
+
+        Self {
+            mesh,
+            transform_bind_group,
+            transform_buffer,
+            trans,
+            instances,
+            instance_buffer,
+            device: device.clone(),
+            queue: queue.clone(),
+            positions: vertices.iter().map(|v| v.position.into()).collect(),
+            indices: indices.to_vec(),
+            bounding_sphere: (center.into(), radius),
+            face_ids: None,
+            highlight: None,
+            edges: None,
+            debug_colors: None,
+            face_colors: None,
+            double_sided: false,
+            textured: true,
+        }
+    }
+
+    /// Returns the vertices and indices drawn for every instance.
+    pub fn mesh(&self) -> &Mesh<Vertex> {
+        &self.mesh
+    }
+
+    /// Overwrites the vertices starting at vertex `offset` in place, see
+    /// [`Mesh::update_vertices()`].
+    ///
+    /// Picking follows the new positions, and like the mesh's bounds the bounding sphere grows to
+    /// take them in but doesn't shrink.
+    pub fn update_vertices(&mut self, offset: u32, vertices: &[Vertex]) -> anyhow::Result<()> {
+        self.mesh.update_vertices(&self.queue, offset, vertices)?;
+        let positions = self.positions.iter_mut().skip(offset as usize);
+        for (position, vertex) in positions.zip(vertices) {
+            *position = vertex.position.into();
+            self.bounding_sphere = merge_spheres(self.bounding_sphere, (*position, 0.0));
+        }
+        Ok(())
+    }
+
+    fn create_instance_buffer(
+        device: &wgpu::Device,
+        instances: &[cgmath::Matrix4<f32>],
+    ) -> wgpu::Buffer {
+        let raw: Vec<InstanceRaw> = instances
+            .iter()
+            .map(|&m| InstanceRaw {
+                transform: m.into(),
+            })
+            .collect();
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Model: Instance Buffer"),
+            contents: bytemuck::cast_slice(&raw),
+            usage: wgpu::BufferUsages::VERTEX,
+        })
+    }
+
+    /// Adds another copy of the mesh drawn with `transform`, relative to the model's transform,
+    /// and returns its index.
+    ///
+    /// All instances are drawn with a single call, so repeated parts like fasteners share one
+    /// set of buffers.
+    pub fn add_instance(&mut self, transform: cgmath::Matrix4<f32>) -> usize {
+        self.instances.push(transform);
+        self.instance_buffer = Self::create_instance_buffer(&self.device, &self.instances);
+        self.instances.len() - 1
+    }
+
+    /// Returns the transform of every instance, relative to the model's.
+    pub fn instances(&self) -> &[cgmath::Matrix4<f32>] {
+        &self.instances
+    }
+
+    /// Returns the [`InstanceRaw`] buffer to bind to vertex buffer slot 1.
+    pub fn instance_buffer(&self) -> &wgpu::Buffer {
+        &self.instance_buffer
+    }
+
+    /// Number of instances to draw, see [`Model::add_instance`].
+    pub fn instance_count(&self) -> u32 {
+        self.instances.len() as u32
+    }
+
+    /// Draws the triangles of `tessellation` along with their edges, colors and faces. `mesh`
+    /// holds the tessellation's vertices, unindexed, every triangle has its own three.
+    ///
+    /// Faces without an authored color are shaded with the material rather than textured.
+    pub fn from_tessellation(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        mesh: Mesh<Vertex>,
+        tessellation: Tessellation,
+    ) -> Self {
+        let indices: Vec<u32> = (0..tessellation.vertices.len() as u32).collect();
+        let mut model = Self::new(
+            device,
+            queue,
+            layout,
+            mesh,
+            &tessellation.vertices,
+            &indices,
+        );
+        model.set_textured(false);
+        model.set_edges(&tessellation.edges);
+        // Every triangle has its own vertices, so faces can't bleed into each other's colors
+        let colors: Vec<[f32; 3]> = tessellation
+            .face_ids
+            .iter()
+            .map(|id| {
+                let surface_type = tessellation.surface_types.get(id).copied();
+                surface_type.map_or([1.0; 3], SurfaceType::color)
+            })
+            .flat_map(|color| [color; 3])
+            .collect();
+        model.set_debug_colors(&colors);
+        if !tessellation.colors.is_empty() {
+            let colors: Vec<[f32; 3]> = tessellation
+                .face_ids
+                .iter()
+                .flat_map(|&id| [tessellation.face_color(id); 3])
+                .collect();
+            model.set_face_colors(&colors);
+        }
+        model.face_ids = Some(tessellation.face_ids);
+        model
+    }
+
+    /// Returns the ray parameter of the closest triangle `ray` hits in any instance, along with
+    /// the BREP face the triangle came from, if known.
+    pub fn intersect(&self, ray: &Ray) -> Option<(f32, Option<FaceId>)> {
+        use cgmath::SquareMatrix;
+        self.instances
+            .iter()
+            .filter_map(|&instance| {
+                // Intersect in model space rather than transforming every triangle
+                let ray = ray.transform((self.trans * instance).invert()?);
+                self.indices
+                    .chunks_exact(3)
+                    .enumerate()
+                    .filter_map(|(triangle, corners)| {
+                        let t = ray.intersect_triangle([
+                            self.positions[corners[0] as usize],
+                            self.positions[corners[1] as usize],
+                            self.positions[corners[2] as usize],
+                        ])?;
+                        let face_id = self.face_ids.as_ref().map(|ids| ids[triangle]);
+                        Some((t, face_id))
+                    })
+                    .min_by(|a, b| a.0.total_cmp(&b.0))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    /// Returns a sphere around every instance as `(center, radius)` in world space, going by the
+    /// mesh's bounding sphere.
+    pub fn bounding_sphere(&self) -> (cgmath::Point3<f32>, f32) {
+        let (center, radius) = self.bounding_sphere;
+        self.instances
+            .iter()
+            .map(|&instance| transform_sphere(self.trans * instance, center, radius))
+            .reduce(merge_spheres)
+            .unwrap_or((center, radius))
+    }
+
+    /// Returns the box around every instance in world space, going by the corners of the mesh's
+    /// bounds. `None` without vertices.
+    pub fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.mesh.bounds()?;
+        let corners = (0..8).map(|i: usize| {
+            let pick = |axis: usize| match i >> axis & 1 {
+                0 => bounds.min[axis],
+                _ => bounds.max[axis],
+            };
+            cgmath::Point3::new(pick(0), pick(1), pick(2))
+        });
+        let corners: Vec<cgmath::Point3<f32>> = corners.collect();
+        Aabb::from_points(self.instances.iter().flat_map(|&instance| {
+            use cgmath::Transform;
+            let transform = self.trans * instance;
+            corners
+                .iter()
+                .map(move |&corner| transform.transform_point(corner).into())
+        }))
+    }
+
+    /// Returns whether any instance reaches into `frustum`, going by the mesh's bounding sphere.
+    pub fn is_visible(&self, frustum: &Frustum) -> bool {
+        let (center, radius) = self.bounding_sphere;
+        self.instances.iter().any(|&instance| {
+            let (center, radius) = transform_sphere(self.trans * instance, center, radius);
+            frustum.intersects_sphere(center, radius)
+        })
+    }
+
+    /// Highlights the triangles tessellated from `face`, or clears the highlight for `None`.
+    ///
+    /// Returns whether any triangle came from `face`.
+    pub fn set_highlight(&mut self, device: &wgpu::Device, face: Option<FaceId>) -> bool {
+        self.highlight = None;
+        let (Some(face), Some(face_ids)) = (face, &self.face_ids) else {
+            return false;
+        };
+        let indices: Vec<u32> = self
+            .indices
+            .chunks_exact(3)
+            .zip(face_ids)
+            .filter(|&(_, &id)| id == face)
+            .flat_map(|(corners, _)| corners.iter().copied())
+            .collect();
+        if indices.is_empty() {
+            return false;
+        }
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Model: Highlight Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        self.highlight = Some((index_buffer, indices.len() as u32));
+        true
+    }
+
+    /// Returns the index buffer of the highlighted triangles and its index count.
+    pub fn highlight(&self) -> Option<(&wgpu::Buffer, u32)> {
+        self.highlight
+            .as_ref()
+            .map(|(buffer, count)| (buffer, *count))
+    }
+
+    /// Replaces the edges drawn over the model with `edges`, in model space.
+    pub fn set_edges(&mut self, edges: &[Polyline]) {
+        let quads: Vec<EdgeVertex> = edges
+            .iter()
+            .flat_map(Polyline::segments)
+            .flat_map(EdgeVertex::quad)
+            .collect();
+        self.edges = (!quads.is_empty()).then(|| {
+            let buffer = self
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Model: Edge Buffer"),
+                    contents: bytemuck::cast_slice(&quads),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+            (buffer, quads.len() as u32)
+        });
+    }
+
+    /// Returns the [`EdgeVertex`] buffer of the edge quads, a triangle list, and its vertex count.
+    pub fn edges(&self) -> Option<(&wgpu::Buffer, u32)> {
+        self.edges.as_ref().map(|(buffer, count)| (buffer, *count))
+    }
+
+    /// Replaces the colors the model is drawn with in debug color modes, one per vertex. STEP
+    /// models are colored by the kind of surface each face lies on, see [`SurfaceType::color`].
+    pub fn set_debug_colors(&mut self, colors: &[[f32; 3]]) {
+        debug_assert_eq!(colors.len(), self.positions.len());
+        self.debug_colors = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Model: Debug Color Buffer"),
+                contents: bytemuck::cast_slice(colors),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        ));
+    }
+
+    /// Returns the per-vertex color buffer to bind to vertex buffer slot 2 in debug color modes,
+    /// `None` for models without one.
+    pub fn debug_colors(&self) -> Option<&wgpu::Buffer> {
+        self.debug_colors.as_ref()
+    }
+
+    /// Replaces the authored colors the model is drawn with instead of its texture, one per
+    /// vertex. STEP models get the colors of their styled faces, see
+    /// [`face_colors`](crate::step::style::face_colors), if any face is styled.
+    pub fn set_face_colors(&mut self, colors: &[[f32; 3]]) {
+        debug_assert_eq!(colors.len(), self.positions.len());
+        self.face_colors = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Model: Face Color Buffer"),
+                contents: bytemuck::cast_slice(colors),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        ));
+    }
+
+    /// Returns the per-vertex color buffer to bind to vertex buffer slot 2 outside of debug color
+    /// modes, `None` for models drawn with their texture.
+    pub fn face_colors(&self) -> Option<&wgpu::Buffer> {
+        self.face_colors.as_ref()
+    }
+
+    /// Draws both sides of every triangle instead of culling the ones facing away, each side lit
+    /// as if it faced the camera.
+    ///
+    /// Open shells, like sheet metal and surface models, otherwise show gaps where their back
+    /// side is seen.
+    pub fn set_double_sided(&mut self, double_sided: bool) {
+        self.double_sided = double_sided;
+    }
+
+    /// Whether back faces are drawn, see [`Model::set_double_sided`].
+    pub fn double_sided(&self) -> bool {
+        self.double_sided
+    }
+
+    /// Samples the texture for the base color, or shades the model with the
+    /// [`Material`](crate::material::Material) for `false`. Either gives way to the model's
+    /// colors in debug color modes, or to its authored colors if it has any.
+    pub fn set_textured(&mut self, textured: bool) {
+        self.textured = textured;
+    }
+
+    /// Whether the model is textured, see [`Model::set_textured`].
+    pub fn textured(&self) -> bool {
+        self.textured
+    }
+
+    pub fn transform(&self) -> cgmath::Matrix4<f32> {
+        self.trans
+    }
+
+    /// Replaces the model's transform and writes it to the GPU buffer.
+    pub fn set_transform(&mut self, m: cgmath::Matrix4<f32>) {
+        self.trans = m;
+        self.queue.write_buffer(
+            &self.transform_buffer,
+            0,
+            bytemuck::cast_slice(&[ModelUniform::new(m)]),
+        );
+    }
+}
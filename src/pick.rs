@@ -0,0 +1,62 @@
+// Dependencies
+use cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+
+/// Determinants below this count the ray as parallel to the triangle
+const PARALLEL_EPSILON: f32 = 1e-8;
+
+/// Half-line starting at `origin`, points along it are `origin + t * direction` for `t >= 0`
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    /// Returns the ray through normalized device coordinates `(x, y)`, from the near to the far
+    /// plane of `view_proj`. Returns `None` if `view_proj` can't be inverted.
+    pub fn unproject(view_proj: Matrix4<f32>, x: f32, y: f32) -> Option<Self> {
+        let inverse = view_proj.invert()?;
+        // wgpu clip space has its depth in 0..1
+        let near = inverse.transform_point(Point3::new(x, y, 0.0));
+        let far = inverse.transform_point(Point3::new(x, y, 1.0));
+        Some(Self {
+            origin: near,
+            direction: far - near,
+        })
+    }
+
+    /// Maps the ray through `m`, an affine transform. Ray parameters stay the same, so hits in
+    /// different spaces can be compared by `t`.
+    pub fn transform(&self, m: Matrix4<f32>) -> Self {
+        Self {
+            origin: m.transform_point(self.origin),
+            direction: m.transform_vector(self.direction),
+        }
+    }
+
+    /// Returns the ray parameter `t` where the ray hits the triangle `[a, b, c]` from either side.
+    ///
+    /// Uses the Möller–Trumbore algorithm.
+    pub fn intersect_triangle(&self, [a, b, c]: [Point3<f32>; 3]) -> Option<f32> {
+        let ab = b - a;
+        let ac = c - a;
+        let p = self.direction.cross(ac);
+        let det = ab.dot(p);
+        if det.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let ao = self.origin - a;
+        let u = ao.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = ao.cross(ab);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = ac.dot(q) * inv_det;
+        (t >= 0.0).then_some(t)
+    }
+}
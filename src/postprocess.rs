@@ -0,0 +1,224 @@
+// Dependencies
+use anyhow::{Result, bail};
+
+// Local modules
+use crate::state::ColorSpace;
+
+/// Format of the color target scenes are drawn into before they're tone mapped
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Curve compressing HDR colors into the `0..1` range of the surface, see [`PostProcess`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapping {
+    /// `c / (1 + c)`, keeps the hue but flattens highlights
+    Reinhard,
+    /// Fit of the ACES filmic curve, with more contrast and a softer roll-off
+    #[default]
+    Aces,
+}
+
+impl ToneMapping {
+    /// Value of the `TONE_MAPPING` override constant of `tonemap.wgsl`
+    fn constant(self) -> f64 {
+        match self {
+            Self::Reinhard => 0.0,
+            Self::Aces => 1.0,
+        }
+    }
+}
+
+impl std::str::FromStr for ToneMapping {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "reinhard" => Ok(Self::Reinhard),
+            "aces" => Ok(Self::Aces),
+            _ => bail!("Unknown tone mapping '{}', expected reinhard or aces", s),
+        }
+    }
+}
+
+/// Fullscreen pass tone mapping the HDR color target scenes are drawn into onto the surface
+pub struct PostProcess {
+    /// Drawn into by the scene, or resolved into with MSAA, sized like the surface
+    hdr_view: wgpu::TextureView,
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// Binds [`PostProcess::hdr_view`] to `@group(0) @binding(0)`
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    /// Format of the surface the pass writes to
+    surface_format: wgpu::TextureFormat,
+    tone_mapping: ToneMapping,
+}
+
+impl PostProcess {
+    /// Creates an HDR target of `width` by `height` pixels, tone mapped into a surface of
+    /// `surface_format` with `tone_mapping`.
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        tone_mapping: ToneMapping,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Tone Mapping Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let hdr_view = Self::create_target(device, width, height);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &hdr_view);
+        let pipeline =
+            Self::create_pipeline(device, &bind_group_layout, surface_format, tone_mapping);
+        Self {
+            hdr_view,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+            surface_format,
+            tone_mapping,
+        }
+    }
+
+    fn create_target(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("HDR Color Texture"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        hdr_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Tone Mapping Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(hdr_view),
+            }],
+        })
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        surface_format: wgpu::TextureFormat,
+        tone_mapping: ToneMapping,
+    ) -> wgpu::RenderPipeline {
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Tone Mapping Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../assets/shaders/tonemap.wgsl").into()),
+        });
+        let srgb_encode = ColorSpace::needs_srgb_encode(surface_format);
+        let constants = [
+            ("ENCODE_SRGB", srgb_encode as u8 as f64),
+            ("TONE_MAPPING", tone_mapping.constant()),
+        ];
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Tone Mapping Pipeline Layout"),
+            bind_group_layouts: &[bind_group_layout],
+            immediate_size: 0,
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Tone Mapping Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        })
+    }
+
+    /// Recreates the HDR target to match a resized surface.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.hdr_view = Self::create_target(device, width, height);
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.hdr_view);
+    }
+
+    /// Switches the curve, rebuilding the pipeline if it changed.
+    pub fn set_tone_mapping(&mut self, device: &wgpu::Device, tone_mapping: ToneMapping) {
+        if tone_mapping == self.tone_mapping {
+            return;
+        }
+        self.pipeline = Self::create_pipeline(
+            device,
+            &self.bind_group_layout,
+            self.surface_format,
+            tone_mapping,
+        );
+        self.tone_mapping = tone_mapping;
+    }
+
+    /// Returns the target scenes are drawn into in place of the surface.
+    pub fn hdr_view(&self) -> &wgpu::TextureView {
+        &self.hdr_view
+    }
+
+    /// Records a pass tone mapping the HDR target into `view`, a texture of the surface's format
+    /// and size.
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Tone Mapping Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // Every pixel is overwritten
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            multiview_mask: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
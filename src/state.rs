@@ -1,78 +1,577 @@
 // STD
-use std::{iter, sync::Arc};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+use std::{iter, sync::Arc, time::Duration};
 
 // Dependencies
+use anyhow::Context;
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
 use wgpu::util::DeviceExt;
-use winit::{event_loop::ActiveEventLoop, keyboard::KeyCode, window::Window};
+use winit::{
+    dpi::PhysicalPosition,
+    event::{MouseButton, MouseScrollDelta},
+    event_loop::ActiveEventLoop,
+    keyboard::KeyCode,
+    window::Window,
+};
 
 // Local modules
-use crate::camera::{Camera, CameraController, CameraUniform};
+use crate::camera::{Camera, CameraController, CameraUniform, MouseButtons, StandardView};
+use crate::clip::ClipUniform;
+use crate::edge::EdgeUniform;
+use crate::frame_timer::FrameTimer;
+use crate::frustum::{CullStats, merge_spheres};
+use crate::gizmo::Gizmo;
+use crate::import;
+use crate::light::LightUniform;
+use crate::material::Material;
+use crate::mesh::{Aabb, Mesh, MeshBuffers, VertexLayout};
+use crate::model::{EdgeVertex, InstanceRaw, Model};
+use crate::pick::Ray;
+use crate::postprocess::{HDR_FORMAT, PostProcess, ToneMapping};
+use crate::step::tessellate::{FaceId, Tessellation, TessellationOptions};
 use crate::texture;
 use crate::{INDICES, VERTICES, mesh::Vertex};
 
+/// Color frames are cleared to until [`State::set_background`] picks another
+pub const DEFAULT_BACKGROUND: wgpu::Color = wgpu::Color {
+    r: 0.1,
+    g: 0.2,
+    b: 0.3,
+    a: 1.0,
+};
+
+/// Default of [`State::camera_transition`]
+pub const DEFAULT_CAMERA_TRANSITION: Duration = Duration::from_millis(250);
+
+/// Depth bias of [`RenderOptions::depth_bias`] unless another is picked, in steps of the depth
+/// format's precision. Like `glPolygonOffset(1, 1)`, enough to settle most coplanar faces without
+/// faces visibly sinking behind their neighbours.
+pub const DEFAULT_DEPTH_BIAS: i32 = 1;
+/// Slope scale of [`RenderOptions::depth_bias_slope_scale`] unless another is picked
+pub const DEFAULT_DEPTH_BIAS_SLOPE_SCALE: f32 = 1.0;
+
+/// How long messages shown with [`State::show_toast`] stay in the window title
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+/// Distance in pixels the cursor may move between pressing and releasing a button for a click
+const CLICK_SLOP: f64 = 4.0;
+
+/// Fraction of the model's bounding box diagonal the section plane moves per key press
+const CLIP_NUDGE: f32 = 0.02;
+
+/// Longest time between two clicks that still counts as a double click
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+
+/// How models are colored in place of their texture, to tell apart what the importer produced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DebugColor {
+    /// Textured as usual, or in the authored colors of STEP faces, see [`Model::face_colors`]
+    #[default]
+    Off,
+    /// Faces colored by the kind of surface they were tessellated on, see
+    /// [`SurfaceType::color`](crate::step::tessellate::SurfaceType::color). Models without
+    /// BREP faces stay textured.
+    SurfaceType,
+}
+
+/// How colors written by the fragment shaders end up on the surface, see
+/// [`RenderOptions::color_space`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// 8-bit sRGB surface, the shader's linear output is encoded by the hardware
+    #[default]
+    Srgb,
+    /// 8-bit surface storing values as they are, the shader encodes sRGB itself
+    Linear,
+    /// [`wgpu::TextureFormat::Rgba16Float`] surface holding linear values beyond `1.0`
+    Hdr,
+}
+
+impl ColorSpace {
+    /// Returns whether `format` stores values as they are while being too narrow for linear
+    /// colors, so the shader has to encode sRGB.
+    pub fn needs_srgb_encode(format: wgpu::TextureFormat) -> bool {
+        !format.is_srgb() && format != wgpu::TextureFormat::Rgba16Float
+    }
+
+    /// Returns whether `format` belongs to the color space.
+    fn matches(self, format: wgpu::TextureFormat) -> bool {
+        match self {
+            Self::Srgb => format.is_srgb(),
+            Self::Linear => matches!(
+                format,
+                wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Rgba8Unorm
+            ),
+            Self::Hdr => format == wgpu::TextureFormat::Rgba16Float,
+        }
+    }
+}
+
+impl std::str::FromStr for ColorSpace {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "srgb" => Ok(Self::Srgb),
+            "linear" => Ok(Self::Linear),
+            "hdr" => Ok(Self::Hdr),
+            _ => anyhow::bail!("Unknown color space '{}', expected srgb, linear or hdr", s),
+        }
+    }
+}
+
+/// How [`State`] draws its models, see [`State::with_options`] and [`State::set_render_options`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    /// Number of samples per pixel for MSAA. Falls back to `1` if the adapter doesn't support it.
+    pub sample_count: u32,
+    /// `Line` draws models as a wireframe. Falls back to `Fill` if the adapter doesn't support
+    /// [`wgpu::Features::POLYGON_MODE_LINE`].
+    pub polygon_mode: wgpu::PolygonMode,
+    /// Faces culled on models that aren't [`Model::double_sided`], `Front` shows the faces wound
+    /// the wrong way
+    pub cull_mode: Option<wgpu::Face>,
+    /// Constant depth offset of every model fragment, in steps of the depth format's precision.
+    /// Raise it along with [`RenderOptions::depth_bias_slope_scale`] when coincident faces of
+    /// mating parts still flicker, `0` turns the bias off.
+    pub depth_bias: i32,
+    /// Depth offset scaled by how steeply a face recedes from the camera, the part of the bias
+    /// that settles faces seen at grazing angles.
+    pub depth_bias_slope_scale: f32,
+    /// [`wgpu::PresentMode::Fifo`] waits for vsync, `Mailbox` and `Immediate` don't. Falls back
+    /// to `Fifo` if the surface supports neither the requested mode nor the other low latency one.
+    /// Read once when the [`State`] is created.
+    pub present_mode: wgpu::PresentMode,
+    /// Picks the surface format. Falls back to [`ColorSpace::Srgb`] if the surface has no format
+    /// of the requested one. Headless rendering always uses 8-bit sRGB. Read once when the
+    /// [`State`] is created.
+    pub color_space: ColorSpace,
+    /// With [`ColorSpace::Hdr`], draws into an [`HDR_FORMAT`] target and tone maps it into an
+    /// 8-bit sRGB surface instead of presenting it as it is. A no-op on 8-bit surfaces, which
+    /// have nothing to map. Read once when the [`State`] is created.
+    pub post_processing: bool,
+    /// Curve of the post-processing pass, see [`RenderOptions::post_processing`]
+    pub tone_mapping: ToneMapping,
+    /// Surface models are shaded with where they have neither a texture nor colors of their own,
+    /// see [`Model::set_textured`]
+    pub material: Material,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            sample_count: 1,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            cull_mode: Some(wgpu::Face::Back),
+            depth_bias: DEFAULT_DEPTH_BIAS,
+            depth_bias_slope_scale: DEFAULT_DEPTH_BIAS_SLOPE_SCALE,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            color_space: ColorSpace::default(),
+            post_processing: false,
+            tone_mapping: ToneMapping::default(),
+            material: Material::default(),
+        }
+    }
+}
+
+/// A pipeline drawing model triangles, in a variant culling back faces and one drawing both
+/// sides for [`Model::double_sided`] models
+struct ModelPipeline {
+    culled: wgpu::RenderPipeline,
+    double_sided: wgpu::RenderPipeline,
+}
+
+impl ModelPipeline {
+    /// Returns the variant drawing `model`.
+    fn get(&self, model: &Model) -> &wgpu::RenderPipeline {
+        if model.double_sided() {
+            &self.double_sided
+        } else {
+            &self.culled
+        }
+    }
+}
+
+/// Every pipeline drawing models, rebuilt together when the [`RenderOptions`] change
+struct Pipelines {
+    /// Textured models
+    render: ModelPipeline,
+    /// Models shaded with the [`Material`]
+    material: ModelPipeline,
+    /// Draws selected faces again over their own depth
+    highlight: ModelPipeline,
+    /// Draws models with a color per vertex instead of their texture, see
+    /// [`Model::debug_colors`] and [`Model::face_colors`]
+    vertex_color: ModelPipeline,
+    /// Draws the BREP edges of models as lines over their faces
+    edge: wgpu::RenderPipeline,
+}
+
+impl Pipelines {
+    /// Creates the pipelines of `shader`, `basic.wgsl`, drawing into targets of `format`.
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        options: &RenderOptions,
+    ) -> Self {
+        let srgb_encode = ColorSpace::needs_srgb_encode(format);
+        let constants = [("ENCODE_SRGB", srgb_encode as u8 as f64)];
+        let multisample = wgpu::MultisampleState {
+            count: options.sample_count,      // 2.
+            mask: !0,                         // 3.
+            alpha_to_coverage_enabled: false, // 4.
+        };
+        // Render Pipeline refers to the incremental stages of graphics processing or compute
+        let create_pipeline = |label: &str,
+                               vs_entry_point: &str,
+                               buffers: &[wgpu::VertexBufferLayout],
+                               fs_entry_point: &str,
+                               depth_compare,
+                               cull_mode| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some(vs_entry_point),
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    buffers,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    // 3.
+                    module: shader,
+                    entry_point: Some(fs_entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        // 4.
+                        format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions {
+                        constants: &constants,
+                        ..Default::default()
+                    },
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList, // 1.
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw, // 2.
+                    cull_mode,
+                    // Setting this to anything other than Fill requires
+                    // Features::NON_FILL_POLYGON_MODE
+                    polygon_mode: options.polygon_mode,
+                    // Requires Features::DEPTH_CLIP_CONTROL
+                    unclipped_depth: false,
+                    // Requires Features::CONSERVATIVE_RASTERIZATION
+                    conservative: false,
+                },
+                // The highlight shares the bias, so it still matches the depth of its faces
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState {
+                        constant: options.depth_bias,
+                        slope_scale: options.depth_bias_slope_scale,
+                        clamp: 0.0,
+                    },
+                }),
+                multisample,
+                multiview_mask: None, // 5.
+                cache: None,          // 6.
+            })
+        };
+        // Double sided models are drawn without culling, the fragment shaders light back faces
+        // from behind
+        let create_model_pipeline =
+            |label: &str,
+             vs_entry_point: &str,
+             buffers: &[wgpu::VertexBufferLayout],
+             fs_entry_point: &str,
+             depth_compare| ModelPipeline {
+                culled: create_pipeline(
+                    label,
+                    vs_entry_point,
+                    buffers,
+                    fs_entry_point,
+                    depth_compare,
+                    options.cull_mode,
+                ),
+                double_sided: create_pipeline(
+                    &format!("{} (Double Sided)", label),
+                    vs_entry_point,
+                    buffers,
+                    fs_entry_point,
+                    depth_compare,
+                    None,
+                ),
+            };
+        let model_buffers = [Vertex::desc(), InstanceRaw::desc()];
+        let render = create_model_pipeline(
+            "Render Pipeline",
+            "vs_main",
+            &model_buffers,
+            "fs_main",
+            wgpu::CompareFunction::Less,
+        );
+        let material = create_model_pipeline(
+            "Material Pipeline",
+            "vs_main",
+            &model_buffers,
+            "fs_material",
+            wgpu::CompareFunction::Less,
+        );
+        let highlight = create_model_pipeline(
+            "Highlight Pipeline",
+            "vs_main",
+            &model_buffers,
+            "fs_highlight",
+            wgpu::CompareFunction::LessEqual,
+        );
+        let vertex_color = create_model_pipeline(
+            "Vertex Color Pipeline",
+            "vs_vertex_color",
+            &[Vertex::desc(), InstanceRaw::desc(), Model::color_desc()],
+            "fs_vertex_color",
+            wgpu::CompareFunction::Less,
+        );
+
+        // Every edge segment is a quad widened on screen by `vs_edge`, see `Model::edges`
+        let edge = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Edge Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_edge"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[EdgeVertex::desc(), InstanceRaw::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_edge"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    // Antialiased, the rim of the quads fades out
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
+            }),
+            // Quads face the camera, whichever way the segment runs
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // The depth bias is applied in `vs_edge`. Edges don't write depth, so the faded rim of
+            // one can't hide the edges it overlaps.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample,
+            multiview_mask: None,
+            cache: None,
+        });
+        Self {
+            render,
+            material,
+            highlight,
+            vertex_color,
+            edge,
+        }
+    }
+}
+
+/// The viewer: models, camera, picking and everything the GPU needs to draw them, into a window or
+/// offscreen
 pub struct State {
-    pub window: Arc<Window>,
-    pub vertex_buffer: wgpu::Buffer,
-    pub vertex_count: u32,
-    pub index_buffer: wgpu::Buffer,
-    pub index_count: u32,
+    /// `None` in headless mode, see [`State::new_headless`]
+    pub window: Option<Arc<Window>>,
+    /// Drawn in order, each with its own transform, unless it's out of view
+    pub models: Vec<Model>,
+    /// Models the last rendered frame drew and left out as being outside of the camera's frustum
+    pub cull_stats: CullStats,
     pub camera: Camera,
     pub camera_controller: CameraController,
     pub camera_uniform: CameraUniform,
     pub camera_buffer: wgpu::Buffer,
     pub camera_bind_group: wgpu::BindGroup,
+    pub clip_uniform: ClipUniform,
+    pub clip_buffer: wgpu::Buffer,
+    /// Section plane set by [`State::set_clip_plane`]
+    clip_plane: Option<[f32; 4]>,
+    pub edge_uniform: EdgeUniform,
+    pub edge_buffer: wgpu::Buffer,
+    pub light_uniform: LightUniform,
+    pub light_buffer: wgpu::Buffer,
+    /// Bound to `@group(3)` along with [`State::material_buffer`]
+    pub light_bind_group: wgpu::BindGroup,
+    /// Uniform of [`RenderOptions::material`], see [`State::set_material`]
+    material_buffer: wgpu::Buffer,
+    /// Axes and ground grid, toggled with G
+    pub gizmo: Gizmo,
+    pub mouse_buttons: MouseButtons,
+    /// Last known cursor position, `None` until the cursor entered the window
+    pub cursor_position: Option<PhysicalPosition<f64>>,
+    /// Where the left mouse button went down, cleared once the cursor moved too far for a click
+    click_start: Option<PhysicalPosition<f64>>,
+    /// When and where the last click happened, to detect double clicks
+    last_click: Option<(Instant, PhysicalPosition<f64>)>,
+    /// BREP face highlighted on every model it belongs to
+    selection: Option<FaceId>,
+    pub frame_timer: FrameTimer,
+    /// Show the frame rate in the window title, toggled with F3
+    pub show_fps_in_title: bool,
+    /// How long preset views and fitting take to ease in, zero jumps right away
+    pub camera_transition: Duration,
+    /// Window title without the frame rate
+    title: String,
+    /// When the message [`State::show_toast`] put in the title comes down again
+    toast_until: Option<Instant>,
+    /// Color frames are cleared to
+    background: wgpu::Color,
     pub diffuse_texture: texture::Texture,
     pub diffuse_bind_group: wgpu::BindGroup,
+    /// Depth attachment, sized and sampled like the color target
+    pub depth_texture: texture::Texture,
+    /// Multisampled color attachment resolved into the surface, or into the HDR target with
+    /// post-processing, `None` without MSAA
+    msaa_view: Option<wgpu::TextureView>,
+    /// Sample counts supported by both the scene's color format and the depth format
+    msaa_flags: wgpu::TextureFormatFeatureFlags,
+    /// Tone mapping pass the scene goes through on its way to the surface, see
+    /// [`RenderOptions::post_processing`]
+    post_process: Option<PostProcess>,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    surface: wgpu::Surface<'static>,
+    /// `None` in headless mode
+    surface: Option<wgpu::Surface<'static>>,
+    /// Configuration of [`State::surface`], or of the offscreen targets in headless mode
     config: wgpu::SurfaceConfiguration,
+    /// Layout of [`State::camera_bind_group`], to rebuild the [`Gizmo`] with
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    /// Layout of [`Model::transform_bind_group`]
+    model_bind_group_layout: wgpu::BindGroupLayout,
     is_surface_configured: bool,
-    render_pipeline: wgpu::RenderPipeline,
+    /// Buffers the meshes of models are sub-allocated from, see [`State::alloc_mesh`]
+    meshes: MeshBuffers,
+    /// Set by [`State::set_render_options`]
+    options: RenderOptions,
+    /// `basic.wgsl` and the layout of its bind groups, to rebuild [`State::pipelines`] from
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: Pipelines,
+    /// Draw BREP edges over the shaded faces, toggled with E
+    pub show_edges: bool,
+    /// Set by [`State::set_debug_color_mode`], toggled with T
+    debug_color: DebugColor,
+    /// GPU the device was opened on
+    adapter_info: wgpu::AdapterInfo,
 }
 
 impl State {
+    /// Creates the viewer drawing into `window` with the default [`RenderOptions`].
     pub async fn new(window: Arc<Window>) -> anyhow::Result<State> {
+        Self::with_options(window, RenderOptions::default()).await
+    }
+
+    /// Creates the viewer drawing into `window` as `options` say.
+    pub async fn with_options(
+        window: Arc<Window>,
+        options: RenderOptions,
+    ) -> anyhow::Result<State> {
+        let instance = Self::create_instance();
+        // Surface is the part of the window to be drawn to
+        let surface = instance
+            .create_surface(window.clone())
+            .context("Couldn't create a surface for the window")?;
         let size = window.inner_size();
+        Self::with_target(instance, Some((window, surface)), size, options).await
+    }
 
-        // API & Device Setup: {{{
+    /// Creates the viewer without a window, for rendering with [`State::render_to_texture`].
+    pub async fn new_headless(
+        width: u32,
+        height: u32,
+        options: RenderOptions,
+    ) -> anyhow::Result<State> {
+        let size = winit::dpi::PhysicalSize::new(width.max(1), height.max(1));
+        Self::with_target(Self::create_instance(), None, size, options).await
+    }
 
-        // Instance is the instance WGPU, which needs a backend to invoke cmd's on the GPU
-        // BackendBit::PRIMARY => Vulkan + Metal + DX12 + Browser WebGPU
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            #[cfg(not(target_arch = "wasm32"))]
-            backends: wgpu::Backends::VULKAN,
-            #[cfg(target_arch = "wasm32")]
-            backends: wgpu::Backends::BROWSER_WEBGPU,
+    // Instance is the instance WGPU, which needs a backend to invoke cmd's on the GPU
+    // BackendBit::PRIMARY => Vulkan + Metal + DX12 + Browser WebGPU
+    // The WGPU_BACKEND environment variable (e.g. "vulkan,dx12") overrides the choice
+    fn create_instance() -> wgpu::Instance {
+        wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: Self::backends(),
             ..Default::default()
-        });
+        })
+    }
 
-        // Surface is the part of the window to be drawn to
-        let surface = match instance.create_surface(window.clone()) {
-            Ok(val) => val,
-            Err(e) => {
-                panic!("Binding `surface` returned error: {:?}", e);
-            }
-        };
+    fn backends() -> wgpu::Backends {
+        #[cfg(not(target_arch = "wasm32"))]
+        return wgpu::Backends::from_env().unwrap_or(wgpu::Backends::PRIMARY);
+        #[cfg(target_arch = "wasm32")]
+        wgpu::Backends::BROWSER_WEBGPU
+    }
 
+    /// Shared by [`State::with_options`] and [`State::new_headless`], `target` is `None` in
+    /// headless mode.
+    async fn with_target(
+        instance: wgpu::Instance,
+        target: Option<(Arc<Window>, wgpu::Surface<'static>)>,
+        size: winit::dpi::PhysicalSize<u32>,
+        mut options: RenderOptions,
+    ) -> anyhow::Result<State> {
+        // API & Device Setup: {{{
         // Adapter to filter device based on capabilities
+        let power_preference = wgpu::PowerPreference::default();
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
+                power_preference,
+                compatible_surface: target.as_ref().map(|(_, surface)| surface),
                 force_fallback_adapter: false,
             })
             .await
-            .unwrap();
+            .with_context(|| {
+                format!(
+                    "No compatible GPU found (backends: {:?}, power preference: {:?})",
+                    Self::backends(),
+                    power_preference
+                )
+            })?;
+        let adapter_info = adapter.get_info();
+        log::info!(
+            "Using {} ({:?}, {:?}), driver {} {}",
+            adapter_info.name,
+            adapter_info.backend,
+            adapter_info.device_type,
+            adapter_info.driver,
+            adapter_info.driver_info
+        );
 
         // Device is a logical abstraction over the physical GPU
         // Queue refers to the work queue which is what runs command buffers
         let (device, queue) = adapter
             .request_device(&wgpu::DeviceDescriptor {
                 label: None,
-                required_features: wgpu::Features::empty(),
+                // Optional, only needed for wireframe rendering
+                required_features: adapter.features() & wgpu::Features::POLYGON_MODE_LINE,
                 experimental_features: wgpu::ExperimentalFeatures::disabled(),
                 // WebGL doesn't support all of wgpu's features, so if
                 // we're building for the web we'll have to disable some.
@@ -85,40 +584,81 @@ impl State {
                 trace: wgpu::Trace::Off,
             })
             .await
-            .unwrap();
+            .with_context(|| format!("Couldn't open a device on '{}'", adapter_info.name))?;
         //}}}
 
         // Surface Creation: {{{
-        // surface capabilities
-        let surface_caps = surface.get_capabilities(&adapter);
-
-        // Shader code in this tutorial assumes an Srgb surface texture. Using a different
-        // one will result all the colors comming out darker. If you want to support non
-        // Srgb surfaces, you'll need to account for that when drawing to the frame.
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .copied()
-            .find(|f| f.is_srgb())
-            .unwrap_or(surface_caps.formats[0]);
-
-        // Surface configuration
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.width,
-            height: size.height,
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
-            desired_maximum_frame_latency: 2,
-            view_formats: vec![],
+        // HDR scenes are tone mapped into an 8-bit surface
+        let post_processing =
+            target.is_some() && options.post_processing && options.color_space == ColorSpace::Hdr;
+        // Without a surface the config only describes the offscreen targets
+        let config = match &target {
+            Some((_, surface)) => {
+                let surface_caps = surface.get_capabilities(&adapter);
+                let color_space = if post_processing {
+                    ColorSpace::Srgb
+                } else {
+                    options.color_space
+                };
+                wgpu::SurfaceConfiguration {
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    format: Self::supported_format(&surface_caps.formats, color_space),
+                    width: size.width,
+                    height: size.height,
+                    present_mode: Self::supported_present_mode(
+                        &surface_caps.present_modes,
+                        options.present_mode,
+                    ),
+                    alpha_mode: surface_caps.alpha_modes[0],
+                    desired_maximum_frame_latency: 2,
+                    view_formats: vec![],
+                }
+            }
+            None => wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                width: size.width,
+                height: size.height,
+                present_mode: wgpu::PresentMode::Fifo,
+                alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+                desired_maximum_frame_latency: 2,
+                view_formats: vec![],
+            },
+        };
+        let post_process = post_processing.then(|| {
+            PostProcess::new(
+                &device,
+                config.format,
+                config.width,
+                config.height,
+                options.tone_mapping,
+            )
+        });
+        let scene_format = match post_process {
+            Some(_) => HDR_FORMAT,
+            None => config.format,
         };
+        let msaa_flags = adapter.get_texture_format_features(scene_format).flags
+            & adapter
+                .get_texture_format_features(texture::Texture::DEPTH_FORMAT)
+                .flags;
+        options.sample_count = Self::supported_sample_count(msaa_flags, options.sample_count);
+        options.polygon_mode = Self::supported_polygon_mode(&device, options.polygon_mode);
         //}}}
 
         // Texture Creation: {{{
+        let depth_texture = texture::Texture::create_depth_texture(
+            &device,
+            &config,
+            options.sample_count,
+            "depth_texture",
+        );
+        let msaa_view =
+            Self::create_msaa_view(&device, &config, scene_format, options.sample_count);
+
         let diffuse_bytes = include_bytes!("../assets/textures/happy-tree.png");
         let diffuse_texture =
-            texture::Texture::from_bytes(&device, &queue, diffuse_bytes, "happy-tree.png").unwrap();
+            texture::Texture::from_bytes(&device, &queue, diffuse_bytes, "happy-tree.png")?;
 
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -161,26 +701,26 @@ impl State {
         });
         //}}}
 
-        // Vertex & Index Buffer Initialization: {{{
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(VERTICES),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-
-        let vertex_count = VERTICES.len() as u32;
-
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(INDICES),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-
-        let index_count = INDICES.len() as u32;
+        // Model Creation: {{{
+        let model_bind_group_layout = Model::bind_group_layout(&device);
+        let mut meshes = MeshBuffers::new(&device);
+        let mesh = meshes.alloc(&device, &queue, VERTICES, Some(INDICES))?;
+        let model = Model::new(
+            &device,
+            &queue,
+            &model_bind_group_layout,
+            mesh,
+            VERTICES,
+            INDICES,
+        );
         //}}}
 
         // Camera Creation: {{{
-        let camera = { Camera::new(config.width as f32 / config.height as f32) };
+        let mut camera = { Camera::new(config.width as f32 / config.height as f32) };
+        // Orbit around the model's bounding-box center
+        if let Some(bounds) = model.bounds() {
+            camera.center_on(bounds.min.into(), bounds.max.into());
+        }
 
         let mut camera_uniform = CameraUniform::new();
         camera_uniform.update_vp(&camera);
@@ -194,28 +734,135 @@ impl State {
         let camera_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("camera_bind_group_layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                entries: &[
+                    // The fragment shader reads the eye position for highlights
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Section plane, cut away in the fragment shader
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Edge width and viewport, edges are widened in the vertex shader and
+                    // faded out at their rim in the fragment shader
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                ],
             });
 
+        let clip_uniform = ClipUniform::new();
+        let clip_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Clip Buffer"),
+            contents: bytemuck::cast_slice(&[clip_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let edge_uniform = EdgeUniform::new(config.width, config.height);
+        let edge_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Edge Buffer"),
+            contents: bytemuck::cast_slice(&[edge_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
         let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &camera_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: clip_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: edge_buffer.as_entire_binding(),
+                },
+            ],
             label: Some("camera_bind_group"),
         });
 
-        let camera_controller = CameraController::new(0.2);
+        let camera_controller = CameraController::new(0.2, 0.1);
+        //}}}
+
+        // Gizmo Creation: {{{
+        let gizmo = Gizmo::new(
+            &device,
+            scene_format,
+            options.sample_count,
+            &camera_bind_group_layout,
+        );
+        //}}}
+
+        // Light Creation: {{{
+        let light_uniform = LightUniform::new();
+
+        let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Light Buffer"),
+            contents: bytemuck::cast_slice(&[light_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let material_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Buffer"),
+            contents: bytemuck::cast_slice(&[options.material.uniform()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        // The material of untextured models is lit along with the light
+        let light_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("light_bind_group_layout"),
+                entries: &[uniform_entry(0), uniform_entry(1)],
+            });
+
+        let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &light_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: material_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("light_bind_group"),
+        });
         //}}}
 
         // Render Pipeline Creation: {{{
@@ -227,55 +874,26 @@ impl State {
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout],
+                bind_group_layouts: &[
+                    &texture_bind_group_layout,
+                    &camera_bind_group_layout,
+                    &model_bind_group_layout,
+                    &light_bind_group_layout,
+                ],
                 immediate_size: 0,
             });
 
-        // Render Pipeline refers to the incremental stages of graphics processing or compute
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                buffers: &[Vertex::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                // 3.
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    // 4.
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList, // 1.
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw, // 2.
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: None, // 1.
-            multisample: wgpu::MultisampleState {
-                count: 1,                         // 2.
-                mask: !0,                         // 3.
-                alpha_to_coverage_enabled: false, // 4.
-            },
-            multiview_mask: None, // 5.
-            cache: None,          // 6.
-        });
+        let pipelines = Pipelines::new(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            scene_format,
+            &options,
+        );
         //}}}
 
+        let (window, surface) = target.unzip();
+        let title = window.as_ref().map(|w| w.title()).unwrap_or_default();
         Ok(Self {
             window,
             surface,
@@ -284,53 +902,820 @@ impl State {
             config,
             diffuse_bind_group,
             diffuse_texture,
-            vertex_buffer,
-            vertex_count,
-            index_buffer,
-            index_count,
+            depth_texture,
+            msaa_view,
+            msaa_flags,
+            post_process,
+            models: vec![model],
+            camera_bind_group_layout,
+            model_bind_group_layout,
             is_surface_configured: false,
             camera,
             camera_controller,
             camera_uniform,
             camera_buffer,
             camera_bind_group,
-            render_pipeline,
+            clip_uniform,
+            clip_buffer,
+            clip_plane: None,
+            edge_uniform,
+            edge_buffer,
+            light_uniform,
+            light_buffer,
+            light_bind_group,
+            material_buffer,
+            gizmo,
+            mouse_buttons: MouseButtons::default(),
+            cursor_position: None,
+            click_start: None,
+            last_click: None,
+            selection: None,
+            frame_timer: FrameTimer::new(),
+            show_fps_in_title: false,
+            toast_until: None,
+            cull_stats: CullStats::default(),
+            camera_transition: DEFAULT_CAMERA_TRANSITION,
+            title,
+            background: DEFAULT_BACKGROUND,
+            meshes,
+            options,
+            shader,
+            pipeline_layout: render_pipeline_layout,
+            pipelines,
+            show_edges: true,
+            debug_color: DebugColor::Off,
+            adapter_info,
         })
     }
 
+    /// Returns `requested` if `flags` support that many samples, `1` otherwise.
+    fn supported_sample_count(flags: wgpu::TextureFormatFeatureFlags, requested: u32) -> u32 {
+        if requested <= 1 || flags.sample_count_supported(requested) {
+            return requested.max(1);
+        }
+        log::warn!(
+            "{}x MSAA isn't supported by the adapter, falling back to 1x",
+            requested
+        );
+        1
+    }
+
+    /// Returns the first of `supported` in `color_space`, falling back to an sRGB format and then
+    /// to the surface's preferred one.
+    fn supported_format(
+        supported: &[wgpu::TextureFormat],
+        color_space: ColorSpace,
+    ) -> wgpu::TextureFormat {
+        if let Some(&format) = supported.iter().find(|&&f| color_space.matches(f)) {
+            return format;
+        }
+        log::warn!(
+            "The surface has no {:?} format, falling back to sRGB",
+            color_space
+        );
+        supported
+            .iter()
+            .copied()
+            .find(|f| f.is_srgb())
+            .unwrap_or(supported[0])
+    }
+
+    /// Returns `requested` if it's in `supported`, the other one of `Mailbox` and `Immediate` for
+    /// a low latency mode and [`wgpu::PresentMode::Fifo`] otherwise.
+    fn supported_present_mode(
+        supported: &[wgpu::PresentMode],
+        requested: wgpu::PresentMode,
+    ) -> wgpu::PresentMode {
+        use wgpu::PresentMode::*;
+        let candidates: &[wgpu::PresentMode] = match requested {
+            // Resolved by wgpu itself, always valid
+            AutoVsync | AutoNoVsync => return requested,
+            Mailbox => &[Mailbox, Immediate],
+            Immediate => &[Immediate, Mailbox],
+            Fifo | FifoRelaxed => &[requested],
+        };
+        if let Some(&mode) = candidates.iter().find(|mode| supported.contains(mode)) {
+            if mode != requested {
+                log::warn!(
+                    "{:?} presentation isn't supported, using {:?}",
+                    requested,
+                    mode
+                );
+            }
+            return mode;
+        }
+        log::warn!(
+            "{:?} presentation isn't supported by the surface, falling back to Fifo",
+            requested
+        );
+        Fifo
+    }
+
+    /// Returns `requested` if `device` can rasterize it, [`wgpu::PolygonMode::Fill`] otherwise.
+    fn supported_polygon_mode(
+        device: &wgpu::Device,
+        requested: wgpu::PolygonMode,
+    ) -> wgpu::PolygonMode {
+        let feature = match requested {
+            wgpu::PolygonMode::Fill => return requested,
+            wgpu::PolygonMode::Line => wgpu::Features::POLYGON_MODE_LINE,
+            wgpu::PolygonMode::Point => wgpu::Features::POLYGON_MODE_POINT,
+        };
+        if device.features().contains(feature) {
+            return requested;
+        }
+        log::warn!(
+            "{:?} polygon mode isn't supported by the adapter, keeping fill mode",
+            requested
+        );
+        wgpu::PolygonMode::Fill
+    }
+
+    /// Creates the multisampled color target of `format` that's resolved into the surface or the
+    /// HDR target, or `None` if `sample_count` is `1`.
+    fn create_msaa_view(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Option<wgpu::TextureView> {
+        if sample_count <= 1 {
+            return None;
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("MSAA Color Texture"),
+            size: wgpu::Extent3d {
+                width: config.width.max(1),
+                height: config.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+
+    /// Format the models are drawn into, [`HDR_FORMAT`] with post-processing and the surface's
+    /// own otherwise.
+    fn scene_format(&self) -> wgpu::TextureFormat {
+        match self.post_process {
+            Some(_) => HDR_FORMAT,
+            None => self.config.format,
+        }
+    }
+
+    /// Recreates the depth, MSAA and HDR targets to match the surface size and sample count.
+    fn recreate_targets(&mut self) {
+        let sample_count = self.options.sample_count;
+        self.depth_texture = texture::Texture::create_depth_texture(
+            &self.device,
+            &self.config,
+            sample_count,
+            "depth_texture",
+        );
+        self.msaa_view = Self::create_msaa_view(
+            &self.device,
+            &self.config,
+            self.scene_format(),
+            sample_count,
+        );
+        if let Some(post_process) = &mut self.post_process {
+            post_process.resize(&self.device, self.config.width, self.config.height);
+        }
+    }
+
+    /// Returns the name, backend and driver of the GPU rendering the window.
+    pub fn adapter_info(&self) -> wgpu::AdapterInfo {
+        self.adapter_info.clone()
+    }
+
+    /// Sets the window title, a no-op in headless mode.
+    fn set_window_title(&self, title: &str) {
+        if let Some(window) = &self.window {
+            window.set_title(title);
+        }
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         if width > 0 && height > 0 {
             self.config.width = width;
             self.config.height = height;
-            self.surface.configure(&self.device, &self.config);
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
+            self.recreate_targets();
+            self.edge_uniform.update_viewport(width, height);
+            self.write_edge_uniform();
             self.is_surface_configured = true;
+        } else {
+            // Minimized, there's nothing to render into
+            self.is_surface_configured = false;
         }
     }
 
     pub fn handle_key(&mut self, event_loop: &ActiveEventLoop, code: KeyCode, is_pressed: bool) {
-        if let (KeyCode::KeyQ, true) = (code, is_pressed) {
-            event_loop.exit()
-        } else  {
-            self.camera_controller.handle_key(code, is_pressed);
+        match (code, is_pressed) {
+            (KeyCode::KeyQ, true) => event_loop.exit(),
+            (KeyCode::KeyP, true) => self.camera.toggle_projection(),
+            (KeyCode::KeyF, true) => self.fit_to_models(),
+            (KeyCode::KeyG, true) => self.set_gizmo_visible(!self.gizmo.visible),
+            (KeyCode::KeyE, true) => self.show_edges = !self.show_edges,
+            (KeyCode::KeyB, true) => {
+                // Follow the first model, so mixed flags end up the same
+                let double_sided = !self.models.first().is_some_and(Model::double_sided);
+                for model in &mut self.models {
+                    model.set_double_sided(double_sided);
+                }
+            }
+            (KeyCode::KeyT, true) => self.set_debug_color_mode(match self.debug_color {
+                DebugColor::Off => DebugColor::SurfaceType,
+                DebugColor::SurfaceType => DebugColor::Off,
+            }),
+            (KeyCode::Digit1 | KeyCode::Numpad1, true) => self.snap_view(StandardView::Front),
+            (KeyCode::Digit3 | KeyCode::Numpad3, true) => self.snap_view(StandardView::Right),
+            (KeyCode::Digit7 | KeyCode::Numpad7, true) => self.snap_view(StandardView::Top),
+            (KeyCode::Digit0 | KeyCode::Numpad0, true) => self.snap_view(StandardView::Isometric),
+            (KeyCode::KeyX, true) => {
+                if self.clip_plane.is_some() {
+                    self.set_clip_plane(None);
+                } else if let Some(bounds) = self.scene_bounds() {
+                    // Through the models' center, keeping the back half
+                    let center_z = (bounds.min[2] + bounds.max[2]) / 2.0;
+                    self.set_clip_plane(Some([0.0, 0.0, -1.0, center_z]));
+                }
+            }
+            (KeyCode::Comma | KeyCode::Period, true) => {
+                let Some(bounds) = self.scene_bounds() else {
+                    return;
+                };
+                let (min, max) = (bounds.min, bounds.max);
+                let diagonal = (0..3)
+                    .map(|i| (max[i] - min[i]).powi(2))
+                    .sum::<f32>()
+                    .sqrt();
+                let step = diagonal * CLIP_NUDGE;
+                self.nudge_clip_plane(if code == KeyCode::Period { step } else { -step });
+            }
+            (KeyCode::KeyM, true) => {
+                let preset = self.options.material.preset().unwrap_or_default().next();
+                log::info!("Material: {:?}", preset);
+                self.set_material(preset.material());
+            }
+            (KeyCode::KeyW, true) => {
+                // Toggle wireframe rendering, the up arrow still moves the camera forward
+                let polygon_mode = match self.options.polygon_mode {
+                    wgpu::PolygonMode::Fill => wgpu::PolygonMode::Line,
+                    _ => wgpu::PolygonMode::Fill,
+                };
+                self.update_render_options(|options| options.polygon_mode = polygon_mode);
+            }
+            (KeyCode::KeyC, true) => {
+                // Cycle face culling, to spot faces wound the wrong way
+                let cull_mode = match self.options.cull_mode {
+                    None => Some(wgpu::Face::Back),
+                    Some(wgpu::Face::Back) => Some(wgpu::Face::Front),
+                    Some(wgpu::Face::Front) => None,
+                };
+                log::info!("Culling: {:?}", cull_mode);
+                self.update_render_options(|options| options.cull_mode = cull_mode);
+            }
+            (KeyCode::BracketLeft | KeyCode::BracketRight, true) => {
+                // Nudge the depth bias until coplanar faces stop fighting
+                let step = if code == KeyCode::BracketRight { 1 } else { -1 };
+                let depth_bias = self.options.depth_bias + step;
+                log::info!("Depth bias: {}", depth_bias);
+                self.update_render_options(|options| options.depth_bias = depth_bias);
+            }
+            (KeyCode::F3, true) => {
+                self.show_fps_in_title = !self.show_fps_in_title;
+                if !self.show_fps_in_title {
+                    self.set_window_title(&self.title);
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            (KeyCode::F12, true) => {
+                let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+                let path = format!("screenshot-{}.png", timestamp);
+                match self.capture_frame(path.as_ref()) {
+                    Ok(()) => log::info!("Saved '{}'", path),
+                    Err(e) => log::error!("{:#}", e),
+                }
+            }
+            _ => {
+                self.camera_controller.handle_key(code, is_pressed);
+            }
+        }
+    }
+
+    /// Eases the camera into showing every model, fitting the sphere around them into view.
+    pub fn fit_to_models(&mut self) {
+        let spheres = self.models.iter().map(Model::bounding_sphere);
+        let Some((center, radius)) = spheres.reduce(merge_spheres) else {
+            return;
+        };
+        self.transition_camera(|camera| camera.fit_to_sphere(center.into(), radius));
+    }
+
+    /// Looks at the center of the models from `view`.
+    pub fn snap_view(&mut self, view: StandardView) {
+        let Some(bounds) = self.scene_bounds() else {
+            return;
+        };
+        self.transition_camera(|camera| {
+            camera.center_on(bounds.min.into(), bounds.max.into());
+            camera.set_view(view);
+        });
+    }
+
+    /// Returns the world space box around every model, `None` without models.
+    pub fn scene_bounds(&self) -> Option<Aabb> {
+        let corners = self.models.iter().filter_map(Model::bounds);
+        Aabb::from_points(corners.flat_map(|bounds| [bounds.min, bounds.max]))
+    }
+
+    /// Eases the camera over [`State::camera_transition`] into the pose `move_camera` puts it in.
+    /// Anything else `move_camera` changes, like the projection, applies right away. The orbit
+    /// pivot goes back to the camera's target.
+    fn transition_camera(&mut self, move_camera: impl FnOnce(&mut Camera)) {
+        self.camera_controller.set_pivot(None);
+        let from = self.camera.pose();
+        move_camera(&mut self.camera);
+        let to = self.camera.pose();
+        self.camera.set_pose(from);
+        self.camera.animate_to(to, self.camera_transition);
+    }
+
+    /// Draws BREP edges `width` pixels wide, regardless of the zoom. Edges are antialiased, so
+    /// fractional widths are fine.
+    pub fn set_edge_width(&mut self, width: f32) {
+        self.edge_uniform.update_width(width);
+        self.write_edge_uniform();
+    }
+
+    /// Width of BREP edges in pixels, see [`State::set_edge_width`].
+    pub fn edge_width(&self) -> f32 {
+        self.edge_uniform.width()
+    }
+
+    /// Uploads [`State::edge_uniform`], after a resize or a width change.
+    fn write_edge_uniform(&self) {
+        self.queue.write_buffer(
+            &self.edge_buffer,
+            0,
+            bytemuck::cast_slice(&[self.edge_uniform]),
+        );
+    }
+
+    /// Points the light along `direction`, from the surface towards the light.
+    pub fn set_light_direction(&mut self, direction: cgmath::Vector3<f32>) {
+        self.light_uniform.update_direction(direction);
+        self.queue.write_buffer(
+            &self.light_buffer,
+            0,
+            bytemuck::cast_slice(&[self.light_uniform]),
+        );
+    }
+
+    /// Cuts away everything on the negative side of the world space plane `(a, b, c, d)`, where
+    /// `a*x + b*y + c*z + d < 0`, or removes the cut for `None`.
+    pub fn set_clip_plane(&mut self, plane: Option<[f32; 4]>) {
+        self.clip_plane = plane;
+        self.clip_uniform.update_plane(plane);
+        self.queue.write_buffer(
+            &self.clip_buffer,
+            0,
+            bytemuck::cast_slice(&[self.clip_uniform]),
+        );
+    }
+
+    pub fn clip_plane(&self) -> Option<[f32; 4]> {
+        self.clip_plane
+    }
+
+    /// Moves the section plane by `distance` along its normal, cutting away more for positive
+    /// distances. Does nothing without a plane.
+    pub fn nudge_clip_plane(&mut self, distance: f32) {
+        let Some([a, b, c, d]) = self.clip_plane else {
+            return;
+        };
+        let length = (a * a + b * b + c * c).sqrt();
+        self.set_clip_plane(Some([a, b, c, d - distance * length]));
+    }
+
+    /// Colors models by `mode` instead of their texture, [`DebugColor::Off`] goes back to the
+    /// texture.
+    pub fn set_debug_color_mode(&mut self, mode: DebugColor) {
+        self.debug_color = mode;
+    }
+
+    pub fn debug_color_mode(&self) -> DebugColor {
+        self.debug_color
+    }
+
+    /// Clears every following frame, including captures, to `color`.
+    pub fn set_background(&mut self, color: wgpu::Color) {
+        self.background = color;
+    }
+
+    /// Shows or hides the axes gizmo together with its ground grid.
+    pub fn set_gizmo_visible(&mut self, visible: bool) {
+        self.gizmo.visible = visible;
+    }
+
+    /// Shows or hides the ground grid, the axes stay as they are.
+    pub fn set_grid_visible(&mut self, visible: bool) {
+        self.gizmo.show_grid = visible;
+    }
+
+    /// Draws every following frame as `options` say, rebuilding the pipelines and the gizmo.
+    ///
+    /// The sample count and polygon mode fall back like they do in [`State::with_options`]. The
+    /// present mode, color space and post-processing are kept, they can't change once the
+    /// [`State`] is created.
+    pub fn set_render_options(&mut self, mut options: RenderOptions) {
+        options.sample_count = Self::supported_sample_count(self.msaa_flags, options.sample_count);
+        options.polygon_mode = Self::supported_polygon_mode(&self.device, options.polygon_mode);
+        options.present_mode = self.options.present_mode;
+        options.color_space = self.options.color_space;
+        options.post_processing = self.options.post_processing;
+        let format = self.scene_format();
+        self.pipelines = Pipelines::new(
+            &self.device,
+            &self.pipeline_layout,
+            &self.shader,
+            format,
+            &options,
+        );
+        let old = std::mem::replace(&mut self.options, options);
+        if old.sample_count != self.options.sample_count {
+            let (visible, show_grid) = (self.gizmo.visible, self.gizmo.show_grid);
+            self.gizmo = Gizmo::new(
+                &self.device,
+                format,
+                self.options.sample_count,
+                &self.camera_bind_group_layout,
+            );
+            self.gizmo.visible = visible;
+            self.gizmo.show_grid = show_grid;
+            self.recreate_targets();
         }
+        if old.material != self.options.material {
+            self.write_material();
+        }
+        if let Some(post_process) = &mut self.post_process {
+            post_process.set_tone_mapping(&self.device, self.options.tone_mapping);
+        }
+    }
+
+    /// Changes the current options with `change`, see [`State::set_render_options`].
+    fn update_render_options(&mut self, change: impl FnOnce(&mut RenderOptions)) {
+        let mut options = self.options.clone();
+        change(&mut options);
+        self.set_render_options(options);
+    }
+
+    /// Returns the options frames are drawn with, after falling back to what the adapter
+    /// supports.
+    pub fn render_options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    /// Shades untextured models with `material` from the next frame on, no pipeline is rebuilt.
+    pub fn set_material(&mut self, material: Material) {
+        self.options.material = material;
+        self.write_material();
+    }
+
+    /// Uploads [`RenderOptions::material`] to [`State::material_buffer`].
+    fn write_material(&self) {
+        let uniform = self.options.material.uniform();
+        self.queue
+            .write_buffer(&self.material_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    /// Sub-allocates a mesh from the buffers shared by every model and uploads `vertices` and
+    /// `indices` into it, see [`MeshBuffers::alloc`].
+    pub fn alloc_mesh<V: VertexLayout>(
+        &mut self,
+        vertices: &[V],
+        indices: Option<&[u32]>,
+    ) -> anyhow::Result<Mesh<V>> {
+        self.meshes
+            .alloc(&self.device, &self.queue, vertices, indices)
+    }
+
+    /// Uploads `vertices` and `indices` with [`State::alloc_mesh`], or into buffers of their own
+    /// once the shared ones are full. Fails if an index is out of range.
+    fn upload_mesh(
+        &mut self,
+        vertices: &[Vertex],
+        indices: Option<&[u32]>,
+    ) -> anyhow::Result<Mesh<Vertex>> {
+        match self.alloc_mesh(vertices, indices) {
+            Ok(mesh) => Ok(mesh),
+            Err(e) => {
+                log::debug!("{:#}, uploading into buffers of its own", e);
+                match indices {
+                    Some(indices) => Mesh::from(&self.device, vertices, indices),
+                    None => Ok(Mesh::from_vertices(&self.device, vertices)),
+                }
+            }
+        }
+    }
+
+    /// Uploads a model that can be drawn with [`State::add_model`]. Fails if an index is out of
+    /// range.
+    pub fn create_model(&mut self, vertices: &[Vertex], indices: &[u32]) -> anyhow::Result<Model> {
+        let mesh = self.upload_mesh(vertices, Some(indices))?;
+        Ok(Model::new(
+            &self.device,
+            &self.queue,
+            &self.model_bind_group_layout,
+            mesh,
+            vertices,
+            indices,
+        ))
+    }
+
+    /// Loads an `.obj`, `.stl`, STEP or `.brep` file, adds it to the drawn models and returns its
+    /// index. The format goes by the file's extension.
+    ///
+    /// STL facets are kept flat, without welding their vertices. STEP and BREP models can be
+    /// picked with [`State::pick`]. Models without texture coordinates, from STL, STEP and BREP
+    /// files, are shaded with the [`RenderOptions::material`].
+    pub fn load_mesh(&mut self, path: &std::path::Path) -> anyhow::Result<usize> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        let model = match extension.to_ascii_lowercase().as_str() {
+            "obj" => {
+                let (vertices, indices) = import::read_obj(path)?;
+                self.create_model(&vertices, &indices)?
+            }
+            "stl" => {
+                let (vertices, indices) = import::read_stl(path, None)?;
+                let mut model = self.create_model(&vertices, &indices)?;
+                model.set_textured(false);
+                model
+            }
+            "step" | "stp" => {
+                // Loading blocks the event loop, so the title is the only place progress can show
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let mut shown = None;
+                let mut progress = |done: f32| {
+                    let percent = (done * 100.0) as u32;
+                    if shown != Some(percent) {
+                        shown = Some(percent);
+                        let title = format!("{} - loading {} {}%", self.title, name, percent);
+                        self.set_window_title(&title);
+                    }
+                };
+                let options = TessellationOptions::auto();
+                let tessellation = import::import_step(path, &options, &mut progress);
+                self.set_window_title(&self.title);
+                let tessellation = tessellation
+                    .with_context(|| format!("Couldn't import '{}'", path.display()))?;
+                self.create_tessellation_model(tessellation)?
+            }
+            "brep" => {
+                let options = TessellationOptions::auto();
+                self.create_tessellation_model(import::import_brep(path, &options)?)?
+            }
+            _ => anyhow::bail!(
+                "Can't load '{}', expected an OBJ, STL, STEP or BREP file",
+                path.display()
+            ),
+        };
+        Ok(self.add_model(model))
+    }
+
+    /// Uploads the triangles of `tessellation` as a pickable model, see
+    /// [`Model::from_tessellation`].
+    fn create_tessellation_model(&mut self, tessellation: Tessellation) -> anyhow::Result<Model> {
+        let mesh = self.upload_mesh(&tessellation.vertices, None)?;
+        Ok(Model::from_tessellation(
+            &self.device,
+            &self.queue,
+            &self.model_bind_group_layout,
+            mesh,
+            tessellation,
+        ))
+    }
+
+    /// Replaces every model with the triangles of `tessellation`, imported elsewhere, say on
+    /// another thread, and fits the camera to them.
+    pub fn load_tessellation(&mut self, tessellation: Tessellation) -> anyhow::Result<()> {
+        let model = self.create_tessellation_model(tessellation)?;
+        let index = self.add_model(model);
+        self.replace_models(index);
+        Ok(())
+    }
+
+    /// Replaces every model with the one loaded from `path`, see [`State::load_mesh`], and fits
+    /// the camera to it. The current models stay if loading fails.
+    pub fn open_file(&mut self, path: &std::path::Path) -> anyhow::Result<()> {
+        let index = self.load_mesh(path)?;
+        self.replace_models(index);
+        Ok(())
+    }
+
+    /// Drops the models before `index` and fits the camera to the ones left.
+    fn replace_models(&mut self, index: usize) {
+        self.models.drain(..index);
+        // The new model's faces may share ids with the selected one, and the pivot lay on the old
+        self.set_selection(None);
+        self.camera_controller.set_pivot(None);
+        self.fit_to_models();
+    }
+
+    /// Shows `message` in the window title for a few seconds, the viewer has no other place for
+    /// text.
+    pub fn show_toast(&mut self, message: &str) {
+        let title = format!("{} - {}", self.title, message);
+        self.set_window_title(&title);
+        self.toast_until = Some(Instant::now() + TOAST_DURATION);
+    }
+
+    /// Adds `model` to the drawn models and returns its index.
+    pub fn add_model(&mut self, mut model: Model) -> usize {
+        model.set_highlight(&self.device, self.selection);
+        self.models.push(model);
+        self.models.len() - 1
+    }
+
+    /// Moves vertices of the model at `index` without rebuilding it, see
+    /// [`Model::update_vertices`].
+    pub fn update_model_vertices(
+        &mut self,
+        index: usize,
+        offset: u32,
+        vertices: &[Vertex],
+    ) -> anyhow::Result<()> {
+        let model = self
+            .models
+            .get_mut(index)
+            .with_context(|| format!("No model {} to update", index))?;
+        model.update_vertices(offset, vertices)
+    }
+
+    /// Removes the model at `index`, shifting the ones after it down by one.
+    pub fn remove_model(&mut self, index: usize) -> Option<Model> {
+        (index < self.models.len()).then(|| self.models.remove(index))
+    }
+
+    /// Returns the BREP face under the window position `(x, y)`, in physical pixels.
+    ///
+    /// Only the closest hit counts, so faces hidden behind other models, including ones that
+    /// weren't tessellated from a BREP, can't be picked.
+    pub fn pick(&self, x: f32, y: f32) -> Option<FaceId> {
+        self.closest_hit(x, y).and_then(|(_, face_id)| face_id)
+    }
+
+    /// Returns the world space point on the closest model under the window position `x`, `y`.
+    pub fn pick_point(&self, x: f32, y: f32) -> Option<cgmath::Point3<f32>> {
+        self.closest_hit(x, y).map(|(point, _)| point)
+    }
+
+    /// Returns where the ray through the window position `x`, `y` first hits a model, along with
+    /// the BREP face it hits, if known.
+    fn closest_hit(&self, x: f32, y: f32) -> Option<(cgmath::Point3<f32>, Option<FaceId>)> {
+        let ndc_x = 2.0 * x / self.config.width as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / self.config.height as f32;
+        let ray = Ray::unproject(self.camera.build_vp_matrix(), ndc_x, ndc_y)?;
+        let (t, face_id) = self
+            .models
+            .iter()
+            .filter_map(|model| model.intersect(&ray))
+            .min_by(|a, b| a.0.total_cmp(&b.0))?;
+        Some((ray.origin + ray.direction * t, face_id))
+    }
+
+    /// Highlights the BREP face `selection` on every model that has it, or clears the highlight.
+    ///
+    /// Face ids are STEP instance ids, so models loaded from different files may share them.
+    pub fn set_selection(&mut self, selection: Option<FaceId>) {
+        self.selection = selection;
+        for model in &mut self.models {
+            model.set_highlight(&self.device, selection);
+        }
+    }
+
+    pub fn selection(&self) -> Option<FaceId> {
+        self.selection
+    }
+
+    pub fn handle_mouse_button(&mut self, button: MouseButton, is_pressed: bool) {
+        if button == MouseButton::Left {
+            if is_pressed {
+                self.click_start = self.cursor_position;
+            } else if let Some(position) = self.click_start.take() {
+                // Clicking empty space, or a model without faces, clears the selection
+                let face_id = self.pick(position.x as f32, position.y as f32);
+                if let Some(face_id) = face_id {
+                    log::info!("Selected face #{}", face_id);
+                }
+                self.set_selection(face_id);
+                self.handle_click(position);
+            }
+        }
+        match button {
+            MouseButton::Left => self.mouse_buttons.left = is_pressed,
+            MouseButton::Middle => self.mouse_buttons.middle = is_pressed,
+            MouseButton::Right => self.mouse_buttons.right = is_pressed,
+            _ => {}
+        }
+    }
+
+    /// Moves the orbit pivot to the point under a double click, or back to the camera's target
+    /// when double clicking empty space.
+    fn handle_click(&mut self, position: PhysicalPosition<f64>) {
+        let now = Instant::now();
+        let is_double_click = self.last_click.is_some_and(|(time, last)| {
+            let (dx, dy) = (position.x - last.x, position.y - last.y);
+            now - time <= DOUBLE_CLICK_TIME && dx * dx + dy * dy <= CLICK_SLOP * CLICK_SLOP
+        });
+        if !is_double_click {
+            self.last_click = Some((now, position));
+            return;
+        }
+        // A third click starts over instead of counting as another double click
+        self.last_click = None;
+        let pivot = self.pick_point(position.x as f32, position.y as f32);
+        match pivot {
+            Some(point) => log::info!("Orbiting around ({}, {}, {})", point.x, point.y, point.z),
+            None => log::info!("Orbiting around the view center"),
+        }
+        self.camera_controller.set_pivot(pivot);
+    }
+
+    pub fn handle_cursor_moved(&mut self, position: PhysicalPosition<f64>) {
+        // Dragging orbits the camera instead of selecting
+        if let Some(start) = self.click_start {
+            let (dx, dy) = (position.x - start.x, position.y - start.y);
+            if dx * dx + dy * dy > CLICK_SLOP * CLICK_SLOP {
+                self.click_start = None;
+            }
+        }
+        if let Some(last) = self.cursor_position {
+            self.camera_controller.handle_mouse(
+                position.x - last.x,
+                position.y - last.y,
+                self.mouse_buttons,
+            );
+        }
+        self.cursor_position = Some(position);
+    }
+
+    pub fn handle_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        self.camera_controller.handle_scroll(delta);
     }
 
     pub fn update(&mut self) {
         self.camera_controller.update_camera(&mut self.camera);
-        self.camera_uniform.update_vp(& self.camera);
-        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+        let frame_time = self.frame_timer.frame_time();
+        self.camera.advance_animation(frame_time);
+        if let Some(until) = self.toast_until
+            && Instant::now() >= until
+        {
+            self.toast_until = None;
+            self.set_window_title(&self.title);
+        }
+        self.camera_uniform.update_vp(&self.camera);
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform]),
+        );
     }
 
-    /// Render onto the surface
+    /// Render onto the surface, does nothing in headless mode
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        self.window.request_redraw();
+        let Some(window) = self.window.clone() else {
+            return Ok(());
+        };
+        window.request_redraw();
+        self.report_frame_time();
 
         // We can't render unless the surface is configured
-        if !self.is_surface_configured {
+        let Some(surface) = self.surface.as_ref().filter(|_| self.is_surface_configured) else {
             return Ok(());
-        }
+        };
 
-        let output = self.surface.get_current_texture()?;
+        let output = match surface.get_current_texture() {
+            Ok(output) => output,
+            // Reconfigure the surface and skip the frame
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                let size = window.inner_size();
+                self.resize(size.width, size.height);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -341,47 +1726,195 @@ impl State {
                 label: Some("Render Encoder"),
             });
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[
-                    // This is what @location(0) in the fragment shader targets
-                    Some(wgpu::RenderPassColorAttachment {
-                        view: &view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 0.1,
-                                g: 0.2,
-                                b: 0.3,
-                                a: 1.0,
-                            }),
-                            store: wgpu::StoreOp::Store,
-                        },
-                        depth_slice: None,
-                    }),
-                ],
-                depth_stencil_attachment: None,
-                multiview_mask: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
+        self.cull_stats = self.draw(&mut encoder, &view);
+
+        self.queue.submit(iter::once(encoder.finish()));
+        output.present();
+
+        Ok(())
+    }
+
+    /// Feeds the frame timer, logging the frame rate once a second.
+    fn report_frame_time(&mut self) {
+        if !self.frame_timer.tick() {
+            return;
+        }
+        let fps = self.frame_timer.average_fps();
+        let frame_time = self.frame_timer.average_frame_time();
+        log::debug!(
+            "{:.1} fps ({:.2?} per frame), {} models drawn, {} culled",
+            fps,
+            frame_time,
+            self.cull_stats.drawn,
+            self.cull_stats.culled
+        );
+        // A toast keeps the title until it's over
+        if self.show_fps_in_title && self.toast_until.is_none() {
+            self.set_window_title(&format!(
+                "{} - {:.1} fps ({:.2?})",
+                self.title, fps, frame_time
+            ));
+        }
+    }
+
+    /// Records the passes drawing every model and the gizmo into `view`, which must match the
+    /// surface's format and size, returning how many models were drawn and left out. With
+    /// post-processing the scene is drawn into the HDR target first and tone mapped into `view`.
+    fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) -> CullStats {
+        let Some(post_process) = &self.post_process else {
+            return self.draw_scene(encoder, view);
+        };
+        let stats = self.draw_scene(encoder, post_process.hdr_view());
+        post_process.draw(encoder, view);
+        stats
+    }
+
+    /// Records a pass drawing every model and the gizmo into `view`, a texture of
+    /// [`State::scene_format`]. Models whose every instance lies outside the camera's frustum are
+    /// left out, returns how many were drawn and left out.
+    fn draw_scene(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) -> CullStats {
+        let frustum = self.camera_uniform.frustum();
+        let visible: Vec<&Model> = self
+            .models
+            .iter()
+            .filter(|model| model.is_visible(&frustum))
+            .collect();
+        let stats = CullStats {
+            drawn: visible.len(),
+            culled: self.models.len() - visible.len(),
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[
+                // This is what @location(0) in the fragment shader targets. With MSAA, render
+                // into the multisampled target and resolve into `view`.
+                Some(wgpu::RenderPassColorAttachment {
+                    view: self.msaa_view.as_ref().unwrap_or(view),
+                    resolve_target: self.msaa_view.as_ref().map(|_| view),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.background),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                }),
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            multiview_mask: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            // Textures
-            render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-            // Camera
-            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-            // Vertex Buffer
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            // Index Buffer
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..self.index_count, 0, 0..1);
+        // Textures
+        render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
+        // Camera
+        render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+        // Light
+        render_pass.set_bind_group(3, &self.light_bind_group, &[]);
+        // Models
+        for &model in &visible {
+            let colors = match self.debug_color {
+                DebugColor::Off => model.face_colors(),
+                DebugColor::SurfaceType => model.debug_colors(),
+            };
+            match colors {
+                Some(colors) => {
+                    render_pass.set_pipeline(self.pipelines.vertex_color.get(model));
+                    render_pass.set_vertex_buffer(2, colors.slice(..));
+                }
+                None if model.textured() => {
+                    render_pass.set_pipeline(self.pipelines.render.get(model))
+                }
+                None => render_pass.set_pipeline(self.pipelines.material.get(model)),
+            }
+            // Model transform
+            render_pass.set_bind_group(2, &model.transform_bind_group, &[]);
+            // Instance Buffer
+            render_pass.set_vertex_buffer(1, model.instance_buffer().slice(..));
+            // Vertex & Index Buffer
+            model
+                .mesh()
+                .draw(&mut render_pass, 0, 0..model.instance_count());
+        }
+        // Selected face, drawn over the models with the same vertices and transforms
+        for &model in &visible {
+            let (Some((index_buffer, index_count)), Some(vertices)) =
+                (model.highlight(), model.mesh().vertex_slice())
+            else {
+                continue;
+            };
+            render_pass.set_pipeline(self.pipelines.highlight.get(model));
+            render_pass.set_bind_group(2, &model.transform_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertices);
+            render_pass.set_vertex_buffer(1, model.instance_buffer().slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..index_count, 0, 0..model.instance_count());
         }
+        // BREP edges, over the faces and the highlight
+        if self.show_edges {
+            render_pass.set_pipeline(&self.pipelines.edge);
+            for &model in &visible {
+                let Some((edge_buffer, vertex_count)) = model.edges() else {
+                    continue;
+                };
+                render_pass.set_bind_group(2, &model.transform_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, edge_buffer.slice(..));
+                render_pass.set_vertex_buffer(1, model.instance_buffer().slice(..));
+                render_pass.draw(0..vertex_count, 0..model.instance_count());
+            }
+        }
+        // Axes & Grid
+        self.gizmo.draw(&mut render_pass, &self.camera_bind_group);
+        stats
+    }
 
+    /// Renders a frame into an offscreen texture and returns its pixels as tightly packed RGBA8
+    /// rows, `width * height * 4` bytes. Works with or without a window.
+    pub fn render_to_texture(&self) -> anyhow::Result<Vec<u8>> {
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size: wgpu::Extent3d {
+                width: self.config.width,
+                height: self.config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Offscreen Encoder"),
+            });
+        self.draw(&mut encoder, &view);
         self.queue.submit(iter::once(encoder.finish()));
-        output.present();
+        texture::Texture::read_rgba8(&self.device, &self.queue, &texture)
+    }
 
+    /// Renders the current view into an offscreen texture and saves it as a PNG at `path`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn capture_frame(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        let pixels = self.render_to_texture()?;
+        image::RgbaImage::from_raw(self.config.width, self.config.height, pixels)
+            .context("Captured frame has the wrong size")?
+            .save(path)
+            .with_context(|| format!("Couldn't save '{}'", path.display()))?;
         Ok(())
     }
 }
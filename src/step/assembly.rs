@@ -0,0 +1,605 @@
+// STD
+use std::collections::{HashMap, HashSet};
+
+// Dependencies
+use anyhow::{Result, bail};
+use cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+
+// Local modules
+use super::geometry::axis2_placement;
+use super::parser::{EntityGraph, Parameter, ParseError};
+use super::tessellate::{Polyline, Tessellation};
+use crate::mesh::Vertex;
+
+/// A product in the assembly structure of a STEP file, with the sub-assemblies and parts it's
+/// made of
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssemblyNode {
+    /// The `PRODUCT_DEFINITION` of the product, `None` for the node grouping several top-level
+    /// products
+    pub product_definition: Option<u64>,
+    /// Name of the product, its id if it's unnamed
+    pub name: String,
+    /// The `NEXT_ASSEMBLY_USAGE_OCCURRENCE` placing the product in its parent, `None` at the root
+    pub occurrence: Option<u64>,
+    /// Maps the product's coordinates to its parent's, the identity at the root
+    pub transform: Matrix4<f64>,
+    /// The shape representation holding the product's geometry, if it has any
+    pub shape_representation: Option<u64>,
+    /// Products used in this one, in order of their occurrences
+    pub children: Vec<AssemblyNode>,
+}
+
+impl AssemblyNode {
+    /// Returns the shape representation of every product in the tree, once per occurrence, with
+    /// the transform from its coordinates to the root's.
+    pub fn placements(&self) -> Vec<(u64, Matrix4<f64>)> {
+        let mut placements = Vec::new();
+        self.collect_placements(Matrix4::identity(), &mut placements);
+        placements
+    }
+
+    fn collect_placements(&self, parent: Matrix4<f64>, out: &mut Vec<(u64, Matrix4<f64>)>) {
+        let transform = parent * self.transform;
+        if let Some(representation) = self.shape_representation {
+            out.push((representation, transform));
+        }
+        for child in &self.children {
+            child.collect_placements(transform, out);
+        }
+    }
+}
+
+/// Returns argument `i` of the record `args` of instance `id` as a reference.
+fn record_ref(id: u64, args: &[Parameter], i: usize) -> Result<u64> {
+    match args.get(i).and_then(Parameter::as_ref) {
+        Some(reference) => Ok(reference),
+        None => Err(ParseError::instance(id, format!("Argument {} is not a reference", i)).into()),
+    }
+}
+
+/// Returns the name of the `PRODUCT` defined by the `PRODUCT_DEFINITION` `id`, or its id if the
+/// name is empty.
+fn product_name(graph: &EntityGraph, id: u64) -> Result<String> {
+    let formation = graph.get(graph.get_as(id, "PRODUCT_DEFINITION")?.ref_arg(2)?)?;
+    let product = graph.get_as(formation.ref_arg(2)?, "PRODUCT")?;
+    let name = product.arg(1)?.as_str().unwrap_or_default();
+    let name = match name {
+        "" => product.arg(0)?.as_str().unwrap_or_default(),
+        name => name,
+    };
+    Ok(name.to_owned())
+}
+
+/// Links between products, their occurrences and the representations of their shapes.
+struct ShapeLinks {
+    /// `PRODUCT_DEFINITION_SHAPE` of every product definition or occurrence
+    shapes: HashMap<u64, u64>,
+    /// Shape representation of every `PRODUCT_DEFINITION_SHAPE`
+    representations: HashMap<u64, u64>,
+    /// Representation relationship of every `PRODUCT_DEFINITION_SHAPE` of an occurrence
+    relationships: HashMap<u64, u64>,
+}
+
+impl ShapeLinks {
+    fn new(graph: &EntityGraph) -> Result<Self> {
+        let mut links = ShapeLinks {
+            shapes: HashMap::new(),
+            representations: HashMap::new(),
+            relationships: HashMap::new(),
+        };
+        for shape in graph.instances_of("PRODUCT_DEFINITION_SHAPE") {
+            links.shapes.insert(shape.ref_arg(2)?, shape.id);
+        }
+        for sdr in graph.instances_of("SHAPE_DEFINITION_REPRESENTATION") {
+            links
+                .representations
+                .insert(sdr.ref_arg(0)?, sdr.ref_arg(1)?);
+        }
+        for cdsr in graph.instances_of("CONTEXT_DEPENDENT_SHAPE_REPRESENTATION") {
+            links
+                .relationships
+                .insert(cdsr.ref_arg(1)?, cdsr.ref_arg(0)?);
+        }
+        Ok(links)
+    }
+
+    /// Returns the shape representation of the product definition `id`.
+    fn representation(&self, id: u64) -> Option<u64> {
+        self.representations.get(self.shapes.get(&id)?).copied()
+    }
+
+    /// Returns the representation relationship placing the occurrence `id` in its parent.
+    fn relationship(&self, id: u64) -> Option<u64> {
+        self.relationships.get(self.shapes.get(&id)?).copied()
+    }
+}
+
+/// Whether the representation `representation` lists `item` among its items.
+fn has_item(graph: &EntityGraph, representation: u64, item: u64) -> bool {
+    let items = graph.get(representation).and_then(|r| r.list_arg(1));
+    items.is_ok_and(|items| items.iter().any(|i| i.as_ref() == Some(item)))
+}
+
+/// Returns the transform from the coordinates of the shape representation `child` to those of its
+/// parent, read from the `ITEM_DEFINED_TRANSFORMATION` of the representation relationship
+/// `relationship`.
+///
+/// The transformation maps its first item, in the child's representation, onto its second, in
+/// the parent's. Some writers swap them, so the items are put in order by which one the child
+/// lists.
+fn occurrence_transform(
+    graph: &EntityGraph,
+    relationship: u64,
+    child: Option<u64>,
+) -> Result<Matrix4<f64>> {
+    let entity = graph.get(relationship)?;
+    let Some(args) = entity.record("REPRESENTATION_RELATIONSHIP_WITH_TRANSFORMATION") else {
+        return Err(ParseError::instance(
+            relationship,
+            "Expected a relationship with a transformation",
+        )
+        .into());
+    };
+    let transformation = record_ref(relationship, args, 0)?;
+    let transformation = graph.get_as(transformation, "ITEM_DEFINED_TRANSFORMATION")?;
+    let mut items = [transformation.ref_arg(2)?, transformation.ref_arg(3)?];
+    if let Some(child) = child
+        && has_item(graph, child, items[1])
+        && !has_item(graph, child, items[0])
+    {
+        items.reverse();
+    }
+    let [child_item, parent_item] = items;
+    let parent_item = axis2_placement(graph, parent_item)?;
+    match axis2_placement(graph, child_item)?.invert() {
+        Some(inverse) => Ok(parent_item * inverse),
+        None => Err(ParseError::instance(child_item, "Placement can't be inverted").into()),
+    }
+}
+
+/// Builds the tree of products used by `graph`, following `NEXT_ASSEMBLY_USAGE_OCCURRENCE`s from
+/// assemblies down to their parts.
+///
+/// Each child carries the transform placing it in its parent, see
+/// [`AssemblyNode::transform`]. Occurrences without a transformation are placed at the origin of
+/// their parent. Files with several top-level products get a root without a product grouping
+/// them, files with a single part a root without children.
+pub fn assembly_tree(graph: &EntityGraph) -> Result<AssemblyNode> {
+    let links = ShapeLinks::new(graph)?;
+    let mut children: HashMap<u64, Vec<(u64, u64)>> = HashMap::new();
+    let mut used = HashSet::new();
+    for occurrence in graph.instances_of("NEXT_ASSEMBLY_USAGE_OCCURRENCE") {
+        let (parent, child) = (occurrence.ref_arg(3)?, occurrence.ref_arg(4)?);
+        children
+            .entry(parent)
+            .or_default()
+            .push((occurrence.id, child));
+        used.insert(child);
+    }
+    let builder = TreeBuilder {
+        graph,
+        links: &links,
+        children: &children,
+    };
+    let mut roots = graph
+        .instances_of("PRODUCT_DEFINITION")
+        .filter(|definition| !used.contains(&definition.id))
+        .map(|definition| builder.node(definition.id, None, &mut Vec::new()))
+        .collect::<Result<Vec<_>>>()?;
+    match roots.len() {
+        0 if used.is_empty() => bail!("No PRODUCT_DEFINITION in the file"),
+        0 => bail!("Every product is used in another one, the assembly contains itself"),
+        1 => Ok(roots.remove(0)),
+        _ => Ok(AssemblyNode {
+            product_definition: None,
+            name: String::new(),
+            occurrence: None,
+            transform: Matrix4::identity(),
+            shape_representation: None,
+            children: roots,
+        }),
+    }
+}
+
+/// Walks the product structure for [`assembly_tree`].
+struct TreeBuilder<'a> {
+    graph: &'a EntityGraph,
+    links: &'a ShapeLinks,
+    /// Occurrences and the products they place, for every assembly
+    children: &'a HashMap<u64, Vec<(u64, u64)>>,
+}
+
+impl TreeBuilder<'_> {
+    /// Builds the node of the `PRODUCT_DEFINITION` `id`, placed by `occurrence`. `path` holds the
+    /// products above it, to catch assemblies that contain themselves.
+    fn node(&self, id: u64, occurrence: Option<u64>, path: &mut Vec<u64>) -> Result<AssemblyNode> {
+        if path.contains(&id) {
+            return Err(ParseError::instance(id, "Product is used inside itself").into());
+        }
+        let shape_representation = self.links.representation(id);
+        let transform = match occurrence.and_then(|o| self.links.relationship(o)) {
+            Some(relationship) => {
+                occurrence_transform(self.graph, relationship, shape_representation)?
+            },
+            None => Matrix4::identity(),
+        };
+        path.push(id);
+        let children = self
+            .children
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .map(|&(occurrence, child)| self.node(child, Some(occurrence), path))
+            .collect::<Result<Vec<_>>>()?;
+        path.pop();
+        Ok(AssemblyNode {
+            product_definition: Some(id),
+            name: product_name(self.graph, id)?,
+            occurrence,
+            transform,
+            shape_representation,
+            children,
+        })
+    }
+}
+
+/// Pairs of representations linked without a transformation, usually a product's
+/// `SHAPE_REPRESENTATION` and the BREP representation holding its geometry.
+fn plain_links(graph: &EntityGraph) -> HashMap<u64, Vec<u64>> {
+    let mut links: HashMap<u64, Vec<u64>> = HashMap::new();
+    for relationship in graph.instances_of("SHAPE_REPRESENTATION_RELATIONSHIP") {
+        if relationship.is("REPRESENTATION_RELATIONSHIP_WITH_TRANSFORMATION") {
+            continue;
+        }
+        // Complex instances keep the representations in their REPRESENTATION_RELATIONSHIP record
+        let args = relationship
+            .record("REPRESENTATION_RELATIONSHIP")
+            .unwrap_or(&relationship.args);
+        if let (Some(a), Some(b)) = (
+            args.get(2).and_then(Parameter::as_ref),
+            args.get(3).and_then(Parameter::as_ref),
+        ) {
+            links.entry(a).or_default().push(b);
+            links.entry(b).or_default().push(a);
+        }
+    }
+    links
+}
+
+/// Returns every instance the items of `representation` and the representations linked to it
+/// are made of.
+///
+/// `MAPPED_ITEM`s aren't followed, they place other representations on their own.
+fn shape_contents(
+    graph: &EntityGraph,
+    representation: u64,
+    links: &HashMap<u64, Vec<u64>>,
+) -> HashSet<u64> {
+    let mut representations = vec![representation];
+    let mut seen_representations = HashSet::from([representation]);
+    let mut stack = Vec::new();
+    while let Some(id) = representations.pop() {
+        if let Ok(items) = graph.get(id).and_then(|r| r.list_arg(1)) {
+            stack.extend(items.iter().filter_map(Parameter::as_ref));
+        }
+        for &linked in links.get(&id).into_iter().flatten() {
+            if seen_representations.insert(linked) {
+                representations.push(linked);
+            }
+        }
+    }
+    let mut contents = HashSet::new();
+    while let Some(id) = stack.pop() {
+        if !contents.insert(id) {
+            continue;
+        }
+        match graph.get(id) {
+            Ok(entity) if !entity.is("MAPPED_ITEM") => stack.extend(entity.references()),
+            _ => {},
+        }
+    }
+    contents
+}
+
+/// Where the faces and edges of the parts of an assembly go, see [`PartPlacements::new`]
+#[derive(Debug, Clone, Default)]
+pub struct PartPlacements {
+    /// Transforms of every `ADVANCED_FACE` and `EDGE_CURVE` in a placed part, one per occurrence
+    transforms: HashMap<u64, Vec<Matrix4<f64>>>,
+}
+
+impl PartPlacements {
+    /// Resolves the occurrences of every part in the [`assembly_tree`] of `graph` into the
+    /// transforms placing their faces and edges in the root's coordinates.
+    ///
+    /// Files without product structure have no placements, geometry outside of every product
+    /// stays where it is.
+    pub fn new(graph: &EntityGraph) -> Result<Self> {
+        if graph.instances_of("PRODUCT_DEFINITION").next().is_none() {
+            return Ok(Self::default());
+        }
+        let links = plain_links(graph);
+        let mut contents: HashMap<u64, HashSet<u64>> = HashMap::new();
+        let mut transforms: HashMap<u64, Vec<Matrix4<f64>>> = HashMap::new();
+        for (representation, transform) in assembly_tree(graph)?.placements() {
+            let contents = contents
+                .entry(representation)
+                .or_insert_with(|| shape_contents(graph, representation, &links));
+            for &id in contents.iter() {
+                let entity = &graph.entities[&id];
+                if entity.is("ADVANCED_FACE") || entity.is("EDGE_CURVE") {
+                    transforms.entry(id).or_default().push(transform);
+                }
+            }
+        }
+        Ok(Self { transforms })
+    }
+
+    /// Returns the transforms of the face or edge `id`, one per occurrence of its part, or
+    /// `None` if it isn't part of a placed product.
+    pub fn get(&self, id: u64) -> Option<&[Matrix4<f64>]> {
+        self.transforms.get(&id).map(Vec::as_slice)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Moves the triangles and edges of `tessellation` to where their parts are placed, copying
+    /// those of parts used more than once. Copies keep the face and edge ids they came from.
+    pub fn place_tessellation(&self, tessellation: &mut Tessellation) {
+        if self.is_empty() {
+            return;
+        }
+        let mut vertices = Vec::with_capacity(tessellation.vertices.len());
+        let mut face_ids = Vec::with_capacity(tessellation.face_ids.len());
+        for (triangle, &face) in tessellation
+            .vertices
+            .chunks_exact(3)
+            .zip(&tessellation.face_ids)
+        {
+            match self.get(face) {
+                Some(transforms) => {
+                    for transform in transforms {
+                        vertices.extend(triangle.iter().map(|v| transform_vertex(transform, v)));
+                        face_ids.push(face);
+                    }
+                },
+                None => {
+                    vertices.extend_from_slice(triangle);
+                    face_ids.push(face);
+                },
+            }
+        }
+        tessellation.vertices = vertices;
+        tessellation.face_ids = face_ids;
+        self.place_edges(&mut tessellation.edges);
+    }
+
+    /// Moves the polylines of `edges` to where their parts are placed, like
+    /// [`place_tessellation`](Self::place_tessellation).
+    pub fn place_edges(&self, edges: &mut Vec<Polyline>) {
+        if self.is_empty() {
+            return;
+        }
+        let mut placed = Vec::with_capacity(edges.len());
+        for edge in edges.drain(..) {
+            let Some(transforms) = self.get(edge.edge_id) else {
+                placed.push(edge);
+                continue;
+            };
+            placed.extend(transforms.iter().map(|transform| {
+                Polyline {
+                    edge_id: edge.edge_id,
+                    points: edge
+                        .points
+                        .iter()
+                        .map(|&p| transform.transform_point(Point3::from(p)).into())
+                        .collect(),
+                }
+            }));
+        }
+        *edges = placed;
+    }
+}
+
+/// Returns `vertex` moved by the rigid `transform`.
+fn transform_vertex(transform: &Matrix4<f64>, vertex: &Vertex) -> Vertex {
+    let position = Point3::from(vertex.position.map(f64::from));
+    let normal = transform.transform_vector(Vector3::from(vertex.normal.map(f64::from)));
+    let normal = if normal.magnitude2() > 0.0 {
+        normal.normalize()
+    } else {
+        normal
+    };
+    Vertex {
+        position: transform.transform_point(position).map(|c| c as f32).into(),
+        normal: normal.map(|c| c as f32).into(),
+        ..*vertex
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::step::{TessellationOptions, parse, read_step, tessellate_faces};
+
+    fn asset(name: &str) -> EntityGraph {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("assets/step")
+            .join(name);
+        read_step(&path).unwrap()
+    }
+
+    /// Assembly `A` with the sub-assembly `B` moved 10 along x, and the part `P` at the origin
+    /// of `A` as well as in `B`, moved 5 along y and turned a quarter around z. `P` is a unit
+    /// square in its BREP representation #36, face #50.
+    const NESTED: &str = "\
+        #1 = CARTESIAN_POINT('',(0.,0.,0.));
+        #2 = DIRECTION('',(0.,0.,1.));
+        #3 = DIRECTION('',(1.,0.,0.));
+        #4 = AXIS2_PLACEMENT_3D('',#1,#2,#3);
+        #5 = CARTESIAN_POINT('',(10.,0.,0.));
+        #6 = AXIS2_PLACEMENT_3D('',#5,#2,#3);
+        #7 = CARTESIAN_POINT('',(0.,5.,0.));
+        #8 = DIRECTION('',(0.,1.,0.));
+        #9 = AXIS2_PLACEMENT_3D('',#7,#2,#8);
+        #10 = PRODUCT('A','A','',());
+        #11 = PRODUCT_DEFINITION_FORMATION('','',#10);
+        #12 = PRODUCT_DEFINITION('','',#11,$);
+        #13 = PRODUCT_DEFINITION_SHAPE('','',#12);
+        #14 = SHAPE_REPRESENTATION('',(#4,#6),$);
+        #15 = SHAPE_DEFINITION_REPRESENTATION(#13,#14);
+        #20 = PRODUCT('B','B','',());
+        #21 = PRODUCT_DEFINITION_FORMATION('','',#20);
+        #22 = PRODUCT_DEFINITION('','',#21,$);
+        #23 = PRODUCT_DEFINITION_SHAPE('','',#22);
+        #24 = SHAPE_REPRESENTATION('',(#4,#9),$);
+        #25 = SHAPE_DEFINITION_REPRESENTATION(#23,#24);
+        #30 = PRODUCT('P','P','',());
+        #31 = PRODUCT_DEFINITION_FORMATION('','',#30);
+        #32 = PRODUCT_DEFINITION('','',#31,$);
+        #33 = PRODUCT_DEFINITION_SHAPE('','',#32);
+        #34 = SHAPE_REPRESENTATION('',(#4),$);
+        #35 = SHAPE_DEFINITION_REPRESENTATION(#33,#34);
+        #36 = ADVANCED_BREP_SHAPE_REPRESENTATION('',(#4,#50),$);
+        #37 = SHAPE_REPRESENTATION_RELATIONSHIP('','',#34,#36);
+        #40 = CARTESIAN_POINT('',(0.,0.,0.));
+        #41 = CARTESIAN_POINT('',(1.,0.,0.));
+        #42 = CARTESIAN_POINT('',(1.,1.,0.));
+        #43 = CARTESIAN_POINT('',(0.,1.,0.));
+        #44 = POLY_LOOP('',(#40,#41,#42,#43));
+        #45 = FACE_OUTER_BOUND('',#44,.T.);
+        #46 = PLANE('',#4);
+        #50 = ADVANCED_FACE('',(#45),#46,.T.);
+        #60 = NEXT_ASSEMBLY_USAGE_OCCURRENCE('1','','',#12,#22,$);
+        #61 = PRODUCT_DEFINITION_SHAPE('','',#60);
+        #62 = ITEM_DEFINED_TRANSFORMATION('','',#4,#6);
+        #63 = (REPRESENTATION_RELATIONSHIP('','',#24,#14)\
+            REPRESENTATION_RELATIONSHIP_WITH_TRANSFORMATION(#62)\
+            SHAPE_REPRESENTATION_RELATIONSHIP());
+        #64 = CONTEXT_DEPENDENT_SHAPE_REPRESENTATION(#63,#61);
+        #70 = NEXT_ASSEMBLY_USAGE_OCCURRENCE('2','','',#22,#32,$);
+        #71 = PRODUCT_DEFINITION_SHAPE('','',#70);
+        #72 = ITEM_DEFINED_TRANSFORMATION('','',#4,#9);
+        #73 = (REPRESENTATION_RELATIONSHIP('','',#34,#24)\
+            REPRESENTATION_RELATIONSHIP_WITH_TRANSFORMATION(#72)\
+            SHAPE_REPRESENTATION_RELATIONSHIP());
+        #74 = CONTEXT_DEPENDENT_SHAPE_REPRESENTATION(#73,#71);
+        #80 = NEXT_ASSEMBLY_USAGE_OCCURRENCE('3','','',#12,#32,$);
+        #81 = PRODUCT_DEFINITION_SHAPE('','',#80);
+        #82 = ITEM_DEFINED_TRANSFORMATION('','',#4,#4);
+        #83 = (REPRESENTATION_RELATIONSHIP('','',#34,#14)\
+            REPRESENTATION_RELATIONSHIP_WITH_TRANSFORMATION(#82)\
+            SHAPE_REPRESENTATION_RELATIONSHIP());
+        #84 = CONTEXT_DEPENDENT_SHAPE_REPRESENTATION(#83,#81);
+    ";
+
+    fn graph(data: &str) -> EntityGraph {
+        parse(&format!(
+            "ISO-10303-21;\nHEADER;\nENDSEC;\nDATA;\n{}\nENDSEC;\nEND-ISO-10303-21;\n",
+            data
+        ))
+        .unwrap()
+    }
+
+    fn assert_maps(transform: &Matrix4<f64>, from: [f64; 3], to: [f64; 3]) {
+        let mapped = transform.transform_point(Point3::from(from));
+        assert!(
+            (mapped - Point3::from(to)).magnitude() < 1e-9,
+            "{:?} went to {:?} instead of {:?}",
+            from,
+            mapped,
+            to
+        );
+    }
+
+    #[test]
+    fn composes_transforms_of_nested_occurrences() {
+        let graph = graph(NESTED);
+        let tree = assembly_tree(&graph).unwrap();
+        assert_eq!(tree.name, "A");
+        let names: Vec<&str> = tree.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["B", "P"]);
+        assert_eq!(tree.children[0].children[0].name, "P");
+
+        // P inside B: turned onto y, moved up in B, then along x with B
+        let placements = PartPlacements::new(&graph).unwrap();
+        let transforms = placements.get(50).unwrap();
+        assert_eq!(transforms.len(), 2);
+        assert_maps(&transforms[0], [0.0, 0.0, 0.0], [10.0, 5.0, 0.0]);
+        assert_maps(&transforms[0], [1.0, 0.0, 0.0], [10.0, 6.0, 0.0]);
+        assert_maps(&transforms[0], [0.0, 1.0, 0.0], [9.0, 5.0, 0.0]);
+        // P used directly in A stays put
+        assert_maps(&transforms[1], [1.0, 1.0, 0.0], [1.0, 1.0, 0.0]);
+        // Placements of the assemblies' own representations aren't faces or edges
+        assert_eq!(placements.get(9), None);
+
+        let mut mesh = tessellate_faces(&graph, &TessellationOptions::default()).unwrap();
+        let triangles = mesh.face_ids.len();
+        placements.place_tessellation(&mut mesh);
+        assert_eq!(mesh.face_ids.len(), 2 * triangles);
+        // Every triangle is followed by its copies, in the order of the transforms
+        for (k, triangle) in mesh.vertices.chunks_exact(3).enumerate() {
+            let [min_x, min_y] = if k % 2 == 0 { [9.0, 5.0] } else { [0.0, 0.0] };
+            for v in triangle {
+                let [x, y, z] = v.position.map(f64::from);
+                assert!(
+                    (min_x - 1e-5..=min_x + 1.0 + 1e-5).contains(&x)
+                        && (min_y - 1e-5..=min_y + 1.0 + 1e-5).contains(&y)
+                        && z.abs() < 1e-5,
+                    "Triangle {} at {:?}",
+                    k,
+                    v.position
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn groups_top_level_products_under_a_root() {
+        let graph = asset("red_cube_blue_cylinder.step");
+        let tree = assembly_tree(&graph).unwrap();
+        assert_eq!(tree.product_definition, None);
+        assert_eq!(tree.shape_representation, None);
+        assert_eq!(tree.transform, Matrix4::identity());
+        let names: Vec<&str> = tree.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["blue_cylinder", "red_cube"]);
+        for child in &tree.children {
+            assert_eq!(child.occurrence, None);
+            assert_eq!(child.transform, Matrix4::identity());
+            assert!(child.shape_representation.is_some(), "{}", child.name);
+            assert!(child.children.is_empty());
+        }
+
+        let placements = tree.placements();
+        assert_eq!(placements.len(), 2);
+        assert!(placements.iter().all(|(_, t)| *t == Matrix4::identity()));
+    }
+
+    #[test]
+    fn places_every_face_of_unmoved_parts_once() {
+        let graph = asset("red_cube_blue_cylinder.step");
+        let placements = PartPlacements::new(&graph).unwrap();
+        assert!(!placements.is_empty());
+        for face in graph.instances_of("ADVANCED_FACE") {
+            assert_eq!(
+                placements.get(face.id),
+                Some([Matrix4::identity()].as_slice()),
+                "Face #{}",
+                face.id
+            );
+        }
+
+        // Parts at the origin of the root stay where they were tessellated
+        let mesh = tessellate_faces(&graph, &TessellationOptions::default()).unwrap();
+        let mut placed = mesh.clone();
+        placements.place_tessellation(&mut placed);
+        assert_eq!(placed.face_ids, mesh.face_ids);
+        assert_eq!(placed.edges, mesh.edges);
+        for (placed, vertex) in placed.vertices.iter().zip(&mesh.vertices) {
+            assert_eq!(placed.position, vertex.position);
+        }
+    }
+}
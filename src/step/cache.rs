@@ -0,0 +1,199 @@
+// STD
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+
+// Dependencies
+use anyhow::Result;
+
+// Local modules
+use super::parser::{EntityGraph, Parameter};
+use super::tessellate::{Tessellation, TessellationOptions, tessellate_faces_with};
+use crate::hash::StableHasher;
+
+/// Most tessellations kept around, the least recently used one is evicted past this
+const CACHE_CAPACITY: usize = 8;
+
+/// Model hash, then the bits of the linear and angular deflection
+type CacheKey = (u64, u64, u64);
+
+/// Cached tessellations, least recently used first
+static CACHE: Mutex<Vec<(CacheKey, Tessellation)>> = Mutex::new(Vec::new());
+
+/// Feeds `parameter` into `state`, numbers by their bit patterns.
+fn hash_parameter(parameter: &Parameter, state: &mut impl Hasher) {
+    std::mem::discriminant(parameter).hash(state);
+    match parameter {
+        Parameter::Ref(id) => id.hash(state),
+        Parameter::String(s) | Parameter::Enum(s) | Parameter::Binary(s) => s.hash(state),
+        Parameter::Real(v) => v.to_bits().hash(state),
+        Parameter::Integer(v) => v.hash(state),
+        Parameter::Typed(name, inner) => {
+            name.hash(state);
+            hash_parameter(inner, state);
+        },
+        Parameter::List(items) => {
+            items.len().hash(state);
+            items.iter().for_each(|p| hash_parameter(p, state));
+        },
+        Parameter::Unset | Parameter::Derived => {},
+    }
+}
+
+/// Returns a hash of every entity instance of `graph`, equal for graphs parsed from the same data.
+pub fn model_hash(graph: &EntityGraph) -> u64 {
+    let mut ids: Vec<u64> = graph.entities.keys().copied().collect();
+    ids.sort_unstable();
+    let mut state = StableHasher::new();
+    for entity in ids.iter().map(|id| &graph.entities[id]) {
+        entity.id.hash(&mut state);
+        let records = std::iter::once((&entity.keyword, &entity.args))
+            .chain(entity.partials.iter().map(|(k, args)| (k, args)));
+        for (keyword, args) in records {
+            keyword.hash(&mut state);
+            args.len().hash(&mut state);
+            args.iter().for_each(|p| hash_parameter(p, &mut state));
+        }
+    }
+    state.finish()
+}
+
+/// Like [`tessellate_faces_with_progress`](super::tessellate::tessellate_faces_with_progress),
+/// but reuses the result of an earlier call for the same model and deflections.
+///
+/// The last few tessellations are kept in a process wide LRU cache, so switching back to an
+/// earlier tolerance returns immediately. `progress` only gets called with 1 on a hit.
+pub fn tessellate_cached(
+    graph: &EntityGraph,
+    options: &TessellationOptions,
+    progress: &mut dyn FnMut(f32),
+) -> Result<Tessellation> {
+    tessellate_cached_with(graph, options, progress, None)
+}
+
+/// Like [`tessellate_cached`], but stops once `cancel` is set. Cancelled tessellations aren't
+/// cached.
+pub(crate) fn tessellate_cached_with(
+    graph: &EntityGraph,
+    options: &TessellationOptions,
+    progress: &mut dyn FnMut(f32),
+    cancel: Option<&AtomicBool>,
+) -> Result<Tessellation> {
+    // Relative deflections resolve the same for the same model, so they share cache entries
+    let options = &options.resolve(graph);
+    let key = (
+        model_hash(graph),
+        options.linear_deflection.to_bits(),
+        options.angular_deflection.to_bits(),
+    );
+    {
+        let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(i) = cache.iter().position(|(k, _)| *k == key) {
+            let entry = cache.remove(i);
+            let tessellation = entry.1.clone();
+            cache.push(entry);
+            progress(1.0);
+            return Ok(tessellation);
+        }
+    }
+
+    // The lock isn't held while tessellating, so other models can be looked up meanwhile
+    let tessellation = tessellate_faces_with(graph, options, progress, cancel)?;
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|(k, _)| *k != key);
+    if cache.len() >= CACHE_CAPACITY {
+        cache.remove(0);
+    }
+    cache.push((key, tessellation.clone()));
+    Ok(tessellation)
+}
+
+/// Drops every tessellation cached by [`tessellate_cached`].
+pub fn clear_tessellation_cache() {
+    CACHE.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::step::read_step;
+
+    /// Held by every test, they all share [`CACHE`]
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    fn cube() -> EntityGraph {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/step/cube.step");
+        read_step(&path).unwrap()
+    }
+
+    fn options(linear_deflection: f64) -> TessellationOptions {
+        TessellationOptions {
+            linear_deflection,
+            ..Default::default()
+        }
+    }
+
+    /// Tessellates `graph` through the cache, returning the result and whether it was a hit.
+    fn cached(graph: &EntityGraph, options: &TessellationOptions) -> (Tessellation, bool) {
+        let mut calls = Vec::new();
+        let tessellation = tessellate_cached(graph, options, &mut |p| calls.push(p)).unwrap();
+        (tessellation, calls == [1.0])
+    }
+
+    fn cached_deflections() -> Vec<u64> {
+        let cache = CACHE.lock().unwrap();
+        cache.iter().map(|((_, linear, _), _)| *linear).collect()
+    }
+
+    #[test]
+    fn hits_return_the_same_tessellation() {
+        let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        clear_tessellation_cache();
+        let graph = cube();
+        let (first, hit) = cached(&graph, &options(0.01));
+        assert!(!hit);
+        let (second, hit) = cached(&graph, &options(0.01));
+        assert!(hit);
+        let bytes = |t: &Tessellation| bytemuck::cast_slice::<_, u8>(&t.vertices).to_vec();
+        assert_eq!(bytes(&second), bytes(&first));
+        assert_eq!(second.face_ids, first.face_ids);
+
+        // Parsing the same file again gives the same key
+        let (_, hit) = cached(&cube(), &options(0.01));
+        assert!(hit);
+        let (_, hit) = cached(&graph, &options(0.02));
+        assert!(!hit, "a different deflection is a miss");
+        assert_eq!(cached_deflections().len(), 2);
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        clear_tessellation_cache();
+        let graph = cube();
+        let deflections: Vec<f64> = (1..=CACHE_CAPACITY + 1).map(|i| i as f64 * 0.01).collect();
+        for &deflection in &deflections[..CACHE_CAPACITY] {
+            cached(&graph, &options(deflection));
+        }
+        // Using the oldest entry again makes the second one the least recently used
+        assert!(cached(&graph, &options(deflections[0])).1);
+        cached(&graph, &options(deflections[CACHE_CAPACITY]));
+
+        let kept = cached_deflections();
+        assert_eq!(kept.len(), CACHE_CAPACITY);
+        assert!(kept.contains(&deflections[0].to_bits()));
+        assert!(!kept.contains(&deflections[1].to_bits()));
+        assert!(!cached(&graph, &options(deflections[1])).1);
+    }
+
+    #[test]
+    fn clearing_empties_the_cache() {
+        let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let graph = cube();
+        cached(&graph, &options(0.01));
+        assert!(!cached_deflections().is_empty());
+        clear_tessellation_cache();
+        assert!(cached_deflections().is_empty());
+        assert!(!cached(&graph, &options(0.01)).1);
+    }
+}
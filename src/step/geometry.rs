@@ -0,0 +1,493 @@
+// Dependencies
+use anyhow::Result;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Vector3};
+
+// Local modules
+use super::parser::{Entity, EntityGraph, Parameter, ParseError};
+use crate::geom::{NurbsCurve, NurbsSurface};
+
+/// Reads a list of exactly three coordinates from argument 1 of `entity`.
+fn coordinates(entity: &Entity) -> Result<[f64; 3]> {
+    let list = entity.list_arg(1)?;
+    if list.len() != 3 {
+        return Err(ParseError::instance(
+            entity.id,
+            format!("Expected 3 coordinates but found {}", list.len()),
+        )
+        .into());
+    }
+    let mut out = [0.0; 3];
+    for (o, p) in out.iter_mut().zip(list) {
+        *o = match p.as_f64() {
+            Some(v) => v,
+            None => {
+                return Err(ParseError::instance(entity.id, "Coordinate is not a number").into());
+            },
+        };
+    }
+    Ok(out)
+}
+
+/// Returns the coordinates of the `CARTESIAN_POINT` `id`.
+///
+/// `VERTEX_POINT`s are followed to the point they reference.
+pub fn cartesian_point(graph: &EntityGraph, id: u64) -> Result<[f64; 3]> {
+    let entity = graph.get(id)?;
+    match entity.keyword.as_str() {
+        "VERTEX_POINT" => cartesian_point(graph, entity.ref_arg(1)?),
+        _ => coordinates(graph.get_as(id, "CARTESIAN_POINT")?),
+    }
+}
+
+/// Returns the normalized `DIRECTION` `id`.
+///
+/// `VECTOR`s are followed to their orientation.
+pub fn direction(graph: &EntityGraph, id: u64) -> Result<[f64; 3]> {
+    let entity = graph.get(id)?;
+    if entity.keyword == "VECTOR" {
+        return direction(graph, entity.ref_arg(1)?);
+    }
+    let [x, y, z] = coordinates(graph.get_as(id, "DIRECTION")?)?;
+    let len = (x * x + y * y + z * z).sqrt();
+    if len == 0.0 {
+        return Err(ParseError::instance(id, "Zero length direction").into());
+    }
+    Ok([x / len, y / len, z / len])
+}
+
+/// Returns every 3D `CARTESIAN_POINT` in the graph in ascending id order.
+///
+/// 2D points (used for parameter space curves) are skipped.
+pub fn cartesian_points(graph: &EntityGraph) -> Result<Vec<(u64, [f64; 3])>> {
+    graph
+        .instances_of("CARTESIAN_POINT")
+        .filter(|e| e.list_arg(1).map(|l| l.len() != 2).unwrap_or(true))
+        .map(|e| Ok((e.id, coordinates(e)?)))
+        .collect()
+}
+
+/// An orthonormal frame read from an `AXIS2_PLACEMENT_3D`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub origin: Point3<f64>,
+    /// The reference direction, projected onto the plane normal to `z`
+    pub x: Vector3<f64>,
+    pub y: Vector3<f64>,
+    /// The placement's axis
+    pub z: Vector3<f64>,
+}
+
+impl Placement {
+    /// Returns the matrix mapping coordinates in this frame to the coordinates it's placed in.
+    pub fn matrix(&self) -> Matrix4<f64> {
+        Matrix4::from_cols(
+            self.x.extend(0.0),
+            self.y.extend(0.0),
+            self.z.extend(0.0),
+            self.origin.to_vec().extend(1.0),
+        )
+    }
+}
+
+/// Reads the `AXIS2_PLACEMENT_3D` `id` into an orthonormal frame.
+///
+/// The axis and reference direction are optional and default to `+Z` and `+X`. The reference
+/// direction need not be exactly perpendicular to the axis, so it's re-orthogonalized.
+pub fn placement(graph: &EntityGraph, id: u64) -> Result<Placement> {
+    let entity = graph.get_as(id, "AXIS2_PLACEMENT_3D")?;
+    let origin = Point3::from(cartesian_point(graph, entity.ref_arg(1)?)?);
+    let z = match entity.arg(2)?.as_ref() {
+        Some(id) => Vector3::from(direction(graph, id)?),
+        None => Vector3::unit_z(),
+    };
+    let ref_dir = match entity.arg(3)?.as_ref() {
+        Some(id) => Vector3::from(direction(graph, id)?),
+        None => Vector3::unit_x(),
+    };
+    let mut x = ref_dir - z * ref_dir.dot(z);
+    if x.magnitude2() < 1e-12 {
+        x = if z.x.abs() < 0.9 {
+            Vector3::unit_x()
+        } else {
+            Vector3::unit_y()
+        };
+        x -= z * x.dot(z);
+    }
+    let x = x.normalize();
+    Ok(Placement {
+        origin,
+        x,
+        y: z.cross(x),
+        z,
+    })
+}
+
+/// Reads the `AXIS2_PLACEMENT_3D` `id` into the matrix mapping coordinates in its frame to the
+/// coordinates it's placed in, see [`placement`].
+pub fn axis2_placement(graph: &EntityGraph, id: u64) -> Result<Matrix4<f64>> {
+    Ok(placement(graph, id)?.matrix())
+}
+
+/// Returns the numbers in the list `list`, an argument of instance `id`.
+fn numbers(id: u64, list: &Parameter) -> Result<Vec<f64>> {
+    let Some(list) = list.as_list() else {
+        return Err(ParseError::instance(id, "Expected a list of numbers").into());
+    };
+    list.iter()
+        .map(|p| {
+            p.as_f64()
+                .ok_or_else(|| ParseError::instance(id, "List item is not a number").into())
+        })
+        .collect()
+}
+
+/// Returns the `CARTESIAN_POINT`s referenced by the list `list`, an argument of instance `id`.
+fn points(graph: &EntityGraph, id: u64, list: &Parameter) -> Result<Vec<[f64; 3]>> {
+    let Some(list) = list.as_list() else {
+        return Err(ParseError::instance(id, "Expected a list of points").into());
+    };
+    list.iter()
+        .map(|p| match p.as_ref() {
+            Some(p) => cartesian_point(graph, p),
+            None => Err(ParseError::instance(id, "Control point is not a reference").into()),
+        })
+        .collect()
+}
+
+/// Repeats every knot in `knots` by its multiplicity in `multiplicities`, for a B-spline of
+/// degree `degree` with `count` control points.
+///
+/// The multiplicities must be non-negative integers adding up to `count + degree + 1`, which is
+/// checked before anything is expanded so a malformed file can't allocate an arbitrary amount.
+fn expand_knots(
+    id: u64,
+    multiplicities: &Parameter,
+    knots: &Parameter,
+    count: usize,
+    degree: usize,
+) -> Result<Vec<f64>> {
+    let multiplicities = numbers(id, multiplicities)?;
+    let knots = numbers(id, knots)?;
+    if multiplicities.len() != knots.len() {
+        return Err(ParseError::instance(id, "Knots and multiplicities differ in length").into());
+    }
+    if multiplicities.iter().any(|&m| m < 0.0 || m.fract() != 0.0) {
+        return Err(ParseError::instance(id, "Knot multiplicities must be whole numbers").into());
+    }
+    let expected = count + degree + 1;
+    let total: f64 = multiplicities.iter().sum();
+    if total != expected as f64 {
+        return Err(ParseError::instance(
+            id,
+            format!(
+                "Knot multiplicities add up to {} but {} control points of degree {} need {}",
+                total, count, degree, expected
+            ),
+        )
+        .into());
+    }
+    Ok(knots
+        .iter()
+        .zip(multiplicities)
+        .flat_map(|(&k, m)| std::iter::repeat_n(k, m as usize))
+        .collect())
+}
+
+/// Returns `degree` as a B-spline degree, which must be a whole number below `count`, the number
+/// of control points.
+fn spline_degree(id: u64, degree: &Parameter, count: usize) -> Result<usize> {
+    match degree.as_f64() {
+        Some(d) if d >= 0.0 && d.fract() == 0.0 && d < count as f64 => Ok(d as usize),
+        _ => Err(ParseError::instance(
+            id,
+            format!("Invalid B-spline degree for {} control points", count),
+        )
+        .into()),
+    }
+}
+
+/// Returns the arguments of the `B_SPLINE_{kind}` record, without its name, and of the
+/// `B_SPLINE_{kind}_WITH_KNOTS` record of `entity`.
+///
+/// Plain B-splines are written as a single `B_SPLINE_{kind}_WITH_KNOTS` instance, rational ones
+/// as a complex instance with one record per supertype.
+fn b_spline_records<'a>(
+    entity: &'a Entity,
+    kind: &str,
+    base_len: usize,
+) -> Result<(&'a [Parameter], &'a [Parameter])> {
+    let with_knots = format!("B_SPLINE_{}_WITH_KNOTS", kind);
+    if entity.keyword == with_knots && entity.partials.is_empty() {
+        if entity.args.len() <= base_len {
+            return Err(
+                ParseError::instance(entity.id, format!("{} is incomplete", with_knots)).into(),
+            );
+        }
+        return Ok((&entity.args[1..=base_len], &entity.args[base_len + 1..]));
+    }
+    match (
+        entity.record(&format!("B_SPLINE_{}", kind)),
+        entity.record(&with_knots),
+    ) {
+        (Some(base), Some(knots)) if base.len() >= base_len => Ok((base, knots)),
+        _ => Err(ParseError::instance(entity.id, format!("Expected a {}", with_knots)).into()),
+    }
+}
+
+/// Reads the `B_SPLINE_CURVE_WITH_KNOTS` `id`, rational or not.
+pub fn b_spline_curve(graph: &EntityGraph, id: u64) -> Result<NurbsCurve> {
+    let entity = graph.get(id)?;
+    let (base, knots) = b_spline_records(entity, "CURVE", 5)?;
+    if knots.len() < 2 {
+        return Err(ParseError::instance(id, "Malformed B-spline curve").into());
+    }
+    let points = points(graph, id, &base[1])?;
+    let degree = spline_degree(id, &base[0], points.len())?;
+    let knots = expand_knots(id, &knots[0], &knots[1], points.len(), degree)?;
+    let weights = match entity.record("RATIONAL_B_SPLINE_CURVE") {
+        Some(rational) => Some(numbers(id, rational.first().unwrap_or(&Parameter::Unset))?),
+        None => None,
+    };
+    NurbsCurve::new(degree, &points, weights.as_deref(), knots)
+        .map_err(|e| ParseError::instance(id, e.to_string()).into())
+}
+
+/// Reads the `B_SPLINE_SURFACE_WITH_KNOTS` `id`, rational or not.
+pub fn b_spline_surface(graph: &EntityGraph, id: u64) -> Result<NurbsSurface> {
+    let entity = graph.get(id)?;
+    let (base, knots) = b_spline_records(entity, "SURFACE", 7)?;
+    let Some(rows) = base[2].as_list() else {
+        return Err(ParseError::instance(id, "Malformed B-spline surface").into());
+    };
+    if knots.len() < 4 {
+        return Err(ParseError::instance(id, "Malformed B-spline surface").into());
+    }
+    let points = rows
+        .iter()
+        .map(|row| points(graph, id, row))
+        .collect::<Result<Vec<_>>>()?;
+    // Rows run along u, so there are `n_u` of them holding `n_v` points each
+    let n_u = points.len();
+    let n_v = points.first().map_or(0, Vec::len);
+    let u_degree = spline_degree(id, &base[0], n_u)?;
+    let v_degree = spline_degree(id, &base[1], n_v)?;
+    let u_knots = expand_knots(id, &knots[0], &knots[2], n_u, u_degree)?;
+    let v_knots = expand_knots(id, &knots[1], &knots[3], n_v, v_degree)?;
+    let weights = match entity
+        .record("RATIONAL_B_SPLINE_SURFACE")
+        .and_then(|r| r.first())
+    {
+        Some(Parameter::List(rows)) => Some(
+            rows.iter()
+                .map(|row| numbers(id, row))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        Some(_) => return Err(ParseError::instance(id, "Malformed B-spline weights").into()),
+        None => None,
+    };
+    NurbsSurface::new(
+        u_degree,
+        v_degree,
+        &points,
+        weights.as_deref(),
+        u_knots,
+        v_knots,
+    )
+    .map_err(|e| ParseError::instance(id, e.to_string()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::step::parse;
+
+    fn graph(data: &str) -> EntityGraph {
+        parse(&format!(
+            "ISO-10303-21;\nHEADER;\nENDSEC;\nDATA;\n{}\nENDSEC;\nEND-ISO-10303-21;\n",
+            data
+        ))
+        .unwrap()
+    }
+
+    /// A quadratic curve through three control points, with `knots` as its knot arguments.
+    fn quadratic(knots: &str) -> EntityGraph {
+        graph(&format!(
+            "#1 = CARTESIAN_POINT('',(0.,0.,0.));\n\
+             #2 = CARTESIAN_POINT('',(1.,1.,0.));\n\
+             #3 = CARTESIAN_POINT('',(2.,0.,0.));\n\
+             #4 = B_SPLINE_CURVE_WITH_KNOTS('',2,(#1,#2,#3),.UNSPECIFIED.,.F.,.F.,{},\
+             .UNSPECIFIED.);",
+            knots
+        ))
+    }
+
+    #[test]
+    fn reads_points_and_directions() {
+        let graph = graph(
+            "#1 = CARTESIAN_POINT('',(1.,2.,3.));\n\
+             #2 = VERTEX_POINT('',#1);\n\
+             #3 = DIRECTION('',(0.,3.,4.));\n\
+             #4 = VECTOR('',#3,10.);\n\
+             #5 = CARTESIAN_POINT('',(5.,6.));\n\
+             #6 = CARTESIAN_POINT('',(1.,2.,3.,4.));\n\
+             #7 = DIRECTION('',(1.,0.));\n\
+             #8 = DIRECTION('',(0.,0.,0.));",
+        );
+        assert_eq!(cartesian_point(&graph, 1).unwrap(), [1.0, 2.0, 3.0]);
+        assert_eq!(cartesian_point(&graph, 2).unwrap(), [1.0, 2.0, 3.0]);
+        assert_eq!(direction(&graph, 3).unwrap(), [0.0, 0.6, 0.8]);
+        // A vector's magnitude is dropped, only its orientation is kept
+        assert_eq!(direction(&graph, 4).unwrap(), [0.0, 0.6, 0.8]);
+
+        for (id, error) in [
+            (5, "Expected 3 coordinates but found 2"),
+            (6, "Expected 3 coordinates but found 4"),
+        ] {
+            let e = cartesian_point(&graph, id).unwrap_err();
+            assert!(e.to_string().contains(error), "#{}: {}", id, e);
+        }
+        let e = direction(&graph, 7).unwrap_err();
+        assert!(e.to_string().contains("found 2"), "{}", e);
+        assert!(direction(&graph, 8).is_err());
+        assert!(cartesian_point(&graph, 3).is_err());
+        assert!(direction(&graph, 1).is_err());
+
+        // The 2D point is skipped, the 4D one fails the whole list
+        assert!(cartesian_points(&graph).is_err());
+        let valid = self::graph(
+            "#1 = CARTESIAN_POINT('',(1.,2.,3.));\n\
+             #2 = VERTEX_POINT('',#1);\n\
+             #3 = CARTESIAN_POINT('',(5.,6.));\n\
+             #4 = CARTESIAN_POINT('',(-1.,0.,0.5));",
+        );
+        assert_eq!(
+            cartesian_points(&valid).unwrap(),
+            [(1, [1.0, 2.0, 3.0]), (4, [-1.0, 0.0, 0.5])]
+        );
+    }
+
+    #[test]
+    fn defaults_and_orthogonalizes_placement_axes() {
+        let graph = graph(
+            "#1 = CARTESIAN_POINT('',(1.,2.,3.));\n\
+             #2 = DIRECTION('',(0.,0.,2.));\n\
+             #3 = DIRECTION('',(1.,0.,1.));\n\
+             #4 = DIRECTION('',(1.,0.,0.));\n\
+             #5 = AXIS2_PLACEMENT_3D('',#1,$,$);\n\
+             #6 = AXIS2_PLACEMENT_3D('',#1,#2,#3);\n\
+             #7 = AXIS2_PLACEMENT_3D('',#1,#4,$);",
+        );
+        // No axis or reference direction: +Z and +X, so only the origin moves
+        assert_eq!(
+            axis2_placement(&graph, 5).unwrap(),
+            Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0))
+        );
+        // A slanted reference direction is projected into the plane normal to the axis
+        let frame = placement(&graph, 6).unwrap();
+        assert_eq!(frame.z, Vector3::unit_z());
+        assert!((frame.x - Vector3::unit_x()).magnitude() < 1e-12);
+        assert!((frame.y - Vector3::unit_y()).magnitude() < 1e-12);
+        // The default reference direction along the axis falls back to one across it
+        let frame = placement(&graph, 7).unwrap();
+        assert_eq!(frame.z, Vector3::unit_x());
+        assert!(frame.x.dot(frame.z).abs() < 1e-12 && (frame.x.magnitude() - 1.0).abs() < 1e-12);
+        assert!((frame.x.cross(frame.y) - frame.z).magnitude() < 1e-12);
+        let matrix = axis2_placement(&graph, 7).unwrap();
+        assert_eq!(matrix.z.truncate(), Vector3::unit_x());
+        assert_eq!(matrix.w, cgmath::Vector4::new(1.0, 2.0, 3.0, 1.0));
+    }
+
+    #[test]
+    fn reads_b_spline_curve() {
+        let curve = b_spline_curve(&quadratic("(3,3),(0.,1.)"), 4).unwrap();
+        assert_eq!(curve.domain(), [0.0, 1.0]);
+        assert_eq!(curve.evaluate(0.0), [0.0, 0.0, 0.0]);
+        let mid = curve.evaluate(0.5);
+        assert!((mid[0] - 1.0).abs() < 1e-12 && (mid[1] - 0.5).abs() < 1e-12);
+        assert_eq!(curve.evaluate(1.0), [2.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn reads_rational_b_spline_curve() {
+        let graph = graph(
+            "#1 = CARTESIAN_POINT('',(1.,0.,0.));\n\
+             #2 = CARTESIAN_POINT('',(1.,1.,0.));\n\
+             #3 = CARTESIAN_POINT('',(0.,1.,0.));\n\
+             #4 = ( BOUNDED_CURVE() B_SPLINE_CURVE(2,(#1,#2,#3),.CIRCULAR_ARC.,.F.,.F.) \
+             B_SPLINE_CURVE_WITH_KNOTS((3,3),(0.,1.),.UNSPECIFIED.) CURVE() \
+             GEOMETRIC_REPRESENTATION_ITEM() RATIONAL_B_SPLINE_CURVE((1.,0.7071067811865476,1.)) \
+             REPRESENTATION_ITEM('') );",
+        );
+        let curve = b_spline_curve(&graph, 4).unwrap();
+        // A quarter circle, every point lies on the unit circle
+        for i in 0..=10 {
+            let [x, y, _] = curve.evaluate(i as f64 / 10.0);
+            assert!((x.hypot(y) - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn rejects_mismatched_knot_multiplicities() {
+        // Three control points of degree 2 need six knots
+        for knots in ["(3,2),(0.,1.)", "(3,4),(0.,1.)", "(3,3,1),(0.,1.,2.)"] {
+            let error = b_spline_curve(&quadratic(knots), 4).unwrap_err();
+            assert!(
+                error.to_string().contains("add up to"),
+                "{}: {}",
+                knots,
+                error
+            );
+        }
+        assert!(b_spline_curve(&quadratic("(3,3),(0.,1.,2.)"), 4).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_multiplicities_without_expanding() {
+        for knots in [
+            "(-3,9),(0.,1.)",
+            "(2.5,3.5),(0.,1.)",
+            "(1000000000000000000,3),(0.,1.)",
+        ] {
+            assert!(b_spline_curve(&quadratic(knots), 4).is_err(), "{}", knots);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_degree() {
+        let graph = graph(
+            "#1 = CARTESIAN_POINT('',(0.,0.,0.));\n\
+             #2 = CARTESIAN_POINT('',(1.,0.,0.));\n\
+             #3 = B_SPLINE_CURVE_WITH_KNOTS('',5,(#1,#2),.UNSPECIFIED.,.F.,.F.,(4,4),(0.,1.),\
+             .UNSPECIFIED.);\n\
+             #4 = B_SPLINE_CURVE_WITH_KNOTS('',-1,(#1,#2),.UNSPECIFIED.,.F.,.F.,(1,1),(0.,1.),\
+             .UNSPECIFIED.);",
+        );
+        assert!(b_spline_curve(&graph, 3).is_err());
+        assert!(b_spline_curve(&graph, 4).is_err());
+    }
+
+    #[test]
+    fn checks_both_surface_knot_vectors() {
+        let points = "#1 = CARTESIAN_POINT('',(0.,0.,0.));\n\
+                      #2 = CARTESIAN_POINT('',(0.,1.,0.));\n\
+                      #3 = CARTESIAN_POINT('',(1.,0.,0.));\n\
+                      #4 = CARTESIAN_POINT('',(1.,1.,1.));\n\
+                      #5 = CARTESIAN_POINT('',(2.,0.,0.));\n\
+                      #6 = CARTESIAN_POINT('',(2.,1.,0.));\n";
+        // Three rows along u of two points along v: degree 2 in u, 1 in v
+        let surface = |u: &str, v: &str| {
+            graph(&format!(
+                "{}#7 = B_SPLINE_SURFACE_WITH_KNOTS('',2,1,((#1,#2),(#3,#4),(#5,#6)),\
+                 .UNSPECIFIED.,.F.,.F.,.F.,{},{},(0.,1.),(0.,1.),.UNSPECIFIED.);",
+                points, u, v
+            ))
+        };
+        let good = b_spline_surface(&surface("(3,3)", "(2,2)"), 7).unwrap();
+        assert_eq!(good.domain(), ([0.0, 1.0], [0.0, 1.0]));
+        assert_eq!(good.evaluate(1.0, 1.0), [2.0, 1.0, 0.0]);
+        // Swapping the multiplicities only fits if both directions are checked
+        assert!(b_spline_surface(&surface("(2,2)", "(3,3)"), 7).is_err());
+        assert!(b_spline_surface(&surface("(3,3)", "(3,3)"), 7).is_err());
+        assert!(b_spline_surface(&surface("(2,2)", "(2,2)"), 7).is_err());
+    }
+}
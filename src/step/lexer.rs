@@ -0,0 +1,492 @@
+// STD
+use std::io::BufRead;
+
+// Dependencies
+use anyhow::{Context, Result, anyhow, bail};
+
+/// A single lexical token of an ISO-10303-21 exchange structure.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    /// Entity instance name, e.g. `#123`
+    EntityId(u64),
+    /// Entity or type keyword, e.g. `CARTESIAN_POINT`
+    Keyword(String),
+    /// Quoted string with `''` escapes already collapsed
+    String(String),
+    Real(f64),
+    Integer(i64),
+    /// Enumeration value without the surrounding dots, e.g. `T` for `.T.`
+    Enumeration(String),
+    /// Hex encoded binary, e.g. `"0FF"`
+    Binary(String),
+    /// `$`, an unset optional argument
+    Unset,
+    /// `*`, an argument derived from a supertype
+    Derived,
+    LParen,
+    RParen,
+    Comma,
+    Equals,
+    Semicolon,
+}
+
+/// Tokenizer for the `DATA;` (and `HEADER;`) sections of a STEP file.
+///
+/// Comments (`/* ... */`) and whitespace are skipped.
+pub struct Lexer<'a> {
+    src: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Self::at_line(src, 1)
+    }
+
+    /// Like [`Lexer::new`], but counts lines from `line`, for `src` cut out of a larger file.
+    pub fn at_line(src: &'a str, line: usize) -> Self {
+        Self { src, pos: 0, line }
+    }
+
+    /// Returns the (1-based) line the lexer is currently at.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the input that hasn't been consumed yet.
+    pub fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.src.as_bytes().get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == b'\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_trivia(&mut self) -> Result<()> {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_ascii_whitespace() => {
+                    self.bump();
+                },
+                Some(b'/') if self.peek_at(1) == Some(b'*') => {
+                    let start = self.line;
+                    self.pos += 2;
+                    loop {
+                        match self.bump() {
+                            Some(b'*') if self.peek() == Some(b'/') => {
+                                self.pos += 1;
+                                break;
+                            },
+                            Some(_) => {},
+                            None => bail!("Unterminated comment starting on line {}", start),
+                        }
+                    }
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Consumes bytes while `pred` holds and returns the consumed slice.
+    fn take_while(&mut self, pred: impl Fn(u8) -> bool) -> &'a str {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !pred(c) {
+                break;
+            }
+            self.bump();
+        }
+        &self.src[start..self.pos]
+    }
+
+    fn lex_string(&mut self) -> Result<Token> {
+        let start = self.line;
+        // Opening quote
+        self.bump();
+        let mut s = String::new();
+        loop {
+            let rest = &self.src[self.pos..];
+            let Some(end) = rest.find('\'') else {
+                bail!("Unterminated string starting on line {}", start);
+            };
+            s.push_str(&rest[..end]);
+            self.line += rest[..end].matches('\n').count();
+            self.pos += end + 1;
+            // `''` is an escaped apostrophe
+            if self.peek() == Some(b'\'') {
+                self.pos += 1;
+                s.push('\'');
+            } else {
+                return Ok(Token::String(s));
+            }
+        }
+    }
+
+    fn lex_binary(&mut self) -> Result<Token> {
+        let line = self.line;
+        self.bump();
+        let hex = self.take_while(|c| c.is_ascii_hexdigit());
+        if self.bump() != Some(b'"') {
+            bail!("Malformed binary literal on line {}", line);
+        }
+        Ok(Token::Binary(hex.to_owned()))
+    }
+
+    fn lex_enumeration(&mut self) -> Result<Token> {
+        let line = self.line;
+        self.bump();
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == b'_');
+        if name.is_empty() || self.bump() != Some(b'.') {
+            bail!("Malformed enumeration on line {}", line);
+        }
+        Ok(Token::Enumeration(name.to_owned()))
+    }
+
+    fn lex_entity_id(&mut self) -> Result<Token> {
+        let line = self.line;
+        self.bump();
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        match digits.parse() {
+            Ok(id) => Ok(Token::EntityId(id)),
+            Err(_) => bail!("Malformed entity id '#{}' on line {}", digits, line),
+        }
+    }
+
+    fn lex_number(&mut self) -> Result<Token> {
+        let line = self.line;
+        let start = self.pos;
+        if let Some(b'+' | b'-') = self.peek() {
+            self.bump();
+        }
+        self.take_while(|c| c.is_ascii_digit());
+        let mut is_real = false;
+        if self.peek() == Some(b'.') {
+            is_real = true;
+            self.bump();
+            self.take_while(|c| c.is_ascii_digit());
+            if let Some(b'E' | b'e') = self.peek() {
+                self.bump();
+                if let Some(b'+' | b'-') = self.peek() {
+                    self.bump();
+                }
+                self.take_while(|c| c.is_ascii_digit());
+            }
+        }
+        let text = &self.src[start..self.pos];
+        let token = if is_real {
+            // Rust doesn't accept a trailing dot before the exponent ("1.E-3")
+            text.replacen(".E", ".0E", 1)
+                .replacen(".e", ".0e", 1)
+                .parse()
+                .map(Token::Real)
+                .ok()
+        } else {
+            text.parse().map(Token::Integer).ok()
+        };
+        match token {
+            Some(token) => Ok(token),
+            None => bail!("Malformed number '{}' on line {}", text, line),
+        }
+    }
+
+    /// Returns the next token, or `None` once the input is exhausted.
+    pub fn next_token(&mut self) -> Result<Option<Token>> {
+        self.skip_trivia()?;
+        let Some(c) = self.peek() else {
+            return Ok(None);
+        };
+        let token = match c {
+            b'(' | b')' | b',' | b'=' | b';' | b'$' | b'*' => {
+                self.bump();
+                match c {
+                    b'(' => Token::LParen,
+                    b')' => Token::RParen,
+                    b',' => Token::Comma,
+                    b'=' => Token::Equals,
+                    b';' => Token::Semicolon,
+                    b'$' => Token::Unset,
+                    _ => Token::Derived,
+                }
+            },
+            b'#' => self.lex_entity_id()?,
+            b'\'' => self.lex_string()?,
+            b'"' => self.lex_binary()?,
+            b'.' => self.lex_enumeration()?,
+            b'+' | b'-' | b'0'..=b'9' => self.lex_number()?,
+            b'A'..=b'Z' | b'a'..=b'z' | b'_' | b'!' => {
+                let start = self.pos;
+                self.bump();
+                self.take_while(|c| c.is_ascii_alphanumeric() || c == b'_');
+                Token::Keyword(self.src[start..self.pos].to_ascii_uppercase())
+            },
+            _ => bail!(
+                "Unexpected character '{}' on line {}",
+                self.src[self.pos..].chars().next().unwrap_or_default(),
+                self.line
+            ),
+        };
+        Ok(Some(token))
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token().transpose()
+    }
+}
+
+/// Splits an exchange structure read from a [`BufRead`] into `;` terminated statements, so only
+/// one statement needs to be in memory at a time.
+///
+/// Semicolons inside strings and comments don't end a statement.
+pub struct Statements<R> {
+    reader: R,
+    /// Line the next statement starts on
+    line: usize,
+    /// Bytes consumed so far
+    consumed: u64,
+}
+
+impl<R: BufRead> Statements<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: 1,
+            consumed: 0,
+        }
+    }
+
+    /// Returns the number of bytes consumed by the statements returned so far.
+    pub fn consumed(&self) -> u64 {
+        self.consumed
+    }
+
+    /// Returns the next statement including its `;`, and the (1-based) line it starts on.
+    ///
+    /// Leading whitespace and comments are part of the statement. Returns `None` once only
+    /// whitespace is left, text after the last `;` is returned as an unterminated statement.
+    pub fn next_statement(&mut self) -> Result<Option<(usize, String)>> {
+        let mut buf = Vec::new();
+        let mut scanned = 0;
+        let (mut in_string, mut in_comment) = (false, false);
+        loop {
+            let read = self
+                .reader
+                .read_until(b';', &mut buf)
+                .with_context(|| format!("Couldn't read the statement on line {}", self.line))?;
+            // Without a `;` at the end the input ran out
+            if read == 0 || buf.last() != Some(&b';') {
+                if buf.iter().all(u8::is_ascii_whitespace) {
+                    return Ok(None);
+                }
+                break;
+            }
+            // Every chunk ends at a `;`, so `/*` and `*/` never straddle two chunks
+            let mut i = scanned;
+            while i < buf.len() {
+                let next = buf.get(i + 1).copied();
+                match buf[i] {
+                    b'*' if in_comment && next == Some(b'/') => {
+                        in_comment = false;
+                        i += 1;
+                    },
+                    b'\'' if !in_comment => in_string = !in_string,
+                    b'/' if !in_string && !in_comment && next == Some(b'*') => {
+                        in_comment = true;
+                        i += 1;
+                    },
+                    _ => {},
+                }
+                i += 1;
+            }
+            scanned = buf.len();
+            if !in_string && !in_comment {
+                break;
+            }
+        }
+        let line = self.line;
+        self.line += buf.iter().filter(|&&c| c == b'\n').count();
+        self.consumed += buf.len() as u64;
+        let statement = String::from_utf8(buf)
+            .map_err(|_| anyhow!("Statement on line {} isn't valid UTF-8", line))?;
+        Ok(Some((line, statement)))
+    }
+}
+
+/// Tokenizes the whole of `src`.
+pub fn tokenize(src: &str) -> Result<Vec<Token>> {
+    Lexer::new(src).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    // STD
+    use std::io::BufReader;
+
+    use super::*;
+
+    #[test]
+    fn collapses_escaped_apostrophes() {
+        let tokens = tokenize("'it''s' ''").unwrap();
+        assert_eq!(
+            tokens,
+            [Token::String("it's".into()), Token::String(String::new())]
+        );
+    }
+
+    #[test]
+    fn skips_comments() {
+        let tokens = tokenize("/* #1 = 'x'; */ #2 /**/= /* multi\nline */ $;").unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::EntityId(2),
+                Token::Equals,
+                Token::Unset,
+                Token::Semicolon
+            ]
+        );
+        assert!(tokenize("#1 /* never closed").is_err());
+    }
+
+    #[test]
+    fn lexes_enumerations_and_special_arguments() {
+        let tokens = tokenize("(.T., .UNSPECIFIED., $, *)").unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::LParen,
+                Token::Enumeration("T".into()),
+                Token::Comma,
+                Token::Enumeration("UNSPECIFIED".into()),
+                Token::Comma,
+                Token::Unset,
+                Token::Comma,
+                Token::Derived,
+                Token::RParen,
+            ]
+        );
+        assert!(tokenize(".T").is_err());
+        assert!(tokenize("..").is_err());
+    }
+
+    #[test]
+    fn lexes_numbers() {
+        let tokens = tokenize("1 -2 3.5 1.E-3 -.5").unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Integer(1),
+                Token::Integer(-2),
+                Token::Real(3.5),
+                Token::Real(1e-3),
+                Token::Real(-0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_multi_line_shape_representation() {
+        let src = "#42 = ADVANCED_BREP_SHAPE_REPRESENTATION( 'part',\n\
+                   \t( #10, ( #11, #12 ),\n\
+                   $, * ), #7 );\n";
+        let mut lexer = Lexer::new(src);
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token().unwrap() {
+            tokens.push(token);
+        }
+        assert_eq!(lexer.line(), 4);
+        assert_eq!(tokens, tokenize(src).unwrap());
+        assert_eq!(
+            tokens,
+            [
+                Token::EntityId(42),
+                Token::Equals,
+                Token::Keyword("ADVANCED_BREP_SHAPE_REPRESENTATION".into()),
+                Token::LParen,
+                Token::String("part".into()),
+                Token::Comma,
+                Token::LParen,
+                Token::EntityId(10),
+                Token::Comma,
+                Token::LParen,
+                Token::EntityId(11),
+                Token::Comma,
+                Token::EntityId(12),
+                Token::RParen,
+                Token::Comma,
+                Token::Unset,
+                Token::Comma,
+                Token::Derived,
+                Token::RParen,
+                Token::Comma,
+                Token::EntityId(7),
+                Token::RParen,
+                Token::Semicolon,
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_lines() {
+        let mut lexer = Lexer::new("#1\n/* a\nb */ 'c\nd' #2");
+        assert_eq!(lexer.next_token().unwrap(), Some(Token::EntityId(1)));
+        assert_eq!(lexer.line(), 1);
+        lexer.next_token().unwrap();
+        assert_eq!(lexer.line(), 4);
+        assert_eq!(lexer.next_token().unwrap(), Some(Token::EntityId(2)));
+        assert_eq!(lexer.next_token().unwrap(), None);
+    }
+
+    #[test]
+    fn splits_statements_across_buffer_boundaries() {
+        let src = "ISO-10303-21;\n#1 = A('x;y', /* ; */ 1);\n\n#2 = B('it'';s');\n  ";
+        // A tiny buffer makes every statement straddle several reads
+        let mut statements = Statements::new(BufReader::with_capacity(3, src.as_bytes()));
+        let mut all = Vec::new();
+        while let Some(statement) = statements.next_statement().unwrap() {
+            all.push(statement);
+        }
+        assert_eq!(
+            all,
+            [
+                (1, "ISO-10303-21;".to_owned()),
+                (1, "\n#1 = A('x;y', /* ; */ 1);".to_owned()),
+                (2, "\n\n#2 = B('it'';s');".to_owned()),
+            ]
+        );
+        assert_eq!(statements.consumed(), src.trim_end().len() as u64);
+    }
+
+    #[test]
+    fn returns_unterminated_statement() {
+        let mut statements =
+            Statements::new(BufReader::with_capacity(2, "#1 = A(;\n#2".as_bytes()));
+        assert_eq!(
+            statements.next_statement().unwrap(),
+            Some((1, "#1 = A(;".to_owned()))
+        );
+        assert_eq!(
+            statements.next_statement().unwrap(),
+            Some((1, "\n#2".to_owned()))
+        );
+        assert_eq!(statements.next_statement().unwrap(), None);
+    }
+}
@@ -0,0 +1,25 @@
+//! Reader and writer for ISO-10303-21 (STEP) exchange files.
+pub mod assembly;
+pub mod cache;
+pub mod geometry;
+pub mod lexer;
+pub mod parser;
+pub mod style;
+pub mod tessellate;
+pub mod units;
+pub mod writer;
+
+pub use assembly::{AssemblyNode, PartPlacements, assembly_tree};
+pub use cache::{clear_tessellation_cache, tessellate_cached};
+pub use parser::{
+    Cancelled, Entity, EntityGraph, Parameter, ParseError, parse, parse_lenient, parse_reader,
+    parse_with_progress, read_step, read_step_cancellable, read_step_lenient,
+    read_step_with_progress,
+};
+pub use style::{DEFAULT_FACE_COLOR, face_colors};
+pub use tessellate::{
+    Polyline, Tessellation, TessellationOptions, UnsupportedSurface, edges, tessellate_faces,
+    triangles, unsupported_surface,
+};
+pub use units::{LengthUnit, model_units, normalize_units};
+pub use writer::{renumber, write_step};
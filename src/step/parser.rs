@@ -0,0 +1,878 @@
+// STD
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Dependencies
+use anyhow::{Context, Result};
+
+// Local modules
+use super::lexer::{Lexer, Statements, Token};
+
+/// A single argument of an entity instance.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Parameter {
+    /// Reference to another instance, e.g. `#12`. Resolved lazily through [`EntityGraph::get`].
+    Ref(u64),
+    String(String),
+    Real(f64),
+    Integer(i64),
+    /// Enumeration value without the dots, e.g. `T` for `.T.`
+    Enum(String),
+    Binary(String),
+    /// Typed value, e.g. `LENGTH_MEASURE(1.0E-06)`
+    Typed(String, Box<Parameter>),
+    List(Vec<Parameter>),
+    /// `$`
+    Unset,
+    /// `*`
+    Derived,
+}
+
+impl Parameter {
+    pub fn as_ref(&self) -> Option<u64> {
+        match self {
+            Parameter::Ref(id) => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Returns the numeric value of a real or integer parameter, looking through typed values.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Parameter::Real(v) => Some(*v),
+            Parameter::Integer(v) => Some(*v as f64),
+            Parameter::Typed(_, inner) => inner.as_f64(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Parameter::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Parameter]> {
+        match self {
+            Parameter::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a `.T.`/`.F.` enumeration.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Parameter::Enum(e) if e == "T" => Some(true),
+            Parameter::Enum(e) if e == "F" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// A parsed entity instance, e.g. `#10 = CARTESIAN_POINT('',(0.,0.,0.));`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+    pub id: u64,
+    pub keyword: String,
+    pub args: Vec<Parameter>,
+    /// The remaining partial records of a complex instance,
+    /// e.g. `( LENGTH_UNIT() NAMED_UNIT(*) SI_UNIT(.MILLI.,.METRE.) )`
+    pub partials: Vec<(String, Vec<Parameter>)>,
+}
+
+impl Entity {
+    /// Returns the arguments of the record named `keyword`, searching every partial record of a
+    /// complex instance.
+    pub fn record(&self, keyword: &str) -> Option<&[Parameter]> {
+        if self.keyword == keyword {
+            return Some(&self.args);
+        }
+        self.partials
+            .iter()
+            .find(|(k, _)| k == keyword)
+            .map(|(_, args)| args.as_slice())
+    }
+
+    /// Whether this instance is (or, for complex instances, contains) `keyword`.
+    pub fn is(&self, keyword: &str) -> bool {
+        self.record(keyword).is_some()
+    }
+
+    /// Returns argument `i`, failing with a [`ParseError`] naming this instance if it's missing.
+    pub fn arg(&self, i: usize) -> Result<&Parameter> {
+        match self.args.get(i) {
+            Some(arg) => Ok(arg),
+            None => Err(ParseError::instance(
+                self.id,
+                format!("{} has no argument {}", self.keyword, i),
+            )
+            .into()),
+        }
+    }
+
+    /// Returns argument `i` as an instance reference.
+    pub fn ref_arg(&self, i: usize) -> Result<u64> {
+        match self.arg(i)?.as_ref() {
+            Some(id) => Ok(id),
+            None => Err(ParseError::instance(
+                self.id,
+                format!("{} argument {} is not a reference", self.keyword, i),
+            )
+            .into()),
+        }
+    }
+
+    /// Returns the ids of every instance this one references, in argument order.
+    pub fn references(&self) -> Vec<u64> {
+        fn collect(p: &Parameter, out: &mut Vec<u64>) {
+            match p {
+                Parameter::Ref(id) => out.push(*id),
+                Parameter::Typed(_, inner) => collect(inner, out),
+                Parameter::List(items) => items.iter().for_each(|p| collect(p, out)),
+                _ => {},
+            }
+        }
+        let mut out = Vec::new();
+        self.args
+            .iter()
+            .chain(self.partials.iter().flat_map(|(_, args)| args))
+            .for_each(|p| collect(p, &mut out));
+        out
+    }
+
+    /// Returns argument `i` as a list.
+    pub fn list_arg(&self, i: usize) -> Result<&[Parameter]> {
+        match self.arg(i)?.as_list() {
+            Some(list) => Ok(list),
+            None => Err(ParseError::instance(
+                self.id,
+                format!("{} argument {} is not a list", self.keyword, i),
+            )
+            .into()),
+        }
+    }
+}
+
+/// The entity instances of a STEP file keyed by instance id.
+#[derive(Debug, Clone, Default)]
+pub struct EntityGraph {
+    pub entities: HashMap<u64, Entity>,
+    /// Schema names from the `FILE_SCHEMA` header entry
+    pub schemas: Vec<String>,
+}
+
+impl EntityGraph {
+    /// Looks up instance `id`, failing if it was never defined.
+    pub fn get(&self, id: u64) -> Result<&Entity> {
+        match self.entities.get(&id) {
+            Some(entity) => Ok(entity),
+            None => Err(ParseError::instance(id, "Reference to undefined instance").into()),
+        }
+    }
+
+    /// Looks up instance `id` and checks that it's a `keyword`.
+    pub fn get_as(&self, id: u64, keyword: &str) -> Result<&Entity> {
+        let entity = self.get(id)?;
+        if !entity.is(keyword) {
+            return Err(ParseError::instance(
+                id,
+                format!("Expected {} but found {}", keyword, entity.keyword),
+            )
+            .into());
+        }
+        Ok(entity)
+    }
+
+    /// Iterates over every instance that is a `keyword`, in ascending id order.
+    pub fn instances_of<'a>(&'a self, keyword: &'a str) -> impl Iterator<Item = &'a Entity> {
+        let mut ids: Vec<u64> = self
+            .entities
+            .values()
+            .filter(|e| e.is(keyword))
+            .map(|e| e.id)
+            .collect();
+        ids.sort_unstable();
+        ids.into_iter().map(|id| &self.entities[&id])
+    }
+
+    /// Fails on the lowest instance that references an undefined one.
+    pub fn check_references(&self) -> Result<()> {
+        let mut ids: Vec<u64> = self.entities.keys().copied().collect();
+        ids.sort_unstable();
+        for id in ids {
+            let entity = &self.entities[&id];
+            if let Some(missing) = entity
+                .references()
+                .into_iter()
+                .find(|r| !self.entities.contains_key(r))
+            {
+                return Err(ParseError::instance(
+                    id,
+                    format!(
+                        "{} references undefined instance #{}",
+                        entity.keyword, missing
+                    ),
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+/// Error raised while parsing, carrying the offending instance id where known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub instance: Option<u64>,
+    pub line: Option<usize>,
+    pub msg: String,
+}
+
+impl ParseError {
+    /// Error about the structure of the file as a whole, rather than a single instance.
+    pub fn new(msg: impl Into<String>) -> Self {
+        Self {
+            instance: None,
+            line: None,
+            msg: msg.into(),
+        }
+    }
+
+    pub fn instance(id: u64, msg: impl Into<String>) -> Self {
+        Self {
+            instance: Some(id),
+            line: None,
+            msg: msg.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        if let Some(id) = self.instance {
+            write!(f, "#{}: ", id)?;
+        }
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Error returned by imports stopped through their cancellation flag, see
+/// [`read_step_cancellable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Import cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Fails with [`Cancelled`] once `cancel` is set.
+pub(crate) fn check_cancelled(cancel: Option<&AtomicBool>) -> Result<()> {
+    match cancel {
+        Some(cancel) if cancel.load(Ordering::Relaxed) => Err(Cancelled.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Number of arguments of the entities the importer relies on.
+///
+/// Instances of these types with any other argument count are rejected at parse time, everything
+/// else is accepted as is.
+fn expected_arity(keyword: &str) -> Option<usize> {
+    Some(match keyword {
+        "CARTESIAN_POINT"
+        | "DIRECTION"
+        | "VERTEX_POINT"
+        | "EDGE_LOOP"
+        | "POLY_LOOP"
+        | "VERTEX_LOOP"
+        | "PLANE"
+        | "CLOSED_SHELL"
+        | "OPEN_SHELL"
+        | "MANIFOLD_SOLID_BREP" => 2,
+        "LINE"
+        | "VECTOR"
+        | "CIRCLE"
+        | "FACE_BOUND"
+        | "FACE_OUTER_BOUND"
+        | "CYLINDRICAL_SURFACE"
+        | "SPHERICAL_SURFACE"
+        | "ADVANCED_BREP_SHAPE_REPRESENTATION" => 3,
+        "AXIS2_PLACEMENT_3D" | "CONICAL_SURFACE" | "ADVANCED_FACE" | "FACE_SURFACE" => 4,
+        "EDGE_CURVE" | "ORIENTED_EDGE" => 5,
+        "B_SPLINE_CURVE_WITH_KNOTS" => 9,
+        "B_SPLINE_SURFACE_WITH_KNOTS" => 13,
+        _ => return None,
+    })
+}
+
+/// Smallest advance in the parsed fraction of a file that's reported to a progress callback
+const PROGRESS_STEP: f32 = 0.01;
+/// Deepest nesting of lists and typed parameters accepted, deeper ones would overflow the stack
+/// of the recursive descent. Real files nest a handful of levels at most.
+const MAX_NESTING: usize = 128;
+
+/// Recursive descent parser over the tokens of a STEP file.
+struct Parser<'a> {
+    lexer: Lexer<'a>,
+    peeked: Option<Token>,
+    /// Instance currently being parsed, for error reporting
+    instance: Option<u64>,
+    /// Number of lists and typed parameters the current parameter is nested in
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Parses `src`, a statement cut out of a file at `line`.
+    fn new(src: &'a str, line: usize) -> Self {
+        Self {
+            lexer: Lexer::at_line(src, line),
+            peeked: None,
+            instance: None,
+            depth: 0,
+        }
+    }
+
+    fn error(&self, msg: impl Into<String>) -> anyhow::Error {
+        ParseError {
+            instance: self.instance,
+            line: Some(self.lexer.line()),
+            msg: msg.into(),
+        }
+        .into()
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token>> {
+        if self.peeked.is_none() {
+            self.peeked = self.lexer.next_token()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = match self.peeked.take() {
+            Some(token) => Some(token),
+            None => self.lexer.next_token()?,
+        };
+        match token {
+            Some(token) => Ok(token),
+            None => Err(self.error("Unexpected end of file")),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        let token = self.next()?;
+        if token != expected {
+            return Err(self.error(format!("Expected {:?} but found {:?}", expected, token)));
+        }
+        Ok(())
+    }
+
+    fn expect_keyword(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Keyword(k) => Ok(k),
+            token => Err(self.error(format!("Expected keyword but found {:?}", token))),
+        }
+    }
+
+    fn parameter(&mut self) -> Result<Parameter> {
+        if self.depth >= MAX_NESTING {
+            return Err(self.error(format!(
+                "Parameters nested deeper than {} levels",
+                MAX_NESTING
+            )));
+        }
+        self.depth += 1;
+        let parameter = self.parameter_at_depth();
+        self.depth -= 1;
+        parameter
+    }
+
+    /// Parses a parameter, [`Parser::parameter`] keeps track of the nesting.
+    fn parameter_at_depth(&mut self) -> Result<Parameter> {
+        Ok(match self.next()? {
+            Token::EntityId(id) => Parameter::Ref(id),
+            Token::String(s) => Parameter::String(s),
+            Token::Real(v) => Parameter::Real(v),
+            Token::Integer(v) => Parameter::Integer(v),
+            Token::Enumeration(e) => Parameter::Enum(e),
+            Token::Binary(b) => Parameter::Binary(b),
+            Token::Unset => Parameter::Unset,
+            Token::Derived => Parameter::Derived,
+            Token::LParen => Parameter::List(self.parameter_list_tail()?),
+            Token::Keyword(k) => {
+                self.expect(Token::LParen)?;
+                let inner = self.parameter()?;
+                self.expect(Token::RParen)?;
+                Parameter::Typed(k, Box::new(inner))
+            },
+            token => return Err(self.error(format!("Unexpected {:?} in parameter list", token))),
+        })
+    }
+
+    /// Parses the remainder of a parenthesized list whose `(` was already consumed.
+    fn parameter_list_tail(&mut self) -> Result<Vec<Parameter>> {
+        let mut params = Vec::new();
+        if self.peek()? == Some(&Token::RParen) {
+            self.next()?;
+            return Ok(params);
+        }
+        loop {
+            params.push(self.parameter()?);
+            match self.next()? {
+                Token::Comma => {},
+                Token::RParen => return Ok(params),
+                token => {
+                    return Err(self.error(format!("Expected ',' or ')' but found {:?}", token)));
+                },
+            }
+        }
+    }
+
+    /// Parses `KEYWORD(args)`.
+    fn record(&mut self) -> Result<(String, Vec<Parameter>)> {
+        let keyword = self.expect_keyword()?;
+        self.expect(Token::LParen)?;
+        Ok((keyword, self.parameter_list_tail()?))
+    }
+
+    /// Parses `#id = KEYWORD(args);` or `#id = ( A(args) B(args) ... );`.
+    fn instance(&mut self) -> Result<Entity> {
+        self.instance = None;
+        let id = match self.next()? {
+            Token::EntityId(id) => id,
+            token => return Err(self.error(format!("Expected instance id but found {:?}", token))),
+        };
+        self.instance = Some(id);
+        self.expect(Token::Equals)?;
+
+        let entity = if self.peek()? == Some(&Token::LParen) {
+            self.next()?;
+            let mut records = Vec::new();
+            while self.peek()? != Some(&Token::RParen) {
+                records.push(self.record()?);
+            }
+            self.next()?;
+            if records.is_empty() {
+                return Err(self.error("Empty complex instance"));
+            }
+            let (keyword, args) = records.remove(0);
+            Entity {
+                id,
+                keyword,
+                args,
+                partials: records,
+            }
+        } else {
+            let (keyword, args) = self.record()?;
+            if let Some(arity) = expected_arity(&keyword)
+                && args.len() != arity
+            {
+                return Err(self.error(format!(
+                    "{} takes {} arguments but {} were given",
+                    keyword,
+                    arity,
+                    args.len()
+                )));
+            }
+            Entity {
+                id,
+                keyword,
+                args,
+                partials: Vec::new(),
+            }
+        };
+        self.expect(Token::Semicolon)?;
+        self.instance = None;
+        Ok(entity)
+    }
+
+    /// Parses a single header entry, collecting the schema names. Returns `false` for the
+    /// `ENDSEC;` closing the header.
+    fn header_entry(&mut self, graph: &mut EntityGraph) -> Result<bool> {
+        let keyword = self.expect_keyword()?;
+        if keyword == "ENDSEC" {
+            self.expect(Token::Semicolon)?;
+            return Ok(false);
+        }
+        self.expect(Token::LParen)?;
+        let args = self.parameter_list_tail()?;
+        self.expect(Token::Semicolon)?;
+        if keyword == "FILE_SCHEMA"
+            && let Some(Parameter::List(names)) = args.first()
+        {
+            graph
+                .schemas
+                .extend(names.iter().filter_map(|p| p.as_str().map(str::to_owned)));
+        }
+        Ok(true)
+    }
+
+    /// Parses `DATA;`, or `DATA(...);` with the optional parameter list of later editions of
+    /// Part 21.
+    fn data_start(&mut self) -> Result<()> {
+        self.expect(Token::Keyword("DATA".into()))?;
+        if self.peek()? == Some(&Token::LParen) {
+            self.next()?;
+            self.parameter_list_tail()?;
+        }
+        self.expect(Token::Semicolon)
+    }
+
+    /// Parses a single instance into `graph`. Returns `false` for the `ENDSEC;` closing the
+    /// section.
+    fn data_entry(&mut self, graph: &mut EntityGraph) -> Result<bool> {
+        if let Some(Token::Keyword(k)) = self.peek()?
+            && k == "ENDSEC"
+        {
+            self.next()?;
+            self.expect(Token::Semicolon)?;
+            return Ok(false);
+        }
+        let entity = self.instance()?;
+        // The first definition wins, lenient parsing skips the later ones
+        if graph.entities.contains_key(&entity.id) {
+            self.instance = Some(entity.id);
+            return Err(self.error("Instance defined more than once"));
+        }
+        graph.entities.insert(entity.id, entity);
+        Ok(true)
+    }
+}
+
+/// Returns the next statement of `statements`, which must exist before `section` ends.
+fn next_in_section(
+    statements: &mut Statements<impl BufRead>,
+    section: &str,
+) -> Result<(usize, String)> {
+    match statements.next_statement()? {
+        Some(statement) => Ok(statement),
+        None => Err(
+            ParseError::new(format!("Unexpected end of file in the {} section", section)).into(),
+        ),
+    }
+}
+
+/// Returns whether `statement` is the `DATA;` opening a section, leading comments included.
+///
+/// Only the first token is lexed, so `END-ISO-10303-21;` doesn't raise an error.
+fn opens_data(statement: &str) -> bool {
+    matches!(Lexer::new(statement).next_token(), Ok(Some(Token::Keyword(k))) if k == "DATA")
+}
+
+/// Parses the statements of `reader` into an [`EntityGraph`], one at a time, calling `progress`
+/// with the number of bytes consumed after each.
+///
+/// Instances that fail to parse are left out and collected in `skipped` if given, otherwise the
+/// first one fails the whole file. `cancel` is checked after each statement too.
+fn parse_statements(
+    reader: impl BufRead,
+    progress: &mut dyn FnMut(u64),
+    mut skipped: Option<&mut Vec<ParseError>>,
+    cancel: Option<&AtomicBool>,
+) -> Result<EntityGraph> {
+    let mut statements = Statements::new(reader);
+    let mut graph = EntityGraph::default();
+
+    // Anything before the header is skipped, including the `ISO-10303-21;` opening the file
+    loop {
+        let Some((_, statement)) = statements.next_statement()? else {
+            return Err(ParseError::new("Not a STEP file: missing HEADER section").into());
+        };
+        if statement
+            .trim_end_matches(';')
+            .trim_end()
+            .ends_with("HEADER")
+        {
+            break;
+        }
+    }
+    loop {
+        let (line, statement) = next_in_section(&mut statements, "HEADER")?;
+        if !Parser::new(&statement, line).header_entry(&mut graph)? {
+            break;
+        }
+    }
+
+    // A file may contain several DATA sections, the first other statement ends the exchange
+    // structure (usually `END-ISO-10303-21;`, which isn't lexable so it must not be parsed)
+    let mut sections = 0;
+    while let Some((line, statement)) = statements.next_statement()? {
+        if !opens_data(&statement) {
+            break;
+        }
+        sections += 1;
+        Parser::new(&statement, line).data_start()?;
+        loop {
+            let (line, statement) = next_in_section(&mut statements, "DATA")?;
+            let mut parser = Parser::new(&statement, line);
+            match parser.data_entry(&mut graph) {
+                Ok(true) => {},
+                Ok(false) => break,
+                Err(e) => {
+                    // Lexer errors are raised without the instance they were found in
+                    let mut error = e.downcast::<ParseError>().unwrap_or_else(|e| ParseError {
+                        instance: parser.instance,
+                        line: None,
+                        msg: format!("{:#}", e),
+                    });
+                    error.line.get_or_insert(line);
+                    let Some(skipped) = skipped.as_deref_mut() else {
+                        return Err(error.into());
+                    };
+                    // Statements end at their `;`, so a bad one can't derail the ones after it
+                    log::warn!("Skipping instance: {}", error);
+                    skipped.push(error);
+                },
+            }
+            progress(statements.consumed());
+            check_cancelled(cancel)?;
+        }
+    }
+    if sections == 0 {
+        return Err(ParseError::new("Not a STEP file: missing DATA section").into());
+    }
+    Ok(graph)
+}
+
+/// Adapts `progress` to take the number of bytes consumed out of `total`, leaving out advances
+/// smaller than [`PROGRESS_STEP`].
+fn fraction_of(total: u64, progress: &mut dyn FnMut(f32)) -> impl FnMut(u64) + '_ {
+    let mut reported = 0.0;
+    move |consumed| {
+        let done = consumed as f32 / total.max(1) as f32;
+        if done - reported >= PROGRESS_STEP {
+            progress(done);
+            reported = done;
+        }
+    }
+}
+
+/// Parses a STEP file into an [`EntityGraph`].
+///
+/// References between instances aren't checked here, so instances may be referenced before (or
+/// without) being defined. Dangling references surface when they're looked up with
+/// [`EntityGraph::get`].
+pub fn parse(src: &str) -> Result<EntityGraph> {
+    parse_with_progress(src, &mut |_| {})
+}
+
+/// Like [`parse`], but calls `progress` with the fraction of `src` parsed so far, from 0 to 1.
+///
+/// Calls are spaced at least a hundredth apart, the last one reports 1 once parsing succeeded.
+pub fn parse_with_progress(src: &str, progress: &mut dyn FnMut(f32)) -> Result<EntityGraph> {
+    let graph = parse_statements(
+        src.as_bytes(),
+        &mut fraction_of(src.len() as u64, progress),
+        None,
+        None,
+    )?;
+    progress(1.0);
+    Ok(graph)
+}
+
+/// Like [`parse`], but leaves out instances that fail to parse instead of failing, returning the
+/// errors they raised alongside the graph.
+///
+/// Only errors confined to a single instance are skipped, a file that isn't structured like a
+/// STEP file still fails. References to the skipped instances dangle.
+pub fn parse_lenient(src: &str) -> Result<(EntityGraph, Vec<ParseError>)> {
+    let mut skipped = Vec::new();
+    let graph = parse_statements(src.as_bytes(), &mut |_| {}, Some(&mut skipped), None)?;
+    Ok((graph, skipped))
+}
+
+/// Parses a STEP file from `reader` one statement at a time, without holding the whole file in
+/// memory. See [`parse`].
+pub fn parse_reader(reader: impl BufRead) -> Result<EntityGraph> {
+    parse_statements(reader, &mut |_| {}, None, None)
+}
+
+/// Reads and parses a STEP file.
+pub fn read_step(path: &Path) -> Result<EntityGraph> {
+    read_step_with_progress(path, &mut |_| {})
+}
+
+/// Like [`read_step`], but reports how much of the file has been parsed, see
+/// [`parse_with_progress`].
+///
+/// The file is streamed through [`parse_reader`], so huge files don't need to fit in memory as
+/// text next to their entity graph.
+pub fn read_step_with_progress(path: &Path, progress: &mut dyn FnMut(f32)) -> Result<EntityGraph> {
+    read_step_file(path, progress, None, None)
+}
+
+/// Like [`read_step_with_progress`], but stops with a [`Cancelled`] error once `cancel` is set,
+/// which another thread may do at any time to abandon a slow import.
+pub fn read_step_cancellable(
+    path: &Path,
+    progress: &mut dyn FnMut(f32),
+    cancel: &AtomicBool,
+) -> Result<EntityGraph> {
+    read_step_file(path, progress, None, Some(cancel))
+}
+
+/// Like [`read_step_with_progress`], but skips instances that fail to parse, see
+/// [`parse_lenient`].
+pub fn read_step_lenient(
+    path: &Path,
+    progress: &mut dyn FnMut(f32),
+) -> Result<(EntityGraph, Vec<ParseError>)> {
+    let mut skipped = Vec::new();
+    let graph = read_step_file(path, progress, Some(&mut skipped), None)?;
+    Ok((graph, skipped))
+}
+
+fn read_step_file(
+    path: &Path,
+    progress: &mut dyn FnMut(f32),
+    skipped: Option<&mut Vec<ParseError>>,
+    cancel: Option<&AtomicBool>,
+) -> Result<EntityGraph> {
+    let file = File::open(path).with_context(|| format!("Couldn't read '{}'", path.display()))?;
+    let total = file.metadata().map(|m| m.len()).unwrap_or_default();
+    let graph = parse_statements(
+        BufReader::new(file),
+        &mut fraction_of(total, progress),
+        skipped,
+        cancel,
+    )
+    .with_context(|| format!("Couldn't parse '{}'", path.display()))?;
+    progress(1.0);
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "ISO-10303-21;\nHEADER;\nFILE_SCHEMA(('AUTOMOTIVE_DESIGN'));\nENDSEC;\n";
+
+    fn file(data: &str) -> String {
+        format!("{}{}END-ISO-10303-21;\n", HEADER, data)
+    }
+
+    #[test]
+    fn parses_instances() {
+        let graph = parse(&file(
+            "DATA;\n#1 = CARTESIAN_POINT('',(0.,1.,2.));\n\
+             #2 = ( LENGTH_UNIT() NAMED_UNIT(*) SI_UNIT(.MILLI.,.METRE.) );\nENDSEC;\n",
+        ))
+        .unwrap();
+        assert_eq!(graph.schemas, ["AUTOMOTIVE_DESIGN"]);
+        assert_eq!(graph.len(), 2);
+        let point = graph.get_as(1, "CARTESIAN_POINT").unwrap();
+        assert_eq!(point.list_arg(1).unwrap()[2], Parameter::Real(2.0));
+        let unit = graph.get(2).unwrap();
+        assert!(unit.is("SI_UNIT") && unit.is("NAMED_UNIT"));
+        assert_eq!(
+            unit.record("SI_UNIT").unwrap()[0],
+            Parameter::Enum("MILLI".into())
+        );
+    }
+
+    #[test]
+    fn skips_comments_before_data() {
+        let graph = parse(&file(
+            "/* generated; by hand */\nDATA;\n#1 = DIRECTION('',(0.,0.,1.));\nENDSEC;\n",
+        ))
+        .unwrap();
+        assert_eq!(graph.len(), 1);
+    }
+
+    #[test]
+    fn reads_every_data_section() {
+        let graph = parse(&file(
+            "DATA;\n#1 = DIRECTION('',(1.,0.,0.));\nENDSEC;\n\
+             DATA;\n#2 = DIRECTION('',(0.,1.,0.));\nENDSEC;\n",
+        ))
+        .unwrap();
+        assert_eq!(graph.len(), 2);
+    }
+
+    #[test]
+    fn rejects_missing_data_section() {
+        let error = parse(&file("")).unwrap_err();
+        let error = error.downcast::<ParseError>().unwrap();
+        assert!(error.msg.contains("missing DATA section"), "{}", error);
+        assert!(parse("DATA;\n#1 = DIRECTION('',(1.,0.,0.));\nENDSEC;\n").is_err());
+    }
+
+    #[test]
+    fn keeps_first_duplicate_definition() {
+        let src = file(
+            "DATA;\n#1 = DIRECTION('first',(1.,0.,0.));\n\
+             #1 = DIRECTION('second',(0.,1.,0.));\nENDSEC;\n",
+        );
+        let error = parse(&src).unwrap_err().downcast::<ParseError>().unwrap();
+        assert_eq!(error.instance, Some(1));
+        assert_eq!(error.line, Some(7));
+
+        let (graph, skipped) = parse_lenient(&src).unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(
+            graph.get(1).unwrap().args[0],
+            Parameter::String("first".into())
+        );
+    }
+
+    #[test]
+    fn lenient_parsing_skips_bad_instances() {
+        let (graph, skipped) = parse_lenient(&file(
+            "DATA;\n#1 = DIRECTION('',(1.,0.,0.));\n#2 = CARTESIAN_POINT('',(0.,0.,0.),3);\n\
+             #3 = DIRECTION('',(1.,0.,0.) ;\n#4 = VERTEX_POINT('',#1);\nENDSEC;\n",
+        ))
+        .unwrap();
+        assert_eq!(graph.len(), 2);
+        let instances: Vec<_> = skipped.iter().map(|e| e.instance).collect();
+        assert_eq!(instances, [Some(2), Some(3)]);
+        assert!(graph.get(4).is_ok());
+    }
+
+    #[test]
+    fn reports_dangling_references() {
+        let graph = parse(&file("DATA;\n#1 = VERTEX_POINT('',#2);\nENDSEC;\n")).unwrap();
+        let error = graph.check_references().unwrap_err();
+        assert!(error.to_string().contains("#2"), "{}", error);
+        assert!(graph.get(2).is_err());
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let nested = format!(
+            "{}0{}",
+            "(".repeat(MAX_NESTING + 1),
+            ")".repeat(MAX_NESTING + 1)
+        );
+        let src = file(&format!("DATA;\n#1 = A({});\nENDSEC;\n", nested));
+        assert!(parse(&src).is_err());
+    }
+
+    #[test]
+    fn reader_matches_str() {
+        let src = file("DATA;\n#1 = DIRECTION('a;b',(1.,0.,0.));\nENDSEC;\n");
+        let graph = parse_reader(BufReader::with_capacity(4, src.as_bytes())).unwrap();
+        assert_eq!(graph.entities, parse(&src).unwrap().entities);
+    }
+}
@@ -0,0 +1,184 @@
+// STD
+use std::collections::{HashMap, HashSet};
+
+// Dependencies
+use anyhow::Result;
+
+// Local modules
+use super::parser::{EntityGraph, ParseError};
+use super::tessellate::FaceId;
+
+/// Color of the faces no style applies to
+pub const DEFAULT_FACE_COLOR: [f32; 3] = [0.7, 0.7, 0.7];
+
+/// Returns the color of the `COLOUR_RGB` or `DRAUGHTING_PRE_DEFINED_COLOUR` `id`.
+fn colour(graph: &EntityGraph, id: u64) -> Result<[f32; 3]> {
+    let entity = graph.get(id)?;
+    if entity.is("COLOUR_RGB") {
+        let mut rgb = [0.0; 3];
+        for (i, channel) in rgb.iter_mut().enumerate() {
+            *channel = match entity.arg(i + 1)?.as_f64() {
+                Some(value) => value.clamp(0.0, 1.0) as f32,
+                None => {
+                    return Err(ParseError::instance(id, "Colour component is not a number").into());
+                },
+            };
+        }
+        return Ok(rgb);
+    }
+    if entity.is("DRAUGHTING_PRE_DEFINED_COLOUR") {
+        let name = entity.arg(0)?.as_str().unwrap_or_default();
+        return match name {
+            "red" => Ok([1.0, 0.0, 0.0]),
+            "green" => Ok([0.0, 1.0, 0.0]),
+            "blue" => Ok([0.0, 0.0, 1.0]),
+            "yellow" => Ok([1.0, 1.0, 0.0]),
+            "magenta" => Ok([1.0, 0.0, 1.0]),
+            "cyan" => Ok([0.0, 1.0, 1.0]),
+            "black" => Ok([0.0, 0.0, 0.0]),
+            "white" => Ok([1.0, 1.0, 1.0]),
+            _ => Err(ParseError::instance(id, format!("Unknown colour '{}'", name)).into()),
+        };
+    }
+    Err(ParseError::instance(
+        id,
+        format!("Expected a colour but found {}", entity.keyword),
+    )
+    .into())
+}
+
+/// Returns the fill color of the `SURFACE_SIDE_STYLE` `id`, `None` if none of its elements has
+/// one.
+fn side_style_colour(graph: &EntityGraph, id: u64) -> Result<Option<[f32; 3]>> {
+    let side_style = graph.get_as(id, "SURFACE_SIDE_STYLE")?;
+    for element in side_style.list_arg(1)?.iter().filter_map(|p| p.as_ref()) {
+        let element = graph.get(element)?;
+        if element.is("SURFACE_STYLE_FILL_AREA") {
+            let fill_area = graph.get_as(element.ref_arg(0)?, "FILL_AREA_STYLE")?;
+            for fill in fill_area.list_arg(1)?.iter().filter_map(|p| p.as_ref()) {
+                let fill = graph.get(fill)?;
+                if fill.is("FILL_AREA_STYLE_COLOUR") {
+                    return colour(graph, fill.ref_arg(1)?).map(Some);
+                }
+            }
+        } else if element.is("SURFACE_STYLE_RENDERING") {
+            return colour(graph, element.ref_arg(1)?).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the surface color the `STYLED_ITEM` `id` assigns, `None` if it only styles curves or
+/// points.
+///
+/// Both sides of a face are drawn alike, a style for either side is taken.
+fn styled_item_colour(graph: &EntityGraph, id: u64) -> Result<Option<[f32; 3]>> {
+    let styled_item = graph.get(id)?;
+    for assignment in styled_item.list_arg(1)?.iter().filter_map(|p| p.as_ref()) {
+        // PRESENTATION_STYLE_ASSIGNMENT or its subtype PRESENTATION_STYLE_BY_CONTEXT
+        let assignment = graph.get(assignment)?;
+        for style in assignment.list_arg(0)?.iter().filter_map(|p| p.as_ref()) {
+            let style = graph.get(style)?;
+            if !style.is("SURFACE_STYLE_USAGE") {
+                continue;
+            }
+            if let Some(colour) = side_style_colour(graph, style.ref_arg(1)?)? {
+                return Ok(Some(colour));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the `ADVANCED_FACE`s of the representation item `id`, following its references down
+/// to the faces, e.g. from a `MANIFOLD_SOLID_BREP` through its shells.
+fn item_faces(graph: &EntityGraph, id: u64) -> Result<Vec<FaceId>> {
+    let mut faces = Vec::new();
+    let mut visited = HashSet::from([id]);
+    let mut stack = vec![id];
+    while let Some(id) = stack.pop() {
+        let entity = graph.get(id)?;
+        if entity.is("ADVANCED_FACE") {
+            faces.push(id);
+            continue;
+        }
+        stack.extend(
+            entity
+                .references()
+                .into_iter()
+                .filter(|&r| visited.insert(r)),
+        );
+    }
+    faces.sort_unstable();
+    Ok(faces)
+}
+
+/// Reads the authored color of every styled `ADVANCED_FACE`, from the `STYLED_ITEM`s and their
+/// `PRESENTATION_STYLE_ASSIGNMENT`s down to the `COLOUR_RGB`.
+///
+/// A style on a solid or shell colors all of its faces, a style on a face takes precedence over
+/// it. Faces left out have no authored color and are drawn in [`DEFAULT_FACE_COLOR`]. Styles that
+/// can't be read are skipped with a warning rather than failing the import.
+pub fn face_colors(graph: &EntityGraph) -> HashMap<FaceId, [f32; 3]> {
+    let mut styled = Vec::new();
+    let styled_items = graph
+        .instances_of("STYLED_ITEM")
+        .chain(graph.instances_of("OVER_RIDING_STYLED_ITEM"));
+    for styled_item in styled_items {
+        let faces = styled_item_colour(graph, styled_item.id).and_then(|colour| {
+            let Some(colour) = colour else {
+                return Ok(None);
+            };
+            let item = styled_item.ref_arg(2)?;
+            let is_face = graph.get(item)?.is("ADVANCED_FACE");
+            Ok(Some((is_face, item_faces(graph, item)?, colour)))
+        });
+        match faces {
+            Ok(Some(faces)) => styled.push(faces),
+            Ok(None) => {},
+            Err(e) => log::warn!("Ignoring style #{}: {:#}", styled_item.id, e),
+        }
+    }
+    // Faces last, so they override the solids they belong to
+    styled.sort_by_key(|&(is_face, _, _)| is_face);
+    let mut colors = HashMap::new();
+    for (_, faces, colour) in styled {
+        for face in faces {
+            colors.insert(face, colour);
+        }
+    }
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::import_step;
+    use crate::step::TessellationOptions;
+
+    #[test]
+    fn colors_faces_of_imported_parts() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("assets/step/red_cube_blue_cylinder.step");
+        let tessellation =
+            import_step(&path, &TessellationOptions::default(), &mut |_| {}).unwrap();
+        let red = [0.8666667, 0.3215686, 0.1568628];
+        let blue = [0.2313726, 0.3803922, 0.7058824];
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-6);
+
+        // The faces of the cube's shell
+        for face in [38, 40, 42, 46, 48, 52] {
+            let color = tessellation.face_color(face);
+            assert!(close(color, red), "#{}: {:?}", face, color);
+        }
+        // The cylinder's side and caps
+        for face in [44, 50, 54] {
+            let color = tessellation.face_color(face);
+            assert!(close(color, blue), "#{}: {:?}", face, color);
+        }
+        // Every tessellated face is styled
+        for &face in &tessellation.face_ids {
+            assert_ne!(tessellation.face_color(face), DEFAULT_FACE_COLOR);
+        }
+    }
+}
@@ -0,0 +1,1821 @@
+// STD
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+use std::fmt;
+use std::sync::atomic::AtomicBool;
+
+// Dependencies
+use anyhow::Result;
+use cgmath::{InnerSpace, Point3, Vector3};
+
+// Local modules
+use super::geometry::{
+    Placement, b_spline_curve, b_spline_surface, cartesian_point, cartesian_points, placement,
+};
+use super::parser::{Entity, EntityGraph, Parameter, ParseError, check_cancelled};
+use super::style::{DEFAULT_FACE_COLOR, face_colors};
+use crate::geom::{NurbsCurve, NurbsSurface, triangulate};
+use crate::mesh::Vertex;
+
+/// STEP instance id of the `ADVANCED_FACE` a triangle was tessellated from
+pub type FaceId = u64;
+
+/// STEP instance id of an `EDGE_CURVE`
+pub type EdgeId = u64;
+
+/// Triangles of a single face and the kind of surface they lie on, or why the face was skipped
+type FaceMesh = Result<(SurfaceType, Vec<Vertex>)>;
+
+/// Points along a BREP edge, from its start vertex to its end vertex
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    pub edge_id: EdgeId,
+    pub points: Vec<[f64; 3]>,
+}
+
+impl Polyline {
+    /// Returns the straight segments between consecutive points.
+    pub fn segments(&self) -> impl Iterator<Item = [[f64; 3]; 2]> + '_ {
+        self.points.windows(2).map(|pair| [pair[0], pair[1]])
+    }
+}
+
+/// Non-indexed triangle list, together with the face each triangle belongs to and the BREP edges
+#[derive(Debug, Default, Clone)]
+pub struct Tessellation {
+    pub vertices: Vec<Vertex>,
+    /// Face of every triangle, `vertices[3 * i..3 * i + 3]` came from `face_ids[i]`
+    pub face_ids: Vec<FaceId>,
+    /// Polylines of the `EDGE_CURVE`s, see [`edges`]
+    pub edges: Vec<Polyline>,
+    /// Faces left out because they couldn't be tessellated, with the reason why
+    pub skipped: Vec<(FaceId, String)>,
+    /// Kind of surface every tessellated face lies on
+    pub surface_types: HashMap<FaceId, SurfaceType>,
+    /// Authored color of every styled face, see [`face_colors`]
+    pub colors: HashMap<FaceId, [f32; 3]>,
+}
+
+impl Tessellation {
+    /// Returns the authored color of face `id`, or [`DEFAULT_FACE_COLOR`] if it isn't styled.
+    pub fn face_color(&self, id: FaceId) -> [f32; 3] {
+        self.colors.get(&id).copied().unwrap_or(DEFAULT_FACE_COLOR)
+    }
+
+    /// Appends the triangles of face `id`, or records why it was skipped.
+    fn append(&mut self, id: FaceId, face: FaceMesh) {
+        match face {
+            Ok((surface_type, vertices)) => {
+                self.vertices.extend(vertices);
+                self.face_ids.resize(self.vertices.len() / 3, id);
+                self.surface_types.insert(id, surface_type);
+            },
+            Err(e) => {
+                log::warn!("Skipping face #{}: {:#}", id, e);
+                self.skipped.push((id, format!("{:#}", e)));
+            },
+        }
+    }
+}
+
+/// Kinds of surfaces faces can be tessellated on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SurfaceType {
+    Plane,
+    Cylinder,
+    Cone,
+    Sphere,
+    BSpline,
+}
+
+impl SurfaceType {
+    /// Returns the kind of the surface instance `surface`, `None` for unsupported surfaces.
+    pub fn of(surface: &Entity) -> Option<Self> {
+        Some(match surface.keyword.as_str() {
+            "PLANE" => SurfaceType::Plane,
+            "CYLINDRICAL_SURFACE" => SurfaceType::Cylinder,
+            "CONICAL_SURFACE" => SurfaceType::Cone,
+            "SPHERICAL_SURFACE" => SurfaceType::Sphere,
+            _ if surface.is("B_SPLINE_SURFACE_WITH_KNOTS") => SurfaceType::BSpline,
+            _ => return None,
+        })
+    }
+
+    /// Linear RGB color telling faces on this kind of surface apart when debugging.
+    pub fn color(self) -> [f32; 3] {
+        match self {
+            SurfaceType::Plane => [0.55, 0.6, 0.7],
+            SurfaceType::Cylinder => [0.2, 0.7, 0.25],
+            SurfaceType::Cone => [0.9, 0.75, 0.1],
+            SurfaceType::Sphere => [0.75, 0.2, 0.8],
+            SurfaceType::BSpline => [0.9, 0.35, 0.15],
+        }
+    }
+}
+
+/// Error raised for a face on a surface [`SurfaceType::of`] doesn't know, see
+/// [`unsupported_surface`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedSurface {
+    /// `ADVANCED_FACE` lying on the surface
+    pub face: FaceId,
+    /// Keyword of the surface instance
+    pub keyword: String,
+}
+
+impl fmt::Display for UnsupportedSurface {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} surfaces aren't supported", self.keyword)
+    }
+}
+
+impl std::error::Error for UnsupportedSurface {}
+
+/// Returns the first `ADVANCED_FACE` of `graph`, in order of instance id, lying on a surface that
+/// can't be tessellated, or `None` if all of them can.
+pub fn unsupported_surface(graph: &EntityGraph) -> Option<UnsupportedSurface> {
+    graph.instances_of("ADVANCED_FACE").find_map(|face| {
+        let surface = graph.get(face.ref_arg(2).ok()?).ok()?;
+        SurfaceType::of(surface)
+            .is_none()
+            .then(|| UnsupportedSurface {
+                face: face.id,
+                keyword: surface.keyword.clone(),
+            })
+    })
+}
+
+/// Smallest angle a single segment spans, bounds the segment count for tiny deflections
+const MIN_ANGLE_STEP: f64 = 1e-3;
+/// Most segments a B-spline is split into along one parameter
+const MAX_SPLINE_SEGMENTS: usize = 1024;
+/// Fraction of a model's bounding box diagonal [`TessellationOptions::auto`] deviates from it by
+pub const AUTO_DEFLECTION: f64 = 1e-3;
+
+/// How finely curved edges and surfaces are subdivided
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TessellationOptions {
+    /// Maximum distance between a curve or surface and the segments or triangles approximating
+    /// it, in model units
+    pub linear_deflection: f64,
+    /// Maximum angle in radians a curve may turn through within a single segment
+    pub angular_deflection: f64,
+    /// Fraction of the model's bounding box diagonal to use as the linear deflection instead of
+    /// `linear_deflection`, see [`TessellationOptions::resolve`]
+    pub relative_deflection: Option<f64>,
+}
+
+impl Default for TessellationOptions {
+    fn default() -> Self {
+        Self {
+            linear_deflection: 0.01,
+            angular_deflection: 20f64.to_radians(),
+            relative_deflection: None,
+        }
+    }
+}
+
+impl TessellationOptions {
+    /// Options scaling the linear deflection with the model, to [`AUTO_DEFLECTION`] of its
+    /// bounding box diagonal, so small and large parts come out equally smooth.
+    ///
+    /// Angles don't depend on the model's size, the angular deflection is the default one.
+    pub fn auto() -> Self {
+        Self {
+            relative_deflection: Some(AUTO_DEFLECTION),
+            ..Self::default()
+        }
+    }
+
+    /// Returns these options with a [`relative_deflection`](Self::relative_deflection) turned
+    /// into an absolute `linear_deflection` for `graph`.
+    ///
+    /// The bounding box spans the 3D `CARTESIAN_POINT`s of `graph`, which include the vertices,
+    /// placements and B-spline control points. Models without any keep `linear_deflection`.
+    pub fn resolve(&self, graph: &EntityGraph) -> Self {
+        let Some(ratio) = self.relative_deflection else {
+            return *self;
+        };
+        let mut options = Self {
+            relative_deflection: None,
+            ..*self
+        };
+        match model_diagonal(graph) {
+            Some(diagonal) if diagonal > 0.0 => options.linear_deflection = diagonal * ratio,
+            _ => log::debug!("Model has no extent, keeping the linear deflection fixed"),
+        }
+        options
+    }
+
+    /// Returns the number of segments an arc of `radius` sweeping `angle` radians is split into.
+    pub fn arc_segments(&self, radius: f64, angle: f64) -> usize {
+        let mut step = self.angular_deflection;
+        // A chord spanning `step` lies `radius * (1 - cos(step / 2))` away from the arc
+        if self.linear_deflection < radius {
+            step = step.min(2.0 * (1.0 - self.linear_deflection / radius).acos());
+        }
+        ((angle.abs() / step.max(MIN_ANGLE_STEP)).ceil() as usize).max(1)
+    }
+}
+
+/// Returns the length of the diagonal of the bounding box of the 3D points of `graph`.
+fn model_diagonal(graph: &EntityGraph) -> Option<f64> {
+    let points = cartesian_points(graph).ok()?;
+    let (_, first) = points.first()?;
+    let (min, max) = points.iter().fold((*first, *first), |(min, max), (_, p)| {
+        (
+            [0, 1, 2].map(|i| min[i].min(p[i])),
+            [0, 1, 2].map(|i| max[i].max(p[i])),
+        )
+    });
+    Some(
+        (0..3)
+            .map(|i| (max[i] - min[i]).powi(2))
+            .sum::<f64>()
+            .sqrt(),
+    )
+}
+
+/// Returns the frame of the `PLANE` `id`.
+fn plane_frame(graph: &EntityGraph, id: u64) -> Result<Placement> {
+    placement(graph, graph.get_as(id, "PLANE")?.ref_arg(1)?)
+}
+
+/// Returns the points strictly between `start` and `end` on the `CIRCLE` `circle`, ordered from
+/// `start` to `end`.
+///
+/// `same_sense` is whether the edge runs counter-clockwise around the circle's axis, like the
+/// circle itself. Coinciding end points make a full circle.
+fn arc_points(
+    graph: &EntityGraph,
+    circle: &Entity,
+    start: Point3<f64>,
+    end: Point3<f64>,
+    same_sense: bool,
+    options: &TessellationOptions,
+) -> Result<Vec<Point3<f64>>> {
+    let frame = placement(graph, circle.ref_arg(1)?)?;
+    let Some(radius) = circle.arg(2)?.as_f64() else {
+        return Err(ParseError::instance(circle.id, "Circle radius is not a number").into());
+    };
+    let angle = |p: Point3<f64>| {
+        let d = p - frame.origin;
+        d.dot(frame.y).atan2(d.dot(frame.x))
+    };
+    let start_angle = angle(start);
+    let sense = if same_sense { 1.0 } else { -1.0 };
+    let mut sweep = ((angle(end) - start_angle) * sense).rem_euclid(TAU);
+    if !(1e-9..=TAU - 1e-9).contains(&sweep) {
+        sweep = TAU;
+    }
+    let segments = options.arc_segments(radius, sweep);
+    Ok((1..segments)
+        .map(|i| {
+            let a = start_angle + sense * sweep * i as f64 / segments as f64;
+            frame.origin + (frame.x * a.cos() + frame.y * a.sin()) * radius
+        })
+        .collect())
+}
+
+/// Doubles `segments` until `within(segments)` holds, or [`MAX_SPLINE_SEGMENTS`] is reached.
+fn refine(segments: usize, within: impl Fn(usize) -> bool) -> usize {
+    let mut segments = segments.clamp(1, MAX_SPLINE_SEGMENTS);
+    while segments < MAX_SPLINE_SEGMENTS && !within(segments) {
+        segments = (segments * 2).min(MAX_SPLINE_SEGMENTS);
+    }
+    segments
+}
+
+/// Returns whether the segment from `a` to `b` stays within `options` of `mid`, the curve's point
+/// halfway between them, and turns by no more than the angular deflection from `tangent_a` to
+/// `tangent_b`.
+fn within_deflection(
+    [a, mid, b]: [Point3<f64>; 3],
+    [tangent_a, tangent_b]: [Vector3<f64>; 2],
+    options: &TessellationOptions,
+) -> bool {
+    let chord_error = (mid - (a + (b - a) * 0.5)).magnitude();
+    let turns = tangent_a.magnitude2() > 0.0
+        && tangent_b.magnitude2() > 0.0
+        && tangent_a.angle(tangent_b).0 > options.angular_deflection;
+    chord_error <= options.linear_deflection && !turns
+}
+
+/// Returns the parameters splitting `curve`'s domain into segments within `options`.
+fn curve_parameters(curve: &NurbsCurve, options: &TessellationOptions) -> Vec<f64> {
+    let [start, end] = curve.domain();
+    let t = |i: usize, segments: usize| start + (end - start) * i as f64 / segments as f64;
+    let point = |t: f64| Point3::from(curve.evaluate(t));
+    let tangent = |t: f64| Vector3::from(curve.tangent(t));
+    let segments = refine(curve.spans() * curve.degree().max(1), |segments| {
+        (0..segments).all(|i| {
+            let (t0, t1) = (t(i, segments), t(i + 1, segments));
+            let mid = 0.5 * (t0 + t1);
+            within_deflection(
+                [point(t0), point(mid), point(t1)],
+                [tangent(t0), tangent(t1)],
+                options,
+            )
+        })
+    });
+    (0..=segments).map(|i| t(i, segments)).collect()
+}
+
+/// Returns the points along `edge`, an `EDGE_CURVE`, from its start vertex up to but excluding
+/// its end vertex.
+fn edge_points(
+    graph: &EntityGraph,
+    edge: &Entity,
+    options: &TessellationOptions,
+) -> Result<Vec<Point3<f64>>> {
+    let curve = graph.get(edge.ref_arg(3)?)?;
+    let mut edge_points = vec![Point3::from(cartesian_point(graph, edge.ref_arg(1)?)?)];
+    match curve.keyword.as_str() {
+        "LINE" => {},
+        "POLYLINE" => {
+            // The end points of the polyline coincide with the edge's vertices
+            let inner = curve.list_arg(1)?;
+            for p in inner.iter().skip(1).take(inner.len().saturating_sub(2)) {
+                if let Some(p) = p.as_ref() {
+                    edge_points.push(Point3::from(cartesian_point(graph, p)?));
+                }
+            }
+            // The polyline runs against the edge
+            if edge.arg(4)?.as_bool() == Some(false) {
+                edge_points[1..].reverse();
+            }
+        },
+        "CIRCLE" => {
+            let end = Point3::from(cartesian_point(graph, edge.ref_arg(2)?)?);
+            let same_sense = edge.arg(4)?.as_bool() != Some(false);
+            let arc = arc_points(graph, curve, edge_points[0], end, same_sense, options)?;
+            edge_points.extend(arc);
+        },
+        _ if curve.is("B_SPLINE_CURVE_WITH_KNOTS") => {
+            let spline = b_spline_curve(graph, curve.id)?;
+            let mut inner: Vec<Point3<f64>> = curve_parameters(&spline, options)
+                .iter()
+                .map(|&t| Point3::from(spline.evaluate(t)))
+                .collect();
+            // The end points of the curve coincide with the edge's vertices
+            inner.pop();
+            inner.remove(0);
+            if edge.arg(4)?.as_bool() == Some(false) {
+                inner.reverse();
+            }
+            edge_points.extend(inner);
+        },
+        other => {
+            return Err(ParseError::instance(
+                curve.id,
+                format!("{} edges aren't supported", other),
+            )
+            .into());
+        },
+    }
+    Ok(edge_points)
+}
+
+/// Returns the ordered corner points of an `EDGE_LOOP` or `POLY_LOOP`.
+///
+/// `CIRCLE` and B-spline edges are subdivided according to `options`, B-spline edges are assumed
+/// to span their whole curve. Fails if an edge isn't a `LINE`, `POLYLINE`, `CIRCLE` or
+/// `B_SPLINE_CURVE_WITH_KNOTS`.
+fn loop_points(
+    graph: &EntityGraph,
+    id: u64,
+    options: &TessellationOptions,
+) -> Result<Vec<Point3<f64>>> {
+    let bound_loop = graph.get(id)?;
+    let mut points = Vec::new();
+    match bound_loop.keyword.as_str() {
+        "POLY_LOOP" => {
+            for p in bound_loop.list_arg(1)? {
+                let Some(p) = p.as_ref() else {
+                    return Err(
+                        ParseError::instance(id, "POLY_LOOP point is not a reference").into(),
+                    );
+                };
+                points.push(Point3::from(cartesian_point(graph, p)?));
+            }
+        },
+        "EDGE_LOOP" => {
+            for oriented in bound_loop.list_arg(1)? {
+                let Some(oriented) = oriented.as_ref() else {
+                    return Err(
+                        ParseError::instance(id, "EDGE_LOOP edge is not a reference").into(),
+                    );
+                };
+                let oriented = graph.get_as(oriented, "ORIENTED_EDGE")?;
+                let forward = oriented.arg(4)?.as_bool().unwrap_or(true);
+                let edge = graph.get_as(oriented.ref_arg(3)?, "EDGE_CURVE")?;
+                let mut edge_points = edge_points(graph, edge, options)?;
+                // Every edge contributes its start point, the end point is the next edge's start
+                if !forward {
+                    edge_points.push(Point3::from(cartesian_point(graph, edge.ref_arg(2)?)?));
+                    edge_points.remove(0);
+                    edge_points.reverse();
+                }
+                points.extend(edge_points);
+            }
+        },
+        other => {
+            return Err(
+                ParseError::instance(id, format!("{} loops aren't supported", other)).into(),
+            );
+        },
+    }
+    Ok(points)
+}
+
+/// Triangulates a single planar `ADVANCED_FACE`, leaving out the holes its inner bounds cut.
+///
+/// Faces without a `FACE_OUTER_BOUND` take the bound enclosing the largest area as their outline.
+fn tessellate_planar_face(
+    graph: &EntityGraph,
+    id: u64,
+    options: &TessellationOptions,
+    out: &mut Vec<Vertex>,
+) -> Result<()> {
+    let face = graph.get(id)?;
+    let frame = plane_frame(graph, face.ref_arg(2)?)?;
+    let mut normal = frame.z;
+    if face.arg(3)?.as_bool() == Some(false) {
+        normal = -normal;
+    }
+
+    // Bounds are triangulated in the plane's own coordinates, which double as uv coordinates
+    let mut loops: Vec<Vec<(Point3<f64>, [f64; 2])>> = Vec::new();
+    let mut outer = None;
+    for bound in face.list_arg(1)?.iter().filter_map(|b| b.as_ref()) {
+        let bound = graph.get(bound)?;
+        let points = loop_points(graph, bound.ref_arg(1)?, options)?;
+        if points.len() < 3 {
+            return Err(ParseError::instance(id, "Face loop has fewer than 3 corners").into());
+        }
+        if bound.keyword == "FACE_OUTER_BOUND" && outer.is_none() {
+            outer = Some(loops.len());
+        }
+        let projected = points.into_iter().map(|p| {
+            let d = p - frame.origin;
+            (p, [d.dot(frame.x), d.dot(frame.y)])
+        });
+        loops.push(projected.collect());
+    }
+    if loops.is_empty() {
+        return Err(ParseError::instance(id, "Face has no bounds").into());
+    }
+    let area = |points: &[(Point3<f64>, [f64; 2])]| {
+        let n = points.len();
+        let twice: f64 = (0..n)
+            .map(|i| {
+                let (a, b) = (points[i].1, points[(i + 1) % n].1);
+                a[0] * b[1] - b[0] * a[1]
+            })
+            .sum();
+        twice.abs()
+    };
+    let outer = outer.unwrap_or_else(|| {
+        (0..loops.len())
+            .max_by(|&a, &b| area(&loops[a]).total_cmp(&area(&loops[b])))
+            .unwrap_or(0)
+    });
+    let outer_loop = loops.remove(outer);
+    let points: Vec<(Point3<f64>, [f64; 2])> = outer_loop
+        .iter()
+        .chain(loops.iter().flatten())
+        .copied()
+        .collect();
+
+    let flat = |points: &[(Point3<f64>, [f64; 2])]| points.iter().map(|p| p.1).collect::<Vec<_>>();
+    let holes: Vec<Vec<[f64; 2]>> = loops.iter().map(|l| flat(l)).collect();
+    let triangles = triangulate(&flat(&outer_loop), &holes);
+
+    let flipped = normal.dot(frame.z) < 0.0;
+    let normal = normal.normalize();
+    let vertex = |(p, uv): (Point3<f64>, [f64; 2])| Vertex {
+        position: [p.x as f32, p.y as f32, p.z as f32],
+        uv_coords: [uv[0] as f32, uv[1] as f32],
+        normal: [normal.x as f32, normal.y as f32, normal.z as f32],
+    };
+    // Triangles wind counter-clockwise around the plane's axis, which may face against the face
+    for [a, mut b, mut c] in triangles {
+        if flipped {
+            std::mem::swap(&mut b, &mut c);
+        }
+        out.extend([a, b, c].map(|k| vertex(points[k])));
+    }
+    Ok(())
+}
+
+/// Distance from the axis below which a point of a surface of revolution counts as on the axis
+const AXIS_EPSILON: f64 = 1e-9;
+
+/// A `CYLINDRICAL_SURFACE`, `CONICAL_SURFACE` or `SPHERICAL_SURFACE` around the `z` axis of its
+/// placement
+///
+/// Points are addressed by the angle `u` around the axis, starting at the placement's `x`, and by
+/// `v` along the profile: the height for cylinders and cones, the latitude for spheres.
+#[derive(Debug, Clone, Copy)]
+enum RevolvedSurface {
+    Cylinder {
+        radius: f64,
+    },
+    /// `radius` is measured at `v = 0`, the cone widens towards `+z` for a positive `semi_angle`
+    Cone {
+        radius: f64,
+        semi_angle: f64,
+    },
+    Sphere {
+        radius: f64,
+    },
+}
+
+impl RevolvedSurface {
+    /// Reads the surface `surface` along with its placement.
+    fn read(graph: &EntityGraph, surface: &Entity) -> Result<(Placement, Self)> {
+        let frame = placement(graph, surface.ref_arg(1)?)?;
+        let number = |i: usize, what: &str| -> Result<f64> {
+            let value = surface.arg(i)?.as_f64();
+            Ok(value.ok_or_else(|| {
+                ParseError::instance(surface.id, format!("{} is not a number", what))
+            })?)
+        };
+        let revolved = match surface.keyword.as_str() {
+            "CYLINDRICAL_SURFACE" => Self::Cylinder {
+                radius: number(2, "Cylinder radius")?,
+            },
+            "CONICAL_SURFACE" => Self::Cone {
+                radius: number(2, "Cone radius")?,
+                semi_angle: number(3, "Cone semi-angle")?,
+            },
+            "SPHERICAL_SURFACE" => Self::Sphere {
+                radius: number(2, "Sphere radius")?,
+            },
+            other => {
+                return Err(ParseError::instance(
+                    surface.id,
+                    format!("{} is not a surface of revolution", other),
+                )
+                .into());
+            },
+        };
+        Ok((frame, revolved))
+    }
+
+    /// Returns the distance from the axis and the height along it at `v`, followed by the
+    /// outward normal's radial and axial components.
+    fn profile(&self, v: f64) -> [f64; 4] {
+        match *self {
+            Self::Cylinder { radius } => [radius, v, 1.0, 0.0],
+            Self::Cone { radius, semi_angle } => [
+                radius + v * semi_angle.tan(),
+                v,
+                semi_angle.cos(),
+                -semi_angle.sin(),
+            ],
+            Self::Sphere { radius } => [radius * v.cos(), radius * v.sin(), v.cos(), v.sin()],
+        }
+    }
+
+    /// Returns `v` of the point `d`, given relative to the placement's origin.
+    fn v(&self, d: Vector3<f64>, frame: &Placement) -> f64 {
+        match self {
+            Self::Cylinder { .. } | Self::Cone { .. } => d.dot(frame.z),
+            Self::Sphere { .. } => (d.dot(frame.z) / d.magnitude()).clamp(-1.0, 1.0).asin(),
+        }
+    }
+
+    /// Returns `v` of the points where the surface meets its axis below and above its bounds.
+    fn poles(&self) -> (Option<f64>, Option<f64>) {
+        match *self {
+            Self::Cylinder { .. } => (None, None),
+            Self::Cone { radius, semi_angle } => {
+                let apex = -radius / semi_angle.tan();
+                if semi_angle > 0.0 {
+                    (Some(apex), None)
+                } else {
+                    (None, Some(apex))
+                }
+            },
+            Self::Sphere { .. } => (Some(-FRAC_PI_2), Some(FRAC_PI_2)),
+        }
+    }
+
+    /// Returns the number of rows the `v` range `sweep` is split into.
+    fn v_segments(&self, sweep: f64, options: &TessellationOptions) -> usize {
+        match *self {
+            // Rulings are straight, so a single row is exact
+            Self::Cylinder { .. } | Self::Cone { .. } => 1,
+            Self::Sphere { radius } => options.arc_segments(radius, sweep),
+        }
+    }
+}
+
+/// Corner of a face bound, or of a piece of the face, in the parameter plane of its surface
+#[derive(Debug, Clone, Copy)]
+struct TrimPoint {
+    uv: [f64; 2],
+    /// Position of corners on a bound, `None` for corners the surface is evaluated at
+    position: Option<Point3<f64>>,
+    /// Whether the segment to the next corner runs along a bound, so points on it stay on the
+    /// chord the neighbouring face shares
+    along_bound: bool,
+}
+
+impl TrimPoint {
+    /// Corner of a bound at `position`, followed by more of the bound.
+    fn on_bound(uv: [f64; 2], position: Point3<f64>) -> Self {
+        Self {
+            uv,
+            position: Some(position),
+            along_bound: true,
+        }
+    }
+
+    /// Corner inside the face, placed on the surface.
+    fn inside(uv: [f64; 2]) -> Self {
+        Self {
+            uv,
+            position: None,
+            along_bound: false,
+        }
+    }
+}
+
+/// Returns the signed area the polygon `points` encloses in the parameter plane, positive if it's
+/// counter-clockwise.
+fn trim_area(points: &[TrimPoint]) -> f64 {
+    let n = points.len();
+    let twice: f64 = (0..n)
+        .map(|i| {
+            let (a, b) = (points[i].uv, points[(i + 1) % n].uv);
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum();
+    0.5 * twice
+}
+
+/// Clips the convex polygon `points` to the side of the line `uv[axis] = at` that `side` points
+/// to, `1.0` for above and `-1.0` for below.
+fn clip_polygon(points: &[TrimPoint], axis: usize, at: f64, side: f64) -> Vec<TrimPoint> {
+    let inside = |p: &TrimPoint| (p.uv[axis] - at) * side >= 0.0;
+    let mut clipped = Vec::with_capacity(points.len() + 1);
+    for (k, &p) in points.iter().enumerate() {
+        let q = points[(k + 1) % points.len()];
+        if inside(&p) {
+            clipped.push(p);
+        }
+        if inside(&p) != inside(&q) {
+            let t = (at - p.uv[axis]) / (q.uv[axis] - p.uv[axis]);
+            let mut uv = [0, 1].map(|c| p.uv[c] + (q.uv[c] - p.uv[c]) * t);
+            uv[axis] = at;
+            let position = match (p.along_bound, p.position, q.position) {
+                (true, Some(a), Some(b)) => Some(a + (b - a) * t),
+                _ => None,
+            };
+            // Leaving the polygon, the segment to the next corner runs along the line
+            clipped.push(TrimPoint {
+                uv,
+                position,
+                along_bound: p.along_bound && !inside(&p),
+            });
+        }
+    }
+    clipped
+}
+
+/// Triangulates the region of a surface's parameter plane inside `outer` and outside `holes`,
+/// split along the grid lines at `us` and `vs` so the triangles follow the surface as closely as
+/// a grid over it.
+///
+/// Triangles wind counter-clockwise in the parameter plane. Corners where a grid line crosses a
+/// bound lie on the bound's chord, the other new corners are left for the caller to place on the
+/// surface. `us` and `vs` are sorted and hold at least two lines each, the outermost cells extend
+/// past them.
+fn trimmed_triangles(
+    outer: &[TrimPoint],
+    holes: &[Vec<TrimPoint>],
+    us: &[f64],
+    vs: &[f64],
+) -> Vec<[TrimPoint; 3]> {
+    let corners: Vec<TrimPoint> = outer
+        .iter()
+        .chain(holes.iter().flatten())
+        .copied()
+        .collect();
+    // Segments along the bounds, by the indices of their ends in `corners`
+    let mut along = HashSet::new();
+    let mut offset = 0;
+    for ring in std::iter::once(outer).chain(holes.iter().map(Vec::as_slice)) {
+        for (i, corner) in ring.iter().enumerate() {
+            let (a, b) = (offset + i, offset + (i + 1) % ring.len());
+            if corner.along_bound {
+                along.insert((a.min(b), a.max(b)));
+            }
+        }
+        offset += ring.len();
+    }
+
+    let flat = |points: &[TrimPoint]| points.iter().map(|p| p.uv).collect::<Vec<_>>();
+    let hole_rings: Vec<Vec<[f64; 2]>> = holes.iter().map(|hole| flat(hole)).collect();
+    // Grid cells overlapping `[lo, hi]` along one parameter, the outermost ones open-ended
+    let cells = |lines: &[f64], lo: f64, hi: f64| {
+        let inner = &lines[1..lines.len() - 1];
+        inner.partition_point(|&l| l <= lo)..=inner.partition_point(|&l| l < hi)
+    };
+    let mut triangles = Vec::new();
+    for indices in triangulate(&flat(outer), &hole_rings) {
+        let triangle: Vec<TrimPoint> = (0..3)
+            .map(|k| {
+                let (a, b) = (indices[k], indices[(k + 1) % 3]);
+                TrimPoint {
+                    along_bound: along.contains(&(a.min(b), a.max(b))),
+                    ..corners[a]
+                }
+            })
+            .collect();
+        let range = |axis: usize| {
+            let values = triangle.iter().map(|p| p.uv[axis]);
+            let lo = values.clone().fold(f64::INFINITY, f64::min);
+            (lo, values.fold(f64::NEG_INFINITY, f64::max))
+        };
+        let ((u_lo, u_hi), (v_lo, v_hi)) = (range(0), range(1));
+        for i in cells(us, u_lo, u_hi) {
+            for j in cells(vs, v_lo, v_hi) {
+                let mut piece = triangle.clone();
+                let last = (us.len() - 2, vs.len() - 2);
+                for (axis, cell, lines, last) in [(0, i, us, last.0), (1, j, vs, last.1)] {
+                    if cell > 0 {
+                        piece = clip_polygon(&piece, axis, lines[cell], 1.0);
+                    }
+                    if cell < last {
+                        piece = clip_polygon(&piece, axis, lines[cell + 1], -1.0);
+                    }
+                }
+                // The pieces are convex, fan them out from their first corner
+                for k in 1..piece.len().saturating_sub(1) {
+                    let fan = [piece[0], piece[k], piece[k + 1]];
+                    let [a, b, c] = fan.map(|p| p.uv);
+                    let area = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+                    if area > 0.0 {
+                        triangles.push(fan);
+                    }
+                }
+            }
+        }
+    }
+    triangles
+}
+
+/// Maps the `points` of a loop on `surface` into its parameter plane, turning `u` on
+/// continuously from one point to the next. Returns the corners along with the angle the loop
+/// turns through around the axis.
+///
+/// Points on the axis take the `u` of either neighbour, as two corners. Returns `None` for loops
+/// lying on the axis entirely.
+fn revolved_loop(
+    points: &[Point3<f64>],
+    surface: &RevolvedSurface,
+    frame: &Placement,
+) -> Option<(Vec<TrimPoint>, f64)> {
+    let on_axis = |d: Vector3<f64>| (d - frame.z * d.dot(frame.z)).magnitude() < AXIS_EPSILON;
+    let start = points.iter().position(|&p| !on_axis(p - frame.origin))?;
+    let point = |k: usize| points[(start + k) % points.len()];
+    // `u` of the points off the axis, from `start` around and back to it
+    let mut us = Vec::with_capacity(points.len() + 1);
+    let mut last: Option<f64> = None;
+    for k in 0..=points.len() {
+        let d = point(k) - frame.origin;
+        if on_axis(d) {
+            us.push(None);
+            continue;
+        }
+        let a = d.dot(frame.y).atan2(d.dot(frame.x));
+        let u = last.map_or(a, |last| last + (a - last + PI).rem_euclid(TAU) - PI);
+        last = Some(u);
+        us.push(Some(u));
+    }
+    let (first, closing) = (us[0]?, us[points.len()]?);
+
+    let mut corners = Vec::with_capacity(points.len());
+    for (k, &u) in us[..points.len()].iter().enumerate() {
+        let p = point(k);
+        let v = surface.v(p - frame.origin, frame);
+        if let Some(u) = u {
+            corners.push(TrimPoint::on_bound([u, v], p));
+            continue;
+        }
+        let before = us[..k].iter().rev().find_map(|&u| u).unwrap_or(first);
+        let after = us[k + 1..].iter().find_map(|&u| u).unwrap_or(closing);
+        // The segment between the two corners runs along the axis, not the bound
+        corners.push(TrimPoint {
+            along_bound: false,
+            ..TrimPoint::on_bound([before, v], p)
+        });
+        corners.push(TrimPoint::on_bound([after, v], p));
+    }
+    Some((corners, closing - first))
+}
+
+/// Cuts the `path` of a loop winding around the axis, which ends where it started a turn further,
+/// where it first meets `u = seam` modulo a turn. Returns the path from there a full turn around,
+/// starting at `seam` exactly.
+fn cut_at_seam(path: &[TrimPoint], seam: f64) -> Vec<TrimPoint> {
+    let n = path.len() - 1;
+    let turn = path[n].uv[0] - path[0].uv[0];
+    // Corner `k` of the loop, carrying on around it past its end
+    let corner = |k: usize| {
+        let mut corner = path[k % n];
+        corner.uv[0] += turn * (k / n) as f64;
+        corner
+    };
+    let turns = |c: &TrimPoint| (c.uv[0] - seam) / TAU;
+    // The first corner on the seam, or the point where the seam crosses the segment before it
+    let mut cut = (0, None);
+    for i in 0..n {
+        let (p, q) = (corner(i), corner(i + 1));
+        let (a, b) = (turns(&p), turns(&q));
+        if (a - a.round()).abs() < 1e-12 {
+            cut = (i, None);
+            break;
+        }
+        let line = if b > a {
+            a.floor() + 1.0
+        } else {
+            a.ceil() - 1.0
+        };
+        if (line - a) * (line - b) < 0.0 {
+            let t = (line - a) / (b - a);
+            let position = match (p.along_bound, p.position, q.position) {
+                (true, Some(pa), Some(pb)) => Some(pa + (pb - pa) * t),
+                _ => None,
+            };
+            let v = p.uv[1] + (q.uv[1] - p.uv[1]) * t;
+            let crossing = TrimPoint {
+                uv: [seam + line * TAU, v],
+                position,
+                ..p
+            };
+            cut = (i + 1, Some(crossing));
+            break;
+        }
+    }
+
+    let (start, crossing) = cut;
+    let mut cut_path = Vec::with_capacity(n + 2);
+    cut_path.extend(crossing);
+    cut_path.extend((start..=start + n).map(corner));
+    if let Some(mut crossing) = crossing {
+        // The path returns to where it was cut, rather than to the corner it started from
+        cut_path.pop();
+        crossing.uv[0] += turn;
+        cut_path.push(crossing);
+    }
+    let shift = seam - cut_path[0].uv[0];
+    for corner in &mut cut_path {
+        corner.uv[0] += shift;
+    }
+    cut_path
+}
+
+/// Closes the loops of a face on a surface of revolution into the outline and holes of its
+/// region in the parameter plane, or `None` if they don't make one.
+///
+/// `rings` are the loops closed in the parameter plane, `windings` the paths of the loops turning
+/// around the axis along with their turn, all counter-clockwise around the outward normal. The
+/// winding paths are cut along a seam clear of the rings, a single one is closed over the pole at
+/// `v_min` or `v_max` it encloses and two winding opposite ways are joined.
+fn revolved_region(
+    mut rings: Vec<Vec<TrimPoint>>,
+    mut windings: Vec<(Vec<TrimPoint>, f64)>,
+    [v_min, v_max]: [f64; 2],
+) -> Option<(Vec<TrimPoint>, Vec<Vec<TrimPoint>>)> {
+    let u_range = |ring: &[TrimPoint]| {
+        let us = ring.iter().map(|c| c.uv[0]);
+        let lo = us.clone().fold(f64::INFINITY, f64::min);
+        (lo, us.fold(f64::NEG_INFINITY, f64::max))
+    };
+    // The seam runs through the middle of the widest gap between the rings' angles
+    let mut spans: Vec<(f64, f64)> = rings
+        .iter()
+        .map(|ring| {
+            let (lo, hi) = u_range(ring);
+            let start = lo.rem_euclid(TAU);
+            (start, start + hi - lo)
+        })
+        .collect();
+    spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut seam = windings.first().map(|(path, _)| path[0].uv[0]);
+    if let (Some(&(first, _)), false) = (spans.first(), windings.is_empty()) {
+        let mut reach = spans[0].1;
+        let mut widest = (
+            first + TAU - spans.iter().fold(0.0, |r: f64, s| r.max(s.1)),
+            None,
+        );
+        for &(start, end) in &spans[1..] {
+            if start - reach > widest.0 {
+                widest = (start - reach, Some(0.5 * (start + reach)));
+            }
+            reach = reach.max(end);
+        }
+        let wrap = 0.5 * (reach + first + TAU);
+        seam = Some(widest.1.unwrap_or(wrap));
+    }
+
+    let outer = match windings.len() {
+        0 => {
+            let area = |ring: &Vec<TrimPoint>| trim_area(ring).abs();
+            let largest =
+                (0..rings.len()).max_by(|&a, &b| area(&rings[a]).total_cmp(&area(&rings[b])))?;
+            seam = Some(u_range(&rings[largest]).0);
+            rings.remove(largest)
+        },
+        1 => {
+            let (path, turn) = windings.pop()?;
+            let mut path = cut_at_seam(&path, seam?);
+            let (first, last) = (path.first()?.uv[0], path.last()?.uv[0]);
+            let pole = if turn > 0.0 { v_max } else { v_min };
+            path.last_mut()?.along_bound = false;
+            path.extend([
+                TrimPoint::inside([last, pole]),
+                TrimPoint::inside([first, pole]),
+            ]);
+            path
+        },
+        2 if windings[0].1 * windings[1].1 < 0.0 => {
+            if windings[0].1 < 0.0 {
+                windings.swap(0, 1);
+            }
+            let mut bottom = cut_at_seam(&windings[0].0, seam?);
+            // The top path runs back from where the bottom one ends, a turn further
+            let mut top = cut_at_seam(&windings[1].0, seam? + TAU);
+            bottom.last_mut()?.along_bound = false;
+            top.last_mut()?.along_bound = false;
+            bottom.extend(top);
+            bottom
+        },
+        _ => return None,
+    };
+    // Move the rings into the turn that starts at the seam
+    let seam = seam?;
+    for ring in &mut rings {
+        let shift = ((seam - u_range(ring).0) / TAU).ceil() * TAU;
+        for corner in ring {
+            corner.uv[0] += shift;
+        }
+    }
+    Some((outer, rings))
+}
+
+/// Triangulates a single `ADVANCED_FACE` on a surface of revolution as a grid over its
+/// parameters, see [`RevolvedSurface`].
+///
+/// Faces covering the `u`/`v` range their bounds span, like ones bounded by circles around the
+/// axis and by lines or arcs along it, are a plain grid. Other faces are trimmed to their bounds,
+/// see [`trimmed_triangles`]. Bounds winding around the axis extend the face to the pole they
+/// enclose, like a sphere's cap or a cone's apex.
+fn tessellate_revolved_face(
+    graph: &EntityGraph,
+    id: u64,
+    options: &TessellationOptions,
+    out: &mut Vec<Vertex>,
+) -> Result<()> {
+    let face = graph.get(id)?;
+    let (frame, surface) = RevolvedSurface::read(graph, graph.get(face.ref_arg(2)?)?)?;
+    let same_sense = face.arg(3)?.as_bool() != Some(false);
+
+    let mut angles = Vec::new();
+    let (mut v_min, mut v_max) = (f64::INFINITY, f64::NEG_INFINITY);
+    // Net angle the bounds turn through around the axis, as seen from the outside
+    let mut winding = 0.0;
+    let (mut rings, mut windings) = (Vec::new(), Vec::new());
+    for bound in face.list_arg(1)?.iter().filter_map(|b| b.as_ref()) {
+        let bound = graph.get(bound)?;
+        let mut points = loop_points(graph, bound.ref_arg(1)?, options)?;
+        // Turn the loop counter-clockwise around the outward normal
+        let reversed = bound.arg(2)?.as_bool() == Some(false);
+        if reversed == same_sense {
+            points.reverse();
+        }
+        for &p in &points {
+            let v = surface.v(p - frame.origin, &frame);
+            v_min = v_min.min(v);
+            v_max = v_max.max(v);
+        }
+        // Loops on the axis have no angle
+        let Some((mut corners, turn)) = revolved_loop(&points, &surface, &frame) else {
+            continue;
+        };
+        angles.extend(corners.iter().map(|c| (c.uv[0] + PI).rem_euclid(TAU) - PI));
+        winding += turn;
+        if turn.abs() > PI {
+            // The loop ends where it started, a turn further around the axis
+            let [u, v] = corners[0].uv;
+            corners.push(TrimPoint::on_bound([u + turn, v], points[0]));
+            windings.push((corners, turn));
+        } else {
+            rings.push(corners);
+        }
+    }
+    let (south, north) = surface.poles();
+    let missing_pole = || ParseError::instance(id, "Face bounds enclose a pole off the surface");
+    if winding > PI {
+        v_max = north.ok_or_else(missing_pole)?;
+    } else if winding < -PI {
+        v_min = south.ok_or_else(missing_pole)?;
+    }
+    if angles.is_empty() || v_max <= v_min {
+        return Err(ParseError::instance(id, "Face bounds don't span the surface").into());
+    }
+
+    // The bounds leave out the widest gap between their angles, unless that gap is no wider than
+    // the sampling of a full circle. Bounds along a single seam go all the way around.
+    angles.sort_by(f64::total_cmp);
+    let (mut u_start, mut gap) = (angles[0], TAU - (angles[angles.len() - 1] - angles[0]));
+    for pair in angles.windows(2) {
+        if pair[1] - pair[0] > gap {
+            (u_start, gap) = (pair[1], pair[1] - pair[0]);
+        }
+    }
+    let max_radius = surface.profile(v_min)[0]
+        .abs()
+        .max(surface.profile(v_max)[0].abs());
+    let full_step = TAU / options.arc_segments(max_radius, TAU) as f64;
+    let u_sweep = if gap > 1.5 * full_step && gap < TAU - 1e-9 {
+        TAU - gap
+    } else {
+        TAU
+    };
+
+    let v_segments = surface.v_segments(v_max - v_min, options);
+    let orientation = if same_sense { 1.0 } else { -1.0 };
+    let vertex = |[u, v]: [f64; 2], position: Option<Point3<f64>>| {
+        let [r, h, normal_r, normal_z] = surface.profile(v);
+        let radial = frame.x * u.cos() + frame.y * u.sin();
+        let p = position.unwrap_or(frame.origin + radial * r + frame.z * h);
+        let normal = (radial * normal_r + frame.z * normal_z) * orientation;
+        Vertex {
+            position: [p.x as f32, p.y as f32, p.z as f32],
+            uv_coords: [(u * max_radius) as f32, h as f32],
+            normal: [normal.x as f32, normal.y as f32, normal.z as f32],
+        }
+    };
+    let on_axis = |v: f64| surface.profile(v)[0].abs() < AXIS_EPSILON;
+
+    // Bounds cutting into the range they span trim the face, unless they only run along a seam.
+    // The grid lines keep a full turn's sampling so they line up across the seams.
+    let region = revolved_region(rings, windings, [v_min, v_max]);
+    if let Some((outer, holes)) = region {
+        let area = trim_area(&outer).abs() - holes.iter().map(|h| trim_area(h).abs()).sum::<f64>();
+        let full = u_sweep * (v_max - v_min);
+        if area > 1e-9 * full && area < (1.0 - 1e-6) * full {
+            let (u_lo, u_hi) = outer
+                .iter()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |r, c| {
+                    (r.0.min(c.uv[0]), r.1.max(c.uv[0]))
+                });
+            let (first, last) = ((u_lo / full_step).floor(), (u_hi / full_step).ceil());
+            let us: Vec<f64> = (0..=((last - first) as usize).max(1))
+                .map(|i| (first + i as f64) * full_step)
+                .collect();
+            let vs: Vec<f64> = (0..=v_segments)
+                .map(|j| v_min + (v_max - v_min) * j as f64 / v_segments as f64)
+                .collect();
+            for triangle in trimmed_triangles(&outer, &holes, &us, &vs) {
+                // Triangles with an edge on the axis collapse
+                if triangle.iter().filter(|c| on_axis(c.uv[1])).count() > 1 {
+                    continue;
+                }
+                let mut triangle = triangle.map(|c| vertex(c.uv, c.position));
+                if !same_sense {
+                    triangle.swap(1, 2);
+                }
+                out.extend(triangle);
+            }
+            return Ok(());
+        }
+    }
+
+    let u_segments = options.arc_segments(max_radius, u_sweep);
+    let grid_vertex = |i: usize, j: usize| {
+        let u = u_start + u_sweep * i as f64 / u_segments as f64;
+        let v = v_min + (v_max - v_min) * j as f64 / v_segments as f64;
+        vertex([u, v], None)
+    };
+    let row_on_axis = |j: usize| on_axis(v_min + (v_max - v_min) * j as f64 / v_segments as f64);
+    for j in 0..v_segments {
+        for i in 0..u_segments {
+            // Counter-clockwise around the outward normal, `u` turns around the axis like `x` to
+            // `y` while `v` runs towards `+z`
+            let mut quad = [
+                grid_vertex(i, j),
+                grid_vertex(i + 1, j),
+                grid_vertex(i + 1, j + 1),
+                grid_vertex(i, j + 1),
+            ];
+            if !same_sense {
+                quad.reverse();
+            }
+            // Rows on the axis collapse their quads into single triangles
+            let (bottom, top) = if same_sense { (j, j + 1) } else { (j + 1, j) };
+            if !row_on_axis(bottom) {
+                out.extend([quad[0], quad[1], quad[2]]);
+            }
+            if !row_on_axis(top) {
+                out.extend([quad[0], quad[2], quad[3]]);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the number of segments along `u` and `v` that keep a grid over `u` x `v` within
+/// `options` of `surface`.
+///
+/// Each direction starts at one segment per knot span and degree and is doubled until every
+/// segment along it, on every grid line across it, is within the deflections.
+fn surface_segments(
+    surface: &NurbsSurface,
+    [u0, u1]: [f64; 2],
+    [v0, v1]: [f64; 2],
+    options: &TessellationOptions,
+) -> (usize, usize) {
+    let lerp = |start: f64, end: f64, i: usize, segments: usize| {
+        start + (end - start) * i as f64 / segments as f64
+    };
+    let point = |u: f64, v: f64| Point3::from(surface.evaluate(u, v));
+    let normal = |u: f64, v: f64| Vector3::from(surface.normal(u, v));
+    // Whether the segments along one parameter are within `options` on the given grid lines
+    let within = |segments: usize, lines: usize, along_u: bool| {
+        (0..=lines).all(|j| {
+            (0..segments).all(|i| {
+                let [a, mid, b] = [i as f64, i as f64 + 0.5, i as f64 + 1.0].map(|i| {
+                    if along_u {
+                        (u0 + (u1 - u0) * i / segments as f64, lerp(v0, v1, j, lines))
+                    } else {
+                        (lerp(u0, u1, j, lines), v0 + (v1 - v0) * i / segments as f64)
+                    }
+                });
+                within_deflection(
+                    [point(a.0, a.1), point(mid.0, mid.1), point(b.0, b.1)],
+                    [normal(a.0, a.1), normal(b.0, b.1)],
+                    options,
+                )
+            })
+        })
+    };
+    let (u_spans, v_spans) = surface.spans();
+    let (u_degree, v_degree) = surface.degrees();
+    let v_initial = (v_spans * v_degree).max(1);
+    let u_segments = refine(u_spans * u_degree, |segments| {
+        within(segments, v_initial, true)
+    });
+    let v_segments = refine(v_initial, |segments| within(segments, u_segments, false));
+    (u_segments, v_segments)
+}
+
+/// Triangulates a single `ADVANCED_FACE` on a `B_SPLINE_SURFACE_WITH_KNOTS` as a grid over its
+/// parameters.
+///
+/// The bounds are mapped into the parameter plane by projecting their points onto the surface.
+/// Faces covering the range their bounds span are a plain grid, other faces are trimmed to their
+/// bounds, see [`trimmed_triangles`]. Faces without bounds, or whose bounds only run along a seam,
+/// cover the surface's whole domain. Faces without a `FACE_OUTER_BOUND` take the bound enclosing
+/// the largest area as their outline.
+fn tessellate_b_spline_face(
+    graph: &EntityGraph,
+    id: u64,
+    options: &TessellationOptions,
+    out: &mut Vec<Vertex>,
+) -> Result<()> {
+    let face = graph.get(id)?;
+    let surface = b_spline_surface(graph, face.ref_arg(2)?)?;
+    let same_sense = face.arg(3)?.as_bool() != Some(false);
+
+    let (u_domain, v_domain) = surface.domain();
+    let (mut u_range, mut v_range) = (
+        [f64::INFINITY, f64::NEG_INFINITY],
+        [f64::INFINITY, f64::NEG_INFINITY],
+    );
+    let mut loops: Vec<Vec<TrimPoint>> = Vec::new();
+    let mut outer = None;
+    for bound in face.list_arg(1)?.iter().filter_map(|b| b.as_ref()) {
+        let bound = graph.get(bound)?;
+        let mut corners = Vec::new();
+        for p in loop_points(graph, bound.ref_arg(1)?, options)? {
+            let [u, v] = surface.closest_parameters(p.into());
+            u_range = [u_range[0].min(u), u_range[1].max(u)];
+            v_range = [v_range[0].min(v), v_range[1].max(v)];
+            corners.push(TrimPoint::on_bound([u, v], p));
+        }
+        if bound.keyword == "FACE_OUTER_BOUND" && outer.is_none() {
+            outer = Some(loops.len());
+        }
+        loops.push(corners);
+    }
+    let spans_range =
+        |[start, end]: [f64; 2], [min, max]: [f64; 2]| end - start > 1e-9 * (max - min);
+    if !spans_range(u_range, u_domain) {
+        u_range = u_domain;
+    }
+    if !spans_range(v_range, v_domain) {
+        v_range = v_domain;
+    }
+
+    let (u_segments, v_segments) = surface_segments(&surface, u_range, v_range, options);
+    let orientation = if same_sense { 1.0 } else { -1.0 };
+    let vertex = |[u, v]: [f64; 2], position: Option<Point3<f64>>| {
+        let p = position.map_or_else(|| surface.evaluate(u, v), Into::into);
+        let normal = surface.normal(u, v).map(|n| (n * orientation) as f32);
+        Vertex {
+            position: p.map(|c| c as f32),
+            uv_coords: [u as f32, v as f32],
+            normal,
+        }
+    };
+    let degenerate = |triangle: &[Vertex; 3]| {
+        let [a, b, c] = triangle.map(|v| Vector3::from(v.position));
+        (b - a).cross(c - a).magnitude2() == 0.0
+    };
+    let lines = |[start, end]: [f64; 2], segments: usize| -> Vec<f64> {
+        (0..=segments)
+            .map(|i| start + (end - start) * i as f64 / segments as f64)
+            .collect()
+    };
+    let (us, vs) = (lines(u_range, u_segments), lines(v_range, v_segments));
+
+    // Bounds cutting into the range they span trim the face
+    let area = |corners: &Vec<TrimPoint>| trim_area(corners).abs();
+    let outer = outer
+        .or_else(|| (0..loops.len()).max_by(|&a, &b| area(&loops[a]).total_cmp(&area(&loops[b]))));
+    if let Some(outer) = outer {
+        let outer = loops.remove(outer);
+        let trimmed = area(&outer) - loops.iter().map(area).sum::<f64>();
+        let full = (u_range[1] - u_range[0]) * (v_range[1] - v_range[0]);
+        if trimmed > 1e-9 * full && trimmed < (1.0 - 1e-6) * full {
+            for triangle in trimmed_triangles(&outer, &loops, &us, &vs) {
+                let mut triangle = triangle.map(|c| vertex(c.uv, c.position));
+                if !same_sense {
+                    triangle.swap(1, 2);
+                }
+                if !degenerate(&triangle) {
+                    out.extend(triangle);
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    let grid_vertex = |i: usize, j: usize| vertex([us[i], vs[j]], None);
+    for j in 0..v_segments {
+        for i in 0..u_segments {
+            // Counter-clockwise around `du × dv`, the surface's normal
+            let mut quad = [
+                grid_vertex(i, j),
+                grid_vertex(i + 1, j),
+                grid_vertex(i + 1, j + 1),
+                grid_vertex(i, j + 1),
+            ];
+            if !same_sense {
+                quad.reverse();
+            }
+            // Collapsed edges, like at a pole, leave a single triangle
+            for triangle in [[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]] {
+                if !degenerate(&triangle) {
+                    out.extend(triangle);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the `ADVANCED_FACE`s that are used reversed, so their triangles must be flipped to
+/// face out of the solid.
+///
+/// A face is reversed by an `ORIENTED_FACE` with a `.F.` orientation, or by belonging to an
+/// `ORIENTED_CLOSED_SHELL` or `ORIENTED_OPEN_SHELL` with one, like the voids of a
+/// `BREP_WITH_VOIDS`. Reversing twice cancels out.
+fn reversed_faces(graph: &EntityGraph) -> Result<HashSet<u64>> {
+    let mut reversed = HashSet::new();
+    let mut toggle = |id: u64| {
+        if !reversed.remove(&id) {
+            reversed.insert(id);
+        }
+    };
+    for oriented in graph.instances_of("ORIENTED_FACE") {
+        if oriented.arg(3)?.as_bool() == Some(false) {
+            toggle(oriented.ref_arg(2)?);
+        }
+    }
+    let shells = graph
+        .instances_of("ORIENTED_CLOSED_SHELL")
+        .chain(graph.instances_of("ORIENTED_OPEN_SHELL"));
+    for oriented in shells {
+        if oriented.arg(3)?.as_bool() != Some(false) {
+            continue;
+        }
+        let shell = graph.get(oriented.ref_arg(2)?)?;
+        for face in shell.list_arg(1)?.iter().filter_map(Parameter::as_ref) {
+            // Missing faces, like ones skipped while parsing, have nothing to flip
+            let Ok(face) = graph.get(face) else {
+                continue;
+            };
+            // An oriented face flips on top of its shell, which the loop above took care of
+            match face.keyword.as_str() {
+                "ORIENTED_FACE" => toggle(face.ref_arg(2)?),
+                _ => toggle(face.id),
+            }
+        }
+    }
+    Ok(reversed)
+}
+
+/// Flips the winding and normals of the non-indexed triangles in `vertices`.
+fn reverse_triangles(vertices: &mut [Vertex]) {
+    for triangle in vertices.chunks_exact_mut(3) {
+        triangle.swap(1, 2);
+        for vertex in triangle {
+            vertex.normal = vertex.normal.map(|n| -n);
+        }
+    }
+}
+
+/// Triangulates a single `ADVANCED_FACE`, dispatching on the surface it lies on. `reversed` flips
+/// the triangles, see [`reversed_faces`].
+///
+/// Errors only concern this face, the caller skips it and carries on with the others. Returns the
+/// kind of surface along with the triangles.
+fn tessellate_face(
+    graph: &EntityGraph,
+    face: &Entity,
+    options: &TessellationOptions,
+    reversed: bool,
+) -> FaceMesh {
+    let surface = graph.get(face.ref_arg(2)?)?;
+    let Some(surface_type) = SurfaceType::of(surface) else {
+        return Err(UnsupportedSurface {
+            face: face.id,
+            keyword: surface.keyword.clone(),
+        }
+        .into());
+    };
+    let tessellate_surface = match surface_type {
+        SurfaceType::Plane => tessellate_planar_face,
+        SurfaceType::Cylinder | SurfaceType::Cone | SurfaceType::Sphere => tessellate_revolved_face,
+        SurfaceType::BSpline => tessellate_b_spline_face,
+    };
+    let mut vertices = Vec::new();
+    tessellate_surface(graph, face.id, options, &mut vertices)?;
+    if reversed {
+        reverse_triangles(&mut vertices);
+    }
+    Ok((surface_type, vertices))
+}
+
+/// Triangulates the `ADVANCED_FACE`s of `graph` one at a time as the iterator advances, in order
+/// of their instance id, along with the kind of surface each lies on or why it was skipped.
+///
+/// `options` must already be resolved against `graph`. Fails if the orientation of the faces
+/// can't be worked out, see [`reversed_faces`].
+fn face_meshes<'a>(
+    graph: &'a EntityGraph,
+    options: TessellationOptions,
+) -> Result<impl ExactSizeIterator<Item = (FaceId, FaceMesh)> + 'a> {
+    let reversed = reversed_faces(graph)?;
+    let faces: Vec<&Entity> = graph.instances_of("ADVANCED_FACE").collect();
+    Ok(faces.into_iter().map(move |face| {
+        let mesh = tessellate_face(graph, face, &options, reversed.contains(&face.id));
+        (face.id, mesh)
+    }))
+}
+
+/// Triangulates the faces of `graph` like [`tessellate_faces`], but lazily, a face at a time as
+/// the iterator advances. Only the triangles of the current face are held, so they can be
+/// streamed out without the whole mesh in memory.
+///
+/// Faces that can't be tessellated are skipped with a warning. Fails upfront if the orientation
+/// of the faces can't be worked out.
+pub fn triangles<'a>(
+    graph: &'a EntityGraph,
+    options: &TessellationOptions,
+) -> Result<impl Iterator<Item = [Vertex; 3]> + 'a> {
+    let faces = face_meshes(graph, options.resolve(graph))?;
+    Ok(faces.flat_map(|(id, mesh)| {
+        let mut vertices = match mesh {
+            Ok((_, vertices)) => vertices.into_iter(),
+            Err(e) => {
+                log::warn!("Skipping face #{}: {:#}", id, e);
+                Vec::new().into_iter()
+            },
+        };
+        std::iter::from_fn(move || Some([vertices.next()?, vertices.next()?, vertices.next()?]))
+    }))
+}
+
+/// Triangulates every `ADVANCED_FACE` on a `PLANE`, `CYLINDRICAL_SURFACE`, `CONICAL_SURFACE`,
+/// `SPHERICAL_SURFACE` or `B_SPLINE_SURFACE_WITH_KNOTS` in `graph` into a non-indexed triangle
+/// list.
+///
+/// Triangles wind counter-clockwise seen from outside the solid and their normals point out of it,
+/// honoring the orientation flags of faces, their bounds and the shells they're used in. Planar
+/// faces are triangulated around the holes their inner bounds cut.
+/// Curved edges and surfaces are subdivided until they're within the deflections of `options`,
+/// resolved against `graph`, see [`TessellationOptions::resolve`].
+/// Faces on other surfaces, bounded by other curves or referencing missing instances are skipped
+/// with a warning and listed in [`Tessellation::skipped`].
+///
+/// With the `parallel` feature the faces are tessellated on the rayon thread pool, see
+/// `tessellate_parallel`. See [`triangles`] to stream the triangles instead of collecting them.
+pub fn tessellate_faces(
+    graph: &EntityGraph,
+    options: &TessellationOptions,
+) -> Result<Tessellation> {
+    #[cfg(feature = "parallel")]
+    return tessellate_parallel(graph, options);
+    #[cfg(not(feature = "parallel"))]
+    tessellate_faces_with_progress(graph, options, &mut |_| {})
+}
+
+/// Returns the `EDGE_CURVE`s of `graph` as polylines, in order of their instance id.
+///
+/// Straight edges become their two end points, curved ones are subdivided like the face bounds
+/// within the deflections of `options`, so the polylines run exactly along the borders of the
+/// faces' triangles. Edges on unsupported curves are skipped with a warning.
+pub fn edges(graph: &EntityGraph, options: &TessellationOptions) -> Vec<Polyline> {
+    let options = &options.resolve(graph);
+    let edges: Vec<&Entity> = graph.instances_of("EDGE_CURVE").collect();
+    let mut polylines = Vec::with_capacity(edges.len());
+    for edge in edges {
+        let points = edge_points(graph, edge, options).and_then(|mut points| {
+            points.push(Point3::from(cartesian_point(graph, edge.ref_arg(2)?)?));
+            Ok(points)
+        });
+        match points {
+            Ok(points) => polylines.push(Polyline {
+                edge_id: edge.id,
+                points: points.into_iter().map(Into::into).collect(),
+            }),
+            Err(e) => log::warn!("Skipping edge #{}: {:#}", edge.id, e),
+        }
+    }
+    polylines
+}
+
+/// Like [`tessellate_faces`], but calls `progress` with the fraction of faces done after each
+/// face, from 0 to 1.
+pub fn tessellate_faces_with_progress(
+    graph: &EntityGraph,
+    options: &TessellationOptions,
+    progress: &mut dyn FnMut(f32),
+) -> Result<Tessellation> {
+    tessellate_faces_with(graph, options, progress, None)
+}
+
+/// Like [`tessellate_faces_with_progress`], but stops with a
+/// [`Cancelled`](super::parser::Cancelled) error once `cancel` is set, checked before each face.
+pub fn tessellate_faces_cancellable(
+    graph: &EntityGraph,
+    options: &TessellationOptions,
+    progress: &mut dyn FnMut(f32),
+    cancel: &AtomicBool,
+) -> Result<Tessellation> {
+    tessellate_faces_with(graph, options, progress, Some(cancel))
+}
+
+/// Shared by the variants of [`tessellate_faces`], `cancel` is checked before each face.
+pub(crate) fn tessellate_faces_with(
+    graph: &EntityGraph,
+    options: &TessellationOptions,
+    progress: &mut dyn FnMut(f32),
+    cancel: Option<&AtomicBool>,
+) -> Result<Tessellation> {
+    let options = &options.resolve(graph);
+    let mut tessellation = Tessellation::default();
+    let mut faces = face_meshes(graph, *options)?;
+    let face_count = faces.len();
+    for done in 0..face_count {
+        check_cancelled(cancel)?;
+        progress(done as f32 / face_count as f32);
+        if let Some((id, mesh)) = faces.next() {
+            tessellation.append(id, mesh);
+        }
+    }
+    check_cancelled(cancel)?;
+    tessellation.edges = edges(graph, options);
+    tessellation.colors = face_colors(graph);
+    progress(1.0);
+    Ok(tessellation)
+}
+
+/// Like [`tessellate_faces_with_progress`], but tessellates the faces on the rayon thread pool
+/// without reporting progress. [`tessellate_faces`] goes through here with the `parallel`
+/// feature.
+///
+/// The output is identical to tessellating the faces in order, they're merged in order of their
+/// instance id regardless of which thread finishes first.
+#[cfg(feature = "parallel")]
+pub fn tessellate_parallel(
+    graph: &EntityGraph,
+    options: &TessellationOptions,
+) -> Result<Tessellation> {
+    use rayon::prelude::*;
+
+    let options = &options.resolve(graph);
+    let reversed = reversed_faces(graph)?;
+    let faces: Vec<&Entity> = graph.instances_of("ADVANCED_FACE").collect();
+    let meshes = faces
+        .par_iter()
+        .map(|face| tessellate_face(graph, face, options, reversed.contains(&face.id)))
+        .collect::<Vec<_>>();
+    let mut tessellation = Tessellation::default();
+    for (face, vertices) in faces.iter().zip(meshes) {
+        tessellation.append(face.id, vertices);
+    }
+    tessellation.edges = edges(graph, options);
+    tessellation.colors = face_colors(graph);
+    Ok(tessellation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::step::parse;
+
+    fn graph(data: &str) -> EntityGraph {
+        parse(&format!(
+            "ISO-10303-21;\nHEADER;\nENDSEC;\nDATA;\n{}\nENDSEC;\nEND-ISO-10303-21;\n",
+            data
+        ))
+        .unwrap()
+    }
+
+    fn area(vertices: &[Vertex]) -> f64 {
+        vertices
+            .chunks_exact(3)
+            .map(|t| {
+                let [a, b, c] = [0, 1, 2].map(|k| Vector3::from(t[k].position.map(f64::from)));
+                0.5 * (b - a).cross(c - a).magnitude()
+            })
+            .sum()
+    }
+
+    /// `POLY_LOOP` `id` through `corners`, numbering its points from `id + 1`.
+    fn poly_loop(id: u64, corners: &[[f64; 3]]) -> String {
+        let mut data = String::new();
+        let mut refs = Vec::new();
+        for (k, [x, y, z]) in corners.iter().enumerate() {
+            let point = id + 1 + k as u64;
+            data += &format!(
+                "#{} = CARTESIAN_POINT('',({:?},{:?},{:?}));\n",
+                point, x, y, z
+            );
+            refs.push(format!("#{}", point));
+        }
+        data + &format!("#{} = POLY_LOOP('',({}));\n", id, refs.join(","))
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn tessellates_in_parallel_like_in_order() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/step");
+        for name in [
+            "cone.step",
+            "cube_with_void.step",
+            "red_cube_blue_cylinder.step",
+        ] {
+            let graph = crate::step::read_step(&dir.join(name)).unwrap();
+            let options = TessellationOptions::default();
+            let in_order = tessellate_faces_with_progress(&graph, &options, &mut |_| {}).unwrap();
+            let parallel = tessellate_faces(&graph, &options).unwrap();
+            let bytes = |t: &Tessellation| bytemuck::cast_slice::<Vertex, u8>(&t.vertices).to_vec();
+            assert_eq!(bytes(&parallel), bytes(&in_order), "{}", name);
+            assert_eq!(parallel.face_ids, in_order.face_ids, "{}", name);
+            assert_eq!(parallel.skipped, in_order.skipped, "{}", name);
+            assert_eq!(parallel.surface_types, in_order.surface_types, "{}", name);
+            assert_eq!(parallel.edges, in_order.edges, "{}", name);
+            assert_eq!(parallel.colors, in_order.colors, "{}", name);
+        }
+    }
+
+    #[test]
+    fn trims_holes_out_of_revolved_faces() {
+        // A cylinder of radius 1 from z = 0 to 2, with a square window cut around +x
+        let mut window = Vec::new();
+        let side = |t: f64| -0.5 + t / 8.0;
+        for k in 0..8 {
+            window.push([side(k as f64), 0.5]);
+        }
+        for k in 0..8 {
+            window.push([0.5, side(k as f64) + 1.0]);
+        }
+        for k in 0..8 {
+            window.push([-side(k as f64), 1.5]);
+        }
+        for k in 0..8 {
+            window.push([-0.5, 1.0 - side(k as f64)]);
+        }
+        // Clockwise around the outward normal, as a hole should be
+        window.reverse();
+        let corners: Vec<[f64; 3]> = window.iter().map(|&[u, v]| [u.cos(), u.sin(), v]).collect();
+        let graph = graph(&format!(
+            "#1 = CARTESIAN_POINT('',(0.,0.,0.));\n\
+             #2 = DIRECTION('',(0.,0.,1.));\n\
+             #3 = DIRECTION('',(1.,0.,0.));\n\
+             #4 = AXIS2_PLACEMENT_3D('',#1,#2,#3);\n\
+             #5 = CYLINDRICAL_SURFACE('',#4,1.);\n\
+             #6 = CARTESIAN_POINT('',(-1.,0.,0.));\n\
+             #7 = VERTEX_POINT('',#6);\n\
+             #8 = CIRCLE('',#4,1.);\n\
+             #9 = EDGE_CURVE('',#7,#7,#8,.T.);\n\
+             #10 = ORIENTED_EDGE('',*,*,#9,.T.);\n\
+             #11 = EDGE_LOOP('',(#10));\n\
+             #12 = CARTESIAN_POINT('',(0.,0.,2.));\n\
+             #13 = AXIS2_PLACEMENT_3D('',#12,#2,#3);\n\
+             #14 = CARTESIAN_POINT('',(0.,-1.,2.));\n\
+             #15 = VERTEX_POINT('',#14);\n\
+             #16 = CIRCLE('',#13,1.);\n\
+             #17 = EDGE_CURVE('',#15,#15,#16,.T.);\n\
+             #18 = ORIENTED_EDGE('',*,*,#17,.F.);\n\
+             #19 = EDGE_LOOP('',(#18));\n\
+             #20 = FACE_OUTER_BOUND('',#11,.T.);\n\
+             #21 = FACE_BOUND('',#19,.T.);\n\
+             #22 = FACE_BOUND('',#100,.T.);\n\
+             #23 = ADVANCED_FACE('',(#20,#21,#22),#5,.T.);\n\
+             {}",
+            poly_loop(100, &corners)
+        ));
+        let mesh = tessellate_faces(&graph, &TessellationOptions::default()).unwrap();
+        assert!(mesh.skipped.is_empty(), "{:?}", mesh.skipped);
+        // The window's chords cut slightly into the cylinder, so it leaves a bit less than 1 out
+        let expected = 4.0 * PI - 1.0;
+        assert!((area(&mesh.vertices) - expected).abs() < 0.02 * expected);
+        for triangle in mesh.vertices.chunks_exact(3) {
+            let c = triangle
+                .iter()
+                .map(|v| Vector3::from(v.position.map(f64::from)))
+                .sum::<Vector3<f64>>()
+                / 3.0;
+            let u = c.y.atan2(c.x);
+            assert!(
+                !(u.abs() < 0.45 && (0.55..1.45).contains(&c.z)),
+                "Triangle inside the window at {:?}",
+                c
+            );
+            for v in triangle {
+                let [x, y, _] = v.position.map(f64::from);
+                let [nx, ny, nz] = v.normal.map(f64::from);
+                assert!(x * nx + y * ny > 0.9 && nz.abs() < 1e-6);
+                assert!(((x * x + y * y).sqrt() - 1.0).abs() < 0.01);
+            }
+        }
+    }
+
+    #[test]
+    fn trims_revolved_faces_to_slanted_bounds() {
+        // A quarter of a cylinder's side cut diagonally, from (u, z) = (0, 0) over (pi/2, 0) to
+        // (0, 1) and back down along the x axis
+        let diagonal: Vec<[f64; 3]> = (0..=16)
+            .map(|k| {
+                let t = k as f64 / 16.0;
+                let u = FRAC_PI_2 * (1.0 - t);
+                [u.cos(), u.sin(), t]
+            })
+            .collect();
+        let mut corners: Vec<[f64; 3]> = (0..16)
+            .map(|k| {
+                let u = FRAC_PI_2 * k as f64 / 16.0;
+                [u.cos(), u.sin(), 0.0]
+            })
+            .collect();
+        corners.extend(diagonal);
+        let graph = graph(&format!(
+            "#1 = CARTESIAN_POINT('',(0.,0.,0.));\n\
+             #2 = DIRECTION('',(0.,0.,1.));\n\
+             #3 = DIRECTION('',(1.,0.,0.));\n\
+             #4 = AXIS2_PLACEMENT_3D('',#1,#2,#3);\n\
+             #5 = CYLINDRICAL_SURFACE('',#4,1.);\n\
+             #6 = FACE_OUTER_BOUND('',#100,.T.);\n\
+             #7 = ADVANCED_FACE('',(#6),#5,.T.);\n\
+             {}",
+            poly_loop(100, &corners)
+        ));
+        let mesh = tessellate_faces(&graph, &TessellationOptions::default()).unwrap();
+        assert!(mesh.skipped.is_empty(), "{:?}", mesh.skipped);
+        // Half of the quarter's area, instead of all of it
+        let expected = FRAC_PI_2 / 2.0;
+        assert!((area(&mesh.vertices) - expected).abs() < 0.02 * expected);
+        for v in &mesh.vertices {
+            let [x, y, z] = v.position.map(f64::from);
+            let u = y.atan2(x);
+            assert!(
+                u > -1e-6 && z < 1.0 - u / FRAC_PI_2 + 0.01,
+                "{:?}",
+                v.position
+            );
+        }
+    }
+
+    #[test]
+    fn trims_b_spline_faces_to_their_bounds() {
+        // A flat patch over the unit square, bounded by the triangle below its diagonal and with
+        // a square hole inside that
+        let graph = graph(&format!(
+            "#1 = CARTESIAN_POINT('',(0.,0.,0.));\n\
+             #2 = CARTESIAN_POINT('',(0.,1.,0.));\n\
+             #3 = CARTESIAN_POINT('',(0.5,0.,0.));\n\
+             #4 = CARTESIAN_POINT('',(0.5,1.,0.));\n\
+             #5 = CARTESIAN_POINT('',(1.,0.,0.));\n\
+             #6 = CARTESIAN_POINT('',(1.,1.,0.));\n\
+             #7 = B_SPLINE_SURFACE_WITH_KNOTS('',2,1,((#1,#2),(#3,#4),(#5,#6)),\
+             .UNSPECIFIED.,.F.,.F.,.F.,(3,3),(2,2),(0.,1.),(0.,1.),.UNSPECIFIED.);\n\
+             #8 = FACE_OUTER_BOUND('',#100,.T.);\n\
+             #9 = FACE_BOUND('',#200,.T.);\n\
+             #10 = ADVANCED_FACE('',(#8,#9),#7,.T.);\n\
+             {}{}",
+            poly_loop(100, &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]),
+            poly_loop(
+                200,
+                &[
+                    [0.1, 0.1, 0.0],
+                    [0.1, 0.3, 0.0],
+                    [0.3, 0.3, 0.0],
+                    [0.3, 0.1, 0.0]
+                ]
+            )
+        ));
+        let mesh = tessellate_faces(&graph, &TessellationOptions::default()).unwrap();
+        assert!(mesh.skipped.is_empty(), "{:?}", mesh.skipped);
+        assert!((area(&mesh.vertices) - (0.5 - 0.04)).abs() < 1e-6);
+        for triangle in mesh.vertices.chunks_exact(3) {
+            let [x, y] = [0, 1].map(|c| {
+                triangle
+                    .iter()
+                    .map(|v| f64::from(v.position[c]))
+                    .sum::<f64>()
+                    / 3.0
+            });
+            assert!(x + y < 1.0, "Triangle past the diagonal at {:?}", (x, y));
+            assert!(!((0.1..0.3).contains(&x) && (0.1..0.3).contains(&y)));
+            for v in triangle {
+                assert_eq!(v.normal, [0.0, 0.0, 1.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn flips_faces_of_reversed_shells_and_oriented_faces() {
+        // Four copies of the unit square facing +z: #10 reversed by an oriented face, #11 by its
+        // oriented shell, #12 by both so it stays, and #13 by neither
+        let graph = graph(&format!(
+            "#1 = CARTESIAN_POINT('',(0.,0.,0.));\n\
+             #2 = DIRECTION('',(0.,0.,1.));\n\
+             #3 = DIRECTION('',(1.,0.,0.));\n\
+             #4 = AXIS2_PLACEMENT_3D('',#1,#2,#3);\n\
+             #5 = PLANE('',#4);\n\
+             #6 = FACE_OUTER_BOUND('',#100,.T.);\n\
+             #10 = ADVANCED_FACE('',(#6),#5,.T.);\n\
+             #11 = ADVANCED_FACE('',(#6),#5,.T.);\n\
+             #12 = ADVANCED_FACE('',(#6),#5,.T.);\n\
+             #13 = ADVANCED_FACE('',(#6),#5,.T.);\n\
+             #20 = ORIENTED_FACE('',*,#10,.F.);\n\
+             #21 = ORIENTED_FACE('',*,#12,.F.);\n\
+             #22 = ORIENTED_FACE('',*,#13,.T.);\n\
+             #23 = CLOSED_SHELL('',(#11,#21));\n\
+             #24 = ORIENTED_CLOSED_SHELL('',*,#23,.F.);\n\
+             #25 = OPEN_SHELL('',(#22));\n\
+             #26 = ORIENTED_OPEN_SHELL('',*,#25,.T.);\n\
+             {}",
+            poly_loop(
+                100,
+                &[
+                    [0.0, 0.0, 0.0],
+                    [1.0, 0.0, 0.0],
+                    [1.0, 1.0, 0.0],
+                    [0.0, 1.0, 0.0]
+                ]
+            )
+        ));
+        assert_eq!(reversed_faces(&graph).unwrap(), HashSet::from([10, 11]));
+
+        let mesh = tessellate_faces(&graph, &TessellationOptions::default()).unwrap();
+        assert!(mesh.skipped.is_empty(), "{:?}", mesh.skipped);
+        for (triangle, &face) in mesh.vertices.chunks_exact(3).zip(&mesh.face_ids) {
+            let z = if matches!(face, 10 | 11) { -1.0 } else { 1.0 };
+            let [a, b, c] = [0, 1, 2].map(|k| Vector3::from(triangle[k].position.map(f64::from)));
+            // Wound counter-clockwise around the normal, like every other face
+            assert!(
+                (b - a).cross(c - a).z * z > 0.0,
+                "Face #{} wound the wrong way",
+                face
+            );
+            for v in triangle {
+                assert_eq!(v.normal, [0.0, 0.0, z as f32], "Face #{}", face);
+            }
+        }
+        let mut faces = mesh.face_ids.clone();
+        faces.dedup();
+        assert_eq!(faces, [10, 11, 12, 13]);
+    }
+}
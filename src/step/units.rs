@@ -0,0 +1,315 @@
+// STD
+use std::collections::HashSet;
+use std::fmt;
+
+// Dependencies
+use anyhow::{Result, bail};
+
+// Local modules
+use super::parser::{Entity, EntityGraph, Parameter, ParseError};
+
+/// Relative difference below which two unit lengths count as the same unit
+const UNIT_TOLERANCE: f64 = 1e-6;
+
+/// Unit of length the coordinates of a STEP file are given in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthUnit {
+    Millimetre,
+    Centimetre,
+    Metre,
+    Inch,
+}
+
+impl LengthUnit {
+    const ALL: [LengthUnit; 4] = [
+        LengthUnit::Millimetre,
+        LengthUnit::Centimetre,
+        LengthUnit::Metre,
+        LengthUnit::Inch,
+    ];
+
+    /// Parses a unit symbol like `mm`, or `in` for inches.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "mm" => Some(LengthUnit::Millimetre),
+            "cm" => Some(LengthUnit::Centimetre),
+            "m" => Some(LengthUnit::Metre),
+            "in" | "inch" => Some(LengthUnit::Inch),
+            _ => None,
+        }
+    }
+
+    /// Returns the unit's symbol, as accepted by [`from_name`](Self::from_name).
+    pub fn symbol(self) -> &'static str {
+        match self {
+            LengthUnit::Millimetre => "mm",
+            LengthUnit::Centimetre => "cm",
+            LengthUnit::Metre => "m",
+            LengthUnit::Inch => "in",
+        }
+    }
+
+    /// Returns the length of one unit in millimetres.
+    pub fn millimetres(self) -> f64 {
+        match self {
+            LengthUnit::Millimetre => 1.0,
+            LengthUnit::Centimetre => 10.0,
+            LengthUnit::Metre => 1000.0,
+            LengthUnit::Inch => 25.4,
+        }
+    }
+
+    /// Returns the factor lengths in this unit are multiplied by to express them in `target`.
+    pub fn scale_to(self, target: LengthUnit) -> f64 {
+        self.millimetres() / target.millimetres()
+    }
+}
+
+impl fmt::Display for LengthUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+/// Returns the length of the `LENGTH_UNIT` `id` in millimetres.
+///
+/// Handles `SI_UNIT` metres with any prefix and `CONVERSION_BASED_UNIT`s defined in terms of
+/// another length unit, like inches as 25.4 millimetres. Fails if the conversions lead back to a
+/// unit they already passed through.
+fn unit_millimetres(graph: &EntityGraph, id: u64) -> Result<f64> {
+    let mut factor = 1.0;
+    let mut seen = HashSet::new();
+    let mut id = id;
+    loop {
+        if !seen.insert(id) {
+            return Err(
+                ParseError::instance(id, "Length unit is defined in terms of itself").into(),
+            );
+        }
+        let unit = graph.get(id)?;
+        if let Some(args) = unit.record("SI_UNIT") {
+            if !matches!(args.get(1), Some(Parameter::Enum(name)) if name == "METRE") {
+                return Err(ParseError::instance(id, "Length unit is not based on metres").into());
+            }
+            let scale = match args.first() {
+                Some(Parameter::Enum(prefix)) => match prefix.as_str() {
+                    "KILO" => 1e6,
+                    "HECTO" => 1e5,
+                    "DECA" => 1e4,
+                    "DECI" => 1e2,
+                    "CENTI" => 1e1,
+                    "MILLI" => 1.0,
+                    "MICRO" => 1e-3,
+                    "NANO" => 1e-6,
+                    other => {
+                        return Err(ParseError::instance(
+                            id,
+                            format!("Unsupported unit prefix {}", other),
+                        )
+                        .into());
+                    },
+                },
+                _ => 1e3,
+            };
+            return Ok(factor * scale);
+        }
+        let Some(args) = unit.record("CONVERSION_BASED_UNIT") else {
+            return Err(ParseError::instance(
+                id,
+                "Length unit is neither an SI nor a conversion based unit",
+            )
+            .into());
+        };
+        let Some(measure) = args.get(1).and_then(Parameter::as_ref) else {
+            return Err(ParseError::instance(id, "Conversion factor is not a reference").into());
+        };
+        let measure = graph.get(measure)?;
+        let Some(conversion) = measure.arg(0)?.as_f64() else {
+            return Err(
+                ParseError::instance(measure.id, "Conversion factor is not a number").into(),
+            );
+        };
+        // The conversion is given in the base unit, which may be converted further
+        factor *= conversion;
+        id = measure.ref_arg(1)?;
+    }
+}
+
+/// Returns every length unit assigned to a representation context of `graph`, by instance id.
+fn length_units(graph: &EntityGraph) -> Result<Vec<(u64, LengthUnit)>> {
+    let mut found = Vec::new();
+    for context in graph.instances_of("GLOBAL_UNIT_ASSIGNED_CONTEXT") {
+        let units = context
+            .record("GLOBAL_UNIT_ASSIGNED_CONTEXT")
+            .and_then(|args| args.first())
+            .and_then(Parameter::as_list)
+            .unwrap_or_default();
+        for id in units.iter().filter_map(Parameter::as_ref) {
+            if !graph.get(id)?.is("LENGTH_UNIT") {
+                continue;
+            }
+            let millimetres = unit_millimetres(graph, id)?;
+            let Some(unit) = LengthUnit::ALL
+                .into_iter()
+                .find(|unit| (millimetres / unit.millimetres() - 1.0).abs() < UNIT_TOLERANCE)
+            else {
+                return Err(ParseError::instance(
+                    id,
+                    format!("Unsupported length unit of {} mm", millimetres),
+                )
+                .into());
+            };
+            found.push((id, unit));
+        }
+    }
+    Ok(found)
+}
+
+/// Returns the unit of length the geometry of `graph` is given in, read from the units assigned
+/// to its representation contexts.
+///
+/// Fails if no context assigns a length unit, if contexts disagree, or if the unit isn't one of
+/// [`LengthUnit`].
+pub fn model_units(graph: &EntityGraph) -> Result<LengthUnit> {
+    let units = length_units(graph)?;
+    let Some(&(_, unit)) = units.first() else {
+        bail!("No length unit is assigned to the geometry");
+    };
+    if let Some(&(_, other)) = units.iter().find(|(_, other)| *other != unit) {
+        bail!("Geometry mixes {} and {} length units", unit, other);
+    }
+    Ok(unit)
+}
+
+/// Arguments holding lengths, by the keyword of the record they belong to
+const LENGTH_ARGUMENTS: &[(&str, &[usize])] = &[
+    ("CARTESIAN_POINT", &[1]),
+    ("VECTOR", &[2]),
+    ("CIRCLE", &[2]),
+    ("ELLIPSE", &[2, 3]),
+    ("CYLINDRICAL_SURFACE", &[2]),
+    ("CONICAL_SURFACE", &[2]),
+    ("SPHERICAL_SURFACE", &[2]),
+    ("TOROIDAL_SURFACE", &[2, 3]),
+];
+
+/// Multiplies every number in `parameter` by `scale`, looking through lists and typed values.
+fn scale_parameter(parameter: &mut Parameter, scale: f64) {
+    match parameter {
+        Parameter::Real(v) => *v *= scale,
+        Parameter::Integer(v) => *parameter = Parameter::Real(*v as f64 * scale),
+        Parameter::Typed(_, inner) => scale_parameter(inner, scale),
+        Parameter::List(items) => items.iter_mut().for_each(|p| scale_parameter(p, scale)),
+        _ => {},
+    }
+}
+
+/// Converts the geometry of `graph` to millimetres in place, returns the unit it was given in.
+///
+/// Scales the coordinates of every `CARTESIAN_POINT` along with the radii and magnitudes of
+/// curves and surfaces, then redefines the length units of the representation contexts as
+/// millimetres, so [`model_units`] reports millimetres afterwards. Fails without touching
+/// `graph` if [`model_units`] does.
+pub fn normalize_units(graph: &mut EntityGraph) -> Result<LengthUnit> {
+    let unit = model_units(graph)?;
+    if unit == LengthUnit::Millimetre {
+        return Ok(unit);
+    }
+    let scale = unit.millimetres();
+    let unit_ids: Vec<u64> = length_units(graph)?.into_iter().map(|(id, _)| id).collect();
+
+    for entity in graph.entities.values_mut() {
+        let records = std::iter::once((entity.keyword.as_str(), &mut entity.args)).chain(
+            entity
+                .partials
+                .iter_mut()
+                .map(|(k, args)| (k.as_str(), args)),
+        );
+        for (keyword, args) in records {
+            // Tolerances are measured in the unit they reference
+            if keyword == "UNCERTAINTY_MEASURE_WITH_UNIT"
+                && args
+                    .get(1)
+                    .and_then(Parameter::as_ref)
+                    .is_some_and(|u| unit_ids.contains(&u))
+            {
+                scale_parameter(&mut args[0], scale);
+                continue;
+            }
+            let Some((_, indices)) = LENGTH_ARGUMENTS.iter().find(|(k, _)| *k == keyword) else {
+                continue;
+            };
+            for &i in *indices {
+                if let Some(arg) = args.get_mut(i) {
+                    scale_parameter(arg, scale);
+                }
+            }
+        }
+    }
+
+    for id in unit_ids {
+        let millimetre = Entity {
+            id,
+            keyword: "LENGTH_UNIT".into(),
+            args: vec![],
+            partials: vec![
+                ("NAMED_UNIT".into(), vec![Parameter::Derived]),
+                (
+                    "SI_UNIT".into(),
+                    vec![
+                        Parameter::Enum("MILLI".into()),
+                        Parameter::Enum("METRE".into()),
+                    ],
+                ),
+            ],
+        };
+        graph.entities.insert(id, millimetre);
+    }
+    Ok(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::step::parse;
+
+    fn graph(data: &str) -> EntityGraph {
+        parse(&format!(
+            "ISO-10303-21;\nHEADER;\nENDSEC;\nDATA;\n{}\nENDSEC;\nEND-ISO-10303-21;\n",
+            data
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn rejects_units_defined_in_terms_of_each_other() {
+        let graph = graph(
+            "#1 = ( CONVERSION_BASED_UNIT('A',#3) LENGTH_UNIT() NAMED_UNIT(*) );\n\
+             #2 = ( CONVERSION_BASED_UNIT('B',#4) LENGTH_UNIT() NAMED_UNIT(*) );\n\
+             #3 = LENGTH_MEASURE_WITH_UNIT(LENGTH_MEASURE(2.),#2);\n\
+             #4 = LENGTH_MEASURE_WITH_UNIT(LENGTH_MEASURE(0.5),#1);\n\
+             #5 = ( GEOMETRIC_REPRESENTATION_CONTEXT(3) GLOBAL_UNIT_ASSIGNED_CONTEXT((#1)) \
+             REPRESENTATION_CONTEXT('','') );",
+        );
+        let error = model_units(&graph).unwrap_err();
+        assert!(
+            error.to_string().contains("in terms of itself"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn follows_chained_conversions() {
+        // A foot of twelve inches, each 25.4 millimetres
+        let graph = graph(
+            "#1 = ( LENGTH_UNIT() NAMED_UNIT(*) SI_UNIT(.MILLI.,.METRE.) );\n\
+             #2 = LENGTH_MEASURE_WITH_UNIT(LENGTH_MEASURE(25.4),#1);\n\
+             #3 = ( CONVERSION_BASED_UNIT('INCH',#2) LENGTH_UNIT() NAMED_UNIT(*) );\n\
+             #4 = LENGTH_MEASURE_WITH_UNIT(LENGTH_MEASURE(12.),#3);\n\
+             #5 = ( CONVERSION_BASED_UNIT('FOOT',#4) LENGTH_UNIT() NAMED_UNIT(*) );",
+        );
+        assert!((unit_millimetres(&graph, 5).unwrap() - 304.8).abs() < 1e-9);
+        assert_eq!(unit_millimetres(&graph, 3).unwrap(), 25.4);
+    }
+}
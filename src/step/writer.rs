@@ -0,0 +1,258 @@
+// STD
+use std::collections::HashMap;
+use std::io::Write;
+
+// Dependencies
+use anyhow::{Result, bail};
+
+// Local modules
+use super::parser::{Entity, EntityGraph, Parameter};
+
+/// Schema written when the graph doesn't name one.
+pub const AP214_SCHEMA: &str = "AUTOMOTIVE_DESIGN { 1 0 10303 214 1 1 1 1 }";
+
+/// Renumbers the instances of `graph` from `#1` in topological order, every instance coming
+/// before the ones it references.
+///
+/// Instances no other one references are visited in ascending id order, each followed depth
+/// first by what it references, in argument order. Instances only reachable through a cycle
+/// follow the same way. The numbering only depends on the graph's structure and the order of
+/// its original ids, so renumbering twice changes nothing. References to undefined instances
+/// are numbered after every defined one and stay dangling.
+pub fn renumber(graph: &mut EntityGraph) {
+    let mut ids: Vec<u64> = graph.entities.keys().copied().collect();
+    ids.sort_unstable();
+    let mut referenced = vec![false; ids.len()];
+    for entity in graph.entities.values() {
+        for r in entity.references() {
+            if let Ok(i) = ids.binary_search(&r) {
+                referenced[i] = true;
+            }
+        }
+    }
+    let roots = ids
+        .iter()
+        .zip(&referenced)
+        .filter(|(_, r)| !**r)
+        .map(|(id, _)| *id);
+    let rest = ids
+        .iter()
+        .zip(&referenced)
+        .filter(|(_, r)| **r)
+        .map(|(id, _)| *id);
+
+    // Iterative so long chains of references can't overflow the stack
+    let mut order: HashMap<u64, u64> = HashMap::with_capacity(ids.len());
+    let mut stack = Vec::new();
+    for start in roots.chain(rest) {
+        stack.push(start);
+        while let Some(id) = stack.pop() {
+            if order.contains_key(&id) {
+                continue;
+            }
+            let Some(entity) = graph.entities.get(&id) else {
+                continue;
+            };
+            order.insert(id, order.len() as u64 + 1);
+            stack.extend(entity.references().into_iter().rev());
+        }
+    }
+
+    let mut entities: Vec<Entity> = graph.entities.drain().map(|(_, e)| e).collect();
+    entities.sort_unstable_by_key(|e| order[&e.id]);
+    let mut next = order.len() as u64 + 1;
+    let mut new_id = |id: u64| {
+        *order.entry(id).or_insert_with(|| {
+            next += 1;
+            next - 1
+        })
+    };
+    for mut entity in entities {
+        entity.id = new_id(entity.id);
+        for p in entity
+            .args
+            .iter_mut()
+            .chain(entity.partials.iter_mut().flat_map(|(_, args)| args))
+        {
+            renumber_parameter(p, &mut new_id);
+        }
+        graph.entities.insert(entity.id, entity);
+    }
+}
+
+fn renumber_parameter(p: &mut Parameter, new_id: &mut impl FnMut(u64) -> u64) {
+    match p {
+        Parameter::Ref(id) => *id = new_id(*id),
+        Parameter::Typed(_, inner) => renumber_parameter(inner, new_id),
+        Parameter::List(items) => items.iter_mut().for_each(|p| renumber_parameter(p, new_id)),
+        _ => {},
+    }
+}
+
+/// Time the header is stamped with, `SOURCE_DATE_EPOCH` if set so builds can be reproduced.
+fn timestamp() -> String {
+    let time = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .unwrap_or_else(chrono::Utc::now);
+    time.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+/// Formats a real so it always carries the decimal point STEP requires, e.g. `10.` or `1.E-07`.
+fn format_real(v: f64) -> Result<String> {
+    if !v.is_finite() {
+        bail!("Can't write non-finite real {}", v);
+    }
+    if v.fract() == 0.0 && v.abs() < 1e15 {
+        return Ok(format!("{}.", v));
+    }
+    let s = format!("{:?}", v);
+    Ok(match s.split_once('e') {
+        Some((mantissa, exponent)) if mantissa.contains('.') => {
+            format!("{}E{}", mantissa, exponent)
+        },
+        Some((mantissa, exponent)) => format!("{}.E{}", mantissa, exponent),
+        None => s,
+    })
+}
+
+/// Quotes `s`, doubling apostrophes and backslashes.
+fn format_string(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "''"))
+}
+
+fn write_parameter(p: &Parameter, w: &mut impl Write) -> Result<()> {
+    match p {
+        Parameter::Ref(id) => write!(w, "#{}", id)?,
+        Parameter::String(s) => write!(w, "{}", format_string(s))?,
+        Parameter::Real(v) => write!(w, "{}", format_real(*v)?)?,
+        Parameter::Integer(v) => write!(w, "{}", v)?,
+        Parameter::Enum(e) => write!(w, ".{}.", e)?,
+        Parameter::Binary(b) => write!(w, "\"{}\"", b)?,
+        Parameter::Typed(keyword, inner) => {
+            write!(w, "{}(", keyword)?;
+            write_parameter(inner, w)?;
+            write!(w, ")")?;
+        },
+        Parameter::List(items) => write_parameters(items, w)?,
+        Parameter::Unset => write!(w, "$")?,
+        Parameter::Derived => write!(w, "*")?,
+    }
+    Ok(())
+}
+
+/// Writes `params` as a parenthesized, comma separated list.
+fn write_parameters(params: &[Parameter], w: &mut impl Write) -> Result<()> {
+    write!(w, "(")?;
+    for (i, p) in params.iter().enumerate() {
+        if i > 0 {
+            write!(w, ",")?;
+        }
+        write_parameter(p, w)?;
+    }
+    write!(w, ")")?;
+    Ok(())
+}
+
+fn write_entity(entity: &Entity, w: &mut impl Write) -> Result<()> {
+    write!(w, "#{} = ", entity.id)?;
+    if entity.partials.is_empty() {
+        write!(w, "{}", entity.keyword)?;
+        write_parameters(&entity.args, w)?;
+    } else {
+        write!(w, "( {}", entity.keyword)?;
+        write_parameters(&entity.args, w)?;
+        for (keyword, args) in &entity.partials {
+            write!(w, " {}", keyword)?;
+            write_parameters(args, w)?;
+        }
+        write!(w, " )")?;
+    }
+    writeln!(w, ";")?;
+    Ok(())
+}
+
+/// Writes `graph` as an ISO-10303-21 file named `name`, its instances [renumbered](renumber).
+///
+/// The header's `FILE_NAME` is stamped with the current time, or `SOURCE_DATE_EPOCH` if set, and
+/// `FILE_SCHEMA` lists the graph's schemas, or AP214 if it has none. With a fixed time the same
+/// graph is always written byte for byte the same. Fails if an instance references one that
+/// isn't defined.
+pub fn write_step(graph: &EntityGraph, name: &str, w: &mut impl Write) -> Result<()> {
+    graph.check_references()?;
+    let mut graph = graph.clone();
+    renumber(&mut graph);
+    let entity_count = graph.entities.len() as u64;
+
+    let timestamp = timestamp();
+    let schemas: Vec<Parameter> = if graph.schemas.is_empty() {
+        vec![Parameter::String(AP214_SCHEMA.into())]
+    } else {
+        graph
+            .schemas
+            .iter()
+            .map(|s| Parameter::String(s.clone()))
+            .collect()
+    };
+    writeln!(w, "ISO-10303-21;")?;
+    writeln!(w, "HEADER;")?;
+    writeln!(w, "FILE_DESCRIPTION(('brepimport export'),'2;1');")?;
+    writeln!(
+        w,
+        "FILE_NAME({},'{}',(''),(''),'brepimport','brepimport','');",
+        format_string(name),
+        timestamp
+    )?;
+    write!(w, "FILE_SCHEMA(")?;
+    write_parameters(&schemas, w)?;
+    writeln!(w, ");")?;
+    writeln!(w, "ENDSEC;")?;
+    writeln!(w, "DATA;")?;
+    for id in 1..=entity_count {
+        write_entity(&graph.entities[&id], w)?;
+    }
+    writeln!(w, "ENDSEC;")?;
+    writeln!(w, "END-ISO-10303-21;")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::step::{parse, read_step};
+
+    fn graph(data: &str) -> EntityGraph {
+        parse(&format!(
+            "ISO-10303-21;\nHEADER;\nENDSEC;\nDATA;\n{}\nENDSEC;\nEND-ISO-10303-21;\n",
+            data
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn renumbering_twice_changes_nothing() {
+        // Out of order ids, a cycle through #30 and #40, a complex instance and a dangling #99
+        let cyclic = graph(
+            "#50 = CARTESIAN_POINT('',(0.,0.,0.));\n\
+             #7 = DIRECTION('',(0.,0.,1.));\n\
+             #12 = AXIS2_PLACEMENT_3D('',#50,#7,$);\n\
+             #30 = LINKED('',#40,#12);\n\
+             #40 = LINKED('',#30,#99);\n\
+             #3 = ( NAMED_UNIT(*) LENGTH_UNIT() SI_UNIT(.MILLI.,.METRE.) );\n\
+             #2 = PLANE('',#12);",
+        );
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("assets/step/cube_with_void.step");
+        for mut graph in [cyclic, read_step(&path).unwrap()] {
+            renumber(&mut graph);
+            let once = graph.entities.clone();
+            let mut ids: Vec<u64> = once.keys().copied().collect();
+            ids.sort_unstable();
+            assert!(ids.iter().copied().eq(1..=ids.len() as u64));
+
+            renumber(&mut graph);
+            assert_eq!(graph.entities, once);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Checks every file shipped in `assets/` against the library and the `brepconv` binary.
+// STD
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Dependencies
+use brepimport::brep::read_brep;
+use brepimport::import::check_schema;
+use brepimport::mesh::manifold::check_manifold;
+use brepimport::mesh::weld::weld;
+use brepimport::step::{
+    LengthUnit, TessellationOptions, model_units, normalize_units, read_step, tessellate_faces,
+};
+
+/// Returns the files in `assets/<dir>` with the extension `extension`, sorted by name.
+fn assets(dir: &str, extension: &str) -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join(dir);
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == extension))
+        .collect();
+    paths.sort();
+    assert!(
+        !paths.is_empty(),
+        "No .{} files in {}",
+        extension,
+        dir.display()
+    );
+    paths
+}
+
+fn brepconv(args: &[&Path]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_brepconv"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn step_assets_parse_and_tessellate() {
+    for path in assets("step", "step") {
+        let graph = read_step(&path).unwrap_or_else(|e| panic!("{}: {:#}", path.display(), e));
+        check_schema(&graph).unwrap();
+        graph.check_references().unwrap();
+        let mesh = tessellate_faces(&graph, &TessellationOptions::default()).unwrap();
+        assert!(
+            mesh.skipped.is_empty(),
+            "{}: skipped {:?}",
+            path.display(),
+            mesh.skipped
+        );
+        assert_eq!(
+            mesh.face_ids.len() * 3,
+            mesh.vertices.len(),
+            "{}",
+            path.display()
+        );
+        assert!(!mesh.vertices.is_empty(), "{}", path.display());
+    }
+}
+
+#[test]
+fn closed_solids_tessellate_watertight() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/step");
+    for name in [
+        "cube.step",
+        "sphere.step",
+        "cube_with_void.step",
+        "cone.step",
+    ] {
+        let graph = read_step(&dir.join(name)).unwrap();
+        let mesh = tessellate_faces(&graph, &TessellationOptions::default()).unwrap();
+        let indices: Vec<u32> = (0..mesh.vertices.len() as u32).collect();
+        let (_, indices) = weld(&mesh.vertices, &indices, 1e-4);
+        let report = check_manifold(&indices);
+        assert!(report.is_watertight(), "{}: {:?}", name, report);
+    }
+}
+
+#[test]
+fn inch_plate_normalizes_to_millimetres() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/step");
+    let plate = read_step(&dir.join("plate_with_hole.step")).unwrap();
+    let mut inch_plate = read_step(&dir.join("plate_with_hole_inch.step")).unwrap();
+    assert_eq!(normalize_units(&mut inch_plate).unwrap(), LengthUnit::Inch);
+    assert_eq!(model_units(&inch_plate).unwrap(), LengthUnit::Millimetre);
+
+    // The inch plate is the millimetre one at a tenth of the numbers, so 2.54 times its size.
+    // Deflections relative to the model's size sample both alike.
+    let options = TessellationOptions::auto();
+    let mesh = tessellate_faces(&plate, &options).unwrap();
+    let inch_mesh = tessellate_faces(&inch_plate, &options).unwrap();
+    assert_eq!(inch_mesh.vertices.len(), mesh.vertices.len());
+    for (inch, mm) in inch_mesh.vertices.iter().zip(&mesh.vertices) {
+        for c in 0..3 {
+            let expected = mm.position[c] * 2.54;
+            assert!(
+                (inch.position[c] - expected).abs() < 1e-4,
+                "{:?} should be {:?} scaled",
+                inch.position,
+                mm.position
+            );
+        }
+    }
+}
+
+#[test]
+fn brep_assets_parse_and_validate() {
+    for path in assets("brep", "brep") {
+        let model = read_brep(&path).unwrap_or_else(|e| panic!("{}: {:#}", path.display(), e));
+        model.validate().unwrap();
+        assert!(model.root.is_some(), "{}", path.display());
+    }
+}
+
+#[test]
+fn brepconv_validates_every_asset() {
+    for path in assets("step", "step")
+        .into_iter()
+        .chain(assets("brep", "brep"))
+    {
+        let output = brepconv(&[Path::new("--validate"), &path]);
+        assert!(
+            output.status.success(),
+            "{}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+#[test]
+fn brepconv_converts_step_to_brep() {
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("step_to_brep");
+    std::fs::create_dir_all(&out_dir).unwrap();
+    for path in assets("step", "step") {
+        let dest = out_dir
+            .join(path.file_name().unwrap())
+            .with_extension("brep");
+        let output = brepconv(&[&path, Path::new("-o"), &dest]);
+        assert!(
+            output.status.success(),
+            "{}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let model = read_brep(&dest).unwrap_or_else(|e| panic!("{}: {:#}", dest.display(), e));
+        model.validate().unwrap();
+        assert!(model.root.is_some(), "{}", dest.display());
+    }
+}
+
+#[test]
+fn brepconv_converts_brep_to_step() {
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("brep_to_step");
+    std::fs::create_dir_all(&out_dir).unwrap();
+    for path in assets("brep", "brep") {
+        let dest = out_dir
+            .join(path.file_name().unwrap())
+            .with_extension("step");
+        let output = brepconv(&[&path, Path::new("-o"), &dest]);
+        assert!(
+            output.status.success(),
+            "{}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let graph = read_step(&dest).unwrap_or_else(|e| panic!("{}: {:#}", dest.display(), e));
+        check_schema(&graph).unwrap();
+        graph.check_references().unwrap();
+    }
+}
+
+#[test]
+fn brepconv_exit_status_tells_the_failure_apart() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("exit_status");
+    std::fs::create_dir_all(&dir).unwrap();
+    let write = |name: &str, schema: &str, data: &str| {
+        let path = dir.join(name);
+        std::fs::write(
+            &path,
+            format!(
+                "ISO-10303-21;\nHEADER;\nFILE_SCHEMA(('{}'));\nENDSEC;\nDATA;\n{}\nENDSEC;\n\
+                 END-ISO-10303-21;\n",
+                schema, data
+            ),
+        )
+        .unwrap();
+        path
+    };
+    let invalid = write(
+        "invalid.step",
+        "AP214",
+        "#1 = CARTESIAN_POINT('',(0.,0.,0.)",
+    );
+    let unknown = write(
+        "unknown.step",
+        "IFC4",
+        "#1 = CARTESIAN_POINT('',(0.,0.,0.));",
+    );
+    // A single face on a torus, which the tessellator doesn't support
+    let torus = write(
+        "torus.step",
+        "AP214",
+        "#1 = CARTESIAN_POINT('',(0.,0.,0.));\n\
+         #2 = DIRECTION('',(0.,0.,1.));\n\
+         #3 = DIRECTION('',(1.,0.,0.));\n\
+         #4 = AXIS2_PLACEMENT_3D('',#1,#2,#3);\n\
+         #5 = TOROIDAL_SURFACE('',#4,10.,2.);\n\
+         #6 = ADVANCED_FACE('',(),#5,.T.);",
+    );
+    let stl = dir.join("out.stl");
+
+    let status = |args: &[&Path]| brepconv(args).status.code();
+    assert_eq!(status(&[Path::new("--no-such-flag")]), Some(1));
+    assert_eq!(
+        status(&[&dir.join("missing.step"), Path::new("-o"), &stl]),
+        Some(2)
+    );
+    assert_eq!(status(&[Path::new("--validate"), &invalid]), Some(3));
+    assert_eq!(status(&[&unknown, Path::new("-o"), &stl]), Some(4));
+    assert_eq!(status(&[&torus, Path::new("-o"), &stl]), Some(5));
+}